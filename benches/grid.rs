@@ -0,0 +1,115 @@
+//! Performance benchmarks for the grid algorithms and atlas packing.
+//!
+//! Run with `cargo bench`. This uses a minimal timing loop instead of a statistical harness like
+//! criterion, so treat the numbers as rough and compare runs on the same machine only.
+
+extern crate calx;
+extern crate euclid;
+extern crate image;
+extern crate rand;
+
+use calx::test_maps::TestMap;
+use calx::{
+    astar_path, hex_neighbors, pack_rects, seeded_rng, CellVector, Dijkstra, DynamicAtlas,
+    GridNode, HexFov, HexGeom, Noise, Sight,
+};
+use euclid::{size2, vec2, Size2D};
+use image::RgbaImage;
+use rand::distributions::Uniform;
+use rand::Rng;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+/// Run a function repeatedly for about a second and print the average time per run.
+///
+/// The results go through `black_box` so the optimizer can't throw away the work.
+fn bench<T, F: FnMut() -> T>(name: &str, mut f: F) {
+    // Warm up.
+    black_box(f());
+
+    let budget = Duration::from_secs(1);
+    let start = Instant::now();
+    let mut n = 0u32;
+    while start.elapsed() < budget {
+        black_box(f());
+        n += 1;
+    }
+    let elapsed = start.elapsed();
+    let per_iter = (elapsed.as_secs() as f64 * 1e9 + elapsed.subsec_nanos() as f64) / n as f64;
+    println!("{:<32} {:>12.0} ns/iter ({} runs)", name, per_iter, n);
+}
+
+/// Number of cells seen from the map center.
+fn fov_size(map: &TestMap, range: i32) -> usize {
+    let map = black_box(map);
+    HexFov::new(Sight::new(vec2(0, 0), range, &|p| !map.is_wall(p))).count()
+}
+
+/// Random sprite-like rectangle sizes.
+fn sprite_sizes(n: usize) -> Vec<Size2D<i32>> {
+    let mut rng = seeded_rng(&"sprites");
+    (0..n)
+        .map(|_| size2(rng.gen_range(4, 33), rng.gen_range(4, 33)))
+        .collect()
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+struct Node(i32, i32);
+
+impl GridNode for Node {
+    fn neighbors(&self) -> Vec<Node> {
+        hex_neighbors(vec2(self.0, self.1))
+            .map(|p: CellVector| Node(p.x, p.y))
+            .collect()
+    }
+}
+
+fn main() {
+    let open = TestMap::open(40);
+    let scattered = TestMap::scattered(&1, 40, 0.2);
+
+    bench("fov/open r=20", || fov_size(&open, 20));
+    bench("fov/scattered r=20", || fov_size(&scattered, 20));
+    bench("fov/scattered r=8", || fov_size(&scattered, 8));
+    bench("fov/open r=6", || fov_size(&open, 6));
+
+    let goal: CellVector = vec2(30, 10);
+    let walls = &scattered;
+    bench("astar/scattered", || {
+        astar_path(vec2(0, 0), black_box(&goal), |&p| {
+            hex_neighbors(p)
+                .filter(move |&q| !walls.is_wall(q))
+                .map(|q: CellVector| (q, (goal - q).hex_dist() as f32))
+        })
+    });
+
+    bench("dijkstra/scattered", || {
+        Dijkstra::new(
+            black_box(vec![Node(0, 0)]),
+            |n| !scattered.is_wall(vec2(n.0, n.1)),
+            1000,
+        )
+    });
+
+    let dist = Uniform::new(0.0f32, 1.0);
+    bench("noise/1000 samples", || {
+        (0..black_box(1000)).map(|i| dist.noise(&i)).sum::<f32>()
+    });
+
+    let sizes = sprite_sizes(500);
+    bench("atlas/pack_rects 500", || {
+        pack_rects(black_box(&sizes), size2(256, 256))
+    });
+
+    let images: Vec<RgbaImage> = sizes
+        .iter()
+        .map(|s| RgbaImage::new(s.width as u32, s.height as u32))
+        .collect();
+    bench("atlas/dynamic 500", || {
+        let mut atlas = DynamicAtlas::new(size2(256, 256));
+        for image in black_box(&images) {
+            atlas.insert(image);
+        }
+        atlas.page_count()
+    });
+}
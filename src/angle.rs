@@ -0,0 +1,141 @@
+use hex::{Dir12, Dir6};
+use std::f32::consts::PI;
+use std::ops::{Add, Mul, Neg, Sub};
+use CellVector;
+
+const TAU: f32 = 2.0 * PI;
+
+/// Direction angle.
+///
+/// Angles increase clockwise starting from `Dir6::North`, so the hex directions are at whole
+/// sixths of a turn and the `Dir12` directions at twelfths of a turn. Angles that differ by
+/// whole turns point in the same direction, and the comparisons and conversions account for
+/// the wrap-around.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate euclid;
+/// # extern crate calx;
+/// # fn main() {
+/// use euclid::vec2;
+/// use calx::{Angle, Dir12, Dir6};
+///
+/// let a = Angle::from_degrees(350.0);
+/// let b = Angle::from_degrees(20.0);
+/// assert!((a.arc_to(b).degrees() - 30.0).abs() < 0.001);
+/// assert!((a.lerp(b, 0.5).normalized().degrees() - 5.0).abs() < 0.001);
+///
+/// assert_eq!(Angle::from_degrees(100.0).to_dir6(), Dir6::Southeast);
+/// assert_eq!(Angle::from_hex_vector(vec2(1, 2)).to_dir12(), Dir12::SouthSouthwest);
+/// # }
+/// ```
+#[derive(Copy, Clone, PartialEq, PartialOrd, Default, Debug, Serialize, Deserialize)]
+pub struct Angle(f32);
+
+impl Angle {
+    pub fn from_radians(radians: f32) -> Angle { Angle(radians) }
+
+    pub fn from_turns(turns: f32) -> Angle { Angle(turns * TAU) }
+
+    pub fn from_degrees(degrees: f32) -> Angle { Angle(degrees.to_radians()) }
+
+    /// Return the angle of a hex grid vector.
+    ///
+    /// The vector is converted from the skewed hex coordinates into the geometric hex layout
+    /// before measuring the angle, so that every hex direction vector gets the angle of its
+    /// direction.
+    pub fn from_hex_vector(v: CellVector) -> Angle {
+        // Screen-like frame with y axis pointing up towards north. Cell space x axis points
+        // southeast and y axis southwest.
+        let (s, c) = (3.0f32.sqrt() / 2.0, 0.5);
+        let x = (v.x - v.y) as f32 * s;
+        let y = -(v.x + v.y) as f32 * c;
+        Angle(x.atan2(y))
+    }
+
+    pub fn radians(self) -> f32 { self.0 }
+
+    pub fn turns(self) -> f32 { self.0 / TAU }
+
+    pub fn degrees(self) -> f32 { self.0.to_degrees() }
+
+    /// Return the same direction as an angle in [0, 1) turns.
+    pub fn normalized(self) -> Angle { Angle(self.0.rem_euclid(TAU)) }
+
+    /// Return the shortest rotation from this angle to another.
+    ///
+    /// The result is in (-1/2, 1/2] turns, positive values are clockwise.
+    pub fn arc_to(self, other: Angle) -> Angle {
+        let d = (other.0 - self.0).rem_euclid(TAU);
+        if d > PI {
+            Angle(d - TAU)
+        } else {
+            Angle(d)
+        }
+    }
+
+    /// Interpolate along the shortest arc from this angle to another.
+    pub fn lerp(self, other: Angle, t: f32) -> Angle { self + self.arc_to(other) * t }
+
+    /// Return the hex direction closest to the angle.
+    pub fn to_dir6(self) -> Dir6 { Dir6::from_int((self.turns() * 6.0).round() as i32) }
+
+    /// Return the 12-way direction closest to the angle.
+    pub fn to_dir12(self) -> Dir12 { Dir12::from_int((self.turns() * 12.0).round() as i32) }
+}
+
+impl From<Dir6> for Angle {
+    fn from(d: Dir6) -> Angle { Angle::from_turns(d as i32 as f32 / 6.0) }
+}
+
+impl From<Dir12> for Angle {
+    fn from(d: Dir12) -> Angle { Angle::from_turns(d as i32 as f32 / 12.0) }
+}
+
+impl Add for Angle {
+    type Output = Angle;
+    fn add(self, rhs: Angle) -> Angle { Angle(self.0 + rhs.0) }
+}
+
+impl Sub for Angle {
+    type Output = Angle;
+    fn sub(self, rhs: Angle) -> Angle { Angle(self.0 - rhs.0) }
+}
+
+impl Mul<f32> for Angle {
+    type Output = Angle;
+    fn mul(self, rhs: f32) -> Angle { Angle(self.0 * rhs) }
+}
+
+impl Neg for Angle {
+    type Output = Angle;
+    fn neg(self) -> Angle { Angle(-self.0) }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Angle;
+    use hex::{Dir12, Dir6};
+
+    #[test]
+    fn test_dir_angles() {
+        for &d in Dir6::iter() {
+            let a = Angle::from_hex_vector(d.to_v2());
+            assert!(a.arc_to(Angle::from(d)).radians().abs() < 0.0001);
+            assert_eq!(a.to_dir6(), d);
+            assert_eq!(Angle::from(d).to_dir12(), Dir12::from_int(d as i32 * 2));
+        }
+
+        for i in 0..12 {
+            let d = Dir12::from_int(i);
+            assert_eq!(Angle::from(d).to_dir12(), d);
+            assert_eq!((Angle::from(d) + Angle::from_turns(-3.0)).to_dir12(), d);
+        }
+
+        // Opposite directions are half a turn apart either way.
+        let a = Angle::from(Dir6::North);
+        let b = Angle::from(Dir6::South);
+        assert!((a.arc_to(b).turns().abs() - 0.5).abs() < 0.0001);
+    }
+}
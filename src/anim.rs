@@ -0,0 +1,365 @@
+//! Frame animations of atlas sprites
+
+use atlas::AtlasItem;
+use serde_json;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::rc::Rc;
+
+/// How an animation continues after its last frame.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub enum LoopMode {
+    /// Stop at the last frame.
+    Once,
+    /// Start again from the first frame.
+    #[default]
+    Loop,
+    /// Play the frames backwards to the first one and then forwards again.
+    PingPong,
+}
+
+/// Sequence of atlas frames with per-frame durations.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Animation {
+    /// Frames and how long they are shown in seconds.
+    pub frames: Vec<(AtlasItem, f64)>,
+    pub mode: LoopMode,
+}
+
+impl Animation {
+    pub fn new(mode: LoopMode) -> Animation {
+        Animation {
+            frames: Vec::new(),
+            mode,
+        }
+    }
+
+    /// Add a frame shown for `duration_s` seconds.
+    pub fn frame(mut self, item: AtlasItem, duration_s: f64) -> Animation {
+        self.frames.push((item, duration_s));
+        self
+    }
+
+    /// Return the length of one pass through the frames in seconds.
+    pub fn duration_s(&self) -> f64 { self.frames.iter().map(|f| f.1).sum() }
+
+    /// Return the index of the frame shown at a time since the start of the animation.
+    pub fn frame_at(&self, time_s: f64) -> usize {
+        let n = self.frames.len();
+        let total = self.duration_s();
+        if n <= 1 || total <= 0.0 {
+            return 0;
+        }
+
+        let t = match self.mode {
+            LoopMode::Once if time_s >= total => return n - 1,
+            LoopMode::Once => time_s,
+            LoopMode::Loop => time_s.rem_euclid(total),
+            LoopMode::PingPong => {
+                // The way back leaves out the end frames so they aren't shown twice in a row.
+                let back: f64 = self.frames[1..n - 1].iter().map(|f| f.1).sum();
+                let t = time_s.rem_euclid(total + back);
+                if t >= total {
+                    return n - 2 - find_frame(self.frames[1..n - 1].iter().rev(), t - total);
+                }
+                t
+            }
+        };
+        find_frame(self.frames.iter(), t)
+    }
+
+    /// Return whether a one-shot animation has run out of frames at a time.
+    pub fn is_finished(&self, time_s: f64) -> bool {
+        self.mode == LoopMode::Once && time_s >= self.duration_s()
+    }
+}
+
+/// Return the index of the frame a time falls on.
+fn find_frame<'a, I: Iterator<Item = &'a (AtlasItem, f64)>>(frames: I, mut t: f64) -> usize {
+    let mut last = 0;
+    for (i, &(_, d)) in frames.enumerate() {
+        if t < d {
+            return i;
+        }
+        t -= d;
+        last = i;
+    }
+    last
+}
+
+/// Playing animation.
+///
+/// Tick the state with the frame's elapsed time, for example `GameLoop::timestep_s` for every
+/// update, and draw the atlas item from `item`. A static image converts into a single frame
+/// state, so drawing code can take `impl Into<AnimState>` and accept both.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate euclid;
+/// # extern crate calx;
+/// # fn main() {
+/// use std::rc::Rc;
+/// use euclid::rect;
+/// use calx::{AnimState, Animation, AtlasItem, LoopMode};
+///
+/// let frame = |i| AtlasItem { page: 0, rect: rect(i * 8, 0, 8, 8) };
+/// let walk = Rc::new(
+///     Animation::new(LoopMode::PingPong)
+///         .frame(frame(0), 0.25)
+///         .frame(frame(1), 0.25)
+///         .frame(frame(2), 0.25),
+/// );
+///
+/// let mut state = AnimState::new(walk);
+/// let mut shown = Vec::new();
+/// for _ in 0..6 {
+///     shown.push(state.frame_index());
+///     state.tick(0.25);
+/// }
+/// assert_eq!(shown, vec![0, 1, 2, 1, 0, 1]);
+///
+/// let still: AnimState = frame(5).into();
+/// assert_eq!(still.item(), frame(5));
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct AnimState {
+    animation: Rc<Animation>,
+    time_s: f64,
+}
+
+impl AnimState {
+    /// Start playing an animation from the beginning.
+    ///
+    /// Panics if the animation has no frames.
+    pub fn new(animation: Rc<Animation>) -> AnimState {
+        assert!(!animation.frames.is_empty(), "Animation has no frames");
+        AnimState {
+            animation,
+            time_s: 0.0,
+        }
+    }
+
+    /// Advance the animation by elapsed time and return the atlas item to show.
+    pub fn tick(&mut self, delta_s: f64) -> AtlasItem {
+        self.time_s += delta_s;
+        self.item()
+    }
+
+    /// Return the atlas item of the current frame.
+    pub fn item(&self) -> AtlasItem { self.animation.frames[self.frame_index()].0 }
+
+    /// Return the index of the current frame.
+    pub fn frame_index(&self) -> usize { self.animation.frame_at(self.time_s) }
+
+    /// Return the time since the animation started in seconds.
+    pub fn time_s(&self) -> f64 { self.time_s }
+
+    /// Return whether a one-shot animation has reached its end.
+    pub fn is_finished(&self) -> bool { self.animation.is_finished(self.time_s) }
+
+    /// Start the animation again from the first frame.
+    pub fn restart(&mut self) { self.time_s = 0.0; }
+
+    pub fn animation(&self) -> &Rc<Animation> { &self.animation }
+}
+
+impl From<AtlasItem> for AnimState {
+    fn from(item: AtlasItem) -> AnimState {
+        AnimState::new(Rc::new(Animation::new(LoopMode::Once).frame(item, 0.0)))
+    }
+}
+
+/// Animation description in an animation data file.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct AnimationSpec {
+    /// Names of the atlas images and how long they are shown in seconds.
+    pub frames: Vec<(String, f64)>,
+    #[serde(default)]
+    pub mode: LoopMode,
+}
+
+/// Error from loading an `AnimationSet`.
+#[derive(Debug)]
+pub enum AnimationError {
+    /// The data file could not be parsed.
+    Parse(serde_json::Error),
+    /// An animation has no frames.
+    NoFrames { name: String },
+    /// An animation refers to an image that isn't in the atlas.
+    MissingImage { name: String, image: String },
+}
+
+impl fmt::Display for AnimationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            AnimationError::Parse(ref e) => write!(f, "Malformed animation data: {}", e),
+            AnimationError::NoFrames { ref name } => {
+                write!(f, "Animation '{}' has no frames", name)
+            }
+            AnimationError::MissingImage {
+                ref name,
+                ref image,
+            } => write!(f, "Animation '{}' uses unknown image '{}'", name, image),
+        }
+    }
+}
+
+impl Error for AnimationError {}
+
+impl From<serde_json::Error> for AnimationError {
+    fn from(e: serde_json::Error) -> AnimationError { AnimationError::Parse(e) }
+}
+
+/// Named animations loaded from a data file.
+///
+/// The data file is a JSON object from animation names to `AnimationSpec` values. Every frame
+/// image is checked against the atlas at load time.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate euclid;
+/// # extern crate calx;
+/// # fn main() {
+/// use std::collections::HashMap;
+/// use euclid::rect;
+/// use calx::{AnimationSet, AtlasItem};
+///
+/// let mut atlas = HashMap::new();
+/// for (i, name) in ["torch_0", "torch_1", "poof"].iter().enumerate() {
+///     let item = AtlasItem { page: 0, rect: rect(i as i32 * 8, 0, 8, 8) };
+///     atlas.insert(name.to_string(), item);
+/// }
+///
+/// let data = r#"{
+///     "torch": { "frames": [["torch_0", 0.2], ["torch_1", 0.2]] },
+///     "poof": { "frames": [["poof", 0.5]], "mode": "Once" }
+/// }"#;
+/// let anims = AnimationSet::from_json(data, |name| atlas.get(name).cloned()).unwrap();
+///
+/// let mut torch = anims.start("torch").unwrap();
+/// assert_eq!(torch.tick(0.3), atlas["torch_1"]);
+/// assert_eq!(torch.tick(0.2), atlas["torch_0"]);
+///
+/// let mut poof = anims.start("poof").unwrap();
+/// poof.tick(1.0);
+/// assert!(poof.is_finished());
+/// # }
+/// ```
+#[derive(Clone, Default, Debug)]
+pub struct AnimationSet {
+    animations: BTreeMap<String, Rc<Animation>>,
+}
+
+impl AnimationSet {
+    /// Load the animations from a JSON data file.
+    ///
+    /// The `atlas` function looks up atlas items by image name.
+    pub fn from_json<F>(json: &str, atlas: F) -> Result<AnimationSet, AnimationError>
+    where
+        F: Fn(&str) -> Option<AtlasItem>,
+    {
+        let specs: BTreeMap<String, AnimationSpec> = serde_json::from_str(json)?;
+        AnimationSet::from_specs(specs, atlas)
+    }
+
+    /// Build the animations from animation descriptions.
+    pub fn from_specs<F>(
+        specs: BTreeMap<String, AnimationSpec>,
+        atlas: F,
+    ) -> Result<AnimationSet, AnimationError>
+    where
+        F: Fn(&str) -> Option<AtlasItem>,
+    {
+        let mut animations = BTreeMap::new();
+        for (name, spec) in specs {
+            if spec.frames.is_empty() {
+                return Err(AnimationError::NoFrames { name });
+            }
+            let mut anim = Animation::new(spec.mode);
+            for (image, duration_s) in spec.frames {
+                let item = match atlas(&image) {
+                    Some(item) => item,
+                    None => return Err(AnimationError::MissingImage { name, image }),
+                };
+                anim = anim.frame(item, duration_s);
+            }
+            animations.insert(name, Rc::new(anim));
+        }
+        Ok(AnimationSet { animations })
+    }
+
+    /// Return an animation by name.
+    pub fn get(&self, name: &str) -> Option<&Rc<Animation>> { self.animations.get(name) }
+
+    /// Start playing an animation by name.
+    pub fn start(&self, name: &str) -> Option<AnimState> {
+        self.get(name).map(|a| AnimState::new(a.clone()))
+    }
+
+    pub fn len(&self) -> usize { self.animations.len() }
+
+    pub fn is_empty(&self) -> bool { self.animations.is_empty() }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{AnimState, Animation, AnimationSet, LoopMode};
+    use atlas::AtlasItem;
+    use euclid::rect;
+    use std::rc::Rc;
+
+    fn frame(i: i32) -> AtlasItem {
+        AtlasItem {
+            page: 0,
+            rect: rect(i, 0, 1, 1),
+        }
+    }
+
+    #[test]
+    fn test_animation() {
+        let anim = |mode| {
+            Animation::new(mode)
+                .frame(frame(0), 1.0)
+                .frame(frame(1), 2.0)
+                .frame(frame(2), 1.0)
+        };
+        let frames = |a: &Animation| (0..12).map(|t| a.frame_at(t as f64)).collect::<Vec<_>>();
+
+        let once = anim(LoopMode::Once);
+        assert_eq!(once.duration_s(), 4.0);
+        assert_eq!(frames(&once), vec![0, 1, 1, 2, 2, 2, 2, 2, 2, 2, 2, 2]);
+        assert!(!once.is_finished(3.5));
+        assert!(once.is_finished(4.0));
+        assert_eq!(once.frame_at(-1.0), 0);
+
+        let looping = anim(LoopMode::Loop);
+        assert_eq!(frames(&looping), vec![0, 1, 1, 2, 0, 1, 1, 2, 0, 1, 1, 2]);
+        assert!(!looping.is_finished(100.0));
+
+        let ping_pong = anim(LoopMode::PingPong);
+        assert_eq!(frames(&ping_pong), vec![0, 1, 1, 2, 1, 1, 0, 1, 1, 2, 1, 1]);
+
+        let mut state = AnimState::new(Rc::new(anim(LoopMode::Once)));
+        assert_eq!(state.tick(1.5), frame(1));
+        assert_eq!(state.time_s(), 1.5);
+        state.tick(10.0);
+        assert!(state.is_finished());
+        assert_eq!(state.item(), frame(2));
+        state.restart();
+        assert_eq!(state.frame_index(), 0);
+
+        let still = AnimState::from(frame(7));
+        assert_eq!(still.item(), frame(7));
+        assert!(still.is_finished());
+
+        let lookup = |name: &str| name.parse().ok().map(frame);
+        let set = AnimationSet::from_json(r#"{ "a": { "frames": [["1", 1.0]] } }"#, lookup);
+        assert_eq!(set.unwrap().get("a").unwrap().mode, LoopMode::Loop);
+        assert!(AnimationSet::from_json(r#"{ "a": { "frames": [] } }"#, lookup).is_err());
+        assert!(AnimationSet::from_json(r#"{ "a": { "frames": [["x", 1.0]] } }"#, lookup).is_err());
+    }
+}
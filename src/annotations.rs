@@ -0,0 +1,212 @@
+//! Named labels and notes attached to map locations
+
+use ascii_map::render_ascii;
+use std::collections::HashSet;
+use std::fmt::Write;
+use CellVector;
+
+/// Named note on a set of map cells.
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Annotation {
+    /// Unique name of the annotation.
+    pub name: String,
+    /// Free-form description shown in the UI.
+    #[serde(default)]
+    pub text: String,
+    /// Character that marks the annotated cells on text maps.
+    #[serde(default)]
+    pub marker: Option<char>,
+    /// Annotated cells, a single cell for point locations.
+    pub cells: Vec<CellVector>,
+}
+
+impl Annotation {
+    /// Create an unmarked annotation for a single cell.
+    pub fn new(name: impl Into<String>, pos: CellVector) -> Annotation {
+        Annotation::region(name, vec![pos])
+    }
+
+    /// Create an unmarked annotation for a region of cells.
+    pub fn region(name: impl Into<String>, cells: impl IntoIterator<Item = CellVector>) -> Self {
+        Annotation {
+            name: name.into(),
+            text: String::new(),
+            marker: None,
+            cells: cells.into_iter().collect(),
+        }
+    }
+
+    /// Set the description text.
+    pub fn text(mut self, text: impl Into<String>) -> Annotation {
+        self.text = text.into();
+        self
+    }
+
+    /// Set the map marker character.
+    pub fn marker(mut self, marker: char) -> Annotation {
+        self.marker = Some(marker);
+        self
+    }
+
+    /// Return whether the annotation covers a cell.
+    pub fn contains(&self, pos: CellVector) -> bool { self.cells.contains(&pos) }
+}
+
+/// Annotation layer for a map.
+///
+/// Store the layer next to the map cells in the map type so that it gets serialized with the
+/// map. Annotations are kept in insertion order and identified by their unique names.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate euclid;
+/// # extern crate calx;
+/// # fn main() {
+/// use euclid::vec2;
+/// use calx::{hex_disc, Annotation, Annotations};
+///
+/// let mut notes = Annotations::new();
+/// notes.insert(Annotation::new("Entrance", vec2(0, 0)).marker('<'));
+/// notes.insert(Annotation::region("Shrine", hex_disc(vec2(3, 0), 1)).text("Smells of incense"));
+///
+/// assert_eq!(notes.get("Shrine").unwrap().text, "Smells of incense");
+/// assert_eq!(notes.at(vec2(3, 1)).next().unwrap().name, "Shrine");
+///
+/// // Annotations show up in text dumps of the map.
+/// let map = vec![(vec2(0, 0), '.'), (vec2(1, 0), '#')];
+/// assert_eq!(notes.render_ascii(map, |c| c), "< #\n\n< Entrance");
+/// # }
+/// ```
+#[derive(Clone, Eq, PartialEq, Default, Debug, Serialize, Deserialize)]
+pub struct Annotations {
+    items: Vec<Annotation>,
+}
+
+impl Annotations {
+    pub fn new() -> Annotations { Default::default() }
+
+    /// Add an annotation, replacing an existing one with the same name.
+    pub fn insert(&mut self, annotation: Annotation) {
+        match self.items.iter_mut().find(|a| a.name == annotation.name) {
+            Some(a) => *a = annotation,
+            None => self.items.push(annotation),
+        }
+    }
+
+    /// Remove and return a named annotation.
+    pub fn remove(&mut self, name: &str) -> Option<Annotation> {
+        let idx = self.items.iter().position(|a| a.name == name)?;
+        Some(self.items.remove(idx))
+    }
+
+    /// Find an annotation by name.
+    pub fn get(&self, name: &str) -> Option<&Annotation> {
+        self.items.iter().find(|a| a.name == name)
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut Annotation> {
+        self.items.iter_mut().find(|a| a.name == name)
+    }
+
+    /// Iterate all annotations in insertion order.
+    pub fn iter(&self) -> impl Iterator<Item = &Annotation> { self.items.iter() }
+
+    /// Iterate the annotations that cover a cell.
+    pub fn at(&self, pos: CellVector) -> impl Iterator<Item = &Annotation> {
+        self.items.iter().filter(move |a| a.contains(pos))
+    }
+
+    /// Return the marker character of a cell, if any annotation covering it has one.
+    ///
+    /// Use this to overlay markers on minimaps and other map views. The most recently added
+    /// annotation wins when several overlap.
+    pub fn marker_at(&self, pos: CellVector) -> Option<char> {
+        self.items
+            .iter()
+            .rev()
+            .filter(|a| a.contains(pos))
+            .find_map(|a| a.marker)
+    }
+
+    pub fn len(&self) -> usize { self.items.len() }
+
+    pub fn is_empty(&self) -> bool { self.items.is_empty() }
+
+    /// Render a map as text with annotation markers and a key for the annotations.
+    ///
+    /// Works like `render_ascii`, but cells with annotation markers show the marker. The key
+    /// below the map lists the annotations that cover at least one of the rendered cells. Use
+    /// this for dumping maps into morgue files and bug reports.
+    pub fn render_ascii<I, T, F>(&self, cells: I, mut legend: F) -> String
+    where
+        I: IntoIterator<Item = (CellVector, T)>,
+        F: FnMut(T) -> char,
+    {
+        let mut seen = HashSet::new();
+        let mut ret = render_ascii(
+            cells.into_iter().map(|(p, x)| {
+                seen.insert(p);
+                (p, self.marker_at(p).unwrap_or_else(|| legend(x)))
+            }),
+            |c| c,
+        );
+
+        let mut key = self
+            .items
+            .iter()
+            .filter(|a| a.cells.iter().any(|p| seen.contains(p)))
+            .peekable();
+        if key.peek().is_some() {
+            ret.push('\n');
+        }
+        for a in key {
+            ret.push('\n');
+            if let Some(c) = a.marker {
+                let _ = write!(ret, "{} ", c);
+            }
+            ret.push_str(&a.name);
+            if !a.text.is_empty() {
+                let _ = write!(ret, ": {}", a.text);
+            }
+        }
+        ret
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Annotation, Annotations};
+    use euclid::vec2;
+    use hex::hex_disc;
+    use serde_json;
+
+    #[test]
+    fn test_annotations() {
+        let mut notes = Annotations::new();
+        notes.insert(Annotation::new("Stairs", vec2(0, 0)).marker('>'));
+        notes.insert(Annotation::region("Lake", hex_disc(vec2(0, 0), 2)).marker('~'));
+        notes.insert(Annotation::new("Far away", vec2(10, 10)).text("Not on the map"));
+        assert_eq!(notes.len(), 3);
+
+        // Later annotations win.
+        assert_eq!(notes.marker_at(vec2(0, 0)), Some('~'));
+        assert_eq!(notes.at(vec2(0, 0)).count(), 2);
+        notes.insert(Annotation::region("Lake", vec![vec2(1, 1)]).marker('~'));
+        assert_eq!(notes.len(), 3);
+        assert_eq!(notes.marker_at(vec2(0, 0)), Some('>'));
+        assert_eq!(notes.marker_at(vec2(2, 2)), None);
+
+        let map = vec![(vec2(0, 0), 0), (vec2(1, 0), 1), (vec2(1, 1), 0)];
+        let text = notes.render_ascii(map, |x| if x == 0 { '.' } else { '#' });
+        assert_eq!(text, "> #\n ~\n\n> Stairs\n~ Lake");
+
+        let saved = serde_json::to_string(&notes).unwrap();
+        let loaded: Annotations = serde_json::from_str(&saved).unwrap();
+        assert_eq!(loaded, notes);
+
+        assert_eq!(notes.remove("Stairs").unwrap().marker, Some('>'));
+        assert!(notes.get("Stairs").is_none());
+        assert_eq!(notes.render_ascii(vec![(vec2(0, 0), 0)], |_| '.'), ".");
+    }
+}
@@ -0,0 +1,151 @@
+//! Text rendering of cell fields
+
+use prefab::TextVector;
+use space::Space;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use {CellVector, TermColor};
+
+/// Render a cell field as text in the oblique hex layout of prefab text maps.
+///
+/// The legend function maps each cell value to a character, a `LegendBuilder` can be used to
+/// assign the characters automatically. Unlike `snapshot`, the origin cell isn't marked, the
+/// left edge of the text is at the leftmost cell and trailing whitespace is trimmed from the
+/// lines.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate euclid;
+/// # extern crate calx;
+/// # fn main() {
+/// use euclid::vec2;
+/// use calx::render_ascii;
+///
+/// let cells = vec![(vec2(0, 0), 1), (vec2(1, 0), 2), (vec2(0, 1), 3), (vec2(1, 1), 4)];
+/// let text = render_ascii(cells, |x| match x {
+///     1 => '@',
+///     2 => '#',
+///     _ => '.',
+/// });
+/// assert_eq!(text, " @ #\n. .");
+/// # }
+/// ```
+pub fn render_ascii<I, T, F>(cells: I, mut legend: F) -> String
+where
+    I: IntoIterator<Item = (CellVector, T)>,
+    F: FnMut(T) -> char,
+{
+    render(cells, |x| (legend(x), None))
+}
+
+/// Render a cell field as text with ANSI terminal colors.
+///
+/// Like `render_ascii`, but the legend function also gives the terminal color of each cell.
+pub fn render_ansi<I, T, F>(cells: I, mut legend: F) -> String
+where
+    I: IntoIterator<Item = (CellVector, T)>,
+    F: FnMut(T) -> (char, TermColor),
+{
+    render(cells, |x| {
+        let (c, col) = legend(x);
+        (c, Some(col))
+    })
+}
+
+fn render<I, T, F>(cells: I, mut legend: F) -> String
+where
+    I: IntoIterator<Item = (CellVector, T)>,
+    F: FnMut(T) -> (char, Option<TermColor>),
+{
+    // Arrange cells in print order.
+    let text: BTreeMap<(i32, i32), (char, Option<TermColor>)> = cells
+        .into_iter()
+        .map(|(p, x)| {
+            let p = TextVector::from_cell_space(p);
+            ((p.y, p.x), legend(x))
+        })
+        .collect();
+
+    let min_x = match text.keys().map(|&(_, x)| x).min() {
+        Some(x) => x,
+        None => return String::new(),
+    };
+
+    let mut ret = String::new();
+    let mut print_y = text.keys().next().unwrap().0;
+    let mut print_x = min_x;
+    let mut color = None;
+
+    for (&(y, x), &(c, col)) in &text {
+        if y > print_y {
+            if color.is_some() {
+                ret.push_str("\x1b[0m");
+                color = None;
+            }
+            for _ in print_y..y {
+                ret.push('\n');
+            }
+            print_y = y;
+            print_x = min_x;
+        }
+
+        for _ in print_x..x {
+            ret.push(' ');
+        }
+        match col {
+            Some(col) if color != Some(col) => {
+                let code = col.base as u32 + if col.is_bright { 90 } else { 30 };
+                let _ = write!(ret, "\x1b[{}m", code);
+                color = Some(col);
+            }
+            _ => {}
+        }
+        ret.push(c);
+        print_x = x + 1;
+    }
+
+    if color.is_some() {
+        ret.push_str("\x1b[0m");
+    }
+    ret
+}
+
+#[cfg(test)]
+mod test {
+    use super::{render_ansi, render_ascii};
+    use euclid::vec2;
+    use hex::hex_disc;
+    use term_color;
+
+    #[test]
+    fn test_render_ascii() {
+        let text = render_ascii(hex_disc(vec2(0, 0), 1).map(|p| (p, p)), |p| {
+            if p == vec2(0, 0) {
+                '@'
+            } else {
+                '#'
+            }
+        });
+        assert_eq!(text, " # #\n# @ #\n # #");
+        assert_eq!(render_ascii(Vec::<(_, char)>::new(), |c| c), "");
+    }
+
+    #[test]
+    fn test_render_ansi() {
+        let cells = vec![
+            (vec2(0, 0), 0),
+            (vec2(1, 0), 1),
+            (vec2(2, 0), 1),
+            (vec2(1, 1), 0),
+        ];
+        let text = render_ansi(cells, |x| {
+            if x == 0 {
+                ('.', term_color::GREEN)
+            } else {
+                ('#', term_color::WHITE)
+            }
+        });
+        assert_eq!(text, "\x1b[32m. \x1b[97m# #\x1b[0m\n \x1b[32m.\x1b[0m");
+    }
+}
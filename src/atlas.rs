@@ -0,0 +1,261 @@
+use euclid::{point2, rect, Rect, Size2D};
+use image::{GenericImage, RgbaImage};
+
+/// Position of an image added to a `DynamicAtlas`.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct AtlasItem {
+    /// Index of the atlas page.
+    pub page: usize,
+    /// Area of the image on the page.
+    pub rect: Rect<i32>,
+}
+
+/// Texture atlas that can grow while the game is running.
+///
+/// Images are packed with a skyline packer, and new pages are added when an image no longer fits
+/// on the existing ones. The atlas keeps track of the page areas that changed since the last call
+/// to `take_dirty`, so the backend only needs to upload those parts of the textures.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate euclid;
+/// # extern crate image;
+/// # extern crate calx;
+/// # fn main() {
+/// use euclid::{rect, size2};
+/// use image::RgbaImage;
+/// use calx::DynamicAtlas;
+///
+/// let mut atlas = DynamicAtlas::new(size2(64, 64));
+/// let a = atlas.insert(&RgbaImage::new(32, 16)).unwrap();
+/// let b = atlas.insert(&RgbaImage::new(32, 32)).unwrap();
+/// assert_eq!((a.page, b.page), (0, 0));
+/// assert_eq!(atlas.take_dirty(), vec![(0, rect(0, 0, 64, 32))]);
+///
+/// // Doesn't fit on the first page anymore.
+/// let c = atlas.insert(&RgbaImage::new(64, 48)).unwrap();
+/// assert_eq!(c.page, 1);
+/// assert_eq!(atlas.take_dirty(), vec![(1, rect(0, 0, 64, 48))]);
+/// assert!(atlas.take_dirty().is_empty());
+/// # }
+/// ```
+pub struct DynamicAtlas {
+    page_size: Size2D<i32>,
+    pages: Vec<Page>,
+}
+
+struct Page {
+    image: RgbaImage,
+    /// Top edge of the used area as horizontal segments from left to right.
+    skyline: Vec<Segment>,
+    dirty: Option<Rect<i32>>,
+}
+
+#[derive(Copy, Clone)]
+struct Segment {
+    x: i32,
+    y: i32,
+    width: i32,
+}
+
+impl Page {
+    fn new(size: Size2D<i32>) -> Page {
+        Page {
+            image: RgbaImage::new(size.width as u32, size.height as u32),
+            skyline: vec![Segment {
+                x: 0,
+                y: 0,
+                width: size.width,
+            }],
+            dirty: None,
+        }
+    }
+
+    /// Find a place for a rectangle, preferring the topmost and then the leftmost position.
+    fn fit(&self, size: Size2D<i32>) -> Option<(usize, i32, i32)> {
+        let page_width = self.image.width() as i32;
+        let page_height = self.image.height() as i32;
+        let mut best: Option<(usize, i32, i32)> = None;
+
+        for (i, seg) in self.skyline.iter().enumerate() {
+            if seg.x + size.width > page_width {
+                break;
+            }
+            // The rectangle rests on the highest segment under it.
+            let mut y = 0;
+            let mut covered = 0;
+            for s in &self.skyline[i..] {
+                if covered >= size.width {
+                    break;
+                }
+                y = y.max(s.y);
+                covered += s.width;
+            }
+            if y + size.height > page_height {
+                continue;
+            }
+            if best.map_or(true, |(_, _, best_y)| y < best_y) {
+                best = Some((i, seg.x, y));
+            }
+        }
+        best
+    }
+
+    /// Raise the skyline over a placed rectangle.
+    fn place(&mut self, i: usize, area: Rect<i32>) {
+        self.skyline.insert(
+            i,
+            Segment {
+                x: area.min_x(),
+                y: area.max_y(),
+                width: area.size.width,
+            },
+        );
+
+        // Cut the segments under the new one.
+        while i + 1 < self.skyline.len() {
+            let end = area.max_x();
+            let next = &mut self.skyline[i + 1];
+            if next.x >= end {
+                break;
+            }
+            let overlap = end - next.x;
+            if overlap >= next.width {
+                self.skyline.remove(i + 1);
+            } else {
+                next.x += overlap;
+                next.width -= overlap;
+                break;
+            }
+        }
+
+        // Merge neighboring segments at the same height.
+        let mut j = 0;
+        while j + 1 < self.skyline.len() {
+            if self.skyline[j].y == self.skyline[j + 1].y {
+                self.skyline[j].width += self.skyline[j + 1].width;
+                self.skyline.remove(j + 1);
+            } else {
+                j += 1;
+            }
+        }
+
+        self.dirty = Some(match self.dirty {
+            Some(dirty) => dirty.union(&area),
+            None => area,
+        });
+    }
+}
+
+impl DynamicAtlas {
+    /// Create an empty atlas with the given texture page size.
+    pub fn new(page_size: Size2D<i32>) -> DynamicAtlas {
+        assert!(
+            page_size.width > 0 && page_size.height > 0,
+            "Empty atlas page size"
+        );
+        DynamicAtlas {
+            page_size,
+            pages: Vec::new(),
+        }
+    }
+
+    /// Add an image to the atlas.
+    ///
+    /// Returns `None` if the image is larger than the atlas pages.
+    pub fn insert(&mut self, image: &RgbaImage) -> Option<AtlasItem> {
+        let size = Size2D::new(image.width() as i32, image.height() as i32);
+        if size.width > self.page_size.width || size.height > self.page_size.height {
+            return None;
+        }
+
+        let found = self
+            .pages
+            .iter()
+            .enumerate()
+            .filter_map(|(p, page)| page.fit(size).map(|fit| (p, fit)))
+            .next();
+        let (page, (i, x, y)) = match found {
+            Some(found) => found,
+            None => {
+                self.pages.push(Page::new(self.page_size));
+                let page = self.pages.len() - 1;
+                (page, (0, 0, 0))
+            }
+        };
+
+        let area = rect(x, y, size.width, size.height);
+        if !area.is_empty() {
+            let target = &mut self.pages[page];
+            target.place(i, area);
+            target.image.copy_from(image, x as u32, y as u32);
+        }
+        Some(AtlasItem { page, rect: area })
+    }
+
+    pub fn page_count(&self) -> usize { self.pages.len() }
+
+    /// Return the image of an atlas page.
+    pub fn page(&self, page: usize) -> &RgbaImage { &self.pages[page].image }
+
+    pub fn page_size(&self) -> Size2D<i32> { self.page_size }
+
+    /// Return the changed area of each changed page since the previous call.
+    ///
+    /// New pages show up as changed pages here, the backend should create textures for pages
+    /// it hasn't seen before.
+    pub fn take_dirty(&mut self) -> Vec<(usize, Rect<i32>)> {
+        self.pages
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(i, page)| page.dirty.take().map(|area| (i, area)))
+            .collect()
+    }
+
+    /// Return the texture coordinates of an item in [0, 1] page units.
+    pub fn uv(&self, item: &AtlasItem) -> Rect<f32> {
+        let (w, h) = (self.page_size.width as f32, self.page_size.height as f32);
+        let r = item.rect.to_f32();
+        Rect::new(
+            point2(r.origin.x / w, r.origin.y / h),
+            Size2D::new(r.size.width / w, r.size.height / h),
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::DynamicAtlas;
+    use euclid::{rect, size2};
+    use image::{Rgba, RgbaImage};
+    use rand::Rng;
+    use rng::seeded_rng;
+
+    #[test]
+    fn test_dynamic_atlas() {
+        let mut rng = seeded_rng(&"atlas");
+        let mut atlas = DynamicAtlas::new(size2(64, 64));
+        let mut items = Vec::new();
+        for i in 0..200u8 {
+            let (w, h) = (rng.gen_range(1, 20), rng.gen_range(1, 20));
+            let image = RgbaImage::from_pixel(w, h, Rgba([i, 0, 0, 255]));
+            items.push((i, atlas.insert(&image).unwrap()));
+        }
+        assert!(atlas.page_count() > 1);
+        assert_eq!(atlas.take_dirty().len(), atlas.page_count());
+
+        for (n, &(_, a)) in items.iter().enumerate() {
+            assert!(rect(0, 0, 64, 64).contains_rect(&a.rect));
+            for &(_, b) in &items[n + 1..] {
+                assert!(a.page != b.page || !a.rect.intersects(&b.rect));
+            }
+        }
+        for &(i, item) in &items {
+            let p = item.rect.origin;
+            assert_eq!(atlas.page(item.page)[(p.x as u32, p.y as u32)].data[0], i);
+        }
+
+        assert!(atlas.insert(&RgbaImage::new(65, 1)).is_none());
+    }
+}
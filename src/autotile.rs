@@ -0,0 +1,431 @@
+//! Neighbor kernels and bitmask autotiling for square grids
+
+use euclid::vec2;
+use serde_json;
+use std::error::Error;
+use std::fmt;
+use CellVector;
+
+/// Offsets of the neighbors of a square cell clockwise from north, with y growing downwards.
+const NEIGHBOR_OFFSETS: [(i32, i32); 8] = [
+    (0, -1),
+    (1, -1),
+    (1, 0),
+    (1, 1),
+    (0, 1),
+    (-1, 1),
+    (-1, 0),
+    (-1, -1),
+];
+
+/// Return the eight neighbors of a square cell clockwise from north.
+pub fn square_neighbors(origin: CellVector) -> impl Iterator<Item = CellVector> {
+    NEIGHBOR_OFFSETS
+        .iter()
+        .map(move |&(x, y)| origin + vec2(x, y))
+}
+
+/// Values of a square cell and its eight neighbors.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct SquareKernel<T> {
+    pub center: T,
+    /// Neighbor values clockwise from north, with y growing downwards.
+    pub n: [T; 8],
+}
+
+impl<T> SquareKernel<T> {
+    /// Build the kernel around a cell from a map lookup function.
+    pub fn new<F: Fn(CellVector) -> T>(pos: CellVector, f: F) -> SquareKernel<T> {
+        let n = |i: usize| f(pos + vec2(NEIGHBOR_OFFSETS[i].0, NEIGHBOR_OFFSETS[i].1));
+        SquareKernel {
+            center: f(pos),
+            n: [n(0), n(1), n(2), n(3), n(4), n(5), n(6), n(7)],
+        }
+    }
+
+    /// Return a bit mask of the neighbors that match a predicate.
+    ///
+    /// Bit 0 is the northern neighbor and the rest go clockwise from it.
+    pub fn mask<F: Fn(&T) -> bool>(&self, f: F) -> u8 {
+        self.n
+            .iter()
+            .enumerate()
+            .fold(0, |acc, (i, x)| if f(x) { acc | (1 << i) } else { acc })
+    }
+}
+
+/// Neighbor configuration used for picking tiles.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum TileMask {
+    /// The four edge neighbors, 16 tiles.
+    Edges,
+    /// Edge neighbors and corner neighbors next to two connected edges, 47 tiles.
+    Blob,
+}
+
+/// Picks tile variants for terrain that joins up with neighboring cells of the same kind.
+///
+/// The default tile order for `TileMask::Edges` is the edge mask with north as bit 0, east as
+/// bit 1, south as bit 2 and west as bit 3. The default order for `TileMask::Blob` is the
+/// ascending order of the 47 distinct masks from `SquareKernel::mask` after corners without
+/// both adjacent edges connected have been cleared. Use `layout` to map the tiles to the order
+/// of an actual tile sheet.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate euclid;
+/// # extern crate calx;
+/// # fn main() {
+/// use euclid::vec2;
+/// use calx::{Autotiler, SquareKernel, TileMask};
+///
+/// // Wall running horizontally through the origin.
+/// let is_wall = |p: calx::CellVector| p.y == 0;
+/// let kernel = SquareKernel::new(vec2(0, 0), is_wall);
+/// let mask = kernel.mask(|&x| x);
+///
+/// let edges = Autotiler::new(TileMask::Edges);
+/// assert_eq!(edges.tile_count(), 16);
+/// // Connected to east and west.
+/// assert_eq!(edges.tile(mask), 0b1010);
+///
+/// let blob = Autotiler::new(TileMask::Blob);
+/// assert_eq!(blob.tile_count(), 47);
+/// assert_eq!(blob.tile(0), 0);
+/// assert_eq!(blob.tile(0xff), 46);
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct Autotiler {
+    kind: TileMask,
+    /// Tile index for every neighbor mask.
+    table: Vec<usize>,
+    tile_count: usize,
+}
+
+impl Autotiler {
+    pub fn new(kind: TileMask) -> Autotiler {
+        let canonical = (0..256).map(|m| canonical_mask(kind, m as u8));
+        let table = match kind {
+            TileMask::Edges => canonical.map(|m| m as usize).collect(),
+            TileMask::Blob => {
+                let mut masks: Vec<u8> = canonical.clone().collect();
+                masks.sort();
+                masks.dedup();
+                canonical
+                    .map(|m| masks.binary_search(&m).unwrap())
+                    .collect()
+            }
+        };
+        let tile_count = match kind {
+            TileMask::Edges => 16,
+            TileMask::Blob => 47,
+        };
+        Autotiler {
+            kind,
+            table,
+            tile_count,
+        }
+    }
+
+    /// Map the default tile order into the tile indices of a tile sheet.
+    ///
+    /// Tile `i` in the default order becomes `layout[i]`.
+    pub fn layout(mut self, layout: &[usize]) -> Autotiler {
+        assert_eq!(
+            layout.len(),
+            self.tile_count,
+            "Layout has wrong number of tiles"
+        );
+        for t in self.table.iter_mut() {
+            *t = layout[*t];
+        }
+        self
+    }
+
+    pub fn kind(&self) -> TileMask { self.kind }
+
+    /// Return the number of distinct tiles the tile set needs.
+    pub fn tile_count(&self) -> usize { self.tile_count }
+
+    /// Return the tile index for a neighbor mask from `SquareKernel::mask`.
+    pub fn tile(&self, mask: u8) -> usize { self.table[mask as usize] }
+}
+
+/// Drop the neighbors from a mask that don't affect the tile.
+fn canonical_mask(kind: TileMask, mask: u8) -> u8 {
+    let bit = |i: usize| mask & (1 << (i % 8)) != 0;
+    match kind {
+        TileMask::Edges => (0..4).fold(0, |acc, i| if bit(i * 2) { acc | (1 << i) } else { acc }),
+        TileMask::Blob => (0..8).fold(0, |acc, i| {
+            // Corners only count if both of the adjacent edges are connected.
+            let on = if i % 2 == 0 {
+                bit(i)
+            } else {
+                bit(i) && bit(i - 1) && bit(i + 1)
+            };
+            if on {
+                acc | (1 << i)
+            } else {
+                acc
+            }
+        }),
+    }
+}
+
+/// Terrain shape of a cell for rule-based autotiling.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Serialize, Deserialize)]
+pub enum Connection {
+    /// Floor or other cell that walls don't join with.
+    Open,
+    /// Thin wall that joins with neighboring walls.
+    Wall,
+    /// Solid block that fills the whole cell.
+    Block,
+}
+
+/// Condition on a single neighbor in a tile rule.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+enum NeighborPattern {
+    Any,
+    Is(Connection),
+    /// Wall or block.
+    Solid,
+}
+
+impl NeighborPattern {
+    fn from_char(c: char) -> Option<NeighborPattern> {
+        match c {
+            '?' => Some(NeighborPattern::Any),
+            '.' => Some(NeighborPattern::Is(Connection::Open)),
+            '#' => Some(NeighborPattern::Is(Connection::Wall)),
+            '*' => Some(NeighborPattern::Is(Connection::Block)),
+            '+' => Some(NeighborPattern::Solid),
+            _ => None,
+        }
+    }
+
+    fn matches(self, c: Connection) -> bool {
+        match self {
+            NeighborPattern::Any => true,
+            NeighborPattern::Is(x) => x == c,
+            NeighborPattern::Solid => c != Connection::Open,
+        }
+    }
+}
+
+/// Tile rule in an autotiling rule data file.
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct TileRuleSpec {
+    /// Neighbor pattern, one character per neighbor clockwise from north like in
+    /// `SquareKernel`.
+    ///
+    /// `.` is open, `#` is a wall, `*` is a block, `+` is either a wall or a block and `?` is
+    /// anything. Whitespace is ignored.
+    pub pattern: String,
+    /// Tile variant used when the pattern matches.
+    pub tile: usize,
+}
+
+#[derive(Deserialize)]
+struct TileRulesFile {
+    #[serde(default)]
+    default: usize,
+    rules: Vec<TileRuleSpec>,
+}
+
+/// Error from building `TileRules`.
+#[derive(Debug)]
+pub enum TileRulesError {
+    /// The data file could not be parsed.
+    Parse(serde_json::Error),
+    /// A rule pattern has invalid characters or the wrong number of neighbors.
+    BadPattern { pattern: String },
+}
+
+impl fmt::Display for TileRulesError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TileRulesError::Parse(ref e) => write!(f, "Malformed tile rules: {}", e),
+            TileRulesError::BadPattern { ref pattern } => {
+                write!(f, "Invalid neighbor pattern '{}'", pattern)
+            }
+        }
+    }
+}
+
+impl Error for TileRulesError {}
+
+impl From<serde_json::Error> for TileRulesError {
+    fn from(e: serde_json::Error) -> TileRulesError { TileRulesError::Parse(e) }
+}
+
+/// Data-driven rules for picking tile variants from the shapes of neighboring cells.
+///
+/// Each rule is a pattern of the eight neighbors and the tile variant to use when the pattern
+/// matches. The first matching rule wins, and the default variant is used when no rule
+/// matches. Terrain types with new ways of joining their neighbors can be added by writing a
+/// new rule file instead of new code.
+///
+/// The data file is a JSON object with a `rules` list of `TileRuleSpec` values and an optional
+/// `default` variant.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate euclid;
+/// # extern crate calx;
+/// # fn main() {
+/// use euclid::vec2;
+/// use calx::{CellVector, Connection, SquareKernel, TileRules};
+///
+/// // Walls that extend towards walls to the east and south, and that show their front face
+/// // when there is open floor to the south.
+/// let rules = TileRules::from_json(r#"{
+///     "default": 0,
+///     "rules": [
+///         { "pattern": "? ? + ? . ? ? ?", "tile": 3 },
+///         { "pattern": "? ? ? ? . ? ? ?", "tile": 2 },
+///         { "pattern": "? ? + ? ? ? ? ?", "tile": 1 }
+///     ]
+/// }"#).unwrap();
+///
+/// // Wall along the top of a corridor that has rock on both sides.
+/// let map = |p: CellVector| match p.y {
+///     0 if p.x >= 0 => Connection::Wall,
+///     1 | 2 => Connection::Open,
+///     _ => Connection::Block,
+/// };
+/// assert_eq!(rules.tile(&SquareKernel::new(vec2(0, 0), map)), 3);
+/// assert_eq!(rules.tile(&SquareKernel::new(vec2(0, -1), map)), 1);
+/// assert_eq!(rules.tile(&SquareKernel::new(vec2(0, 2), map)), 0);
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct TileRules {
+    rules: Vec<([NeighborPattern; 8], usize)>,
+    default: usize,
+}
+
+impl TileRules {
+    /// Load the rules from a JSON data file.
+    pub fn from_json(json: &str) -> Result<TileRules, TileRulesError> {
+        let file: TileRulesFile = serde_json::from_str(json)?;
+        TileRules::from_specs(&file.rules, file.default)
+    }
+
+    /// Build the rules from rule descriptions in priority order.
+    pub fn from_specs(specs: &[TileRuleSpec], default: usize) -> Result<TileRules, TileRulesError> {
+        let mut rules = Vec::new();
+        for spec in specs {
+            let bad = || TileRulesError::BadPattern {
+                pattern: spec.pattern.clone(),
+            };
+            let elts = spec
+                .pattern
+                .chars()
+                .filter(|c| !c.is_whitespace())
+                .map(NeighborPattern::from_char)
+                .collect::<Option<Vec<_>>>()
+                .ok_or_else(bad)?;
+            if elts.len() != 8 {
+                return Err(bad());
+            }
+            let mut pattern = [NeighborPattern::Any; 8];
+            pattern.copy_from_slice(&elts);
+            rules.push((pattern, spec.tile));
+        }
+        Ok(TileRules { rules, default })
+    }
+
+    /// Return the tile variant for a cell's neighborhood.
+    pub fn tile(&self, kernel: &SquareKernel<Connection>) -> usize {
+        self.rules
+            .iter()
+            .find(|(pattern, _)| pattern.iter().zip(&kernel.n).all(|(p, &c)| p.matches(c)))
+            .map_or(self.default, |&(_, tile)| tile)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        square_neighbors, Autotiler, Connection, SquareKernel, TileMask, TileRuleSpec, TileRules,
+    };
+    use euclid::vec2;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_kernel() {
+        let k = SquareKernel::new(vec2(10, 20), |p| p);
+        assert_eq!(k.center, vec2(10, 20));
+        assert_eq!(k.n[0], vec2(10, 19));
+        assert_eq!(k.n[2], vec2(11, 20));
+        assert_eq!(
+            k.n.to_vec(),
+            square_neighbors(vec2(10, 20)).collect::<Vec<_>>()
+        );
+        assert_eq!(k.mask(|p| p.x == 9), 0b1110_0000);
+    }
+
+    #[test]
+    fn test_autotiler() {
+        let edges = Autotiler::new(TileMask::Edges);
+        // Corners are ignored.
+        assert_eq!(edges.tile(0b1010_1010), 0);
+        assert_eq!(edges.tile(0b0101_0101), 15);
+        assert_eq!(edges.tile(0b0000_0100), 2);
+
+        let blob = Autotiler::new(TileMask::Blob);
+        let tiles: HashSet<_> = (0..256).map(|m| blob.tile(m as u8)).collect();
+        assert_eq!(tiles.len(), 47);
+        assert!(tiles.iter().all(|&t| t < 47));
+        // Northeast corner only counts with both north and east.
+        assert_eq!(blob.tile(0b0000_0011), blob.tile(0b0000_0001));
+        assert_ne!(blob.tile(0b0000_0111), blob.tile(0b0000_0101));
+        // Northwest corner wraps around to the north edge.
+        assert_ne!(blob.tile(0b1100_0001), blob.tile(0b0100_0001));
+
+        let reversed: Vec<usize> = (0..16).rev().collect();
+        let edges = Autotiler::new(TileMask::Edges).layout(&reversed);
+        assert_eq!(edges.tile(0), 15);
+        assert_eq!(edges.kind(), TileMask::Edges);
+    }
+    #[test]
+    fn test_tile_rules() {
+        let spec = |pattern: &str, tile| TileRuleSpec {
+            pattern: pattern.to_string(),
+            tile,
+        };
+        let rules = TileRules::from_specs(
+            &[
+                spec("#???????", 1),
+                spec("*??? ????", 2),
+                spec("+???.???", 3),
+            ],
+            7,
+        )
+        .unwrap();
+
+        let kernel = |n: Connection, s: Connection| SquareKernel {
+            center: Connection::Wall,
+            n: [n, s, s, s, s, s, s, s],
+        };
+        use super::Connection::*;
+        assert_eq!(rules.tile(&kernel(Wall, Open)), 1);
+        assert_eq!(rules.tile(&kernel(Block, Block)), 2);
+        assert_eq!(rules.tile(&kernel(Open, Open)), 7);
+
+        let rules = TileRules::from_specs(&[spec("+???.???", 3)], 0).unwrap();
+        assert_eq!(rules.tile(&kernel(Block, Open)), 3);
+        assert_eq!(rules.tile(&kernel(Wall, Open)), 3);
+        assert_eq!(rules.tile(&kernel(Wall, Wall)), 0);
+
+        assert!(TileRules::from_specs(&[spec("???????", 0)], 0).is_err());
+        assert!(TileRules::from_specs(&[spec("???????x", 0)], 0).is_err());
+        assert!(TileRules::from_json(r#"{ "rules": [ { "pattern": "????????" } ] }"#).is_err());
+        let rules = TileRules::from_json(r#"{ "rules": [] }"#).unwrap();
+        assert_eq!(rules.tile(&kernel(Wall, Wall)), 0);
+    }
+}
@@ -0,0 +1,201 @@
+//! Monte Carlo simulation of game balance
+
+use rand::{Rng, XorShiftRng};
+use rng::seeded_rng;
+use stats::{percentile, Histogram, RunningStats};
+use std::fmt;
+use std::hash::Hash;
+use std::thread;
+
+/// Result of a single simulated encounter.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Encounter {
+    /// Whether the side being balanced won the encounter.
+    pub won: bool,
+    /// Damage value to collect statistics on, eg. damage taken by the player.
+    pub damage: f64,
+}
+
+/// Collected results of a balance simulation.
+#[derive(Clone, Debug)]
+pub struct BalanceReport {
+    /// Number of simulated encounters.
+    pub trials: usize,
+    /// Number of won encounters.
+    pub wins: usize,
+    /// Statistics of the encounter damage values.
+    pub damage: RunningStats,
+    /// The damage values of the encounters in trial order.
+    pub damage_samples: Vec<f64>,
+}
+
+impl BalanceReport {
+    /// Return the fraction of won encounters.
+    pub fn win_rate(&self) -> f64 {
+        if self.trials == 0 {
+            0.0
+        } else {
+            self.wins as f64 / self.trials as f64
+        }
+    }
+
+    /// Return a confidence interval for the true win rate.
+    ///
+    /// Uses the Wilson score interval, `z` is the number of standard deviations of the normal
+    /// distribution for the confidence level, eg. 1.96 for 95 % confidence.
+    pub fn win_rate_interval(&self, z: f64) -> (f64, f64) {
+        if self.trials == 0 {
+            return (0.0, 1.0);
+        }
+        let n = self.trials as f64;
+        let p = self.win_rate();
+        let denominator = 1.0 + z * z / n;
+        let center = (p + z * z / (2.0 * n)) / denominator;
+        let spread = z * (p * (1.0 - p) / n + z * z / (4.0 * n * n)).sqrt() / denominator;
+        ((center - spread).max(0.0), (center + spread).min(1.0))
+    }
+
+    /// Return the damage value below which the fraction `p` of the encounters fall.
+    pub fn damage_percentile(&self, p: f64) -> f64 {
+        percentile(&mut self.damage_samples.clone(), p).unwrap_or(0.0)
+    }
+
+    /// Return a histogram of the damage values over their whole range.
+    pub fn damage_histogram(&self, n_buckets: usize) -> Histogram {
+        let (min, max) = (self.damage.min(), self.damage.max());
+        let max = if max > min { max } else { min + 1.0 };
+        // Widen the range a little so that the maximum value lands in the last bucket.
+        let mut ret = Histogram::new(min, max + (max - min) * 1e-9, n_buckets);
+        for &x in &self.damage_samples {
+            ret.push(x);
+        }
+        ret
+    }
+}
+
+impl fmt::Display for BalanceReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (lo, hi) = self.win_rate_interval(1.96);
+        writeln!(
+            f,
+            "win rate: {:.1} % (95 % CI {:.1} - {:.1} %) over {} trials",
+            self.win_rate() * 100.0,
+            lo * 100.0,
+            hi * 100.0,
+            self.trials
+        )?;
+        writeln!(
+            f,
+            "damage: mean {:.2}, std dev {:.2}, median {:.2}, 90th percentile {:.2}",
+            self.damage.mean(),
+            self.damage.std_dev(),
+            self.damage_percentile(0.5),
+            self.damage_percentile(0.9)
+        )?;
+        write!(
+            f,
+            "damage distribution: {}",
+            self.damage_histogram(20).sparkline()
+        )
+    }
+}
+
+/// Run a randomized encounter many times in parallel and collect the results.
+///
+/// Each trial gets its own random number generator derived from the seed and the trial number,
+/// so the report is the same for the same seed regardless of how the trials are spread over
+/// the worker threads.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate rand;
+/// # extern crate calx;
+/// # fn main() {
+/// use rand::Rng;
+/// use calx::{simulate_encounters, Encounter};
+///
+/// // Hero with 10 HP against a goblin with 6 HP, both hit half the time for 1-3 damage.
+/// let report = simulate_encounters(&"goblin fight", 2000, |rng| {
+///     let (mut hero_hp, mut goblin_hp) = (10, 6);
+///     while hero_hp > 0 && goblin_hp > 0 {
+///         if rng.gen_bool(0.5) {
+///             goblin_hp -= rng.gen_range(1, 4);
+///         }
+///         if goblin_hp > 0 && rng.gen_bool(0.5) {
+///             hero_hp -= rng.gen_range(1, 4);
+///         }
+///     }
+///     Encounter { won: hero_hp > 0, damage: (10 - hero_hp.max(0)) as f64 }
+/// });
+///
+/// let (lo, hi) = report.win_rate_interval(1.96);
+/// assert!(lo > 0.6 && hi < 1.0);
+/// println!("{}", report);
+/// # }
+/// ```
+pub fn simulate_encounters<F>(seed: &impl Hash, trials: usize, encounter: F) -> BalanceReport
+where
+    F: Fn(&mut XorShiftRng) -> Encounter + Sync,
+{
+    let base_seed = seeded_rng(seed).gen::<u64>();
+    let n_threads = thread::available_parallelism().map_or(1, |n| n.get());
+    let chunk = ((trials + n_threads - 1) / n_threads).max(1);
+
+    let encounter = &encounter;
+    let results: Vec<Encounter> = thread::scope(|s| {
+        let handles: Vec<_> = (0..trials)
+            .step_by(chunk)
+            .map(|start| {
+                s.spawn(move || {
+                    (start..(start + chunk).min(trials))
+                        .map(|i| encounter(&mut seeded_rng(&(base_seed, i))))
+                        .collect::<Vec<Encounter>>()
+                })
+            })
+            .collect();
+
+        handles
+            .into_iter()
+            .flat_map(|h| h.join().expect("Encounter simulation panicked"))
+            .collect()
+    });
+
+    BalanceReport {
+        trials,
+        wins: results.iter().filter(|e| e.won).count(),
+        damage: results.iter().map(|e| e.damage).collect(),
+        damage_samples: results.iter().map(|e| e.damage).collect(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{simulate_encounters, Encounter};
+    use rand::Rng;
+
+    #[test]
+    fn test_coin_flip() {
+        let flip = |rng: &mut _| {
+            let won = Rng::gen_bool(rng, 0.5);
+            Encounter {
+                won,
+                damage: if won { 0.0 } else { 2.0 },
+            }
+        };
+        let report = simulate_encounters(&1, 10_000, flip);
+        assert_eq!(report.trials, 10_000);
+        let (lo, hi) = report.win_rate_interval(3.0);
+        assert!(lo < 0.5 && 0.5 < hi);
+        assert!(hi - lo < 0.04);
+        assert_eq!(report.damage_percentile(0.0), 0.0);
+        assert_eq!(report.damage_percentile(1.0), 2.0);
+        assert_eq!(report.damage_histogram(2).count(), 10_000);
+
+        // Same seed, same results.
+        assert_eq!(
+            simulate_encounters(&1, 10_000, flip).damage_samples,
+            report.damage_samples
+        );
+    }
+}
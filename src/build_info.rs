@@ -0,0 +1,130 @@
+//! Build metadata stamping
+//!
+//! Call `emit_build_info` from the game's build script and use the `build_info!` macro in the
+//! game to get the metadata of the build. Stamp bug reports, logs and saves with it so that every
+//! artifact tells which build produced it.
+
+use std::env;
+use std::fmt;
+use std::process::Command;
+use time;
+
+/// Metadata of a program build.
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct BuildInfo {
+    /// Crate version from Cargo.toml.
+    pub version: String,
+    /// Short git commit hash, with a "-dirty" suffix if there were uncommitted changes.
+    pub git_hash: Option<String>,
+    /// UTC date of the build as YYYY-MM-DD.
+    pub build_date: Option<String>,
+    /// Cargo build profile, "debug" or "release".
+    pub profile: Option<String>,
+}
+
+impl fmt::Display for BuildInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.version)?;
+        let extra: Vec<&str> = [&self.git_hash, &self.build_date, &self.profile]
+            .iter()
+            .filter_map(|x| x.as_ref().map(|s| &s[..]))
+            .collect();
+        if !extra.is_empty() {
+            write!(f, " ({})", extra.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Build the `BuildInfo` of the crate where the macro is used.
+///
+/// The version is always available, the other fields are set if the crate's build script called
+/// `emit_build_info`.
+///
+/// # Examples
+///
+/// ```
+/// #[macro_use]
+/// extern crate calx;
+///
+/// # fn main() {
+/// let info = build_info!();
+/// let save_format = calx::SaveFormat::new(&info.to_string());
+/// println!("Running {}", info);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! build_info {
+    () => {
+        $crate::BuildInfo {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            git_hash: option_env!("CALX_BUILD_GIT_HASH").map(|s| s.to_string()),
+            build_date: option_env!("CALX_BUILD_DATE").map(|s| s.to_string()),
+            profile: option_env!("CALX_BUILD_PROFILE").map(|s| s.to_string()),
+        }
+    };
+}
+
+/// Pass build metadata to the crate being built, call from a build script.
+///
+/// Git information is left out if git isn't available or the crate isn't in a git repository.
+///
+/// # Examples
+///
+/// ```no_run
+/// // build.rs
+/// extern crate calx;
+///
+/// fn main() {
+///     calx::emit_build_info();
+/// }
+/// ```
+pub fn emit_build_info() {
+    if let Some(hash) = git(&["rev-parse", "--short", "HEAD"]) {
+        let is_dirty = git(&["status", "--porcelain"]).is_some_and(|s| !s.is_empty());
+        let suffix = if is_dirty { "-dirty" } else { "" };
+        println!("cargo:rustc-env=CALX_BUILD_GIT_HASH={}{}", hash, suffix);
+    }
+    if let Some(dir) = git(&["rev-parse", "--git-dir"]) {
+        println!("cargo:rerun-if-changed={}/HEAD", dir);
+        println!("cargo:rerun-if-changed={}/index", dir);
+    }
+
+    if let Ok(date) = time::strftime("%Y-%m-%d", &time::now_utc()) {
+        println!("cargo:rustc-env=CALX_BUILD_DATE={}", date);
+    }
+
+    if let Ok(profile) = env::var("PROFILE") {
+        println!("cargo:rustc-env=CALX_BUILD_PROFILE={}", profile);
+    }
+}
+
+/// Run a git command and return its trimmed output if it succeeded.
+fn git(args: &[&str]) -> Option<String> {
+    let output = Command::new("git").args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    String::from_utf8(output.stdout)
+        .ok()
+        .map(|s| s.trim().to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use super::BuildInfo;
+
+    #[test]
+    fn test_display() {
+        let mut info = BuildInfo {
+            version: "0.1.0".to_string(),
+            git_hash: None,
+            build_date: None,
+            profile: None,
+        };
+        assert_eq!(info.to_string(), "0.1.0");
+        info.git_hash = Some("abc1234".to_string());
+        info.profile = Some("release".to_string());
+        assert_eq!(info.to_string(), "0.1.0 (abc1234, release)");
+    }
+}
@@ -0,0 +1,272 @@
+//! Overworld climate and biomes
+
+use grid::Grid;
+use noise::Perlin;
+use rand::Rng;
+use rng::WeightedTable;
+use std::collections::HashMap;
+
+/// Overworld terrain type determined by the climate.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Serialize, Deserialize)]
+pub enum Biome {
+    Ocean,
+    Mountain,
+    Ice,
+    Tundra,
+    Taiga,
+    Grassland,
+    TemperateForest,
+    TemperateRainforest,
+    Desert,
+    Savanna,
+    TropicalForest,
+    TropicalRainforest,
+}
+
+impl Biome {
+    /// Classify land with a Whittaker-style table.
+    ///
+    /// Temperature and moisture are in [0, 1], from coldest to hottest and from driest to
+    /// wettest.
+    pub fn classify(temperature: f32, moisture: f32) -> Biome {
+        use Biome::*;
+        let (t, m) = (temperature, moisture);
+        if t < 0.15 {
+            Ice
+        } else if t < 0.3 {
+            Tundra
+        } else if t < 0.5 {
+            if m < 0.3 {
+                Grassland
+            } else {
+                Taiga
+            }
+        } else if t < 0.75 {
+            match m {
+                m if m < 0.2 => Desert,
+                m if m < 0.45 => Grassland,
+                m if m < 0.75 => TemperateForest,
+                _ => TemperateRainforest,
+            }
+        } else {
+            match m {
+                m if m < 0.25 => Desert,
+                m if m < 0.55 => Savanna,
+                m if m < 0.8 => TropicalForest,
+                _ => TropicalRainforest,
+            }
+        }
+    }
+}
+
+/// Climate of an overworld cell.
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Climate {
+    /// Temperature from 0 (coldest) to 1 (hottest).
+    pub temperature: f32,
+    /// Moisture from 0 (driest) to 1 (wettest).
+    pub moisture: f32,
+    /// Height of the cell from the heightmap.
+    pub altitude: f32,
+    pub biome: Biome,
+}
+
+/// Simple climate model for assigning biomes to an overworld heightmap.
+///
+/// Temperature falls off from the equator, which runs horizontally through the middle of the
+/// map, towards the poles at the top and bottom edges, and drops further with altitude above
+/// the sea level. Moisture comes from noise. Heightmap values are expected to be in [0, 1].
+///
+/// # Examples
+///
+/// ```
+/// # extern crate euclid;
+/// # extern crate calx;
+/// # fn main() {
+/// use euclid::rect;
+/// use calx::noise::Perlin;
+/// use calx::{Biome, ClimateModel, Grid};
+///
+/// let terrain = Perlin::new(1);
+/// let heightmap = Grid::from_fn(rect(0, 0, 80, 40), |p| {
+///     terrain.fbm2(p.x as f64 / 16.0, p.y as f64 / 16.0, 4, 0.5) as f32 * 0.5 + 0.5
+/// });
+///
+/// let biomes = ClimateModel::new(1).sea_level(0.45).biomes(&heightmap);
+/// assert_eq!(biomes.bounds(), heightmap.bounds());
+/// // Poles are frozen.
+/// assert!(biomes
+///     .iter()
+///     .filter(|&(p, _)| p.y == 0)
+///     .all(|(_, &b)| b == Biome::Ocean || b == Biome::Ice || b == Biome::Mountain));
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct ClimateModel {
+    noise: Perlin,
+    sea_level: f32,
+    mountain_level: f32,
+    lapse_rate: f32,
+    moisture_scale: f64,
+}
+
+impl ClimateModel {
+    /// Create a climate model with the seed for the moisture noise.
+    pub fn new(seed: u64) -> ClimateModel {
+        ClimateModel {
+            noise: Perlin::new(seed),
+            sea_level: 0.4,
+            mountain_level: 0.85,
+            lapse_rate: 0.5,
+            moisture_scale: 16.0,
+        }
+    }
+
+    /// Set the altitude below which cells are ocean.
+    pub fn sea_level(mut self, sea_level: f32) -> ClimateModel {
+        self.sea_level = sea_level;
+        self
+    }
+
+    /// Set the altitude above which cells are bare mountain.
+    pub fn mountain_level(mut self, mountain_level: f32) -> ClimateModel {
+        self.mountain_level = mountain_level;
+        self
+    }
+
+    /// Set how much the temperature drops per unit of altitude above sea level.
+    pub fn lapse_rate(mut self, lapse_rate: f32) -> ClimateModel {
+        self.lapse_rate = lapse_rate;
+        self
+    }
+
+    /// Set the size of the moisture noise features in cells.
+    pub fn moisture_scale(mut self, cells: f64) -> ClimateModel {
+        self.moisture_scale = cells;
+        self
+    }
+
+    /// Compute the climate of every heightmap cell.
+    pub fn climate(&self, heightmap: &Grid<f32>) -> Grid<Climate> {
+        let bounds = heightmap.bounds();
+        let (top, height) = (bounds.min_y() as f32, bounds.size.height.max(2) as f32);
+
+        heightmap.map(|p, &altitude| {
+            // Latitude from -1 at the top edge to 1 at the bottom edge.
+            let latitude = (p.y as f32 - top) / (height - 1.0) * 2.0 - 1.0;
+            let elevation = (altitude - self.sea_level).max(0.0);
+            let temperature = (1.0 - latitude.abs() - elevation * self.lapse_rate).clamp(0.0, 1.0);
+
+            let (x, y) = (
+                p.x as f64 / self.moisture_scale,
+                p.y as f64 / self.moisture_scale,
+            );
+            let moisture = (self.noise.fbm2(x, y, 4, 0.5) as f32 * 0.5 + 0.5).clamp(0.0, 1.0);
+
+            let biome = if altitude < self.sea_level {
+                Biome::Ocean
+            } else if altitude >= self.mountain_level {
+                Biome::Mountain
+            } else {
+                Biome::classify(temperature, moisture)
+            };
+
+            Climate {
+                temperature,
+                moisture,
+                altitude,
+                biome,
+            }
+        })
+    }
+
+    /// Compute the biome of every heightmap cell.
+    pub fn biomes(&self, heightmap: &Grid<f32>) -> Grid<Biome> {
+        self.climate(heightmap).map(|_, c| c.biome)
+    }
+}
+
+/// Weighted spawn tables for each biome.
+///
+/// # Examples
+///
+/// ```
+/// use calx::{seeded_rng, Biome, BiomeSpawns};
+///
+/// let spawns = BiomeSpawns::new()
+///     .table(Biome::Tundra, vec![(3.0, "wolf"), (1.0, "mammoth")])
+///     .table(Biome::Desert, vec![(1.0, "scorpion")]);
+///
+/// let mut rng = seeded_rng(&1);
+/// assert_eq!(spawns.spawn(&mut rng, Biome::Desert), Some(&"scorpion"));
+/// assert_eq!(spawns.spawn(&mut rng, Biome::Ocean), None);
+/// ```
+#[derive(Clone, Debug)]
+pub struct BiomeSpawns<T> {
+    tables: HashMap<Biome, WeightedTable<T>>,
+}
+
+impl<T> BiomeSpawns<T> {
+    pub fn new() -> BiomeSpawns<T> {
+        BiomeSpawns {
+            tables: HashMap::new(),
+        }
+    }
+
+    /// Set the weighted spawns of a biome.
+    pub fn table(mut self, biome: Biome, spawns: impl IntoIterator<Item = (f64, T)>) -> Self {
+        self.tables.insert(biome, WeightedTable::new(spawns));
+        self
+    }
+
+    /// Pick a random spawn for a biome, `None` if the biome has no spawn table.
+    pub fn spawn<R: Rng + ?Sized>(&self, rng: &mut R, biome: Biome) -> Option<&T> {
+        self.tables.get(&biome).map(|t| t.pick(rng))
+    }
+}
+
+impl<T> Default for BiomeSpawns<T> {
+    fn default() -> Self { BiomeSpawns::new() }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Biome, ClimateModel};
+    use euclid::{rect, vec2};
+    use grid::Grid;
+
+    #[test]
+    fn test_climate() {
+        assert_eq!(Biome::classify(0.0, 1.0), Biome::Ice);
+        assert_eq!(Biome::classify(0.9, 0.0), Biome::Desert);
+        assert_eq!(Biome::classify(0.9, 1.0), Biome::TropicalRainforest);
+        assert_eq!(Biome::classify(0.6, 0.6), Biome::TemperateForest);
+
+        // Flat land with a mountain ridge down the middle.
+        let heightmap = Grid::from_fn(rect(0, 0, 21, 41), |p| {
+            if p.x == 10 {
+                0.9
+            } else if p.x < 2 {
+                0.1
+            } else {
+                0.5 + p.x as f32 * 0.01
+            }
+        });
+        let model = ClimateModel::new(3);
+        let climate = model.climate(&heightmap);
+
+        // Hottest at the equator, coldest at the poles.
+        assert!(climate[vec2(5, 20)].temperature > 0.9);
+        assert_eq!(climate[vec2(5, 0)].temperature, 0.0);
+        assert_eq!(climate[vec2(5, 40)].temperature, 0.0);
+        // Higher means colder.
+        assert!(climate[vec2(15, 20)].temperature < climate[vec2(5, 20)].temperature);
+
+        assert_eq!(climate[vec2(0, 20)].biome, Biome::Ocean);
+        assert_eq!(climate[vec2(10, 20)].biome, Biome::Mountain);
+        assert!(climate
+            .iter()
+            .all(|(_, c)| c.moisture >= 0.0 && c.moisture <= 1.0));
+        assert_eq!(model.biomes(&heightmap), climate.map(|_, c| c.biome));
+    }
+}
@@ -197,6 +197,9 @@ impl FromStr for SRgba {
 ///
 /// This is the canonical color representation that the rendering engine
 /// expects to get.
+///
+/// Colors parse from the same string representations as `SRgba` and are
+/// displayed as the `#RRGGBBAA` hex value of the corresponding sRGB color.
 #[derive(Copy, Clone, PartialEq, PartialOrd, Debug, Serialize, Deserialize)]
 pub struct Rgba {
     /// Linear red component
@@ -232,6 +235,10 @@ impl FromStr for Rgba {
     }
 }
 
+impl fmt::Display for Rgba {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { SRgba::from(*self).fmt(f) }
+}
+
 impl From<SRgba> for Rgba {
     fn from(c: SRgba) -> Rgba {
         Rgba::new(
@@ -326,6 +333,231 @@ pub fn to_srgb(linear: f32) -> f32 {
     }
 }
 
+/// Map a value in [0, 1] to a color along a gradient.
+///
+/// The gradient stops are spaced evenly over the range and the colors between them are
+/// interpolated in linear color space. Values outside the range get the end colors.
+///
+/// # Examples
+///
+/// ```
+/// use calx::{gradient, Rgba, SRgba};
+///
+/// let stops = [Rgba::from(0x000000ffu32), Rgba::from(0xff0000ffu32), Rgba::from(0xffff00ffu32)];
+/// assert_eq!(SRgba::from(gradient(&stops, -1.0)), SRgba::rgb(0, 0, 0));
+/// assert_eq!(SRgba::from(gradient(&stops, 0.5)), SRgba::rgb(255, 0, 0));
+/// assert_eq!(SRgba::from(gradient(&stops, 0.75)), SRgba::rgb(255, 188, 0));
+/// ```
+pub fn gradient(stops: &[Rgba], t: f32) -> Rgba {
+    assert!(!stops.is_empty(), "Gradient needs at least one color");
+    let pos = t.clamp(0.0, 1.0) * (stops.len() - 1) as f32;
+    let i = (pos.floor() as usize).min(stops.len() - 1);
+    if i + 1 == stops.len() {
+        return stops[i];
+    }
+    stops[i] + (stops[i + 1] - stops[i]) * (pos - i as f32)
+}
+
+/// Color as hue, saturation, value and alpha.
+///
+/// Hue is in degrees in [0, 360), the other components are in [0, 1]. The components are
+/// computed from the sRGB color values like in most image editors, alpha is the sRGB alpha value
+/// scaled to [0, 1].
+///
+/// # Examples
+///
+/// ```
+/// use calx::{Hsva, SRgba};
+///
+/// let orange = Hsva::from(SRgba::rgb(255, 128, 0));
+/// assert!((orange.h - 30.1).abs() < 0.1);
+/// assert!((orange.v - 1.0).abs() < 1e-5);
+///
+/// // Darken by dropping the value.
+/// let brown = Hsva { v: 0.5, ..orange };
+/// assert_eq!(SRgba::from(brown), SRgba::rgb(128, 64, 0));
+/// ```
+#[derive(Copy, Clone, PartialEq, PartialOrd, Debug, Serialize, Deserialize)]
+pub struct Hsva {
+    pub h: f32,
+    pub s: f32,
+    pub v: f32,
+    pub a: f32,
+}
+
+impl Hsva {
+    pub fn new(h: f32, s: f32, v: f32, a: f32) -> Hsva { Hsva { h, s, v, a } }
+}
+
+impl From<Rgba> for Hsva {
+    fn from(c: Rgba) -> Hsva {
+        let (h, min, max) = hue(c);
+        let s = if max > 0.0 { (max - min) / max } else { 0.0 };
+        Hsva::new(h, s, max, to_srgb(c.a))
+    }
+}
+
+impl From<Hsva> for Rgba {
+    fn from(c: Hsva) -> Rgba {
+        let chroma = c.v * c.s;
+        from_hue(c.h, chroma, c.v - chroma, c.a)
+    }
+}
+
+impl From<SRgba> for Hsva {
+    fn from(c: SRgba) -> Hsva { Rgba::from(c).into() }
+}
+
+impl From<Hsva> for SRgba {
+    fn from(c: Hsva) -> SRgba { Rgba::from(c).into() }
+}
+
+/// Color as hue, saturation, lightness and alpha.
+///
+/// The components have the same ranges as in `Hsva`. Lightness 0.5 with full saturation gives
+/// the pure hue, 0 is black and 1 is white.
+///
+/// # Examples
+///
+/// ```
+/// use calx::{Hsla, SRgba};
+///
+/// let sky = Hsla::new(210.0, 1.0, 0.75, 1.0);
+/// assert_eq!(SRgba::from(sky), SRgba::rgb(128, 191, 255));
+/// ```
+#[derive(Copy, Clone, PartialEq, PartialOrd, Debug, Serialize, Deserialize)]
+pub struct Hsla {
+    pub h: f32,
+    pub s: f32,
+    pub l: f32,
+    pub a: f32,
+}
+
+impl Hsla {
+    pub fn new(h: f32, s: f32, l: f32, a: f32) -> Hsla { Hsla { h, s, l, a } }
+}
+
+impl From<Rgba> for Hsla {
+    fn from(c: Rgba) -> Hsla {
+        let (h, min, max) = hue(c);
+        let l = (max + min) / 2.0;
+        let s = if max > min {
+            (max - min) / (1.0 - (2.0 * l - 1.0).abs())
+        } else {
+            0.0
+        };
+        Hsla::new(h, s, l, to_srgb(c.a))
+    }
+}
+
+impl From<Hsla> for Rgba {
+    fn from(c: Hsla) -> Rgba {
+        let chroma = (1.0 - (2.0 * c.l - 1.0).abs()) * c.s;
+        from_hue(c.h, chroma, c.l - chroma / 2.0, c.a)
+    }
+}
+
+impl From<SRgba> for Hsla {
+    fn from(c: SRgba) -> Hsla { Rgba::from(c).into() }
+}
+
+impl From<Hsla> for SRgba {
+    fn from(c: Hsla) -> SRgba { Rgba::from(c).into() }
+}
+
+/// Return the hue and the smallest and largest sRGB components of a color.
+fn hue(c: Rgba) -> (f32, f32, f32) {
+    let (r, g, b) = (to_srgb(c.r), to_srgb(c.g), to_srgb(c.b));
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let d = max - min;
+
+    let h = if d <= 0.0 {
+        0.0
+    } else if max == r {
+        60.0 * ((g - b) / d).rem_euclid(6.0)
+    } else if max == g {
+        60.0 * ((b - r) / d + 2.0)
+    } else {
+        60.0 * ((r - g) / d + 4.0)
+    };
+    (h, min, max)
+}
+
+/// Build a linear color from hue, chroma, sRGB offset and sRGB alpha.
+fn from_hue(h: f32, chroma: f32, m: f32, a: f32) -> Rgba {
+    let h = h.rem_euclid(360.0) / 60.0;
+    let x = chroma * (1.0 - (h % 2.0 - 1.0).abs());
+    let (r, g, b) = match h as u32 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    Rgba::new(
+        to_linear(r + m),
+        to_linear(g + m),
+        to_linear(b + m),
+        to_linear(a),
+    )
+}
+
+/// Color gradient through colors at given positions.
+///
+/// Unlike `gradient`, the color stops can be at arbitrary positions. Colors are interpolated in
+/// linear color space. Positions before the first stop get the first color and positions after
+/// the last stop get the last one.
+///
+/// # Examples
+///
+/// ```
+/// use calx::{ColorRamp, SRgba};
+///
+/// let health_bar = ColorRamp::new()
+///     .stop(0.0, SRgba::rgb(255, 0, 0))
+///     .stop(0.25, SRgba::rgb(255, 255, 0))
+///     .stop(1.0, SRgba::rgb(0, 255, 0));
+/// assert_eq!(SRgba::from(health_bar.sample(0.25)), SRgba::rgb(255, 255, 0));
+/// assert_eq!(SRgba::from(health_bar.sample(2.0)), SRgba::rgb(0, 255, 0));
+/// ```
+#[derive(Clone, PartialEq, Default, Debug, Serialize, Deserialize)]
+pub struct ColorRamp {
+    stops: Vec<(f32, Rgba)>,
+}
+
+impl ColorRamp {
+    pub fn new() -> ColorRamp { Default::default() }
+
+    /// Add a color stop to the ramp.
+    pub fn stop(mut self, pos: f32, color: impl Into<Rgba>) -> ColorRamp {
+        let i = self.stops.iter().take_while(|&&(p, _)| p <= pos).count();
+        self.stops.insert(i, (pos, color.into()));
+        self
+    }
+
+    pub fn stops(&self) -> &[(f32, Rgba)] { &self.stops }
+
+    /// Return the color of the ramp at a position.
+    ///
+    /// A ramp with no stops is transparent black.
+    pub fn sample(&self, pos: f32) -> Rgba {
+        let i = self.stops.iter().take_while(|&&(p, _)| p <= pos).count();
+        if self.stops.is_empty() {
+            return Rgba::new(0.0, 0.0, 0.0, 0.0);
+        } else if i == 0 {
+            return self.stops[0].1;
+        } else if i == self.stops.len() {
+            return self.stops[i - 1].1;
+        }
+
+        let (p1, c1) = self.stops[i - 1];
+        let (p2, c2) = self.stops[i];
+        c1 + (c2 - c1) * ((pos - p1) / (p2 - p1))
+    }
+}
+
 macro_rules! color_constants {
     {
         $($name:ident = ([$sr:expr, $sg:expr, $sb:expr], [$r:expr, $g:expr, $b:expr]),)+
@@ -926,6 +1158,11 @@ mod test {
             SRgba::from_str("RED")
         );
         assert_eq!(Ok(Rgba::new(1.0, 0.0, 0.0, 1.0)), Rgba::from_str("RED"));
+        assert_eq!(Rgba::from_str("powderblue").unwrap().to_string(), "#B0E0E6FF");
+        assert_eq!(
+            Rgba::from_str(&Rgba::from_str("#12345678").unwrap().to_string()),
+            Rgba::from_str("#12345678")
+        );
 
         assert_eq!(SRgba::new(0x33, 0x77, 0xbb, 0xff), 0x3377bbff.into());
 
@@ -965,4 +1202,30 @@ mod test {
             assert_eq!(sc, SRgba::from(c));
         }
     }
+
+    #[test]
+    fn test_hsv_hsl() {
+        use super::{Hsla, Hsva, Rgba, SRgba};
+        use rand::Rng;
+        use rng::seeded_rng;
+
+        let mut rng = seeded_rng(&1);
+        for _ in 0..1000 {
+            let c = SRgba::new(rng.gen(), rng.gen(), rng.gen(), rng.gen());
+            assert_eq!(c, SRgba::from(Hsva::from(c)));
+            assert_eq!(c, SRgba::from(Hsla::from(c)));
+        }
+
+        let close = |a: [f32; 4], b: [f32; 4]| a.iter().zip(&b).all(|(x, y)| (x - y).abs() < 1e-5);
+        let red = Rgba::new(1.0, 0.0, 0.0, 1.0);
+        let hsv = Hsva::from(red);
+        assert!(close([hsv.h, hsv.s, hsv.v, hsv.a], [0.0, 1.0, 1.0, 1.0]));
+        let hsl = Hsla::from(red);
+        assert!(close([hsl.h, hsl.s, hsl.l, hsl.a], [0.0, 1.0, 0.5, 1.0]));
+        // Hue wraps around.
+        assert_eq!(
+            SRgba::from(Hsva::new(-120.0, 1.0, 1.0, 1.0)),
+            SRgba::rgb(0, 0, 255)
+        );
+    }
 }
@@ -0,0 +1,264 @@
+//! Persistent settings files
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::map::Entry;
+use serde_json::{self, Value};
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use system::app_data_path;
+
+/// Error from loading or storing a config file.
+#[derive(Debug)]
+pub enum ConfigError {
+    /// Reading or writing the file failed.
+    Io(io::Error),
+    /// The file could not be parsed into the settings type.
+    Parse(serde_json::Error),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ConfigError::Io(ref e) => write!(f, "Config file IO error: {}", e),
+            ConfigError::Parse(ref e) => write!(f, "Malformed config file: {}", e),
+        }
+    }
+}
+
+impl Error for ConfigError {}
+
+impl From<io::Error> for ConfigError {
+    fn from(e: io::Error) -> ConfigError { ConfigError::Io(e) }
+}
+
+impl From<serde_json::Error> for ConfigError {
+    fn from(e: serde_json::Error) -> ConfigError { ConfigError::Parse(e) }
+}
+
+/// Game settings stored in a JSON file.
+///
+/// Fields missing from the file get their values from the settings type's `Default`, and the
+/// file is written back with the missing fields filled in, so new settings added in a game
+/// update show up in the player's existing file. A missing file is created with the default
+/// settings.
+///
+/// The game can call `reload_if_changed` every now and then to pick up changes the player made
+/// to the file while the game is running.
+///
+/// # Examples
+///
+/// ```no_run
+/// # #[macro_use] extern crate serde_derive;
+/// # extern crate calx;
+/// # fn main() {
+/// use calx::Config;
+///
+/// #[derive(Serialize, Deserialize)]
+/// #[serde(default)]
+/// struct Settings {
+///     fullscreen: bool,
+///     volume: f32,
+/// }
+///
+/// impl Default for Settings {
+///     fn default() -> Settings {
+///         Settings {
+///             fullscreen: false,
+///             volume: 0.8,
+///         }
+///     }
+/// }
+///
+/// let mut config: Config<Settings> = Config::load("mygame", "settings.json").unwrap();
+/// println!("Volume is {}", config.volume);
+///
+/// // Once per second or so.
+/// if config.reload_if_changed().unwrap() {
+///     println!("Settings changed, volume is now {}", config.volume);
+/// }
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct Config<T> {
+    path: PathBuf,
+    value: T,
+    /// File contents after the last load or save, for noticing changes.
+    contents: String,
+}
+
+impl<T: Serialize + DeserializeOwned + Default> Config<T> {
+    /// Load a settings file from the application data directory.
+    pub fn load(app_name: &str, file_name: &str) -> Result<Config<T>, ConfigError> {
+        Config::load_from(app_data_path(app_name).join(file_name))
+    }
+
+    /// Load a settings file from a path.
+    pub fn load_from(path: impl Into<PathBuf>) -> Result<Config<T>, ConfigError> {
+        let mut ret = Config {
+            path: path.into(),
+            value: T::default(),
+            contents: String::new(),
+        };
+        ret.reload()?;
+        Ok(ret)
+    }
+
+    /// Return the path of the settings file.
+    pub fn path(&self) -> &Path { &self.path }
+
+    /// Change the settings and write them to the file.
+    pub fn set(&mut self, value: T) -> Result<(), ConfigError> {
+        self.value = value;
+        self.save()
+    }
+
+    /// Write the current settings to the file.
+    pub fn save(&mut self) -> Result<(), ConfigError> {
+        let contents = serde_json::to_string_pretty(&self.value)?;
+        self.write(contents)
+    }
+
+    /// Load the settings again if the file has changed since it was last loaded or saved.
+    ///
+    /// Returns whether the settings were reloaded. If the file can't be parsed, the error is
+    /// returned and the old settings are kept.
+    pub fn reload_if_changed(&mut self) -> Result<bool, ConfigError> {
+        match fs::read_to_string(&self.path) {
+            Ok(ref s) if *s == self.contents => Ok(false),
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e.into()),
+            Ok(_) => self.reload().map(|_| true),
+        }
+    }
+
+    fn reload(&mut self) -> Result<(), ConfigError> {
+        let mut merged = serde_json::to_value(T::default())?;
+        match fs::read_to_string(&self.path) {
+            Ok(s) => {
+                // Remember the file as it was read, so that a broken file is only reported once
+                // and a failure writing it back doesn't show up as a change.
+                let file = serde_json::from_str::<Value>(&s);
+                self.contents = s;
+                merge(&mut merged, file?);
+            }
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+        let contents = serde_json::to_string_pretty(&merged)?;
+        self.value = serde_json::from_value(merged)?;
+        self.write(contents)
+    }
+
+    fn write(&mut self, contents: String) -> Result<(), ConfigError> {
+        if contents != self.contents {
+            if let Some(dir) = self.path.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            fs::write(&self.path, &contents)?;
+            self.contents = contents;
+        }
+        Ok(())
+    }
+}
+
+impl<T> ::std::ops::Deref for Config<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T { &self.value }
+}
+
+/// Merge the fields of a JSON value over another one.
+///
+/// Objects are merged field by field and other values are replaced.
+fn merge(base: &mut Value, over: Value) {
+    match (base, over) {
+        (&mut Value::Object(ref mut base), Value::Object(over)) => {
+            for (k, v) in over {
+                match base.entry(k) {
+                    Entry::Occupied(mut e) => merge(e.get_mut(), v),
+                    Entry::Vacant(e) => {
+                        e.insert(v);
+                    }
+                }
+            }
+        }
+        (base, over) => *base = over,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Config;
+    use std::fs;
+    use tempdir::TempDir;
+
+    #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+    #[serde(default)]
+    struct Settings {
+        name: String,
+        volume: f32,
+        keys: Keys,
+    }
+
+    #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+    #[serde(default)]
+    struct Keys {
+        up: char,
+        down: char,
+    }
+
+    impl Default for Settings {
+        fn default() -> Settings {
+            Settings {
+                name: "player".to_string(),
+                volume: 0.5,
+                keys: Keys::default(),
+            }
+        }
+    }
+
+    impl Default for Keys {
+        fn default() -> Keys {
+            Keys { up: 'k', down: 'j' }
+        }
+    }
+
+    #[test]
+    fn test_config() {
+        let dir = TempDir::new("calx-config").unwrap();
+        let path = dir.path().join("game").join("settings.json");
+
+        // Missing file gets created with the defaults.
+        let mut config: Config<Settings> = Config::load_from(&path).unwrap();
+        assert_eq!(*config, Settings::default());
+        assert!(path.exists());
+        assert!(!config.reload_if_changed().unwrap());
+
+        // Missing fields are filled in and written back.
+        fs::write(&path, r#"{ "volume": 1.0, "keys": { "up": "w" } }"#).unwrap();
+        assert!(config.reload_if_changed().unwrap());
+        assert_eq!(config.volume, 1.0);
+        assert_eq!(config.keys.up, 'w');
+        assert_eq!(config.keys.down, 'j');
+        assert_eq!(config.name, "player");
+        let saved = fs::read_to_string(&path).unwrap();
+        assert!(saved.contains("\"down\": \"j\""));
+        assert!(!config.reload_if_changed().unwrap());
+
+        // Broken edits keep the old settings.
+        fs::write(&path, "{ volume: ").unwrap();
+        assert!(config.reload_if_changed().is_err());
+        assert!(!config.reload_if_changed().unwrap());
+        assert_eq!(config.volume, 1.0);
+
+        let mut settings = (*config).clone();
+        settings.name = "rogue".to_string();
+        config.set(settings).unwrap();
+        let loaded: Config<Settings> = Config::load_from(config.path()).unwrap();
+        assert_eq!(loaded.name, "rogue");
+    }
+}
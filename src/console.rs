@@ -0,0 +1,444 @@
+//! Drop-down debug console with user-defined commands
+
+use euclid::{point2, rect, Rect};
+use gui::{GuiDraw, GuiEvent, GuiStyle};
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::fmt;
+use text::split_line;
+
+type CommandHandler = Box<dyn FnMut(&[&str]) -> String>;
+
+/// In-game console for debug commands and log output.
+///
+/// Commands are registered with a name and a handler function that gets the whitespace-separated
+/// arguments of the command line and returns text to print. The console is also a text sink
+/// through `fmt::Write`, so log output can be mirrored into it with `writeln!`.
+///
+/// Game state can be exposed for inspection with `inspect`, typing the name of an inspected
+/// variable prints its current value. Tab completes command and variable names, and the up and
+/// down keys step through the history of entered lines.
+///
+/// The backend toggles the console with a key of its choosing, sends input events to it with
+/// `event` while it's open, and draws the commands from `draw`.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate euclid;
+/// # extern crate calx;
+/// # fn main() {
+/// use std::fmt::Write;
+/// use euclid::rect;
+/// use calx::{Console, GuiEvent, GuiStyle};
+///
+/// let mut console = Console::new(100);
+/// console.register("spawn", |args| match args {
+///     [name] => format!("Spawned {}", name),
+///     _ => "Usage: spawn <name>".to_string(),
+/// });
+///
+/// writeln!(console, "Level {} generated", 1).unwrap();
+/// console.inspect("turn", 12);
+///
+/// console.toggle();
+/// for c in "sp".chars() {
+///     console.event(GuiEvent::Char(c));
+/// }
+/// console.event(GuiEvent::Tab);
+/// for c in "orc".chars() {
+///     console.event(GuiEvent::Char(c));
+/// }
+/// console.event(GuiEvent::Enter);
+/// console.execute("turn");
+///
+/// let log: Vec<&str> = console.lines().collect();
+/// assert_eq!(
+///     log,
+///     vec!["Level 1 generated", "> spawn orc", "Spawned orc", "> turn", "turn = 12"]
+/// );
+///
+/// let _draw = console.draw(rect(0.0, 0.0, 320.0, 120.0), &GuiStyle::default());
+/// # }
+/// ```
+pub struct Console {
+    commands: BTreeMap<String, CommandHandler>,
+    /// Inspected variables with their latest values.
+    variables: BTreeMap<String, String>,
+    lines: VecDeque<String>,
+    max_lines: usize,
+    /// Text written without a line end yet.
+    partial: String,
+    input: String,
+    /// Previously entered lines from oldest to newest.
+    history: Vec<String>,
+    /// Position in history when browsing it, `history.len()` when at the new input line.
+    history_pos: usize,
+    /// Number of rows the log view is scrolled back from the latest line.
+    scroll: usize,
+    is_open: bool,
+}
+
+impl Console {
+    /// Create a console that keeps at most `max_lines` lines of log.
+    pub fn new(max_lines: usize) -> Console {
+        Console {
+            commands: BTreeMap::new(),
+            variables: BTreeMap::new(),
+            lines: VecDeque::new(),
+            max_lines,
+            partial: String::new(),
+            input: String::new(),
+            history: Vec::new(),
+            history_pos: 0,
+            scroll: 0,
+            is_open: false,
+        }
+    }
+
+    /// Register a command handler.
+    ///
+    /// The handler gets the arguments after the command name and returns text to print,
+    /// empty text prints nothing. Registering a name again replaces the old handler.
+    pub fn register<F>(&mut self, name: &str, handler: F)
+    where
+        F: FnMut(&[&str]) -> String + 'static,
+    {
+        self.commands.insert(name.to_string(), Box::new(handler));
+    }
+
+    /// Return the names of the registered commands in alphabetical order.
+    pub fn command_names(&self) -> impl Iterator<Item = &str> {
+        self.commands.keys().map(|s| s.as_str())
+    }
+
+    /// Set the value of an inspected variable.
+    ///
+    /// Call this whenever the value changes, or just once every frame. Typing the name of the
+    /// variable in the console prints the latest value. Command names take precedence over
+    /// variable names.
+    pub fn inspect<T: fmt::Display>(&mut self, name: &str, value: T) {
+        self.variables.insert(name.to_string(), value.to_string());
+    }
+
+    /// Iterate the inspected variables and their values in alphabetical order.
+    pub fn variables(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.variables.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+
+    /// Print text to the console log, one log line per line of text.
+    ///
+    /// Text written through `fmt::Write` is collected until a line end, printing also ends any
+    /// unfinished line.
+    pub fn print(&mut self, text: &str) {
+        self.write(text);
+        if !self.partial.is_empty() {
+            self.end_line();
+        }
+    }
+
+    fn write(&mut self, text: &str) {
+        let mut parts = text.split('\n');
+        self.partial.push_str(parts.next().unwrap_or(""));
+        for part in parts {
+            self.end_line();
+            self.partial.push_str(part);
+        }
+    }
+
+    fn end_line(&mut self) {
+        let line = ::std::mem::take(&mut self.partial);
+        self.lines.push_back(line);
+        while self.lines.len() > self.max_lines {
+            self.lines.pop_front();
+        }
+    }
+
+    /// Iterate the lines of the log from oldest to newest.
+    pub fn lines(&self) -> impl Iterator<Item = &str> { self.lines.iter().map(|s| s.as_str()) }
+
+    /// Run a command line as if it had been typed into the console.
+    pub fn execute(&mut self, line: &str) {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        if words.is_empty() {
+            return;
+        }
+        self.print(&format!("> {}", line.trim()));
+        let output = match self.commands.get_mut(words[0]) {
+            Some(handler) => handler(&words[1..]),
+            None => match self.variables.get(words[0]) {
+                Some(value) => format!("{} = {}", words[0], value),
+                None => format!("Unknown command '{}'", words[0]),
+            },
+        };
+        self.print(&output);
+        self.scroll = 0;
+    }
+
+    /// Complete the command or variable name being typed on the input line.
+    ///
+    /// A unique match is completed in full, otherwise the input is extended to the longest
+    /// common prefix of the matches and the matches are printed.
+    fn complete(&mut self) {
+        if self.input.contains(char::is_whitespace) {
+            return;
+        }
+        let matches: BTreeSet<&String> = self
+            .commands
+            .keys()
+            .chain(self.variables.keys())
+            .filter(|name| name.starts_with(&self.input))
+            .collect();
+        let mut names = matches.iter();
+        let mut prefix = match names.next() {
+            Some(first) => first.as_str(),
+            None => return,
+        };
+        for name in names {
+            let len = prefix
+                .char_indices()
+                .zip(name.chars())
+                .find(|&((_, a), b)| a != b)
+                .map_or(prefix.len(), |((i, _), _)| i);
+            prefix = &prefix[..len];
+        }
+
+        if matches.len() == 1 {
+            self.input = format!("{} ", prefix);
+        } else {
+            let input = prefix.to_string();
+            let list = matches
+                .iter()
+                .map(|s| s.as_str())
+                .collect::<Vec<_>>()
+                .join("  ");
+            self.print(&list);
+            self.input = input;
+        }
+    }
+
+    /// Move in the input history, towards older lines if `offset` is negative.
+    fn browse_history(&mut self, offset: isize) {
+        let pos = (self.history_pos as isize + offset).max(0) as usize;
+        let pos = pos.min(self.history.len());
+        if pos != self.history_pos {
+            self.history_pos = pos;
+            self.input = self.history.get(pos).cloned().unwrap_or_default();
+        }
+    }
+
+    pub fn is_open(&self) -> bool { self.is_open }
+
+    /// Open or close the console.
+    pub fn toggle(&mut self) { self.is_open = !self.is_open; }
+
+    /// Handle an input event.
+    ///
+    /// Returns whether the console used the event. A closed console doesn't use any events.
+    pub fn event(&mut self, event: GuiEvent) -> bool {
+        if !self.is_open {
+            return false;
+        }
+        match event {
+            GuiEvent::Char(c) if !c.is_control() => self.input.push(c),
+            GuiEvent::Backspace => {
+                self.input.pop();
+            }
+            GuiEvent::Enter => {
+                let line = ::std::mem::take(&mut self.input);
+                if !line.trim().is_empty() && self.history.last() != Some(&line) {
+                    self.history.push(line.clone());
+                    if self.history.len() > self.max_lines {
+                        self.history.remove(0);
+                    }
+                }
+                self.history_pos = self.history.len();
+                self.execute(&line);
+            }
+            GuiEvent::Tab => self.complete(),
+            GuiEvent::Up => self.browse_history(-1),
+            GuiEvent::Down => self.browse_history(1),
+            GuiEvent::Scroll(amount) => {
+                let scroll = (self.scroll as f32 + amount).round().max(0.0) as usize;
+                self.scroll = scroll.min(self.lines.len());
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    /// Draw the console log and input line into an area.
+    ///
+    /// Long log lines are wrapped to the width of the area.
+    pub fn draw(&self, area: Rect<f32>, style: &GuiStyle) -> Vec<GuiDraw> {
+        let mut ret = vec![GuiDraw::Fill {
+            area,
+            color: style.background,
+        }];
+        let (cw, row_h) = (style.char_width, style.row_height);
+        let rows = (area.size.height / row_h).floor() as usize;
+        if rows == 0 {
+            return ret;
+        }
+        let log_rows = rows - 1;
+        let x = area.origin.x + cw / 2.0;
+
+        // Wrap lines from the newest one up until the view is full.
+        let mut wrapped: Vec<&str> = Vec::new();
+        for line in self.lines.iter().rev() {
+            if wrapped.len() >= log_rows + self.scroll {
+                break;
+            }
+            let mut parts: Vec<&str> = split_line(line, |_| cw, area.size.width - cw).collect();
+            parts.reverse();
+            wrapped.extend(parts);
+        }
+        let skip = self.scroll.min(wrapped.len().saturating_sub(log_rows));
+        let visible: Vec<&str> = wrapped.into_iter().skip(skip).take(log_rows).collect();
+        let top = log_rows - visible.len();
+        for (i, text) in visible.into_iter().rev().enumerate() {
+            ret.push(GuiDraw::Text {
+                pos: point2(x, area.origin.y + (top + i) as f32 * row_h),
+                text: text.to_string(),
+                color: style.text,
+            });
+        }
+
+        let y = area.origin.y + log_rows as f32 * row_h;
+        let prompt = format!("> {}", self.input);
+        let caret_x = x + cw * prompt.chars().count() as f32;
+        ret.push(GuiDraw::Text {
+            pos: point2(x, y),
+            text: prompt,
+            color: style.text,
+        });
+        ret.push(GuiDraw::Fill {
+            area: rect(caret_x, y, 1.0, row_h),
+            color: style.highlight,
+        });
+        ret
+    }
+}
+
+impl fmt::Write for Console {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.write(s);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Console;
+    use euclid::rect;
+    use gui::{GuiDraw, GuiEvent, GuiStyle};
+    use std::fmt::Write;
+
+    #[test]
+    fn test_console() {
+        let mut console = Console::new(4);
+        console.register("add", |args| {
+            let sum: i32 = args.iter().filter_map(|a| a.parse::<i32>().ok()).sum();
+            sum.to_string()
+        });
+        console.register("quiet", |_| String::new());
+        let names: Vec<&str> = console.command_names().collect();
+        assert_eq!(names, vec!["add", "quiet"]);
+
+        // Log output can be written in pieces.
+        write!(console, "x = {}", 1).unwrap();
+        write!(console, ", y = {}\nz", 2).unwrap();
+        assert_eq!(console.lines().collect::<Vec<_>>(), vec!["x = 1, y = 2"]);
+        console.print("");
+        assert_eq!(console.lines().last(), Some("z"));
+
+        // Closed console ignores input.
+        assert!(!console.event(GuiEvent::Char('x')));
+        console.toggle();
+        assert!(console.is_open());
+        for c in "ad 1".chars() {
+            console.event(GuiEvent::Char(c));
+        }
+        console.event(GuiEvent::Enter);
+        assert_eq!(console.lines().last(), Some("Unknown command 'ad'"));
+
+        console.execute("  add 1 2   3 ");
+        console.execute("quiet");
+        console.execute("");
+        assert_eq!(
+            console.lines().collect::<Vec<_>>(),
+            vec!["Unknown command 'ad'", "> add 1 2   3", "6", "> quiet"]
+        );
+
+        // Three rows, two for the log and one for the input line.
+        let style = GuiStyle::default();
+        let area = rect(0.0, 0.0, 200.0, style.row_height * 3.0);
+        let texts = |console: &Console| -> Vec<String> {
+            console
+                .draw(area, &style)
+                .into_iter()
+                .filter_map(|d| match d {
+                    GuiDraw::Text { text, .. } => Some(text),
+                    _ => None,
+                })
+                .collect()
+        };
+        console.event(GuiEvent::Char('q'));
+        assert_eq!(texts(&console), vec!["6", "> quiet", "> q"]);
+        console.event(GuiEvent::Scroll(10.0));
+        assert_eq!(texts(&console)[0], "Unknown command 'ad'");
+    }
+
+    #[test]
+    fn test_history_and_completion() {
+        let mut console = Console::new(10);
+        console.register("spawn", |args| args.join(" "));
+        console.register("speed", |_| String::new());
+        console.inspect("seed", 1234);
+        console.inspect("seed", 4321);
+        console.toggle();
+        let type_line = |console: &mut Console, line: &str| {
+            for c in line.chars() {
+                console.event(GuiEvent::Char(c));
+            }
+        };
+
+        // Variables print their latest value.
+        type_line(&mut console, "seed");
+        console.event(GuiEvent::Enter);
+        assert_eq!(console.lines().last(), Some("seed = 4321"));
+
+        // Ambiguous completion extends to the common prefix and lists the choices.
+        type_line(&mut console, "s");
+        console.event(GuiEvent::Tab);
+        assert_eq!(console.input, "s");
+        assert_eq!(console.lines().last(), Some("seed  spawn  speed"));
+        type_line(&mut console, "p");
+        console.event(GuiEvent::Tab);
+        assert_eq!(console.input, "sp");
+        type_line(&mut console, "a");
+        console.event(GuiEvent::Tab);
+        assert_eq!(console.input, "spawn ");
+        type_line(&mut console, "orc");
+        console.event(GuiEvent::Enter);
+        assert_eq!(console.lines().last(), Some("orc"));
+
+        // Arguments aren't completed.
+        type_line(&mut console, "spawn s");
+        console.event(GuiEvent::Tab);
+        assert_eq!(console.input, "spawn s");
+        console.event(GuiEvent::Enter);
+
+        console.event(GuiEvent::Up);
+        assert_eq!(console.input, "spawn s");
+        console.event(GuiEvent::Up);
+        assert_eq!(console.input, "spawn orc");
+        console.event(GuiEvent::Up);
+        console.event(GuiEvent::Up);
+        assert_eq!(console.input, "seed");
+        console.event(GuiEvent::Down);
+        assert_eq!(console.input, "spawn orc");
+        console.event(GuiEvent::Down);
+        console.event(GuiEvent::Down);
+        assert_eq!(console.input, "");
+    }
+}
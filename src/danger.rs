@@ -0,0 +1,153 @@
+//! Danger fields for keeping out of the reach of threats
+
+use hex_fov::{HexFov, Sight};
+use std::collections::{HashMap, HashSet};
+use CellVector;
+
+/// Enemy or other source of danger that can hit the cells it sees within its reach.
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Threat {
+    pub pos: CellVector,
+    /// Distance in cells the threat can attack to, 1 for melee.
+    pub reach: i32,
+    /// How dangerous the threat is, for example its expected damage per turn.
+    pub strength: f32,
+}
+
+impl Threat {
+    pub fn new(pos: CellVector, reach: i32, strength: f32) -> Threat {
+        Threat {
+            pos,
+            reach,
+            strength,
+        }
+    }
+}
+
+/// Per-cell danger from visible threats and hazardous terrain.
+///
+/// Each threat adds its strength to every cell it can see within its reach. Hazards like fire
+/// add danger to single cells. Use `cost` as an extra path cost to route around danger, or
+/// check `is_safe` to rule dangerous cells out completely. The cells can also be drawn as an
+/// overlay with `iter`.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate euclid;
+/// # extern crate calx;
+/// # fn main() {
+/// use euclid::vec2;
+/// use calx::{DangerMap, Threat};
+///
+/// // Archer behind a wall segment at x = 1.
+/// let archer = Threat::new(vec2(0, 0), 4, 3.0);
+/// let orc = Threat::new(vec2(-5, 0), 1, 5.0);
+/// let mut danger = DangerMap::new(&[archer, orc], |p| p.x != 1 || p.y < -1);
+/// // Fire on the floor.
+/// danger.add_hazard(vec2(0, 5), 10.0);
+///
+/// assert_eq!(danger.get(vec2(-3, 0)), 3.0);
+/// assert_eq!(danger.get(vec2(-4, 0)), 8.0);
+/// // Shadow of the wall.
+/// assert!(danger.is_safe(vec2(3, 0)));
+/// assert!(!danger.is_safe(vec2(0, 5)));
+/// # }
+/// ```
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct DangerMap {
+    cells: HashMap<CellVector, f32>,
+}
+
+impl DangerMap {
+    /// Build the danger map of a set of threats.
+    ///
+    /// Threats can't attack through cells that aren't transparent.
+    pub fn new<'a, I, F>(threats: I, is_transparent: F) -> DangerMap
+    where
+        I: IntoIterator<Item = &'a Threat>,
+        F: Fn(CellVector) -> bool,
+    {
+        let mut ret = DangerMap::default();
+        for threat in threats {
+            ret.add_threat(threat, &is_transparent);
+        }
+        ret
+    }
+
+    /// Add the danger of another threat.
+    pub fn add_threat(&mut self, threat: &Threat, is_transparent: &dyn Fn(CellVector) -> bool) {
+        if threat.reach < 0 {
+            return;
+        }
+        // Collect into a set first, the FOV can visit cells on arc boundaries more than once.
+        let cells: HashSet<CellVector> =
+            HexFov::new(Sight::new(threat.pos, threat.reach, is_transparent))
+                .map(|(offset, _)| threat.pos + offset)
+                .collect();
+        for pos in cells {
+            self.add_hazard(pos, threat.strength);
+        }
+    }
+
+    /// Add danger to a single cell.
+    pub fn add_hazard(&mut self, pos: CellVector, danger: f32) {
+        *self.cells.entry(pos).or_insert(0.0) += danger;
+    }
+
+    /// Return the danger level of a cell.
+    pub fn get(&self, pos: CellVector) -> f32 { self.cells.get(&pos).cloned().unwrap_or(0.0) }
+
+    /// Return whether a cell has no danger.
+    pub fn is_safe(&self, pos: CellVector) -> bool { self.get(pos) <= 0.0 }
+
+    /// Return the extra path cost for moving into a cell.
+    ///
+    /// The weight is the extra cost of one unit of danger.
+    pub fn cost(&self, pos: CellVector, weight: f32) -> f32 { self.get(pos) * weight }
+
+    /// Iterate the cells with danger.
+    pub fn iter(&self) -> impl Iterator<Item = (CellVector, f32)> + '_ {
+        self.cells.iter().map(|(&p, &d)| (p, d))
+    }
+
+    /// Return the most dangerous cell level in the map, for scaling overlay colors.
+    pub fn max_danger(&self) -> f32 { self.cells.values().cloned().fold(0.0, f32::max) }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{DangerMap, Threat};
+    use euclid::vec2;
+    use hex::HexGeom;
+
+    #[test]
+    fn test_danger_map() {
+        let goblin = Threat::new(vec2(0, 0), 1, 2.0);
+        let danger = DangerMap::new(&[goblin], |_| true);
+        assert_eq!(danger.iter().count(), 7);
+        assert!(danger.iter().all(|(p, d)| p.hex_dist() <= 1 && d == 2.0));
+        assert_eq!(danger.cost(vec2(1, 1), 0.5), 1.0);
+        assert!(danger.is_safe(vec2(2, 0)));
+
+        // Overlapping threats add up.
+        let mut danger = DangerMap::new(&[goblin, Threat::new(vec2(2, 0), 1, 1.5)], |_| true);
+        assert_eq!(danger.get(vec2(1, 0)), 3.5);
+        assert_eq!(danger.max_danger(), 3.5);
+        danger.add_hazard(vec2(9, 9), 4.0);
+        assert_eq!(danger.max_danger(), 4.0);
+
+        // Walls block reach.
+        let danger = DangerMap::new(&[Threat::new(vec2(0, 0), 3, 1.0)], |p| p != vec2(1, 0));
+        assert!(!danger.is_safe(vec2(1, 0)));
+        assert!(danger.is_safe(vec2(2, 0)));
+        assert!(!danger.is_safe(vec2(-3, 0)));
+
+        assert!(
+            DangerMap::new(&[Threat::new(vec2(0, 0), -1, 1.0)], |_| true)
+                .iter()
+                .next()
+                .is_none()
+        );
+    }
+}
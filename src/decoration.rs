@@ -0,0 +1,238 @@
+//! Cosmetic map decoration passes
+
+use hex::{hex_disc, hex_neighbors};
+use rand::distributions::Uniform;
+use rand::{Rng, XorShiftRng};
+use rng::{seeded_rng, RngExt};
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+use CellVector;
+use Noise;
+
+/// A decoration pass modifies the cells of a generated map using a random number generator.
+pub type DecorationPass<T> = Box<dyn Fn(&mut XorShiftRng, &mut HashMap<CellVector, T>)>;
+
+/// Ordered set of decoration passes run over a map.
+///
+/// The passes are run in the order they were added. Each pass gets its own random number
+/// generator derived from the decoration seed and the pass name, so adding, removing or changing
+/// one pass does not change the output of the others.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate euclid;
+/// # extern crate calx;
+/// # fn main() {
+/// use std::collections::HashMap;
+/// use euclid::vec2;
+/// use calx::{automaton_decoration, noise_decoration, scatter_decoration, Decorator};
+///
+/// let floor_only = |c: &char, d: char| if *c == '.' { Some(d) } else { None };
+/// let decorator = Decorator::new()
+///     .pass("moss", noise_decoration(4.0, 0.7, move |c| floor_only(c, '"')))
+///     .pass("rubble", scatter_decoration(3, 0.5, move |c| floor_only(c, ',')))
+///     .pass("puddles", automaton_decoration(0.45, 3, move |c| floor_only(c, '~')));
+///
+/// let mut map = HashMap::new();
+/// for y in 0..32 {
+///     for x in 0..32 {
+///         map.insert(vec2(x, y), if x % 8 == 0 { '#' } else { '.' });
+///     }
+/// }
+///
+/// let mut map2 = map.clone();
+/// decorator.run(&123, &mut map);
+/// decorator.run(&123, &mut map2);
+/// assert_eq!(map, map2);
+/// assert!(map.iter().all(|(p, &c)| (p.x % 8 == 0) == (c == '#')));
+/// # }
+/// ```
+pub struct Decorator<T> {
+    passes: Vec<(String, DecorationPass<T>)>,
+}
+
+impl<T> Decorator<T> {
+    /// Create a decorator with no passes.
+    pub fn new() -> Decorator<T> { Decorator { passes: Vec::new() } }
+
+    /// Add a named pass to run after the previously added passes.
+    pub fn pass<F>(mut self, name: &str, f: F) -> Decorator<T>
+    where
+        F: Fn(&mut XorShiftRng, &mut HashMap<CellVector, T>) + 'static,
+    {
+        self.passes.push((name.to_string(), Box::new(f)));
+        self
+    }
+
+    /// Run all the passes over a map.
+    pub fn run(&self, seed: &impl Hash, map: &mut HashMap<CellVector, T>) {
+        for (name, pass) in &self.passes {
+            let mut rng = seeded_rng(&(seed, name));
+            pass(&mut rng, map);
+        }
+    }
+}
+
+impl<T> Default for Decorator<T> {
+    fn default() -> Self { Decorator::new() }
+}
+
+/// Return the cells of a map in a deterministic order.
+///
+/// Hash map iteration order varies between runs, so passes that consume random numbers per cell
+/// must go through the cells in a fixed order to stay reproducible.
+fn sorted_cells<T>(map: &HashMap<CellVector, T>) -> Vec<CellVector> {
+    let mut ret: Vec<CellVector> = map.keys().cloned().collect();
+    ret.sort_by_key(|p| (p.y, p.x));
+    ret
+}
+
+/// Smoothly interpolated lattice noise in [0, 1).
+fn value_noise(salt: u32, pos: CellVector, scale: f32) -> f32 {
+    let (x, y) = (pos.x as f32 / scale, pos.y as f32 / scale);
+    let (x0, y0) = (x.floor(), y.floor());
+    let smooth = |t: f32| t * t * (3.0 - 2.0 * t);
+    let (sx, sy) = (smooth(x - x0), smooth(y - y0));
+
+    let unit = Uniform::new(0.0f32, 1.0);
+    let corner = |dx: i32, dy: i32| -> f32 { unit.noise(&(salt, x0 as i32 + dx, y0 as i32 + dy)) };
+
+    let top = corner(0, 0) + (corner(1, 0) - corner(0, 0)) * sx;
+    let bottom = corner(0, 1) + (corner(1, 1) - corner(0, 1)) * sx;
+    top + (bottom - top) * sy
+}
+
+/// Decorate cells where smooth noise exceeds a threshold.
+///
+/// The noise varies over distances of roughly `scale` cells, so the decorated cells form blobs
+/// that look like patches of moss or lichen. Higher threshold values produce fewer decorations.
+/// The `decorate` function returns the decorated version of a cell or `None` if the cell can't
+/// be decorated.
+pub fn noise_decoration<T, F>(
+    scale: f32,
+    threshold: f32,
+    decorate: F,
+) -> impl Fn(&mut XorShiftRng, &mut HashMap<CellVector, T>)
+where
+    F: Fn(&T) -> Option<T>,
+{
+    debug_assert!(scale > 0.0);
+    move |rng: &mut XorShiftRng, map: &mut HashMap<CellVector, T>| {
+        let salt: u32 = rng.gen();
+        for pos in sorted_cells(map) {
+            if value_noise(salt, pos, scale) < threshold {
+                continue;
+            }
+            if let Some(t) = decorate(&map[&pos]) {
+                map.insert(pos, t);
+            }
+        }
+    }
+}
+
+/// Scatter decorations so that no two are closer than `min_distance` to each other.
+///
+/// Candidate cells are visited in random order and each one that can be decorated and is far
+/// enough from the previous decorations gets decorated with probability `density`. This gives
+/// an evenly spread Poisson disc scatter for things like rubble or bones.
+pub fn scatter_decoration<T, F>(
+    min_distance: i32,
+    density: f32,
+    decorate: F,
+) -> impl Fn(&mut XorShiftRng, &mut HashMap<CellVector, T>)
+where
+    F: Fn(&T) -> Option<T>,
+{
+    move |rng: &mut XorShiftRng, map: &mut HashMap<CellVector, T>| {
+        let mut cells = sorted_cells(map);
+        rng.shuffle(&mut cells);
+
+        let mut placed = HashSet::new();
+        for pos in cells {
+            if hex_disc(pos, min_distance - 1).any(|p| placed.contains(&p)) {
+                continue;
+            }
+            if let Some(t) = decorate(&map[&pos]) {
+                if rng.with_chance(density) {
+                    map.insert(pos, t);
+                    placed.insert(pos);
+                }
+            }
+        }
+    }
+}
+
+/// Decorate blobs of cells grown with a hex cellular automaton.
+///
+/// Each cell that can be decorated starts out alive with probability `initial_chance`. On each
+/// iteration, live cells with at least three live neighbors survive and dead cells with at least
+/// four live neighbors come alive. The cells alive after the iterations are decorated, which
+/// gives rounded patches that work well for puddles.
+pub fn automaton_decoration<T, F>(
+    initial_chance: f32,
+    iterations: usize,
+    decorate: F,
+) -> impl Fn(&mut XorShiftRng, &mut HashMap<CellVector, T>)
+where
+    F: Fn(&T) -> Option<T>,
+{
+    move |rng: &mut XorShiftRng, map: &mut HashMap<CellVector, T>| {
+        let cells: Vec<(CellVector, T)> = sorted_cells(map)
+            .into_iter()
+            .filter_map(|p| decorate(&map[&p]).map(|t| (p, t)))
+            .collect();
+
+        let mut alive: HashSet<CellVector> = cells
+            .iter()
+            .filter(|_| rng.with_chance(initial_chance))
+            .map(|&(p, _)| p)
+            .collect();
+
+        for _ in 0..iterations {
+            alive = cells
+                .iter()
+                .map(|&(p, _)| p)
+                .filter(|&p| {
+                    let n = hex_neighbors(p).filter(|q| alive.contains(q)).count();
+                    n >= if alive.contains(&p) { 3 } else { 4 }
+                })
+                .collect();
+        }
+
+        for (p, t) in cells {
+            if alive.contains(&p) {
+                map.insert(p, t);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{scatter_decoration, Decorator};
+    use euclid::vec2;
+    use hex::HexGeom;
+    use std::collections::HashMap;
+    use CellVector;
+
+    #[test]
+    fn test_scatter_distance() {
+        let decorator = Decorator::new().pass("bones", scatter_decoration(3, 1.0, |_| Some(true)));
+        let mut map: HashMap<CellVector, bool> = HashMap::new();
+        for y in -20..20 {
+            for x in -20..20 {
+                map.insert(vec2(x, y), false);
+            }
+        }
+        decorator.run(&"scatter", &mut map);
+
+        let bones: Vec<CellVector> = map.iter().filter(|e| *e.1).map(|e| *e.0).collect();
+        assert!(bones.len() > 20);
+        for &a in &bones {
+            for &b in &bones {
+                assert!(a == b || (a - b).hex_dist() >= 3);
+            }
+        }
+    }
+}
@@ -0,0 +1,147 @@
+//! Deferred publish-subscribe event delivery
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::rc::{Rc, Weak};
+
+/// Publish-subscribe message channel with deferred delivery.
+///
+/// Published events are queued until `dispatch` is called, usually once per frame, and then
+/// copied to the inbox of every live subscription. Events published while handling dispatched
+/// events are delivered on the next dispatch. The bus is a shared handle, clones of it publish
+/// to the same subscribers.
+///
+/// # Examples
+///
+/// ```
+/// use calx::EventBus;
+///
+/// let bus = EventBus::new();
+/// let ui = bus.subscribe();
+/// let game = bus.subscribe();
+///
+/// bus.publish("quit");
+/// assert_eq!(ui.poll(), None);
+///
+/// bus.dispatch();
+/// assert_eq!(ui.drain(), vec!["quit"]);
+/// assert_eq!(game.poll(), Some("quit"));
+/// assert_eq!(game.poll(), None);
+/// ```
+pub struct EventBus<E> {
+    inner: Rc<RefCell<Inner<E>>>,
+}
+
+struct Inner<E> {
+    pending: Vec<E>,
+    subscribers: Vec<Weak<RefCell<VecDeque<E>>>>,
+}
+
+/// Receiving end of an `EventBus`.
+///
+/// Dropping the subscription unsubscribes it from the bus.
+pub struct Subscription<E> {
+    inbox: Rc<RefCell<VecDeque<E>>>,
+}
+
+impl<E: Clone> EventBus<E> {
+    /// Create a new bus with no subscribers.
+    pub fn new() -> EventBus<E> {
+        EventBus {
+            inner: Rc::new(RefCell::new(Inner {
+                pending: Vec::new(),
+                subscribers: Vec::new(),
+            })),
+        }
+    }
+
+    /// Create a new subscription that receives events dispatched from now on.
+    pub fn subscribe(&self) -> Subscription<E> {
+        let inbox = Rc::new(RefCell::new(VecDeque::new()));
+        self.inner
+            .borrow_mut()
+            .subscribers
+            .push(Rc::downgrade(&inbox));
+        Subscription { inbox }
+    }
+
+    /// Queue an event for the next dispatch.
+    pub fn publish(&self, event: E) { self.inner.borrow_mut().pending.push(event); }
+
+    /// Deliver the queued events to all subscribers.
+    pub fn dispatch(&self) {
+        let mut inner = self.inner.borrow_mut();
+        let events = ::std::mem::take(&mut inner.pending);
+        inner.subscribers.retain(|s| match s.upgrade() {
+            Some(inbox) => {
+                inbox.borrow_mut().extend(events.iter().cloned());
+                true
+            }
+            None => false,
+        });
+    }
+
+    /// Return the number of live subscriptions.
+    pub fn subscriber_count(&self) -> usize {
+        self.inner
+            .borrow()
+            .subscribers
+            .iter()
+            .filter(|s| s.upgrade().is_some())
+            .count()
+    }
+}
+
+impl<E: Clone> Default for EventBus<E> {
+    fn default() -> Self { EventBus::new() }
+}
+
+impl<E> Clone for EventBus<E> {
+    fn clone(&self) -> Self {
+        EventBus {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<E> Subscription<E> {
+    /// Take the oldest delivered event.
+    pub fn poll(&self) -> Option<E> { self.inbox.borrow_mut().pop_front() }
+
+    /// Take all the delivered events.
+    pub fn drain(&self) -> Vec<E> { self.inbox.borrow_mut().drain(..).collect() }
+
+    /// Return whether there are no delivered events waiting.
+    pub fn is_empty(&self) -> bool { self.inbox.borrow().is_empty() }
+}
+
+#[cfg(test)]
+mod test {
+    use super::EventBus;
+
+    #[test]
+    fn test_event_bus() {
+        let bus = EventBus::new();
+        let a = bus.subscribe();
+        {
+            let _b = bus.subscribe();
+            assert_eq!(bus.subscriber_count(), 2);
+        }
+        assert_eq!(bus.subscriber_count(), 1);
+
+        // Events published before subscribing are still delivered on dispatch.
+        bus.clone().publish(1);
+        let c = bus.subscribe();
+        bus.publish(2);
+        bus.dispatch();
+
+        // Events published while handling dispatched events wait for the next dispatch.
+        for e in a.drain() {
+            bus.publish(e * 10);
+        }
+        assert_eq!(c.drain(), vec![1, 2]);
+        assert!(a.is_empty());
+        bus.dispatch();
+        assert_eq!(a.drain(), vec![10, 20]);
+    }
+}
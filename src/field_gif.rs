@@ -0,0 +1,398 @@
+//! Animated GIF export of cell fields and screen captures
+
+use gif::{self, Encoder, Repeat, SetParameter};
+use image::{self, RgbaImage};
+use prefab::MinimapSpace;
+use space::Transformation;
+use std::collections::{HashMap, VecDeque};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use time;
+use {CellVector, SRgba};
+
+/// Recorder for turning a sequence of cell fields into an animated GIF.
+///
+/// Push a frame for each step of a process you want to look at, such as the iterations of a
+/// cellular automaton map generator, then save the whole sequence. The cells are drawn with the
+/// hex minimap layout, each cell is a two by two block of pixels before scaling.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate euclid;
+/// # extern crate calx;
+/// # fn main() {
+/// use euclid::vec2;
+/// use calx::{gradient, hex_disc, FieldGif, Rgba, SRgba};
+///
+/// let heat = [Rgba::from(0x000000ffu32), Rgba::from(0xff0000ffu32), Rgba::from(0xffff00ffu32)];
+/// let mut gif = FieldGif::new().frame_delay(200).scale(4);
+/// for step in 1..5 {
+///     let fire = hex_disc(vec2(0, 0), step).map(|p| (p, p.x.abs().max(p.y.abs()) as f32 / 4.0));
+///     gif.push_frame(fire, |t| SRgba::from(gradient(&heat, 1.0 - t)));
+/// }
+///
+/// let mut data = Vec::new();
+/// gif.write(&mut data).unwrap();
+/// assert!(data.starts_with(b"GIF89a"));
+/// # }
+/// ```
+pub struct FieldGif {
+    frames: Vec<HashMap<CellVector, SRgba>>,
+    frame_delay_ms: u32,
+    scale: u32,
+    background: SRgba,
+}
+
+impl FieldGif {
+    /// Create an empty recorder that shows each frame for 100 ms.
+    pub fn new() -> FieldGif {
+        FieldGif {
+            frames: Vec::new(),
+            frame_delay_ms: 100,
+            scale: 1,
+            background: SRgba::rgb(0, 0, 0),
+        }
+    }
+
+    /// Set how long each frame is shown in milliseconds.
+    ///
+    /// GIF timing has 10 ms precision.
+    pub fn frame_delay(mut self, ms: u32) -> FieldGif {
+        self.frame_delay_ms = ms;
+        self
+    }
+
+    /// Set the pixel magnification of the output.
+    pub fn scale(mut self, scale: u32) -> FieldGif {
+        assert!(scale > 0, "Scale must be positive");
+        self.scale = scale;
+        self
+    }
+
+    /// Set the color for the area not covered by any cells.
+    pub fn background(mut self, color: SRgba) -> FieldGif {
+        self.background = color;
+        self
+    }
+
+    /// Add a frame with the cells mapped to colors by a function.
+    pub fn push_frame<I, T, F>(&mut self, cells: I, mut color: F)
+    where
+        I: IntoIterator<Item = (CellVector, T)>,
+        F: FnMut(T) -> SRgba,
+    {
+        self.frames
+            .push(cells.into_iter().map(|(p, x)| (p, color(x))).collect());
+    }
+
+    /// Return the number of recorded frames.
+    pub fn len(&self) -> usize { self.frames.len() }
+
+    /// Return whether no frames have been recorded.
+    pub fn is_empty(&self) -> bool { self.frames.is_empty() }
+
+    /// Draw the frames into images.
+    ///
+    /// All frames share the same bounds that cover the cells of every frame.
+    pub fn render(&self) -> Vec<RgbaImage> {
+        let points: Vec<[i32; 2]> = self
+            .frames
+            .iter()
+            .flat_map(|f| f.keys())
+            .map(|&p| MinimapSpace::unproject(p))
+            .collect();
+        if points.is_empty() {
+            return Vec::new();
+        }
+
+        let min_x = points.iter().map(|p| p[0]).min().unwrap();
+        let min_y = points.iter().map(|p| p[1]).min().unwrap();
+        let max_x = points.iter().map(|p| p[0]).max().unwrap();
+        let max_y = points.iter().map(|p| p[1]).max().unwrap();
+
+        // Cells tile the minimap plane as staggered two by two pixel blocks.
+        let s = self.scale;
+        let width = (max_x - min_x + 2) as u32 * s;
+        let height = (max_y - min_y + 2) as u32 * s;
+
+        self.frames
+            .iter()
+            .map(|frame| {
+                let mut img =
+                    RgbaImage::from_pixel(width, height, image::Rgba::from(self.background));
+                for (&p, &c) in frame {
+                    let [x, y] = MinimapSpace::unproject(p);
+                    let (x, y) = ((x - min_x) as u32 * s, (y - min_y) as u32 * s);
+                    for py in y..y + 2 * s {
+                        for px in x..x + 2 * s {
+                            img.put_pixel(px, py, image::Rgba::from(c));
+                        }
+                    }
+                }
+                img
+            })
+            .collect()
+    }
+
+    /// Encode the frames as a looping animated GIF.
+    pub fn write<W: Write>(&self, w: W) -> io::Result<()> {
+        let delay = self.frame_delay_ms;
+        write_gif(w, self.render().into_iter().map(|img| (img, delay)))
+    }
+
+    /// Save the frames into an animated GIF file.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        self.write(BufWriter::new(File::create(path)?))
+    }
+}
+
+impl Default for FieldGif {
+    fn default() -> Self { FieldGif::new() }
+}
+
+/// Rolling recorder of the most recent screen frames.
+///
+/// Push every rendered frame into the recorder and it keeps the ones from the last few seconds.
+/// When something interesting happens, such as the player hitting the bug report key, save the
+/// buffer as an animated GIF. Frames with at most 256 colors keep their exact palette.
+///
+/// Frames are only captured at the rate set with `fps` to keep the memory use in check. If the
+/// frame size changes, the earlier frames are dropped.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate image;
+/// # extern crate calx;
+/// # fn main() {
+/// use calx::FrameRecorder;
+/// use image::{Rgba, RgbaImage};
+///
+/// let mut recorder = FrameRecorder::new(2.0).fps(10.0);
+/// for i in 0..100u8 {
+///     let frame = RgbaImage::from_pixel(16, 16, Rgba([i, 0, 0, 255]));
+///     // Timestamps are normally taken from the clock with `push`.
+///     recorder.push_at(i as f64 / 30.0, frame);
+/// }
+/// // Two seconds at 10 frames per second.
+/// assert_eq!(recorder.len(), 20);
+///
+/// let mut data = Vec::new();
+/// recorder.write(&mut data).unwrap();
+/// assert!(data.starts_with(b"GIF89a"));
+/// # }
+/// ```
+pub struct FrameRecorder {
+    frames: VecDeque<(f64, RgbaImage)>,
+    duration_s: f64,
+    interval_s: f64,
+}
+
+impl FrameRecorder {
+    /// Create a recorder that keeps the frames of the last `duration_s` seconds at 30 FPS.
+    pub fn new(duration_s: f64) -> FrameRecorder {
+        FrameRecorder {
+            frames: VecDeque::new(),
+            duration_s,
+            interval_s: 1.0 / 30.0,
+        }
+    }
+
+    /// Set the highest frame rate to capture at.
+    pub fn fps(mut self, fps: f64) -> FrameRecorder {
+        assert!(fps > 0.0, "Frame rate must be positive");
+        self.interval_s = 1.0 / fps;
+        self
+    }
+
+    /// Capture a frame using the current time.
+    ///
+    /// Returns whether the frame was recorded or skipped to keep the capture frame rate.
+    pub fn push(&mut self, frame: RgbaImage) -> bool { self.push_at(time::precise_time_s(), frame) }
+
+    /// Capture a frame with a timestamp in seconds.
+    ///
+    /// Returns whether the frame was recorded or skipped to keep the capture frame rate.
+    pub fn push_at(&mut self, t: f64, frame: RgbaImage) -> bool {
+        if let Some(&(last_t, ref last)) = self.frames.back() {
+            if last.dimensions() != frame.dimensions() {
+                self.frames.clear();
+            } else if t - last_t < self.interval_s - 1e-9 {
+                return false;
+            }
+        }
+        self.frames.push_back((t, frame));
+        while self
+            .frames
+            .front()
+            .is_some_and(|&(t0, _)| t - t0 >= self.duration_s - 1e-9)
+        {
+            self.frames.pop_front();
+        }
+        true
+    }
+
+    /// Return the number of buffered frames.
+    pub fn len(&self) -> usize { self.frames.len() }
+
+    /// Return whether the buffer is empty.
+    pub fn is_empty(&self) -> bool { self.frames.is_empty() }
+
+    /// Drop all buffered frames.
+    pub fn clear(&mut self) { self.frames.clear(); }
+
+    /// Encode the buffered frames as a looping animated GIF.
+    ///
+    /// Each frame is shown for the time until the next captured frame.
+    pub fn write<W: Write>(&self, w: W) -> io::Result<()> {
+        let interval_ms = (self.interval_s * 1000.0) as u32;
+        let delays: Vec<u32> = self
+            .frames
+            .iter()
+            .zip(self.frames.iter().skip(1))
+            .map(|(a, b)| ((b.0 - a.0) * 1000.0) as u32)
+            .chain(Some(interval_ms))
+            .collect();
+        write_gif(
+            w,
+            self.frames
+                .iter()
+                .zip(delays)
+                .map(|((_, img), delay)| (img.clone(), delay)),
+        )
+    }
+
+    /// Save the buffered frames into an animated GIF file.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        self.write(BufWriter::new(File::create(path)?))
+    }
+}
+
+/// Write same-sized images with frame delays in milliseconds as a looping animated GIF.
+fn write_gif<W, I>(w: W, frames: I) -> io::Result<()>
+where
+    W: Write,
+    I: IntoIterator<Item = (RgbaImage, u32)>,
+{
+    let mut frames = frames.into_iter().peekable();
+    let (width, height) = match frames.peek() {
+        Some((img, _)) => img.dimensions(),
+        None => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "No frames to write",
+            ))
+        }
+    };
+    if width > 0xffff || height > 0xffff {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Image too large for GIF",
+        ));
+    }
+    let (width, height) = (width as u16, height as u16);
+
+    let mut encoder = Encoder::new(w, width, height, &[])?;
+    encoder.set(Repeat::Infinite)?;
+    for (img, delay_ms) in frames {
+        let mut frame = encode_frame(width, height, img.into_raw());
+        frame.delay = (delay_ms / 10).min(0xffff) as u16;
+        encoder.write_frame(&frame)?;
+    }
+    Ok(())
+}
+
+/// Convert RGBA pixels into a GIF frame.
+///
+/// Frames with at most 256 colors keep their exact colors, frames with more get quantized.
+fn encode_frame(width: u16, height: u16, mut pixels: Vec<u8>) -> gif::Frame<'static> {
+    let mut palette = Vec::new();
+    let mut color_idx = HashMap::new();
+    let mut indices = Vec::with_capacity(pixels.len() / 4);
+
+    for c in pixels.chunks(4) {
+        let key = [c[0], c[1], c[2]];
+        let n = color_idx.len();
+        let idx = *color_idx.entry(key).or_insert(n);
+        if idx == n {
+            if n == 256 {
+                return gif::Frame::from_rgba_speed(width, height, &mut pixels, 10);
+            }
+            palette.extend_from_slice(&key);
+        }
+        indices.push(idx as u8);
+    }
+
+    gif::Frame::from_palette_pixels(width, height, &indices, &palette, None)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{FieldGif, FrameRecorder};
+    use euclid::vec2;
+    use image::{Rgba, RgbaImage};
+    use image_decode::decode_animation;
+    use SRgba;
+
+    #[test]
+    fn test_gif_roundtrip() {
+        let red = SRgba::rgb(255, 0, 0);
+        let blue = SRgba::rgb(0, 0, 255);
+
+        let mut gif = FieldGif::new().frame_delay(50).scale(2);
+        gif.push_frame(vec![(vec2(0, 0), true), (vec2(1, 0), false)], |x| {
+            if x {
+                red
+            } else {
+                blue
+            }
+        });
+        gif.push_frame(vec![(vec2(0, 0), false)], |_| blue);
+
+        let mut data = Vec::new();
+        gif.write(&mut data).unwrap();
+        let frames = decode_animation(&data).unwrap();
+        assert_eq!(frames.len(), 2);
+        assert!((frames[0].delay_s - 0.05).abs() < 0.001);
+
+        // Cell (0, 0) is at the left edge, (1, 0) two pixels right and one pixel down from it
+        // before scaling.
+        let img = &frames[0].image;
+        assert_eq!(img.dimensions(), (8, 6));
+        assert_eq!(SRgba::from(*img.get_pixel(0, 0)), red);
+        assert_eq!(SRgba::from(*img.get_pixel(4, 2)), blue);
+        assert_eq!(SRgba::from(*img.get_pixel(4, 0)), SRgba::rgb(0, 0, 0));
+        assert_eq!(SRgba::from(*frames[1].image.get_pixel(0, 0)), blue);
+    }
+
+    #[test]
+    fn test_frame_recorder() {
+        let mut recorder = FrameRecorder::new(1.0).fps(10.0);
+        assert!(recorder.write(Vec::new()).is_err());
+
+        let frame = |c: u8| RgbaImage::from_pixel(4, 4, Rgba([c, c, c, 255]));
+        assert!(recorder.push_at(0.0, frame(0)));
+        // Too soon after the previous frame.
+        assert!(!recorder.push_at(0.05, frame(1)));
+        for i in 1..15 {
+            assert!(recorder.push_at(i as f64 * 0.1, frame(i as u8 * 10)));
+        }
+        assert_eq!(recorder.len(), 10);
+
+        let mut data = Vec::new();
+        recorder.write(&mut data).unwrap();
+        let frames = decode_animation(&data).unwrap();
+        assert_eq!(frames.len(), 10);
+        assert!((frames[0].delay_s - 0.1).abs() < 0.001);
+        // Oldest frames have been dropped.
+        assert_eq!(frames[0].image.get_pixel(0, 0)[0], 50);
+
+        // Resizing the window starts a new recording.
+        recorder.push_at(2.0, RgbaImage::new(8, 8));
+        assert_eq!(recorder.len(), 1);
+        recorder.clear();
+        assert!(recorder.is_empty());
+    }
+}
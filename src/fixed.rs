@@ -0,0 +1,203 @@
+//! Fixed-point arithmetic for platform independent computation
+//!
+//! Floating point results can differ between platforms and compiler settings, which breaks
+//! replays and lockstep multiplayer that expect the world generation to be bit-identical
+//! everywhere. The fixed-point type and the helpers here only use integer operations.
+//!
+//! The seeded random number generator is also integer-only and hashes seeds the same way on
+//! every platform, so `Standard.noise(&seed)` gives deterministic `Fixed` noise.
+
+use rand::distributions::{Distribution, Standard};
+use rand::Rng;
+use std::fmt;
+use std::ops::{Add, AddAssign, Div, Mul, Neg, Sub, SubAssign};
+
+/// Signed 16.16 fixed-point number.
+///
+/// # Examples
+///
+/// ```
+/// use calx::Fixed;
+///
+/// let a = Fixed::from_int(3);
+/// let b = Fixed::from_ratio(1, 4);
+/// assert_eq!(a * b, Fixed::from_ratio(3, 4));
+/// assert_eq!((a / Fixed::from_int(2)).to_string(), "1.5");
+/// assert_eq!(Fixed::ZERO.lerp(a, b), Fixed::from_ratio(3, 4));
+/// ```
+#[derive(
+    Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Default, Debug, Serialize, Deserialize,
+)]
+pub struct Fixed(i32);
+
+const FRAC_BITS: u32 = 16;
+
+impl Fixed {
+    pub const ZERO: Fixed = Fixed(0);
+    pub const HALF: Fixed = Fixed(1 << (FRAC_BITS - 1));
+    pub const ONE: Fixed = Fixed(1 << FRAC_BITS);
+
+    /// Build a fixed-point number from its raw bit representation.
+    pub fn from_bits(bits: i32) -> Fixed { Fixed(bits) }
+
+    /// Return the raw bit representation.
+    pub fn to_bits(self) -> i32 { self.0 }
+
+    pub fn from_int(x: i32) -> Fixed { Fixed(x << FRAC_BITS) }
+
+    /// Build the fixed-point number closest to the fraction `num / den`.
+    pub fn from_ratio(num: i32, den: i32) -> Fixed {
+        debug_assert!(den != 0);
+        Fixed((((num as i64) << FRAC_BITS) / den as i64) as i32)
+    }
+
+    /// Convert to floating point for display purposes.
+    ///
+    /// Don't feed the result back into computations that need to stay deterministic.
+    pub fn to_f32(self) -> f32 { self.0 as f32 / (1 << FRAC_BITS) as f32 }
+
+    /// Round towards negative infinity.
+    pub fn floor(self) -> i32 { self.0 >> FRAC_BITS }
+
+    /// Return the fractional part, always in [0, 1).
+    pub fn fract(self) -> Fixed { Fixed(self.0 & ((1 << FRAC_BITS) - 1)) }
+
+    pub fn abs(self) -> Fixed { Fixed(self.0.abs()) }
+
+    /// Interpolate linearly from `self` to `other`.
+    pub fn lerp(self, other: Fixed, t: Fixed) -> Fixed { self + (other - self) * t }
+}
+
+impl From<i32> for Fixed {
+    fn from(x: i32) -> Fixed { Fixed::from_int(x) }
+}
+
+impl Add for Fixed {
+    type Output = Fixed;
+    fn add(self, rhs: Fixed) -> Fixed { Fixed(self.0 + rhs.0) }
+}
+
+impl AddAssign for Fixed {
+    fn add_assign(&mut self, rhs: Fixed) { self.0 += rhs.0; }
+}
+
+impl Sub for Fixed {
+    type Output = Fixed;
+    fn sub(self, rhs: Fixed) -> Fixed { Fixed(self.0 - rhs.0) }
+}
+
+impl SubAssign for Fixed {
+    fn sub_assign(&mut self, rhs: Fixed) { self.0 -= rhs.0; }
+}
+
+impl Mul for Fixed {
+    type Output = Fixed;
+    fn mul(self, rhs: Fixed) -> Fixed {
+        Fixed(((self.0 as i64 * rhs.0 as i64) >> FRAC_BITS) as i32)
+    }
+}
+
+impl Mul<i32> for Fixed {
+    type Output = Fixed;
+    fn mul(self, rhs: i32) -> Fixed { Fixed(self.0 * rhs) }
+}
+
+impl Div for Fixed {
+    type Output = Fixed;
+    fn div(self, rhs: Fixed) -> Fixed {
+        Fixed((((self.0 as i64) << FRAC_BITS) / rhs.0 as i64) as i32)
+    }
+}
+
+impl Neg for Fixed {
+    type Output = Fixed;
+    fn neg(self) -> Fixed { Fixed(-self.0) }
+}
+
+impl fmt::Display for Fixed {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { write!(f, "{}", self.to_f32()) }
+}
+
+/// Uniformly distributed values in [0, 1).
+impl Distribution<Fixed> for Standard {
+    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> Fixed {
+        Fixed((rng.next_u32() >> (32 - FRAC_BITS)) as i32)
+    }
+}
+
+/// Fixed-point version of `ease::quadratic_in`.
+pub fn quadratic_in(t: Fixed) -> Fixed { t * t }
+
+/// Fixed-point version of `ease::quadratic_out`.
+pub fn quadratic_out(t: Fixed) -> Fixed { -(t * (t - Fixed::from_int(2))) }
+
+/// Fixed-point version of `ease::quadratic_in_out`.
+pub fn quadratic_in_out(t: Fixed) -> Fixed {
+    if t < Fixed::HALF {
+        t * t * 2
+    } else {
+        t * t * -2 + t * 4 - Fixed::ONE
+    }
+}
+
+/// Fixed-point version of `ease::cubic_in`.
+pub fn cubic_in(t: Fixed) -> Fixed { t * t * t }
+
+/// Fixed-point version of `ease::cubic_out`.
+pub fn cubic_out(t: Fixed) -> Fixed {
+    let u = t - Fixed::ONE;
+    u * u * u + Fixed::ONE
+}
+
+/// Fixed-point version of `ease::cubic_in_out`.
+pub fn cubic_in_out(t: Fixed) -> Fixed {
+    if t < Fixed::HALF {
+        t * t * t * 4
+    } else {
+        let u = t * 2 - Fixed::from_int(2);
+        u * u * u * Fixed::HALF + Fixed::ONE
+    }
+}
+
+/// Smooth Hermite interpolation curve from 0 to 1.
+pub fn smoothstep(t: Fixed) -> Fixed { t * t * (Fixed::from_int(3) - t * 2) }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ease;
+    use Noise;
+
+    type EasingPair = (fn(Fixed) -> Fixed, fn(f32) -> f32);
+
+    #[test]
+    fn test_easing_matches_float() {
+        let fns: Vec<EasingPair> = vec![
+            (quadratic_in, ease::quadratic_in),
+            (quadratic_out, ease::quadratic_out),
+            (quadratic_in_out, ease::quadratic_in_out),
+            (cubic_in, ease::cubic_in),
+            (cubic_out, ease::cubic_out),
+            (cubic_in_out, ease::cubic_in_out),
+        ];
+
+        for (fixed, float) in fns {
+            for i in 0..=16 {
+                let t = Fixed::from_ratio(i, 16);
+                assert!((fixed(t).to_f32() - float(t.to_f32())).abs() < 0.001);
+            }
+        }
+    }
+
+    #[test]
+    fn test_fixed_noise() {
+        // Exact bit patterns, these must not change between platforms.
+        let x: Fixed = Standard.noise(&(12u32, 34u32));
+        assert_eq!(x.to_bits(), 3781);
+        assert!(x >= Fixed::ZERO && x < Fixed::ONE);
+
+        assert_eq!(Fixed::from_int(-3).floor(), -3);
+        assert_eq!(Fixed::from_ratio(-1, 2).floor(), -1);
+        assert_eq!(Fixed::from_ratio(-1, 4).fract(), Fixed::from_ratio(3, 4));
+    }
+}
@@ -38,10 +38,18 @@ pub struct Fov<P, T> {
 impl<P: PolarPoint, T: FovValue> Fov<P, T> {
     /// Create a new field of view iterator with a seed userdata for the origin position.
     pub fn new(init: T) -> Fov<P, T> {
+        let (begin, end) = P::unit_circle_endpoints();
+        Fov::with_arc(init, begin, end)
+    }
+
+    /// Create a field of view iterator that only covers the given arc of the unit circle.
+    ///
+    /// The end point must be further along the circle than the begin point.
+    pub fn with_arc(init: T, begin: P, end: P) -> Fov<P, T> {
         // We could run f for (0, 0) here, but the traditional way for the FOV to work is to only
         // consider your surroundings, not the origin site itself.
         Fov {
-            stack: vec![Arc::new(init.clone())],
+            stack: vec![Arc::expand(begin, end, init.clone())],
             // The FOV algorithm will not generate the origin point, so we use
             // the side channel to explicitly add it in the beginning.
             side_channel: vec![(vec2(0, 0), init)],
@@ -95,11 +103,6 @@ struct Arc<P, T> {
 }
 
 impl<P: PolarPoint, T: FovValue> Arc<P, T> {
-    pub fn new(init: T) -> Arc<P, T> {
-        let (begin, end) = P::unit_circle_endpoints();
-        Self::expand(begin, end, init)
-    }
-
     fn expand(begin: P, end: P, prev_value: T) -> Arc<P, T> {
         let group_value = prev_value.advance(begin.to_v2());
         Arc {
@@ -0,0 +1,307 @@
+//! Message framing for network protocols
+//!
+//! Messages are serialized into frames that can be sent over any byte stream. A frame is a
+//! 7 byte header followed by the payload:
+//!
+//! * payload length in bytes, `u32` little-endian
+//! * protocol version, `u16` little-endian
+//! * flags, `u8`, bit 0 is set when the payload is deflate compressed
+//!
+//! The payload is the JSON serialization of the message.
+
+use deflate;
+use inflate::InflateStream;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json;
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Read, Write};
+
+const HEADER_SIZE: usize = 7;
+const FLAG_COMPRESSED: u8 = 1;
+
+/// Error from encoding or decoding message frames.
+#[derive(Debug)]
+pub enum FrameError {
+    /// Reading or writing the stream failed.
+    Io(io::Error),
+    /// The message could not be encoded or decoded.
+    Serialization(serde_json::Error),
+    /// The frame was sent with a different protocol version.
+    VersionMismatch { expected: u16, found: u16 },
+    /// The frame is larger than the allowed maximum size.
+    TooLarge(usize),
+    /// The compressed payload is corrupt.
+    Compression(String),
+}
+
+impl fmt::Display for FrameError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            FrameError::Io(ref e) => write!(f, "Frame IO error: {}", e),
+            FrameError::Serialization(ref e) => write!(f, "Malformed message: {}", e),
+            FrameError::VersionMismatch { expected, found } => write!(
+                f,
+                "Protocol version mismatch, expected {}, got {}",
+                expected, found
+            ),
+            FrameError::TooLarge(size) => write!(f, "Frame of {} bytes is too large", size),
+            FrameError::Compression(ref e) => write!(f, "Bad compressed payload: {}", e),
+        }
+    }
+}
+
+impl Error for FrameError {}
+
+impl From<io::Error> for FrameError {
+    fn from(e: io::Error) -> FrameError { FrameError::Io(e) }
+}
+
+impl From<serde_json::Error> for FrameError {
+    fn from(e: serde_json::Error) -> FrameError { FrameError::Serialization(e) }
+}
+
+/// Encoder and decoder of message frames for a protocol version.
+///
+/// Use `write` and `read` with blocking streams like `TcpStream`. For non-blocking transports
+/// and transports that deliver data in arbitrary chunks, feed the incoming bytes to a
+/// `FrameDecoder`.
+///
+/// # Examples
+///
+/// ```
+/// # #[macro_use] extern crate serde_derive;
+/// # extern crate calx;
+/// # fn main() {
+/// use calx::FrameCodec;
+///
+/// #[derive(Serialize, Deserialize, PartialEq, Debug)]
+/// enum Message {
+///     Hello { name: String },
+///     Input(u32, Vec<u8>),
+/// }
+///
+/// let codec = FrameCodec::new(1).compress_threshold(64);
+/// let mut stream = Vec::new();
+/// codec.write(&mut stream, &Message::Hello { name: "Alice".into() }).unwrap();
+/// codec.write(&mut stream, &Message::Input(12, vec![0; 1000])).unwrap();
+/// // The long message got compressed.
+/// assert!(stream.len() < 1000);
+///
+/// let mut stream = &stream[..];
+/// let msg: Message = codec.read(&mut stream).unwrap();
+/// assert_eq!(msg, Message::Hello { name: "Alice".into() });
+/// let msg: Message = codec.read(&mut stream).unwrap();
+/// assert_eq!(msg, Message::Input(12, vec![0; 1000]));
+///
+/// // Peers with a different protocol version are rejected.
+/// let mut stream = Vec::new();
+/// FrameCodec::new(2).write(&mut stream, &Message::Input(1, vec![])).unwrap();
+/// assert!(codec.read::<_, Message>(&mut &stream[..]).is_err());
+/// # }
+/// ```
+#[derive(Copy, Clone, Debug)]
+pub struct FrameCodec {
+    version: u16,
+    compress_threshold: usize,
+    max_frame_size: usize,
+}
+
+impl FrameCodec {
+    /// Create a codec for a protocol version.
+    ///
+    /// Payloads over 1 KiB are compressed and frames over 16 MiB are rejected by default.
+    pub fn new(version: u16) -> FrameCodec {
+        FrameCodec {
+            version,
+            compress_threshold: 1 << 10,
+            max_frame_size: 1 << 24,
+        }
+    }
+
+    /// Set the payload size in bytes above which payloads are compressed.
+    pub fn compress_threshold(mut self, bytes: usize) -> FrameCodec {
+        self.compress_threshold = bytes;
+        self
+    }
+
+    /// Set the largest accepted payload size in bytes, before and after decompression.
+    pub fn max_frame_size(mut self, bytes: usize) -> FrameCodec {
+        self.max_frame_size = bytes;
+        self
+    }
+
+    /// Serialize a message into a frame.
+    pub fn encode<T: Serialize>(&self, msg: &T) -> Result<Vec<u8>, FrameError> {
+        let mut payload = serde_json::to_vec(msg)?;
+        let mut flags = 0;
+        if payload.len() > self.compress_threshold {
+            let compressed = deflate::deflate_bytes(&payload);
+            // Don't bother if compression doesn't help.
+            if compressed.len() < payload.len() {
+                payload = compressed;
+                flags |= FLAG_COMPRESSED;
+            }
+        }
+        if payload.len() > self.max_frame_size || payload.len() > u32::MAX as usize {
+            return Err(FrameError::TooLarge(payload.len()));
+        }
+
+        let mut ret = Vec::with_capacity(HEADER_SIZE + payload.len());
+        ret.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        ret.extend_from_slice(&self.version.to_le_bytes());
+        ret.push(flags);
+        ret.extend_from_slice(&payload);
+        Ok(ret)
+    }
+
+    /// Write a message frame to a stream.
+    pub fn write<W: Write, T: Serialize>(&self, mut writer: W, msg: &T) -> Result<(), FrameError> {
+        writer.write_all(&self.encode(msg)?)?;
+        Ok(())
+    }
+
+    /// Read a message frame from a stream.
+    ///
+    /// Blocks until the whole frame has been read.
+    pub fn read<R: Read, T: DeserializeOwned>(&self, mut reader: R) -> Result<T, FrameError> {
+        let mut header = [0u8; HEADER_SIZE];
+        reader.read_exact(&mut header)?;
+        let len = self.check_header(&header)?;
+        let mut payload = vec![0u8; len];
+        reader.read_exact(&mut payload)?;
+        self.decode_payload(header[6], &payload)
+    }
+
+    /// Validate a frame header and return the payload length.
+    fn check_header(&self, header: &[u8]) -> Result<usize, FrameError> {
+        let len = u32::from_le_bytes([header[0], header[1], header[2], header[3]]) as usize;
+        let version = u16::from_le_bytes([header[4], header[5]]);
+        if version != self.version {
+            return Err(FrameError::VersionMismatch {
+                expected: self.version,
+                found: version,
+            });
+        }
+        if len > self.max_frame_size {
+            return Err(FrameError::TooLarge(len));
+        }
+        Ok(len)
+    }
+
+    fn decode_payload<T: DeserializeOwned>(
+        &self,
+        flags: u8,
+        payload: &[u8],
+    ) -> Result<T, FrameError> {
+        if flags & FLAG_COMPRESSED != 0 {
+            let data = self.inflate(payload)?;
+            Ok(serde_json::from_slice(&data)?)
+        } else {
+            Ok(serde_json::from_slice(payload)?)
+        }
+    }
+
+    /// Decompress a payload, stopping as soon as the output goes over the size limit.
+    fn inflate(&self, payload: &[u8]) -> Result<Vec<u8>, FrameError> {
+        let mut stream = InflateStream::new();
+        let mut data = Vec::new();
+        let mut pos = 0;
+        loop {
+            let (read, bytes) = stream
+                .update(&payload[pos..])
+                .map_err(FrameError::Compression)?;
+            if bytes.is_empty() {
+                return Ok(data);
+            }
+            if data.len() + bytes.len() > self.max_frame_size {
+                return Err(FrameError::TooLarge(data.len() + bytes.len()));
+            }
+            data.extend_from_slice(bytes);
+            pos += read;
+        }
+    }
+}
+
+/// Incremental frame decoder for data that arrives in arbitrary pieces.
+pub struct FrameDecoder {
+    codec: FrameCodec,
+    buffer: Vec<u8>,
+}
+
+impl FrameDecoder {
+    pub fn new(codec: FrameCodec) -> FrameDecoder {
+        FrameDecoder {
+            codec,
+            buffer: Vec::new(),
+        }
+    }
+
+    /// Add received bytes to the decoder.
+    pub fn push(&mut self, bytes: &[u8]) { self.buffer.extend_from_slice(bytes); }
+
+    /// Decode the next complete message from the received data.
+    ///
+    /// Returns `Ok(None)` if there isn't a whole frame available yet. After an error, the stream
+    /// is out of sync and the connection should be dropped.
+    pub fn next_message<T: DeserializeOwned>(&mut self) -> Result<Option<T>, FrameError> {
+        if self.buffer.len() < HEADER_SIZE {
+            return Ok(None);
+        }
+        let len = self.codec.check_header(&self.buffer[..HEADER_SIZE])?;
+        if self.buffer.len() < HEADER_SIZE + len {
+            return Ok(None);
+        }
+
+        let frame: Vec<u8> = self.buffer.drain(..HEADER_SIZE + len).collect();
+        self.codec
+            .decode_payload(frame[6], &frame[HEADER_SIZE..])
+            .map(Some)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{FrameCodec, FrameDecoder, FrameError};
+
+    #[test]
+    fn test_decoder() {
+        let codec = FrameCodec::new(3).compress_threshold(10);
+        let mut data = Vec::new();
+        for i in 0..20 {
+            codec.write(&mut data, &vec![i; i as usize]).unwrap();
+        }
+
+        // Feed the data in awkward pieces.
+        let mut decoder = FrameDecoder::new(codec);
+        let mut messages = Vec::new();
+        for chunk in data.chunks(5) {
+            decoder.push(chunk);
+            while let Some(msg) = decoder.next_message::<Vec<u32>>().unwrap() {
+                messages.push(msg);
+            }
+        }
+        assert_eq!(messages.len(), 20);
+        for (i, msg) in messages.iter().enumerate() {
+            assert_eq!(msg, &vec![i as u32; i]);
+        }
+
+        // Oversized frames are rejected from the header.
+        let small = FrameCodec::new(3).max_frame_size(100);
+        let frame = codec.encode(&"x".repeat(200)).unwrap();
+        match small.read::<_, String>(&frame[..]) {
+            Err(FrameError::TooLarge(_)) => {}
+            _ => panic!("Oversized frame accepted"),
+        }
+
+        // Decompression bombs are stopped at the size limit.
+        let bomb = FrameCodec::new(3).encode(&"x".repeat(1 << 20)).unwrap();
+        assert!(bomb.len() < 4096);
+        let limited = FrameCodec::new(3).max_frame_size(4096);
+        match limited.read::<_, String>(&bomb[..]) {
+            Err(FrameError::TooLarge(size)) => assert!(size < 1 << 20),
+            _ => panic!("Decompression bomb accepted"),
+        }
+    }
+}
@@ -0,0 +1,103 @@
+use prefab::FromPrefab;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::Path;
+use CellVector;
+
+/// Environment variable that makes `assert_golden` overwrite the golden files.
+pub const UPDATE_GOLDENS_VAR: &str = "CALX_UPDATE_GOLDENS";
+
+/// Render a cell map into a deterministic text snapshot.
+///
+/// The snapshot uses the sparse hex prefab text layout with the origin cell marked with brackets,
+/// so it can be parsed back with `IntoPrefab`.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate euclid;
+/// # extern crate calx;
+/// # fn main() {
+/// use euclid::vec2;
+/// use calx::snapshot;
+///
+/// let cells = vec![(vec2(0, 0), '@'), (vec2(1, 0), '#'), (vec2(0, 1), '.')];
+/// assert_eq!(" [@]#\n .", snapshot(cells));
+/// # }
+/// ```
+pub fn snapshot<I: IntoIterator<Item = (CellVector, char)>>(cells: I) -> String {
+    let map: HashMap<CellVector, char> = cells.into_iter().collect();
+    String::from_prefab(&map)
+}
+
+/// Compare a text snapshot against a checked-in golden file.
+///
+/// Panics with both versions of the text if they differ, or if the golden file doesn't exist.
+/// Set the `CALX_UPDATE_GOLDENS` environment variable when running the tests to create missing
+/// golden files and to overwrite existing ones after an intended change.
+pub fn assert_golden<P: AsRef<Path>>(path: P, actual: &str) {
+    let path = path.as_ref();
+
+    if env::var_os(UPDATE_GOLDENS_VAR).is_some() {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).expect("Failed to create golden file directory");
+        }
+        fs::write(path, actual).expect("Failed to write golden file");
+        return;
+    }
+
+    if !path.exists() {
+        panic!(
+            "Golden file {} does not exist\n\nActual:\n{}\n\n\
+             Set {} to create the golden file.",
+            path.display(),
+            actual,
+            UPDATE_GOLDENS_VAR
+        );
+    }
+
+    let expected = fs::read_to_string(path).expect("Failed to read golden file");
+    let normalize = |s: &str| {
+        s.lines()
+            .map(|line| line.trim_end())
+            .collect::<Vec<_>>()
+            .join("\n")
+            .trim_end()
+            .to_string()
+    };
+
+    if normalize(&expected) != normalize(actual) {
+        panic!(
+            "Snapshot does not match golden file {}\n\nExpected:\n{}\n\nActual:\n{}\n\n\
+             Set {} to update the golden file.",
+            path.display(),
+            expected,
+            actual,
+            UPDATE_GOLDENS_VAR
+        );
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{assert_golden, UPDATE_GOLDENS_VAR};
+    use std::env;
+    use std::fs;
+    use std::panic;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_missing_golden() {
+        if env::var_os(UPDATE_GOLDENS_VAR).is_some() {
+            return;
+        }
+        let dir = TempDir::new("calx-golden").unwrap();
+        let path = dir.path().join("missing.txt");
+        assert!(panic::catch_unwind(|| assert_golden(&path, "@")).is_err());
+        assert!(!path.exists());
+
+        fs::write(&path, "@  \n").unwrap();
+        assert_golden(&path, "@");
+    }
+}
@@ -0,0 +1,301 @@
+use euclid::{point2, size2, vec2, TypedRect};
+use mem_stats::HeapSize;
+use std::convert::TryFrom;
+use std::ops::{Index, IndexMut};
+use CellSpace;
+use CellVector;
+
+/// Dense rectangular cell field.
+///
+/// Cells are stored in row-major order over a rectangle that can start at any cell position.
+///
+/// The field of view, search and field functions take cell predicates instead of map types,
+/// `predicate` turns a test on the cell values into one.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate euclid;
+/// # extern crate calx;
+/// # fn main() {
+/// use euclid::{rect, vec2};
+/// use calx::{FlowField, Grid};
+///
+/// let mut grid = Grid::new(rect(-2, -2, 5, 5), '.');
+/// grid[vec2(1, 0)] = '#';
+/// assert_eq!(grid.get(vec2(1, 0)), Some(&'#'));
+/// assert_eq!(grid.get(vec2(3, 0)), None);
+///
+/// let walls = grid.map(|_, &c| c == '#');
+/// assert_eq!(walls.iter().filter(|&(_, &w)| w).count(), 1);
+///
+/// grid.resize(rect(0, 0, 4, 4), ' ');
+/// assert_eq!(grid[vec2(1, 0)], '#');
+/// assert_eq!(grid[vec2(2, 2)], '.');
+/// assert_eq!(grid[vec2(3, 3)], ' ');
+///
+/// let is_floor = grid.predicate(|&c| c == '.');
+/// let field = FlowField::new(&[vec2(0, 0)], |&p| is_floor(p), 10);
+/// assert_eq!(field.distance(vec2(2, 2)), Some(2));
+/// assert!(!field.contains(vec2(1, 0)));
+/// # }
+/// ```
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(try_from = "GridData<T>")]
+pub struct Grid<T> {
+    origin: CellVector,
+    width: i32,
+    height: i32,
+    cells: Vec<T>,
+}
+
+/// Serialized fields of a `Grid`, validated before use.
+#[derive(Deserialize)]
+struct GridData<T> {
+    origin: CellVector,
+    width: i32,
+    height: i32,
+    cells: Vec<T>,
+}
+
+impl<T> TryFrom<GridData<T>> for Grid<T> {
+    type Error = &'static str;
+
+    fn try_from(data: GridData<T>) -> Result<Grid<T>, &'static str> {
+        if data.width < 0 || data.height < 0 {
+            return Err("Negative grid size");
+        }
+        let fits = |origin: i32, size: i32| i64::from(origin) + i64::from(size) <= 1 << 31;
+        if !fits(data.origin.x, data.width) || !fits(data.origin.y, data.height) {
+            return Err("Grid extends outside the cell coordinate range");
+        }
+        if data.cells.len() as u64 != data.width as u64 * data.height as u64 {
+            return Err("Grid cell count doesn't match its size");
+        }
+        Ok(Grid {
+            origin: data.origin,
+            width: data.width,
+            height: data.height,
+            cells: data.cells,
+        })
+    }
+}
+
+impl<T> Grid<T> {
+    /// Create a grid by computing the value for each cell.
+    pub fn from_fn<F>(bounds: TypedRect<i32, CellSpace>, mut f: F) -> Grid<T>
+    where
+        F: FnMut(CellVector) -> T,
+    {
+        let width = bounds.size.width.max(0);
+        let height = bounds.size.height.max(0);
+        let origin = bounds.origin.to_vector();
+        let cells = (0..width * height)
+            .map(|i| f(origin + vec2(i % width, i / width)))
+            .collect();
+        Grid {
+            origin,
+            width,
+            height,
+            cells,
+        }
+    }
+
+    /// Create a grid filled with a value.
+    pub fn new(bounds: TypedRect<i32, CellSpace>, value: T) -> Grid<T>
+    where
+        T: Clone,
+    {
+        Grid::from_fn(bounds, |_| value.clone())
+    }
+
+    /// Return the rectangle covered by the grid.
+    pub fn bounds(&self) -> TypedRect<i32, CellSpace> {
+        TypedRect::new(
+            point2(self.origin.x, self.origin.y),
+            size2(self.width, self.height),
+        )
+    }
+
+    pub fn width(&self) -> i32 { self.width }
+
+    pub fn height(&self) -> i32 { self.height }
+
+    /// Return the number of cells in the grid.
+    pub fn len(&self) -> usize { self.cells.len() }
+
+    pub fn is_empty(&self) -> bool { self.cells.is_empty() }
+
+    /// Return whether a position is inside the grid.
+    pub fn contains(&self, pos: CellVector) -> bool { self.index_of(pos).is_some() }
+
+    pub fn get(&self, pos: CellVector) -> Option<&T> {
+        let idx = self.index_of(pos)?;
+        Some(&self.cells[idx])
+    }
+
+    pub fn get_mut(&mut self, pos: CellVector) -> Option<&mut T> {
+        let idx = self.index_of(pos)?;
+        Some(&mut self.cells[idx])
+    }
+
+    /// Return a cell predicate that tests the value of a cell.
+    ///
+    /// Positions outside the grid fail the test.
+    pub fn predicate<'a, F>(&'a self, f: F) -> impl Fn(CellVector) -> bool + 'a
+    where
+        F: Fn(&T) -> bool + 'a,
+    {
+        move |pos| self.get(pos).is_some_and(&f)
+    }
+
+    /// Iterate the cell positions and values in row-major order.
+    pub fn iter<'a>(&'a self) -> impl Iterator<Item = (CellVector, &'a T)> + 'a {
+        let (origin, width) = (self.origin, self.width);
+        self.cells
+            .iter()
+            .enumerate()
+            .map(move |(i, t)| (pos_of(origin, width, i), t))
+    }
+
+    /// Iterate the cell positions and mutable values in row-major order.
+    pub fn iter_mut<'a>(&'a mut self) -> impl Iterator<Item = (CellVector, &'a mut T)> + 'a {
+        let (origin, width) = (self.origin, self.width);
+        self.cells
+            .iter_mut()
+            .enumerate()
+            .map(move |(i, t)| (pos_of(origin, width, i), t))
+    }
+
+    /// Build a new grid with the same bounds by converting each cell.
+    pub fn map<U, F>(&self, mut f: F) -> Grid<U>
+    where
+        F: FnMut(CellVector, &T) -> U,
+    {
+        Grid {
+            origin: self.origin,
+            width: self.width,
+            height: self.height,
+            cells: self.iter().map(|(p, t)| f(p, t)).collect(),
+        }
+    }
+
+    /// Change the bounds of the grid.
+    ///
+    /// Cells inside both the old and the new bounds keep their values, new cells are filled with
+    /// `fill`.
+    pub fn resize(&mut self, bounds: TypedRect<i32, CellSpace>, fill: T)
+    where
+        T: Clone,
+    {
+        let mut old: Vec<Option<T>> = ::std::mem::take(&mut self.cells)
+            .into_iter()
+            .map(Some)
+            .collect();
+        let (old_origin, old_width, old_height) = (self.origin, self.width, self.height);
+
+        *self = Grid::from_fn(bounds, |p| {
+            let d = p - old_origin;
+            if d.x >= 0 && d.y >= 0 && d.x < old_width && d.y < old_height {
+                if let Some(t) = old[(d.x + d.y * old_width) as usize].take() {
+                    return t;
+                }
+            }
+            fill.clone()
+        });
+    }
+
+    fn index_of(&self, pos: CellVector) -> Option<usize> {
+        let d = pos - self.origin;
+        if d.x >= 0 && d.y >= 0 && d.x < self.width && d.y < self.height {
+            Some((d.x + d.y * self.width) as usize)
+        } else {
+            None
+        }
+    }
+}
+
+fn pos_of(origin: CellVector, width: i32, idx: usize) -> CellVector {
+    let i = idx as i32;
+    origin + vec2(i % width, i / width)
+}
+
+impl<T> Index<CellVector> for Grid<T> {
+    type Output = T;
+
+    fn index(&self, pos: CellVector) -> &T {
+        let idx = self.index_of(pos).expect("Position outside grid");
+        &self.cells[idx]
+    }
+}
+
+impl<T> IndexMut<CellVector> for Grid<T> {
+    fn index_mut(&mut self, pos: CellVector) -> &mut T {
+        let idx = self.index_of(pos).expect("Position outside grid");
+        &mut self.cells[idx]
+    }
+}
+
+impl<T> HeapSize for Grid<T> {
+    fn heap_size(&self) -> usize { self.cells.heap_size() }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Grid;
+    use euclid::{rect, vec2};
+    use hex_fov::{HexFov, Sight};
+    use serde_json;
+
+    #[test]
+    fn test_grid() {
+        let grid = Grid::from_fn(rect(3, -1, 4, 2), |p| p.x * 10 + p.y);
+        assert_eq!(grid.len(), 8);
+        assert_eq!(grid.bounds(), rect(3, -1, 4, 2));
+        assert_eq!(grid[vec2(6, 0)], 60);
+        assert!(!grid.contains(vec2(7, 0)));
+        assert!(!grid.contains(vec2(3, -2)));
+        assert!(grid.iter().all(|(p, &v)| v == p.x * 10 + p.y));
+
+        let saved = serde_json::to_string(&grid).unwrap();
+        let grid2: Grid<i32> = serde_json::from_str(&saved).unwrap();
+        assert_eq!(grid, grid2);
+
+        // Cells can be seen through a grid predicate.
+        let walls = Grid::from_fn(rect(-4, -4, 9, 9), |p| p == vec2(1, 0));
+        let is_transparent = walls.predicate(|&w| !w);
+        let seen: Vec<_> = HexFov::new(Sight::new(vec2(0, 0), 6, &is_transparent))
+            .map(|(p, _)| p)
+            .collect();
+        assert!(seen.contains(&vec2(1, 0)));
+        assert!(!seen.contains(&vec2(2, 0)));
+        assert!(!seen.contains(&vec2(5, 0)));
+
+        let mut empty = Grid::new(rect(0, 0, -3, 2), 0);
+        assert!(empty.is_empty());
+        empty.resize(rect(0, 0, 2, 2), 1);
+        assert_eq!(empty.iter().map(|(_, &v)| v).sum::<i32>(), 4);
+    }
+
+    #[test]
+    fn test_corrupt_grid() {
+        let load = |json: &str| serde_json::from_str::<Grid<u8>>(json);
+        let grid = |width: i32, height: i32, cells: &str| {
+            format!(
+                r#"{{ "origin": [0, 0], "width": {}, "height": {}, "cells": {} }}"#,
+                width, height, cells
+            )
+        };
+        assert!(load(&grid(2, 2, "[1, 2, 3, 4]")).is_ok());
+        assert!(load(&grid(0, 0, "[]")).is_ok());
+        // Truncated cells.
+        assert!(load(&grid(2, 2, "[1, 2, 3]")).is_err());
+        assert!(load(&grid(2, 2, "[1, 2, 3, 4, 5]")).is_err());
+        // Negative sizes.
+        assert!(load(&grid(-2, -2, "[1, 2, 3, 4]")).is_err());
+        assert!(load(&grid(-1, 0, "[]")).is_err());
+        // Bounds past the coordinate range.
+        let far = r#"{ "origin": [2147483647, 0], "width": 2, "height": 1, "cells": [1, 2] }"#;
+        assert!(load(far).is_err());
+    }
+}
@@ -0,0 +1,231 @@
+//! Debug overlay of the map grid
+
+use euclid::{point2, vec2, Point2D, Rect};
+use gui::{GuiDraw, GuiStyle};
+use space::{CellShape, ScreenProjection};
+use CellVector;
+
+/// Cell outline corners in `CellSpace` for the edges shared with the +x, +x+y and +y neighbors.
+///
+/// The other edges of a cell are drawn by the neighbors on the other side.
+const HEX_EDGES: [[f32; 2]; 4] = [
+    [1.0 / 3.0, -1.0 / 3.0],
+    [2.0 / 3.0, 1.0 / 3.0],
+    [1.0 / 3.0, 2.0 / 3.0],
+    [-1.0 / 3.0, 1.0 / 3.0],
+];
+
+/// Cell outline corners in `CellSpace` for the edges shared with the +x and +y neighbors.
+const SQUARE_EDGES: [[f32; 2]; 3] = [[0.5, -0.5], [0.5, 0.5], [-0.5, 0.5]];
+
+/// Toggleable overlay that draws the cell outlines and coordinates of the map.
+///
+/// The overlay uses the same `ScreenProjection` as the map view, so the outlines show where
+/// the projection thinks the cells are. Labels show the `CellSpace` coordinates of each cell,
+/// which helps when debugging space conversions. Update the projection when the camera moves.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate euclid;
+/// # extern crate calx;
+/// # fn main() {
+/// use euclid::{point2, rect};
+/// use calx::{GridOverlay, GuiDraw, GuiStyle, IsoSpace, AffineSpace};
+///
+/// let mut overlay = GridOverlay::new(IsoSpace::PROJECTION);
+/// overlay.projection.origin = [160.0, 100.0];
+/// let screen = rect(0.0, 0.0, 320.0, 200.0);
+/// assert!(overlay.draw(screen, &GuiStyle::default()).is_empty());
+///
+/// overlay.toggle();
+/// let draw = overlay.draw(screen, &GuiStyle::default());
+/// assert!(draw.iter().any(|d| match d {
+///     GuiDraw::Text { pos, text, .. } => text == "0,0" && pos.x < 160.0 && pos.y < 100.0,
+///     _ => false,
+/// }));
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct GridOverlay {
+    /// Projection from `CellSpace` to the screen.
+    pub projection: ScreenProjection,
+    /// Whether to draw the coordinate labels.
+    pub show_labels: bool,
+    is_visible: bool,
+}
+
+impl GridOverlay {
+    /// Create a hidden overlay with coordinate labels.
+    pub fn new(projection: ScreenProjection) -> GridOverlay {
+        GridOverlay {
+            projection,
+            show_labels: true,
+            is_visible: false,
+        }
+    }
+
+    /// Show or hide the overlay.
+    pub fn toggle(&mut self) { self.is_visible = !self.is_visible; }
+
+    pub fn is_visible(&self) -> bool { self.is_visible }
+
+    /// Draw the outlines and labels of the cells that are visible in a screen area.
+    ///
+    /// Returns nothing if the overlay is hidden.
+    pub fn draw(&self, screen: Rect<f32>, style: &GuiStyle) -> Vec<GuiDraw> {
+        let mut ret = Vec::new();
+        if !self.is_visible {
+            return ret;
+        }
+
+        let edges: &[[f32; 2]] = match self.projection.shape {
+            CellShape::Hex => &HEX_EDGES,
+            CellShape::Square => &SQUARE_EDGES,
+        };
+        for cell in self.visible_cells(screen) {
+            let (x, y) = (cell.x as f32, cell.y as f32);
+            let corners: Vec<Point2D<f32>> = edges
+                .iter()
+                .map(|c| self.to_screen([x + c[0], y + c[1]]))
+                .collect();
+            for w in corners.windows(2) {
+                ret.push(GuiDraw::Line {
+                    start: w[0],
+                    end: w[1],
+                    color: style.highlight,
+                });
+            }
+
+            if self.show_labels {
+                let text = format!("{},{}", cell.x, cell.y);
+                let center = self.to_screen([x, y]);
+                let w = text.chars().count() as f32 * style.char_width;
+                ret.push(GuiDraw::Text {
+                    pos: point2(center.x - w / 2.0, center.y - style.row_height / 2.0),
+                    text,
+                    color: style.text,
+                });
+            }
+        }
+        ret
+    }
+
+    /// Return the cells whose outlines can reach the screen area.
+    fn visible_cells(&self, screen: Rect<f32>) -> Vec<CellVector> {
+        let p = &self.projection;
+        // Distance from a cell center past which the cell can't reach the screen.
+        let reach = p.x_axis[0].abs() + p.y_axis[0].abs() + p.x_axis[1].abs() + p.y_axis[1].abs();
+        let area = screen.inflate(reach, reach);
+
+        let corners = [
+            [area.min_x(), area.min_y()],
+            [area.max_x(), area.min_y()],
+            [area.min_x(), area.max_y()],
+            [area.max_x(), area.max_y()],
+        ];
+        let cells: Vec<[f32; 2]> = corners.iter().map(|&c| p.screen_to_cell_f32(c)).collect();
+        let span = |i: usize| {
+            let min = cells.iter().map(|c| c[i]).fold(f32::INFINITY, f32::min);
+            let max = cells.iter().map(|c| c[i]).fold(f32::NEG_INFINITY, f32::max);
+            (min.floor() as i32, max.ceil() as i32)
+        };
+        let ((min_x, max_x), (min_y, max_y)) = (span(0), span(1));
+
+        let mut ret = Vec::new();
+        for y in min_y..=max_y {
+            for x in min_x..=max_x {
+                if area.contains(&self.to_screen([x as f32, y as f32])) {
+                    ret.push(vec2(x, y));
+                }
+            }
+        }
+        ret
+    }
+
+    /// Project a fractional `CellSpace` position to the screen.
+    fn to_screen(&self, pos: [f32; 2]) -> Point2D<f32> {
+        let p = &self.projection;
+        point2(
+            p.origin[0] + pos[0] * p.x_axis[0] + pos[1] * p.y_axis[0],
+            p.origin[1] + pos[0] * p.x_axis[1] + pos[1] * p.y_axis[1],
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::GridOverlay;
+    use euclid::{point2, rect};
+    use gui::{GuiDraw, GuiStyle};
+    use space::{CellShape, ScreenProjection};
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_square_grid() {
+        let mut overlay = GridOverlay::new(ScreenProjection {
+            x_axis: [10.0, 0.0],
+            y_axis: [0.0, 10.0],
+            origin: [5.0, 5.0],
+            shape: CellShape::Square,
+        });
+        overlay.toggle();
+        assert!(overlay.is_visible());
+        let style = GuiStyle::default();
+        let draw = overlay.draw(rect(0.0, 0.0, 30.0, 20.0), &style);
+
+        let labels: HashSet<&str> = draw
+            .iter()
+            .filter_map(|d| match d {
+                GuiDraw::Text { text, .. } => Some(&text[..]),
+                _ => None,
+            })
+            .collect();
+        for label in &["0,0", "2,0", "0,1", "2,1"] {
+            assert!(labels.contains(label));
+        }
+        assert!(!labels.contains("-9,0"));
+
+        // The edge between cells (0, 0) and (1, 0) is drawn exactly once.
+        let edge = |d: &&GuiDraw| match d {
+            GuiDraw::Line { start, end, .. } => {
+                *start == point2(10.0, 0.0) && *end == point2(10.0, 10.0)
+            }
+            _ => false,
+        };
+        assert_eq!(draw.iter().filter(edge).count(), 1);
+
+        overlay.show_labels = false;
+        let draw = overlay.draw(rect(0.0, 0.0, 30.0, 20.0), &style);
+        assert!(draw.iter().all(|d| matches!(d, GuiDraw::Line { .. })));
+    }
+
+    #[test]
+    fn test_hex_grid() {
+        let mut overlay = GridOverlay::new(ScreenProjection {
+            x_axis: [24.0, 13.856],
+            y_axis: [-24.0, 13.856],
+            origin: [0.0, 0.0],
+            shape: CellShape::Hex,
+        });
+        overlay.toggle();
+        let draw = overlay.draw(rect(-50.0, -50.0, 100.0, 100.0), &GuiStyle::default());
+
+        // Hex edges are all as long as the circumradius.
+        let mut n_lines = 0;
+        for d in &draw {
+            if let GuiDraw::Line { start, end, .. } = d {
+                assert!(((*end - *start).length() - 16.0).abs() < 0.01);
+                n_lines += 1;
+            }
+        }
+        assert!(n_lines > 0);
+
+        // Three edges for each labeled cell.
+        let n_labels = draw
+            .iter()
+            .filter(|d| matches!(d, GuiDraw::Text { .. }))
+            .count();
+        assert_eq!(n_lines, n_labels * 3);
+    }
+}
@@ -0,0 +1,552 @@
+//! Immediate mode GUI widgets for menus and debug panels
+
+use euclid::{point2, rect, Point2D, Rect};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::mem;
+use std::panic::Location;
+use Rgba;
+
+/// Input event translated from the backend's event stream.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum GuiEvent {
+    /// Mouse cursor moved to a position in GUI coordinates.
+    MouseMove(Point2D<f32>),
+    /// Left mouse button was pressed.
+    MouseDown,
+    /// Left mouse button was released.
+    MouseUp,
+    /// Mouse wheel was turned, positive values scroll up.
+    Scroll(f32),
+    /// Printable character was typed.
+    Char(char),
+    Backspace,
+    Enter,
+    Tab,
+    /// Up arrow key.
+    Up,
+    /// Down arrow key.
+    Down,
+}
+
+/// Drawing command for the backend to render.
+#[derive(Clone, PartialEq, Debug)]
+pub enum GuiDraw {
+    /// Fill an area with a solid color.
+    Fill { area: Rect<f32>, color: Rgba },
+    /// Draw a line of text with its top left corner at a position.
+    Text {
+        pos: Point2D<f32>,
+        text: String,
+        color: Rgba,
+    },
+    /// Draw a one pixel wide line segment.
+    Line {
+        start: Point2D<f32>,
+        end: Point2D<f32>,
+        color: Rgba,
+    },
+}
+
+/// Sizes and colors of the widgets.
+#[derive(Clone, PartialEq, Debug)]
+pub struct GuiStyle {
+    /// Height of a line of text and a list row.
+    pub row_height: f32,
+    /// Width of a character in the GUI font, the GUI assumes a fixed-width font.
+    pub char_width: f32,
+    /// Widget background.
+    pub background: Rgba,
+    /// Background of the widget under the mouse cursor.
+    pub hover: Rgba,
+    /// Background of the widget being clicked or dragged.
+    pub active: Rgba,
+    /// Check marks, slider handles, text cursors and selected list rows.
+    pub highlight: Rgba,
+    pub text: Rgba,
+}
+
+impl Default for GuiStyle {
+    fn default() -> GuiStyle {
+        GuiStyle {
+            row_height: 12.0,
+            char_width: 6.0,
+            background: Rgba::from(0x303040ff),
+            hover: Rgba::from(0x505068ff),
+            active: Rgba::from(0x707090ff),
+            highlight: Rgba::from(0x5080c0ff),
+            text: Rgba::from(0xe0e0e0ff),
+        }
+    }
+}
+
+/// Identifier of a widget, derived from where the widget was called in the source code.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+struct WidgetId(u64);
+
+/// Immediate mode GUI context.
+///
+/// Widgets are drawn and updated with a single method call every frame, and return whether the
+/// user interacted with them. Widget identities are derived from the calling location in the
+/// source code, so the caller doesn't need to keep widget state around. Widgets called from the
+/// same line in a loop need to be told apart with `with_id`.
+///
+/// Feed the frame's input events to `event`, call the widgets, and then draw the commands
+/// from `end_frame` with the backend.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate euclid;
+/// # extern crate calx;
+/// # fn main() {
+/// use euclid::{point2, rect};
+/// use calx::{Gui, GuiEvent};
+///
+/// let mut gui = Gui::default();
+/// let mut sound_on = true;
+/// let mut volume = 0.5;
+///
+/// // Simulate the player clicking the toggle box over three frames.
+/// let input = vec![
+///     vec![GuiEvent::MouseMove(point2(15.0, 15.0))],
+///     vec![GuiEvent::MouseDown],
+///     vec![GuiEvent::MouseUp],
+/// ];
+///
+/// for events in input {
+///     for e in events {
+///         gui.event(e);
+///     }
+///
+///     gui.toggle(rect(10.0, 10.0, 100.0, 12.0), "Sound", &mut sound_on);
+///     gui.slider(rect(10.0, 30.0, 100.0, 12.0), &mut volume, 0.0, 1.0);
+///     if gui.button(rect(10.0, 50.0, 100.0, 12.0), "Done") {
+///         println!("Options closed");
+///     }
+///
+///     for _cmd in gui.end_frame() {
+///         // Draw the command with the backend.
+///     }
+/// }
+///
+/// assert!(!sound_on);
+/// # }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Gui {
+    pub style: GuiStyle,
+    mouse_pos: Point2D<f32>,
+    mouse_down: bool,
+    /// Mouse button went down during this frame.
+    pressed: bool,
+    /// Mouse button went up during this frame.
+    released: bool,
+    scroll: f32,
+    /// Text input events of this frame.
+    typed: Vec<GuiEvent>,
+    /// Widget under the mouse cursor.
+    hot: Option<WidgetId>,
+    /// Widget that the mouse button was pressed on.
+    active: Option<WidgetId>,
+    /// Widget receiving text input.
+    focus: Option<WidgetId>,
+    id_stack: Vec<u64>,
+    /// First visible row of list views.
+    scroll_rows: HashMap<WidgetId, usize>,
+    commands: Vec<GuiDraw>,
+}
+
+impl Gui {
+    pub fn new(style: GuiStyle) -> Gui {
+        Gui {
+            style,
+            ..Default::default()
+        }
+    }
+
+    /// Feed an input event for the current frame.
+    pub fn event(&mut self, event: GuiEvent) {
+        match event {
+            GuiEvent::MouseMove(pos) => self.mouse_pos = pos,
+            GuiEvent::MouseDown => {
+                self.mouse_down = true;
+                self.pressed = true;
+            }
+            GuiEvent::MouseUp => {
+                self.mouse_down = false;
+                self.released = true;
+            }
+            GuiEvent::Scroll(amount) => self.scroll += amount,
+            e => self.typed.push(e),
+        }
+    }
+
+    /// End the frame and return the drawing commands of the widgets.
+    pub fn end_frame(&mut self) -> Vec<GuiDraw> {
+        if self.released {
+            self.active = None;
+        }
+        self.pressed = false;
+        self.released = false;
+        self.scroll = 0.0;
+        self.typed.clear();
+        self.hot = None;
+        mem::take(&mut self.commands)
+    }
+
+    /// Return whether the mouse cursor is over a widget.
+    ///
+    /// Use this to keep clicks on the GUI from passing through to the game view.
+    pub fn is_mouse_captured(&self) -> bool { self.hot.is_some() || self.active.is_some() }
+
+    /// Call widgets in a scope with an extra key mixed into their ids.
+    ///
+    /// Use this when calling widgets in a loop, with the loop index or item as the key.
+    pub fn with_id<K: Hash, F: FnOnce(&mut Gui) -> R, R>(&mut self, key: K, f: F) -> R {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        self.id_stack.push(hasher.finish());
+        let ret = f(self);
+        self.id_stack.pop();
+        ret
+    }
+
+    /// Draw a line of text.
+    pub fn label(&mut self, area: Rect<f32>, text: &str) {
+        let color = self.style.text;
+        self.text(area, text, color);
+    }
+
+    /// Draw a button and return whether it was clicked.
+    #[track_caller]
+    pub fn button(&mut self, area: Rect<f32>, text: &str) -> bool {
+        let id = self.caller_id();
+        let clicked = self.interact(id, area);
+        let color = self.widget_color(id);
+        self.fill(area, color);
+        self.label(area, text);
+        clicked
+    }
+
+    /// Draw a toggle box with a label and flip the value when clicked.
+    ///
+    /// Returns whether the value changed.
+    #[track_caller]
+    pub fn toggle(&mut self, area: Rect<f32>, text: &str, value: &mut bool) -> bool {
+        let id = self.caller_id();
+        let clicked = self.interact(id, area);
+        if clicked {
+            *value = !*value;
+        }
+        let h = self.style.row_height.min(area.size.height);
+        let check = rect(area.origin.x, area.origin.y, h, h);
+        let color = self.widget_color(id);
+        self.fill(check, color);
+        if *value {
+            let mark = check.inflate(-h / 4.0, -h / 4.0);
+            let color = self.style.highlight;
+            self.fill(mark, color);
+        }
+        let label = rect(area.origin.x + h, area.origin.y, area.size.width - h, h);
+        self.label(label, text);
+        clicked
+    }
+
+    /// Draw a horizontal slider for a value between `min` and `max`.
+    ///
+    /// Returns whether the value changed.
+    #[track_caller]
+    pub fn slider(&mut self, area: Rect<f32>, value: &mut f32, min: f32, max: f32) -> bool {
+        let id = self.caller_id();
+        self.interact(id, area);
+        let old = *value;
+        if self.active == Some(id) && area.size.width > 0.0 {
+            let t = ((self.mouse_pos.x - area.origin.x) / area.size.width).clamp(0.0, 1.0);
+            *value = min + t * (max - min);
+        }
+
+        let color = self.widget_color(id);
+        self.fill(area, color);
+        let t = if max != min {
+            ((*value - min) / (max - min)).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        let handle_w = self.style.char_width.min(area.size.width);
+        let handle = rect(
+            area.origin.x + t * (area.size.width - handle_w),
+            area.origin.y,
+            handle_w,
+            area.size.height,
+        );
+        let color = self.style.highlight;
+        self.fill(handle, color);
+        *value != old
+    }
+
+    /// Draw a scrollable list of items and update the selection when a row is clicked.
+    ///
+    /// The list scrolls with the mouse wheel when the cursor is over it. Returns whether the
+    /// selection changed.
+    #[track_caller]
+    pub fn list_view<S: AsRef<str>>(
+        &mut self,
+        area: Rect<f32>,
+        items: &[S],
+        selected: &mut Option<usize>,
+    ) -> bool {
+        let id = self.caller_id();
+        let row_h = self.style.row_height;
+        let visible = ((area.size.height / row_h).floor() as usize).max(1);
+        let max_first = items.len().saturating_sub(visible);
+
+        let mut first = self.scroll_rows.get(&id).cloned().unwrap_or(0);
+        if area.contains(&self.mouse_pos) && self.scroll != 0.0 {
+            first = (first as f32 - self.scroll).round().max(0.0) as usize;
+        }
+        first = first.min(max_first);
+        self.scroll_rows.insert(id, first);
+
+        let old = *selected;
+        if self.interact(id, area) {
+            let row = first + ((self.mouse_pos.y - area.origin.y) / row_h) as usize;
+            if row < items.len() {
+                *selected = Some(row);
+            }
+        }
+
+        let color = self.style.background;
+        self.fill(area, color);
+        for (i, item) in items.iter().enumerate().skip(first).take(visible) {
+            let row = rect(
+                area.origin.x,
+                area.origin.y + (i - first) as f32 * row_h,
+                area.size.width,
+                row_h,
+            );
+            if *selected == Some(i) {
+                let color = self.style.highlight;
+                self.fill(row, color);
+            }
+            self.label(row, item.as_ref());
+        }
+        *selected != old
+    }
+
+    /// Draw a text input field for editing a string.
+    ///
+    /// Clicking the field gives it keyboard focus, and clicking elsewhere or pressing enter
+    /// takes the focus away. Returns whether the text changed.
+    #[track_caller]
+    pub fn text_input(&mut self, area: Rect<f32>, text: &mut String) -> bool {
+        let id = self.caller_id();
+        if self.interact(id, area) {
+            self.focus = Some(id);
+        } else if self.pressed && !area.contains(&self.mouse_pos) && self.focus == Some(id) {
+            self.focus = None;
+        }
+
+        let mut changed = false;
+        if self.focus == Some(id) {
+            for e in &self.typed {
+                match *e {
+                    GuiEvent::Char(c) if !c.is_control() => {
+                        text.push(c);
+                        changed = true;
+                    }
+                    GuiEvent::Backspace => changed |= text.pop().is_some(),
+                    GuiEvent::Enter => {
+                        self.focus = None;
+                        break;
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        let color = if self.focus == Some(id) {
+            self.style.hover
+        } else {
+            self.style.background
+        };
+        self.fill(area, color);
+        self.label(area, text);
+        if self.focus == Some(id) {
+            let x = area.origin.x + self.style.char_width * (text.chars().count() as f32 + 0.5);
+            let caret = rect(x, area.origin.y, 1.0, area.size.height);
+            let color = self.style.highlight;
+            self.fill(caret, color);
+        }
+        changed
+    }
+
+    #[track_caller]
+    fn caller_id(&self) -> WidgetId {
+        let loc = Location::caller();
+        let mut hasher = DefaultHasher::new();
+        (loc.file(), loc.line(), loc.column()).hash(&mut hasher);
+        self.id_stack.hash(&mut hasher);
+        WidgetId(hasher.finish())
+    }
+
+    /// Update the mouse state of a widget and return whether it was clicked.
+    ///
+    /// A click is a press and release of the mouse button both on the widget.
+    fn interact(&mut self, id: WidgetId, area: Rect<f32>) -> bool {
+        let inside = area.contains(&self.mouse_pos);
+        if inside {
+            self.hot = Some(id);
+            if self.pressed {
+                self.active = Some(id);
+            }
+        }
+        self.active == Some(id) && self.released && inside
+    }
+
+    fn widget_color(&self, id: WidgetId) -> Rgba {
+        if self.active == Some(id) && self.mouse_down {
+            self.style.active
+        } else if self.hot == Some(id) {
+            self.style.hover
+        } else {
+            self.style.background
+        }
+    }
+
+    fn fill(&mut self, area: Rect<f32>, color: Rgba) {
+        self.commands.push(GuiDraw::Fill { area, color });
+    }
+
+    fn text(&mut self, area: Rect<f32>, text: &str, color: Rgba) {
+        if text.is_empty() {
+            return;
+        }
+        let y = area.origin.y + (area.size.height - self.style.row_height) / 2.0;
+        self.commands.push(GuiDraw::Text {
+            pos: point2(area.origin.x + self.style.char_width / 2.0, y),
+            text: text.to_string(),
+            color,
+        });
+    }
+}
+
+/// Draw values in [0, 1] as a bar graph rising from the bottom of an area.
+///
+/// The bars are spread evenly over the width of the area, oldest value first for time series.
+pub fn bar_graph(area: Rect<f32>, heights: &[f32], color: Rgba) -> Vec<GuiDraw> {
+    let w = area.size.width / heights.len().max(1) as f32;
+    heights
+        .iter()
+        .enumerate()
+        .filter(|&(_, &h)| h > 0.0)
+        .map(|(i, &h)| {
+            let h = h.min(1.0) * area.size.height;
+            GuiDraw::Fill {
+                area: rect(area.origin.x + i as f32 * w, area.max_y() - h, w, h),
+                color,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{bar_graph, Gui, GuiDraw, GuiEvent};
+    use euclid::{point2, rect};
+    use Rgba;
+
+    fn click(gui: &mut Gui, x: f32, y: f32) {
+        gui.event(GuiEvent::MouseMove(point2(x, y)));
+        gui.event(GuiEvent::MouseDown);
+        gui.event(GuiEvent::MouseUp);
+    }
+
+    #[test]
+    fn test_widgets() {
+        let mut gui = Gui::default();
+        let items = ["a", "b", "c", "d", "e"];
+        let mut selected = None;
+        let mut name = String::new();
+        let mut clicks = Vec::new();
+
+        let mut frame = |gui: &mut Gui, selected: &mut Option<usize>, name: &mut String| {
+            for i in 0..3 {
+                let area = rect(0.0, i as f32 * 10.0, 50.0, 10.0);
+                if gui.with_id(i, |gui| gui.button(area, "x")) {
+                    clicks.push(i);
+                }
+            }
+            gui.list_view(rect(100.0, 0.0, 50.0, 24.0), &items, selected);
+            gui.text_input(rect(0.0, 100.0, 50.0, 12.0), name);
+            gui.end_frame()
+        };
+
+        click(&mut gui, 10.0, 15.0);
+        frame(&mut gui, &mut selected, &mut name);
+        // Dragging off the button doesn't click it.
+        gui.event(GuiEvent::MouseMove(point2(10.0, 5.0)));
+        gui.event(GuiEvent::MouseDown);
+        frame(&mut gui, &mut selected, &mut name);
+        assert!(gui.is_mouse_captured());
+        gui.event(GuiEvent::MouseMove(point2(60.0, 5.0)));
+        gui.event(GuiEvent::MouseUp);
+        frame(&mut gui, &mut selected, &mut name);
+        assert!(!gui.is_mouse_captured());
+
+        // Two rows fit in the list, scroll down and pick the first visible one.
+        gui.event(GuiEvent::MouseMove(point2(110.0, 5.0)));
+        gui.event(GuiEvent::Scroll(-10.0));
+        frame(&mut gui, &mut selected, &mut name);
+        click(&mut gui, 110.0, 5.0);
+        let draw = frame(&mut gui, &mut selected, &mut name);
+        assert_eq!(selected, Some(3));
+        assert!(draw.iter().any(|d| match *d {
+            GuiDraw::Text { ref text, .. } => text == "e",
+            _ => false,
+        }));
+
+        // Typing only goes to the focused field.
+        gui.event(GuiEvent::Char('q'));
+        frame(&mut gui, &mut selected, &mut name);
+        click(&mut gui, 10.0, 105.0);
+        frame(&mut gui, &mut selected, &mut name);
+        for c in "orx".chars() {
+            gui.event(GuiEvent::Char(c));
+        }
+        gui.event(GuiEvent::Backspace);
+        gui.event(GuiEvent::Char('c'));
+        frame(&mut gui, &mut selected, &mut name);
+        gui.event(GuiEvent::Enter);
+        gui.event(GuiEvent::Char('!'));
+        frame(&mut gui, &mut selected, &mut name);
+        assert_eq!(name, "orc");
+
+        assert_eq!(clicks, vec![1]);
+    }
+
+    #[test]
+    fn test_bar_graph() {
+        let color = Rgba::from(0xff0000ff);
+        let bars = bar_graph(rect(10.0, 0.0, 40.0, 20.0), &[0.5, 0.0, 2.0, 0.25], color);
+        assert_eq!(
+            bars,
+            vec![
+                GuiDraw::Fill {
+                    area: rect(10.0, 10.0, 10.0, 10.0),
+                    color,
+                },
+                GuiDraw::Fill {
+                    area: rect(30.0, 0.0, 10.0, 20.0),
+                    color,
+                },
+                GuiDraw::Fill {
+                    area: rect(40.0, 15.0, 10.0, 5.0),
+                    color,
+                },
+            ]
+        );
+        assert!(bar_graph(rect(0.0, 0.0, 10.0, 10.0), &[], color).is_empty());
+    }
+}
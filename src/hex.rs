@@ -4,7 +4,8 @@ use rand::distributions::{Distribution, Standard};
 use rand::Rng;
 use std::cmp::max;
 use std::f32::consts::PI;
-use std::ops::{Add, Sub};
+use std::iter::FromIterator;
+use std::ops::{Add, BitAnd, BitOr, Sub};
 use std::slice;
 use CellVector;
 
@@ -25,15 +26,13 @@ impl HexGeom for CellVector {
 }
 
 /// Return offsets to neighboring hexes.
+///
+/// Use `DirSet6::neighbors` for only some of the neighbors.
 pub fn hex_neighbors<P, R>(origin: P) -> impl Iterator<Item = R>
 where
     P: Clone + Add<CellVector, Output = R>,
 {
-    use Dir6::*;
-
-    [North, Northeast, Southeast, South, Southwest, Northwest]
-        .iter()
-        .map(move |&d| origin.clone() + d.into())
+    DirSet6::all().neighbors(origin)
 }
 
 /// Return offsets to the four cardinal directions.
@@ -50,6 +49,189 @@ where
         .map(move |&d| origin.clone() + d.into())
 }
 
+/// Round fractional `CellSpace` hex coordinates to the hex cell that contains them.
+pub fn hex_round(x: f32, y: f32) -> CellVector {
+    // Convert to cube coordinates where each hex axis gets its own component, round each
+    // component and then fix the one with the largest rounding error to make the components sum
+    // to zero again. This places the cell boundaries on the actual hexagon edges.
+    let cube = [x, -y, y - x];
+    let mut rounded = [cube[0].round(), cube[1].round(), cube[2].round()];
+    let err = [
+        (rounded[0] - cube[0]).abs(),
+        (rounded[1] - cube[1]).abs(),
+        (rounded[2] - cube[2]).abs(),
+    ];
+
+    if err[0] > err[1] && err[0] > err[2] {
+        rounded[0] = -rounded[1] - rounded[2];
+    } else if err[1] > err[2] {
+        rounded[1] = -rounded[0] - rounded[2];
+    }
+
+    vec2(rounded[0] as i32, -rounded[1] as i32)
+}
+
+/// Iterate the cells on a line between two hex cells.
+///
+/// The line includes both endpoints and every step moves to an adjacent cell. Lines that pass
+/// exactly between two cells are nudged to the same side regardless of which end the line is
+/// drawn from, so `hex_line(b, a)` visits the same cells as `hex_line(a, b)` in reverse order.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate euclid;
+/// # extern crate calx;
+/// # fn main() {
+/// use euclid::vec2;
+/// use calx::hex_line;
+///
+/// let line: Vec<_> = hex_line(vec2(0, 0), vec2(2, 0)).collect();
+/// assert_eq!(line, vec![vec2(0, 0), vec2(1, 0), vec2(2, 0)]);
+///
+/// let mut back: Vec<_> = hex_line(vec2(3, -1), vec2(-2, 2)).collect();
+/// back.reverse();
+/// assert_eq!(back, hex_line(vec2(-2, 2), vec2(3, -1)).collect::<Vec<_>>());
+/// # }
+/// ```
+pub fn hex_line(a: CellVector, b: CellVector) -> impl Iterator<Item = CellVector> {
+    // Constant offset for both ends of the line, so the nudge doesn't depend on direction.
+    const NUDGE: (f32, f32) = (1e-3, 3e-3);
+
+    let n = (b - a).hex_dist();
+    let d = b - a;
+    (0..=n).map(move |i| {
+        let t = if n == 0 { 0.0 } else { i as f32 / n as f32 };
+        hex_round(
+            a.x as f32 + d.x as f32 * t + NUDGE.0,
+            a.y as f32 + d.y as f32 * t + NUDGE.1,
+        )
+    })
+}
+
+/// Return whether there is an unobstructed line between two hex cells.
+///
+/// The endpoints themselves are not checked for obstruction. The result is the same in both
+/// directions.
+pub fn line_of_sight<F>(a: CellVector, b: CellVector, is_blocked: F) -> bool
+where
+    F: Fn(CellVector) -> bool,
+{
+    hex_line(a, b)
+        .filter(|&p| p != a && p != b)
+        .all(|p| !is_blocked(p))
+}
+
+/// Convert a hex position into cube coordinates.
+///
+/// `CellVector` hex positions are axial coordinates. The cube coordinates have a separate axis
+/// for each of the three hex axes, and the components always sum to zero.
+pub fn hex_to_cube(v: CellVector) -> [i32; 3] { [v.x, -v.y, v.y - v.x] }
+
+/// Convert cube coordinates into a hex position.
+pub fn hex_from_cube(cube: [i32; 3]) -> CellVector {
+    debug_assert_eq!(cube[0] + cube[1] + cube[2], 0, "Invalid cube coordinates");
+    vec2(cube[0], -cube[1])
+}
+
+/// Convert a hex position into offset coordinates.
+///
+/// The offset coordinates are the column and the row of the cell in the flat-top hex
+/// projection, with the odd columns shifted half a cell down. Use them for storing hex maps in
+/// rectangular arrays and for reading maps drawn in external tools.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate euclid;
+/// # extern crate calx;
+/// # fn main() {
+/// use euclid::vec2;
+/// use calx::{hex_from_offset, hex_to_offset, Dir6};
+///
+/// assert_eq!(hex_to_offset(vec2(0, 0)), (0, 0));
+/// assert_eq!(hex_to_offset(Dir6::South.to_v2()), (0, 1));
+/// assert_eq!(hex_to_offset(Dir6::Southeast.to_v2()), (1, 0));
+/// assert_eq!(hex_to_offset(Dir6::Northeast.to_v2()), (1, -1));
+/// assert_eq!(hex_from_offset(1, -1), Dir6::Northeast.to_v2());
+/// # }
+/// ```
+pub fn hex_to_offset(v: CellVector) -> (i32, i32) {
+    (v.x - v.y, Integer::div_floor(&(v.x + v.y), &2))
+}
+
+/// Convert offset coordinates into a hex position.
+///
+/// See `hex_to_offset` for the offset coordinate layout.
+pub fn hex_from_offset(col: i32, row: i32) -> CellVector {
+    let sum = 2 * row + col.mod_floor(&2);
+    vec2((sum + col) / 2, (sum - col) / 2)
+}
+
+/// Rotate a hex position around a center point by 60 degree steps clockwise.
+///
+/// Negative steps rotate counterclockwise.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate euclid;
+/// # extern crate calx;
+/// # fn main() {
+/// use std::collections::HashSet;
+/// use euclid::vec2;
+/// use calx::{hex_mirror, hex_rotate, Dir6};
+///
+/// let north = Dir6::North.to_v2();
+/// assert_eq!(hex_rotate(north, vec2(0, 0), 1), Dir6::Northeast.to_v2());
+/// assert_eq!(hex_rotate(north, vec2(0, 0), -1), Dir6::Northwest.to_v2());
+///
+/// // Transform a whole prefab.
+/// let shape: HashSet<_> = vec![vec2(0, 0), vec2(1, 0), vec2(2, 0)].into_iter().collect();
+/// let turned: HashSet<_> = shape.iter().map(|&p| hex_rotate(p, vec2(1, 0), 3)).collect();
+/// assert_eq!(turned, shape);
+/// let flipped: HashSet<_> = shape.iter().map(|&p| hex_mirror(p, vec2(0, 0))).collect();
+/// assert!(flipped.contains(&vec2(0, 2)));
+/// # }
+/// ```
+pub fn hex_rotate(pos: CellVector, around: CellVector, steps: i32) -> CellVector {
+    let [x, y, z] = hex_to_cube(pos - around);
+    // Each clockwise step moves the cube components one slot to the right and flips their signs.
+    let rotated = match steps.mod_floor(&6) {
+        0 => [x, y, z],
+        1 => [-z, -x, -y],
+        2 => [y, z, x],
+        3 => [-x, -y, -z],
+        4 => [z, x, y],
+        _ => [-y, -z, -x],
+    };
+    around + hex_from_cube(rotated)
+}
+
+/// Mirror a hex position across the north-south axis going through a center point.
+pub fn hex_mirror(pos: CellVector, around: CellVector) -> CellVector {
+    let v = pos - around;
+    around + vec2(v.y, v.x)
+}
+
+/// Return an iterator for the points at exactly the given distance from the origin.
+///
+/// The ring starts from the north and goes clockwise. A ring with radius 0 is just the origin.
+pub fn hex_ring<P, R>(origin: P, radius: i32) -> impl Iterator<Item = R>
+where
+    P: Clone + Add<CellVector, Output = R>,
+{
+    let n = if radius == 0 { 1 } else { 6 * radius.max(0) };
+    (0..n).map(move |i| {
+        if radius == 0 {
+            return origin.clone() + vec2(0, 0);
+        }
+        let rod = Dir6::from_int(i / radius);
+        let tangent = Dir6::from_int(i / radius + 2);
+        origin.clone() + (rod.to_v2() * radius + tangent.to_v2() * (i % radius))
+    })
+}
+
 /// Return an iterator for all the points in the hex disc with the given radius.
 pub fn hex_disc<P, R>(origin: P, radius: i32) -> HexDisc<P>
 where
@@ -168,6 +350,11 @@ impl Dir6 {
     /// Iterate through the six hex dirs in the standard order.
     pub fn iter() -> slice::Iter<'static, Dir6> { DIRS.iter() }
 
+    /// Return the number of 60 degree clockwise steps from this direction to another.
+    ///
+    /// The result is between -2 and 3, negative for counterclockwise turns.
+    pub fn angle_to(self, other: Dir6) -> i32 { (other as i32 - self as i32 + 2).mod_floor(&6) - 2 }
+
     /// Return whether the direction is along the axes used in fake isometric graphics.
     pub fn is_fake_isometric(self) -> bool {
         use Dir6::*;
@@ -229,6 +416,12 @@ pub enum Dir12 {
 }
 
 impl Dir12 {
+    /// Convert an integer to a 12-way dir using modular arithmetic.
+    pub fn from_int(i: i32) -> Dir12 { DIRS12[i.mod_floor(&12) as usize] }
+
+    /// Iterate through the twelve dirs in the standard order.
+    pub fn iter() -> slice::Iter<'static, Dir12> { DIRS12.iter() }
+
     /// If there is exactly one cluster of neighbors in the neighbor mask,
     /// return a direction pointing away from that cluster.
     pub fn away_from(neighbors: &[bool; 6]) -> Option<Dir12> {
@@ -253,8 +446,7 @@ impl Dir12 {
         let away_dir: u8 = ((center_dir + 6) % 12) as u8;
         debug_assert!(away_dir < 12);
 
-        // XXX: Unsafe because I'm too lazy to do int conversion func by hand.
-        return Some(unsafe { ::std::mem::transmute(away_dir) });
+        return Some(Dir12::from_int(away_dir as i32));
 
         fn find_cluster(neighbors: &[bool; 6]) -> Option<(usize, usize)> {
             // Start of the active cluster, inclusive.
@@ -293,6 +485,139 @@ impl Dir12 {
     }
 }
 
+static DIRS12: [Dir12; 12] = [
+    Dir12::North,
+    Dir12::NorthNortheast,
+    Dir12::Northeast,
+    Dir12::East,
+    Dir12::Southeast,
+    Dir12::SouthSoutheast,
+    Dir12::South,
+    Dir12::SouthSouthwest,
+    Dir12::Southwest,
+    Dir12::West,
+    Dir12::Northwest,
+    Dir12::NorthNorthwest,
+];
+
+macro_rules! dir_set {
+    ($(#[$attr:meta])* $name:ident, $dir:ident, $n:expr) => {
+        $(#[$attr])*
+        #[derive(Copy, Clone, Eq, PartialEq, Hash, Default, Debug, Serialize, Deserialize)]
+        pub struct $name(u16);
+
+        impl $name {
+            /// Set with every direction.
+            pub fn all() -> $name { $name((1 << $n) - 1) }
+
+            /// Set with the directions for which the predicate is true.
+            pub fn from_fn<F: Fn($dir) -> bool>(f: F) -> $name {
+                $dir::iter().cloned().filter(|&d| f(d)).collect()
+            }
+
+            pub fn contains(self, dir: $dir) -> bool { self.0 & (1 << dir as u16) != 0 }
+
+            pub fn insert(&mut self, dir: $dir) { self.0 |= 1 << dir as u16; }
+
+            pub fn remove(&mut self, dir: $dir) { self.0 &= !(1 << dir as u16); }
+
+            pub fn len(self) -> usize { self.0.count_ones() as usize }
+
+            pub fn is_empty(self) -> bool { self.0 == 0 }
+
+            /// Return the set of the directions not in this set.
+            pub fn complement(self) -> $name { $name(!self.0 & $name::all().0) }
+
+            /// Rotate every direction in the set clockwise by the given number of steps.
+            pub fn rotate(self, steps: i32) -> $name {
+                let steps = steps.mod_floor(&$n) as u16;
+                let bits = (self.0 << steps) | (self.0 >> (($n - steps) % $n));
+                $name(bits & $name::all().0)
+            }
+
+            /// Iterate the directions in the set in the standard order.
+            pub fn iter(self) -> impl Iterator<Item = $dir> {
+                $dir::iter().cloned().filter(move |&d| self.contains(d))
+            }
+        }
+
+        impl FromIterator<$dir> for $name {
+            fn from_iter<I: IntoIterator<Item = $dir>>(iter: I) -> $name {
+                let mut ret = $name::default();
+                for d in iter {
+                    ret.insert(d);
+                }
+                ret
+            }
+        }
+
+        impl BitOr for $name {
+            type Output = $name;
+            fn bitor(self, rhs: $name) -> $name { $name(self.0 | rhs.0) }
+        }
+
+        impl BitAnd for $name {
+            type Output = $name;
+            fn bitand(self, rhs: $name) -> $name { $name(self.0 & rhs.0) }
+        }
+
+        impl From<$dir> for $name {
+            fn from(dir: $dir) -> $name { $name(1 << dir as u16) }
+        }
+    }
+}
+
+dir_set! {
+    /// Compact set of hex directions, such as the passable exits of a cell.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use calx::{Dir6, DirSet6};
+    ///
+    /// let exits: DirSet6 = vec![Dir6::North, Dir6::Southeast].into_iter().collect();
+    /// assert!(exits.contains(Dir6::North));
+    /// assert_eq!(exits.complement().len(), 4);
+    /// assert_eq!(
+    ///     exits.rotate(1).iter().collect::<Vec<_>>(),
+    ///     vec![Dir6::Northeast, Dir6::South]
+    /// );
+    /// ```
+    DirSet6, Dir6, 6
+}
+
+dir_set! {
+    /// Compact set of 12-way hex directions.
+    DirSet12, Dir12, 12
+}
+
+impl DirSet6 {
+    /// Iterate the neighbor positions of a cell in the directions of the set.
+    ///
+    /// Use this to generate search graph neighbors from precomputed exits without building
+    /// intermediate collections.
+    pub fn neighbors<P, R>(self, origin: P) -> impl Iterator<Item = R>
+    where
+        P: Clone + Add<CellVector, Output = R>,
+    {
+        self.iter().map(move |d| origin.clone() + d.into())
+    }
+}
+
+impl From<[bool; 6]> for DirSet6 {
+    fn from(mask: [bool; 6]) -> DirSet6 { DirSet6::from_fn(|d| mask[d as usize]) }
+}
+
+impl From<DirSet6> for [bool; 6] {
+    fn from(set: DirSet6) -> [bool; 6] {
+        let mut ret = [false; 6];
+        for d in set.iter() {
+            ret[d as usize] = true;
+        }
+        ret
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::hex_disc;
@@ -379,6 +704,36 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_dir_set() {
+        use super::{DirSet12, DirSet6};
+
+        let mut set = DirSet6::default();
+        assert!(set.is_empty());
+        set.insert(Northwest);
+        set.insert(North);
+        assert_eq!(2, set.len());
+        assert_eq!(vec![North, Northwest], set.iter().collect::<Vec<_>>());
+        assert_eq!(
+            vec![North, Northeast],
+            set.rotate(1).iter().collect::<Vec<_>>()
+        );
+        assert_eq!(set, set.rotate(-6));
+        assert_eq!(set, set.rotate(2).rotate(-2));
+        assert_eq!(DirSet6::all(), set | set.complement());
+        assert!((set & set.complement()).is_empty());
+
+        let mask = [true, false, false, true, true, false];
+        assert_eq!(mask, <[bool; 6]>::from(DirSet6::from(mask)));
+
+        let neighbors: Vec<_> = set.neighbors(vec2(10, 10)).collect();
+        assert_eq!(vec![vec2(9, 9), vec2(9, 10)], neighbors);
+
+        let set = DirSet12::from(Dir12::NorthNorthwest);
+        assert_eq!(vec![Dir12::North], set.rotate(1).iter().collect::<Vec<_>>());
+        assert_eq!(11, set.complement().len());
+    }
+
     #[test]
     fn test_hex_disc() {
         use super::HexGeom;
@@ -397,4 +752,71 @@ mod test {
             }
         }
     }
+
+    #[test]
+    fn test_hex_transforms() {
+        use super::{
+            hex_from_cube, hex_from_offset, hex_mirror, hex_ring, hex_rotate, hex_to_cube,
+            hex_to_offset, HexGeom,
+        };
+        use std::collections::HashSet;
+
+        for p in hex_disc(vec2(0, 0), 6) {
+            assert_eq!(hex_to_cube(p).iter().sum::<i32>(), 0);
+            assert_eq!(hex_from_cube(hex_to_cube(p)), p);
+            let (col, row) = hex_to_offset(p);
+            assert_eq!(hex_from_offset(col, row), p);
+
+            let c = vec2(2, -1);
+            assert_eq!(hex_rotate(p, c, 6), p);
+            assert_eq!(hex_rotate(hex_rotate(p, c, 2), c, -2), p);
+            assert_eq!((hex_rotate(p, c, 1) - c).hex_dist(), (p - c).hex_dist());
+            assert_eq!(hex_mirror(hex_mirror(p, c), c), p);
+        }
+
+        for (i, &d) in Dir6::iter().enumerate() {
+            assert_eq!(hex_rotate(d.to_v2(), vec2(0, 0), 1), (d + 1).to_v2());
+            assert_eq!(North.angle_to(d), [0, 1, 2, 3, -2, -1][i]);
+            assert_eq!(d.angle_to(d + 1), 1);
+        }
+        assert_eq!(hex_mirror(Northeast.to_v2(), vec2(0, 0)), Northwest.to_v2());
+        assert_eq!(hex_mirror(South.to_v2(), vec2(0, 0)), South.to_v2());
+
+        // Odd columns are shifted down.
+        assert_eq!(hex_to_offset(Southwest.to_v2()), (-1, 0));
+        assert_eq!(hex_to_offset(Northwest.to_v2()), (-1, -1));
+
+        for r in 0..5 {
+            let ring: Vec<_> = hex_ring(vec2(3, 3), r).collect();
+            assert_eq!(ring.len(), if r == 0 { 1 } else { 6 * r as usize });
+            assert_eq!(ring.iter().collect::<HashSet<_>>().len(), ring.len());
+            assert!(ring.iter().all(|&p| (p - vec2(3, 3)).hex_dist() == r));
+        }
+        assert_eq!(hex_ring(vec2(0, 0), 1).next(), Some(North.to_v2()));
+        assert_eq!(hex_ring::<_, ::CellVector>(vec2(0, 0), -1).count(), 0);
+    }
+
+    #[test]
+    fn test_hex_line() {
+        use super::{hex_line, line_of_sight};
+        use test_maps::{check_path, check_property, random_cell};
+
+        check_property(&"hex_line", 200, |rng| {
+            let (a, b) = (random_cell(rng, 20), random_cell(rng, 20));
+            let line: Vec<_> = hex_line(a, b).collect();
+            check_path(&line, |_| true)?;
+
+            let mut back: Vec<_> = hex_line(b, a).collect();
+            back.reverse();
+            if line[0] != a || line[line.len() - 1] != b || line != back {
+                return Err(format!("Bad line from {:?} to {:?}: {:?}", a, b, line));
+            }
+            Ok(())
+        });
+
+        let wall = |p| p == vec2(1, 0);
+        assert!(!line_of_sight(vec2(0, 0), vec2(3, 0), wall));
+        assert!(line_of_sight(vec2(0, 0), vec2(1, 0), wall));
+        assert!(line_of_sight(vec2(0, 0), vec2(0, 3), wall));
+    }
 }
@@ -1,10 +1,129 @@
+use angle::Angle;
 use euclid::vec2;
-use fov::{Fov, PolarPoint};
-use hex::Dir6;
+use fov::{FovValue, PolarPoint};
+use hex::{Dir6, HexGeom};
 use num::Integer;
+use std::collections::HashMap;
+use std::mem;
+use std::sync::OnceLock;
 use CellVector;
 
-pub type HexFov<T> = Fov<HexPolarPoint, T>;
+/// Largest circle radius with precomputed cell positions and arc end points.
+///
+/// Short-range FOVs only ever visit the precomputed circles.
+const TABLE_RADIUS: u32 = 8;
+
+/// Marker for a missing arc table state.
+const NO_STATE: u16 = u16::MAX;
+
+/// Cell positions of the hex circles up to `TABLE_RADIUS` in winding order.
+///
+/// The circle of radius r starts at index 3 * r * (r - 1).
+fn circle_table() -> &'static [CellVector] {
+    static TABLE: OnceLock<Vec<CellVector>> = OnceLock::new();
+    TABLE.get_or_init(|| {
+        (1..=TABLE_RADIUS)
+            .flat_map(|r| (0..6 * r as i32).map(move |i| circle_pos(r, i)))
+            .collect()
+    })
+}
+
+/// Arc end point states of the circles up to `TABLE_RADIUS`.
+///
+/// The states are exactly the `HexPolarPoint` values that the general FOV algorithm reaches when
+/// starting from the full unit circle, so arcs built from them come out identical to the general
+/// algorithm, but need no floating point math.
+struct ArcTable {
+    points: Vec<HexPolarPoint>,
+    winding_index: Vec<i32>,
+    end_index: Vec<i32>,
+    /// State of the expanded point, `NO_STATE` for points on the outermost circle.
+    expand: Vec<u16>,
+    /// States of the points stepped to winding index w on circle r, at 3 * r * (r - 1) + w - 1.
+    stepped: Vec<u16>,
+    /// States of the full unit circle end points.
+    unit_circle: (u16, u16),
+}
+
+impl ArcTable {
+    fn new() -> ArcTable {
+        let mut ret = ArcTable {
+            points: Vec::new(),
+            winding_index: Vec::new(),
+            end_index: Vec::new(),
+            expand: Vec::new(),
+            stepped: Vec::new(),
+            unit_circle: (0, 0),
+        };
+        let mut index = HashMap::new();
+
+        let (begin, end) = HexPolarPoint::unit_circle_endpoints();
+        ret.unit_circle = (ret.insert(&mut index, begin), ret.insert(&mut index, end));
+        for r in 1..=TABLE_RADIUS {
+            for w in 1..=6 * r as i32 {
+                let p = HexPolarPoint {
+                    pos: (w - 1) as f32 + 0.5,
+                    radius: r,
+                };
+                debug_assert!(p.winding_index() == w);
+                let state = ret.insert(&mut index, p);
+                ret.stepped.push(state);
+            }
+        }
+
+        // Expanding adds new states to the end, keep going until they're all expanded.
+        let mut i = 0;
+        while i < ret.points.len() {
+            let p = ret.points[i];
+            if p.radius < TABLE_RADIUS {
+                ret.expand[i] = ret.insert(&mut index, p.expand());
+            }
+            i += 1;
+        }
+        debug_assert!(ret.points.len() < NO_STATE as usize);
+        ret
+    }
+
+    fn insert(&mut self, index: &mut HashMap<(u32, u32), u16>, p: HexPolarPoint) -> u16 {
+        let points = &mut self.points;
+        let winding_index = &mut self.winding_index;
+        let end_index = &mut self.end_index;
+        let expand = &mut self.expand;
+        *index.entry((p.pos.to_bits(), p.radius)).or_insert_with(|| {
+            points.push(p);
+            winding_index.push(p.winding_index());
+            end_index.push(p.end_index());
+            expand.push(NO_STATE);
+            (points.len() - 1) as u16
+        })
+    }
+
+    /// Return the state of a point stepped to a winding index, if it's in the table.
+    fn stepped(&self, radius: u32, winding_index: i32) -> Option<u16> {
+        let r = radius as i32;
+        if radius <= TABLE_RADIUS && winding_index >= 1 && winding_index <= 6 * r {
+            Some(self.stepped[(3 * r * (r - 1) + winding_index - 1) as usize])
+        } else {
+            None
+        }
+    }
+}
+
+fn arc_table() -> &'static ArcTable {
+    static TABLE: OnceLock<ArcTable> = OnceLock::new();
+    TABLE.get_or_init(ArcTable::new)
+}
+
+/// Return the position of a cell along a hex circle.
+fn circle_pos(radius: u32, index: i32) -> CellVector {
+    let sector = index.mod_floor(&(radius as i32 * 6)) / radius as i32;
+    let offset = index.mod_floor(&(radius as i32));
+
+    let rod = Dir6::from_int(sector).to_v2();
+    let tangent = Dir6::from_int(sector + 2).to_v2();
+
+    rod * (radius as i32) + tangent * offset
+}
 
 /// Points on a hex circle expressed in polar coordinates.
 #[derive(Copy, Clone, PartialEq)]
@@ -41,13 +160,11 @@ impl PolarPoint for HexPolarPoint {
             return vec2(0, 0);
         }
         let index = self.winding_index();
-        let sector = index.mod_floor(&(self.radius as i32 * 6)) / self.radius as i32;
-        let offset = index.mod_floor(&(self.radius as i32));
-
-        let rod = Dir6::from_int(sector).to_v2();
-        let tangent = Dir6::from_int(sector + 2).to_v2();
-
-        rod * (self.radius as i32) + tangent * offset
+        if self.radius <= TABLE_RADIUS {
+            let r = self.radius as i32;
+            return circle_table()[(3 * r * (r - 1) + index.mod_floor(&(6 * r))) as usize];
+        }
+        circle_pos(self.radius, index)
     }
 
     fn expand(&self) -> Self {
@@ -60,6 +177,285 @@ impl PolarPoint for HexPolarPoint {
     fn advance(&mut self) { self.pos = (self.pos + 0.5).floor() + 0.5; }
 }
 
+/// End point of a FOV arc, a precomputed table state or a general polar point.
+#[derive(Copy, Clone)]
+enum ArcPoint {
+    Table(u16),
+    General(HexPolarPoint),
+}
+
+impl ArcPoint {
+    fn polar(self) -> HexPolarPoint {
+        match self {
+            ArcPoint::Table(i) => arc_table().points[i as usize],
+            ArcPoint::General(p) => p,
+        }
+    }
+
+    fn winding_index(self) -> i32 {
+        match self {
+            ArcPoint::Table(i) => arc_table().winding_index[i as usize],
+            ArcPoint::General(p) => p.winding_index(),
+        }
+    }
+
+    fn end_index(self) -> i32 {
+        match self {
+            ArcPoint::Table(i) => arc_table().end_index[i as usize],
+            ArcPoint::General(p) => p.end_index(),
+        }
+    }
+
+    fn expand(self) -> ArcPoint {
+        if let ArcPoint::Table(i) = self {
+            let next = arc_table().expand[i as usize];
+            if next != NO_STATE {
+                return ArcPoint::Table(next);
+            }
+        }
+        ArcPoint::General(self.polar().expand())
+    }
+
+    /// Return the point that `HexPolarPoint::advance` steps to at a winding index.
+    fn stepped(radius: u32, winding_index: i32) -> ArcPoint {
+        match arc_table().stepped(radius, winding_index) {
+            Some(i) => ArcPoint::Table(i),
+            None => ArcPoint::General(HexPolarPoint {
+                pos: (winding_index - 1) as f32 + 0.5,
+                radius,
+            }),
+        }
+    }
+}
+
+/// Arc of cells on one circle of a `HexFov` that share a FOV value.
+struct HexArc<T> {
+    radius: u32,
+    begin: ArcPoint,
+    /// Winding index of the cell currently being processed.
+    pt: i32,
+    end: ArcPoint,
+    /// The user value from the previous circle.
+    prev_value: T,
+    /// The user value for this group.
+    group_value: Option<T>,
+    /// Whether the group value was computed for the current point, so it can't split there.
+    is_checked: bool,
+}
+
+impl<T: FovValue> HexArc<T> {
+    fn expand(radius: u32, begin: ArcPoint, end: ArcPoint, prev_value: T) -> HexArc<T> {
+        let pt = begin.winding_index();
+        let group_value = prev_value.advance(cell_pos(radius, pt));
+        HexArc {
+            radius,
+            begin,
+            pt,
+            end,
+            prev_value,
+            group_value,
+            is_checked: true,
+        }
+    }
+
+    /// Return the current point as an arc end point.
+    fn pt_point(&self) -> ArcPoint {
+        if self.pt == self.begin.winding_index() {
+            self.begin
+        } else {
+            ArcPoint::stepped(self.radius, self.pt)
+        }
+    }
+}
+
+/// Return the cell position at a winding index of a hex circle.
+fn cell_pos(radius: u32, index: i32) -> CellVector {
+    if radius <= TABLE_RADIUS {
+        let r = radius as i32;
+        circle_table()[(3 * r * (r - 1) + index.mod_floor(&(6 * r))) as usize]
+    } else {
+        circle_pos(radius, index)
+    }
+}
+
+/// Field of view iterator for hex maps.
+///
+/// Runs the arc splitting algorithm of `Fov` with `HexPolarPoint` coordinates, yielding the same
+/// cells and values in the same order. The arc end points on the circles up to radius 8 come
+/// from a precomputed table, which makes the short-range FOVs of monsters cheaper. Arcs that go
+/// past the table continue with the general polar point computation.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate euclid;
+/// # extern crate calx;
+/// # fn main() {
+/// use std::collections::HashSet;
+/// use euclid::vec2;
+/// use calx::{CellVector, FovValue, HexFov, HexGeom};
+///
+/// #[derive(PartialEq, Clone)]
+/// struct Sight(i32);
+///
+/// impl FovValue for Sight {
+///     fn advance(&self, offset: CellVector) -> Option<Self> {
+///         let is_wall = offset == vec2(1, 1);
+///         if !is_wall && offset.hex_dist() < self.0 { Some(self.clone()) } else { None }
+///     }
+/// }
+///
+/// let seen: HashSet<CellVector> = HexFov::new(Sight(4)).map(|(p, _)| p).collect();
+/// assert!(seen.contains(&vec2(0, 0)));
+/// assert!(seen.contains(&vec2(-3, 0)));
+/// // Shadow behind the wall.
+/// assert!(!seen.contains(&vec2(1, 1)));
+/// assert!(!seen.contains(&vec2(3, 3)));
+/// # }
+/// ```
+pub struct HexFov<T> {
+    stack: Vec<HexArc<T>>,
+    /// The origin cell, returned before the arcs.
+    origin: Option<T>,
+}
+
+impl<T: FovValue> HexFov<T> {
+    /// Create a new field of view iterator with a seed userdata for the origin position.
+    pub fn new(init: T) -> HexFov<T> {
+        let (begin, end) = arc_table().unit_circle;
+        HexFov::from_arc(init, ArcPoint::Table(begin), ArcPoint::Table(end))
+    }
+
+    fn from_arc(init: T, begin: ArcPoint, end: ArcPoint) -> HexFov<T> {
+        HexFov {
+            stack: vec![HexArc::expand(1, begin, end, init.clone())],
+            origin: Some(init),
+        }
+    }
+
+    /// Create a field of view restricted to a cone.
+    ///
+    /// The cone is centered on the `facing` direction and spans `arc_width` in total. A width of
+    /// a full turn or more gives the regular all-around field of view.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate euclid;
+    /// # extern crate calx;
+    /// # fn main() {
+    /// use std::collections::HashSet;
+    /// use euclid::vec2;
+    /// use calx::{Angle, CellVector, Dir6, FovValue, HexFov, HexGeom};
+    ///
+    /// #[derive(PartialEq, Clone)]
+    /// struct Sight(i32);
+    ///
+    /// impl FovValue for Sight {
+    ///     fn advance(&self, offset: CellVector) -> Option<Self> {
+    ///         if offset.hex_dist() < self.0 { Some(self.clone()) } else { None }
+    ///     }
+    /// }
+    ///
+    /// let width = Angle::from_degrees(120.0);
+    /// let cone: HashSet<CellVector> =
+    ///     HexFov::sector(Sight(5), Dir6::North, width).map(|(p, _)| p).collect();
+    /// assert!(cone.contains(&vec2(-3, -3)));
+    /// assert!(cone.contains(&vec2(0, -2)));
+    /// assert!(!cone.contains(&vec2(2, 2)));
+    ///
+    /// let beam: HashSet<CellVector> =
+    ///     HexFov::beam(Sight(5), Dir6::Southeast).map(|(p, _)| p).collect();
+    /// assert_eq!(beam.len(), 5);
+    /// assert!(beam.contains(&vec2(4, 0)));
+    /// # }
+    /// ```
+    pub fn sector(init: T, facing: impl Into<Angle>, arc_width: Angle) -> HexFov<T> {
+        if arc_width.turns() >= 1.0 {
+            return HexFov::new(init);
+        }
+
+        // Unit circle positions are in sixths of a turn.
+        let center = facing.into().normalized().turns() * 6.0;
+        let half_width = arc_width.turns().max(0.0) * 3.0;
+        HexFov::from_arc(
+            init,
+            ArcPoint::General(HexPolarPoint {
+                pos: center - half_width,
+                radius: 1,
+            }),
+            ArcPoint::General(HexPolarPoint {
+                pos: center + half_width,
+                radius: 1,
+            }),
+        )
+    }
+
+    /// Create a field of view along a narrow beam in the given direction.
+    ///
+    /// The beam is one cell wide along the hex directions, for targeting breath weapons and other
+    /// straight line effects that still need to be stopped by obstacles.
+    pub fn beam(init: T, facing: impl Into<Angle>) -> HexFov<T> {
+        HexFov::sector(init, facing, Angle::from_turns(0.001))
+    }
+}
+
+impl<T: FovValue> Iterator for HexFov<T> {
+    type Item = (CellVector, T);
+
+    fn next(&mut self) -> Option<(CellVector, T)> {
+        if let Some(init) = self.origin.take() {
+            return Some((vec2(0, 0), init));
+        }
+
+        while let Some(mut arc) = self.stack.pop() {
+            let pos = cell_pos(arc.radius, arc.pt);
+
+            if !arc.is_checked {
+                let next_value = arc.prev_value.advance(pos);
+                if next_value != arc.group_value {
+                    // Split the arc at the current point, the part before it continues on the
+                    // next circle.
+                    let (begin, pt) = (arc.begin, arc.pt_point());
+                    let group_value = mem::replace(&mut arc.group_value, next_value);
+                    let radius = arc.radius;
+                    arc.begin = pt;
+                    arc.is_checked = true;
+                    self.stack.push(arc);
+                    if let Some(group_value) = group_value {
+                        self.stack.push(HexArc::expand(
+                            radius + 1,
+                            begin.expand(),
+                            pt.expand(),
+                            group_value,
+                        ));
+                    }
+                    continue;
+                }
+            }
+
+            let ret = arc.group_value.clone();
+            arc.pt += 1;
+            arc.is_checked = false;
+            if arc.pt < arc.end.end_index() {
+                self.stack.push(arc);
+            } else if let Some(group_value) = arc.group_value {
+                self.stack.push(HexArc::expand(
+                    arc.radius + 1,
+                    arc.begin.expand(),
+                    arc.end.expand(),
+                    group_value,
+                ));
+            }
+
+            if let Some(ret) = ret {
+                return Some((pos, ret));
+            }
+        }
+        None
+    }
+}
+
 /// Special operations for FOV iterators using hex geometry.
 pub trait HexFovIter: Sized {
     type Value;
@@ -153,14 +549,58 @@ where
     }
 }
 
+/// FOV value for line of sight limited by range and opaque cells.
+///
+/// Opaque cells are seen, but block the sight behind them.
+#[derive(Clone)]
+pub struct Sight<'a> {
+    origin: CellVector,
+    range: i32,
+    is_transparent: &'a dyn Fn(CellVector) -> bool,
+    transparent: bool,
+}
+
+impl<'a> Sight<'a> {
+    pub fn new(
+        origin: CellVector,
+        range: i32,
+        is_transparent: &'a dyn Fn(CellVector) -> bool,
+    ) -> Sight<'a> {
+        Sight {
+            origin,
+            range,
+            is_transparent,
+            transparent: true,
+        }
+    }
+}
+
+impl<'a> PartialEq for Sight<'a> {
+    fn eq(&self, other: &Sight<'a>) -> bool { self.transparent == other.transparent }
+}
+
+impl<'a> FovValue for Sight<'a> {
+    fn advance(&self, offset: CellVector) -> Option<Self> {
+        if !self.transparent || offset.hex_dist() > self.range {
+            return None;
+        }
+        let mut ret = self.clone();
+        ret.transparent = (self.is_transparent)(self.origin + offset);
+        Some(ret)
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::CellVector;
-    use super::{HexFov, HexFovIter};
+    use super::{circle_pos, HexFov, HexFovIter, HexPolarPoint, Sight, TABLE_RADIUS};
+    use angle::Angle;
     use euclid::vec2;
-    use fov::FovValue;
+    use fov::{Fov, FovValue, PolarPoint};
     use hex::HexGeom;
-    use std::collections::HashMap;
+    use rand::Rng;
+    use rng::seeded_rng;
+    use std::collections::{HashMap, HashSet};
     use std::iter::FromIterator;
 
     #[derive(PartialEq, Eq, Clone)]
@@ -192,4 +632,105 @@ mod test {
         assert!(field.contains_key(&vec2(1, 0)));
         assert!(field.contains_key(&vec2(1, -1)));
     }
+
+    /// Polar point that always computes the positions without the precomputed table.
+    #[derive(Copy, Clone, PartialEq)]
+    struct UncachedPoint(HexPolarPoint);
+
+    impl PolarPoint for UncachedPoint {
+        fn unit_circle_endpoints() -> (Self, Self) {
+            let (a, b) = HexPolarPoint::unit_circle_endpoints();
+            (UncachedPoint(a), UncachedPoint(b))
+        }
+
+        fn is_below(&self, other: &Self) -> bool { self.0.is_below(&other.0) }
+
+        fn to_v2(&self) -> CellVector {
+            if self.0.radius == 0 {
+                vec2(0, 0)
+            } else {
+                circle_pos(self.0.radius, self.0.winding_index())
+            }
+        }
+
+        fn expand(&self) -> Self { UncachedPoint(self.0.expand()) }
+
+        fn advance(&mut self) { self.0.advance(); }
+    }
+
+    #[test]
+    fn small_radius_table() {
+        let mut rng = seeded_rng(&"hex_fov");
+        for _ in 0..10000 {
+            let p = HexPolarPoint {
+                pos: rng.gen_range(-100.0, 100.0),
+                radius: rng.gen_range(1, TABLE_RADIUS + 3),
+            };
+            assert_eq!(p.to_v2(), UncachedPoint(p).to_v2());
+        }
+
+        // Whole FOVs on random maps come out the same as with the general algorithm, also
+        // when they extend past the table.
+        for seed in 0..50 {
+            let mut rng = seeded_rng(&seed);
+            let walls: HashSet<CellVector> = (0..40)
+                .map(|_| vec2(rng.gen_range(-9, 10), rng.gen_range(-9, 10)))
+                .collect();
+            let is_transparent = |p| !walls.contains(&p);
+            let range = rng.gen_range(1, TABLE_RADIUS as i32 + 3);
+            let sight = Sight::new(vec2(0, 0), range, &is_transparent);
+
+            let fast: Vec<CellVector> = HexFov::new(sight.clone()).map(|(p, _)| p).collect();
+            let slow: Vec<CellVector> = Fov::<UncachedPoint, _>::new(sight)
+                .map(|(p, _)| p)
+                .collect();
+            assert_eq!(fast, slow);
+
+            // Values that change along the line of sight split the arcs a lot more.
+            let murk = Murk {
+                range,
+                fog: &walls,
+                density: 0,
+            };
+            let fast: Vec<_> = HexFov::new(murk.clone())
+                .map(|(p, m)| (p, m.density))
+                .collect();
+            let slow: Vec<_> = Fov::<UncachedPoint, _>::new(murk.clone())
+                .map(|(p, m)| (p, m.density))
+                .collect();
+            assert_eq!(fast, slow);
+
+            let facing = Angle::from_turns(rng.gen_range(0.0, 1.0));
+            let width = Angle::from_turns(rng.gen_range(0.0, 1.0));
+            let fast: Vec<_> = HexFov::sector(murk.clone(), facing, width)
+                .map(|(p, m)| (p, m.density))
+                .collect();
+            let (center, half_width) = (facing.normalized().turns() * 6.0, width.turns() * 3.0);
+            let point = |pos| UncachedPoint(HexPolarPoint { pos, radius: 1 });
+            let slow: Vec<_> =
+                Fov::with_arc(murk, point(center - half_width), point(center + half_width))
+                    .map(|(p, m)| (p, m.density))
+                    .collect();
+            assert_eq!(fast, slow);
+        }
+    }
+
+    /// FOV value that gets thicker through fog cells until it can't be seen through.
+    #[derive(Clone, PartialEq)]
+    struct Murk<'a> {
+        range: i32,
+        fog: &'a HashSet<CellVector>,
+        density: i32,
+    }
+
+    impl<'a> FovValue for Murk<'a> {
+        fn advance(&self, offset: CellVector) -> Option<Self> {
+            if self.density >= 3 || offset.hex_dist() > self.range {
+                return None;
+            }
+            let mut ret = self.clone();
+            ret.density += self.fog.contains(&offset) as i32;
+            Some(ret)
+        }
+    }
 }
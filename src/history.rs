@@ -0,0 +1,218 @@
+use std::collections::VecDeque;
+
+/// A reversible operation on a target value.
+pub trait Command {
+    type Target;
+
+    /// Perform the operation.
+    fn apply(&mut self, target: &mut Self::Target);
+
+    /// Reverse the effects of a previous `apply`.
+    fn undo(&mut self, target: &mut Self::Target);
+}
+
+/// Undo and redo history of commands.
+///
+/// Commands executed between `begin_group` and `end_group` are undone and redone together as a
+/// single step. When the number of undo steps exceeds the capacity of the history, the oldest
+/// steps are forgotten.
+///
+/// # Examples
+///
+/// ```
+/// use calx::{Command, History};
+///
+/// struct Push(char);
+///
+/// impl Command for Push {
+///     type Target = String;
+///     fn apply(&mut self, s: &mut String) { s.push(self.0); }
+///     fn undo(&mut self, s: &mut String) { s.pop(); }
+/// }
+///
+/// let mut text = String::new();
+/// let mut history = History::new(100);
+///
+/// history.execute(Push('a'), &mut text);
+/// history.begin_group();
+/// history.execute(Push('b'), &mut text);
+/// history.execute(Push('c'), &mut text);
+/// history.end_group();
+/// assert_eq!(text, "abc");
+///
+/// history.undo(&mut text);
+/// assert_eq!(text, "a");
+/// history.redo(&mut text);
+/// assert_eq!(text, "abc");
+/// ```
+pub struct History<C> {
+    undo: VecDeque<Vec<C>>,
+    redo: Vec<Vec<C>>,
+    group: Option<Vec<C>>,
+    group_depth: usize,
+    capacity: usize,
+}
+
+impl<C: Command> History<C> {
+    /// Create an empty history that remembers up to `capacity` undo steps.
+    pub fn new(capacity: usize) -> History<C> {
+        History {
+            undo: VecDeque::new(),
+            redo: Vec::new(),
+            group: None,
+            group_depth: 0,
+            capacity,
+        }
+    }
+
+    /// Apply a command and record it in the history.
+    ///
+    /// Executing a new command discards the redo steps.
+    pub fn execute(&mut self, mut command: C, target: &mut C::Target) {
+        command.apply(target);
+        self.redo.clear();
+        match self.group {
+            Some(ref mut group) => group.push(command),
+            None => self.push_step(vec![command]),
+        }
+    }
+
+    /// Start grouping the following commands into a single undo step.
+    ///
+    /// Groups can be nested, the step is finished when the outermost group ends.
+    pub fn begin_group(&mut self) {
+        if self.group_depth == 0 {
+            self.group = Some(Vec::new());
+        }
+        self.group_depth += 1;
+    }
+
+    /// End a group started with `begin_group`.
+    pub fn end_group(&mut self) {
+        debug_assert!(self.group_depth > 0, "end_group without begin_group");
+        self.group_depth = self.group_depth.saturating_sub(1);
+        if self.group_depth == 0 {
+            if let Some(group) = self.group.take() {
+                if !group.is_empty() {
+                    self.push_step(group);
+                }
+            }
+        }
+    }
+
+    /// Undo the most recent step.
+    ///
+    /// Returns false if there was nothing to undo.
+    pub fn undo(&mut self, target: &mut C::Target) -> bool {
+        self.close_groups();
+        match self.undo.pop_back() {
+            Some(mut step) => {
+                for command in step.iter_mut().rev() {
+                    command.undo(target);
+                }
+                self.redo.push(step);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Redo the most recently undone step.
+    ///
+    /// Returns false if there was nothing to redo.
+    pub fn redo(&mut self, target: &mut C::Target) -> bool {
+        self.close_groups();
+        match self.redo.pop() {
+            Some(mut step) => {
+                for command in step.iter_mut() {
+                    command.apply(target);
+                }
+                self.undo.push_back(step);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Return whether there are steps to undo.
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty() || self.group.as_ref().is_some_and(|g| !g.is_empty())
+    }
+
+    /// Return whether there are steps to redo.
+    pub fn can_redo(&self) -> bool { !self.redo.is_empty() }
+
+    /// Forget all undo and redo steps.
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+        if let Some(ref mut group) = self.group {
+            group.clear();
+        }
+    }
+
+    fn push_step(&mut self, step: Vec<C>) {
+        self.undo.push_back(step);
+        while self.undo.len() > self.capacity {
+            self.undo.pop_front();
+        }
+    }
+
+    /// Finish any open groups so that undo and redo operate on whole steps.
+    fn close_groups(&mut self) {
+        while self.group_depth > 0 {
+            self.end_group();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Command, History};
+
+    struct Add(i32);
+
+    impl Command for Add {
+        type Target = i32;
+        fn apply(&mut self, x: &mut i32) { *x += self.0; }
+        fn undo(&mut self, x: &mut i32) { *x -= self.0; }
+    }
+
+    #[test]
+    fn test_history() {
+        let mut x = 0;
+        let mut history = History::new(2);
+        for i in 1..=3 {
+            history.execute(Add(i), &mut x);
+        }
+        assert_eq!(x, 6);
+
+        // Capacity limits the undo depth.
+        assert!(history.undo(&mut x));
+        assert!(history.undo(&mut x));
+        assert!(!history.undo(&mut x));
+        assert_eq!(x, 1);
+
+        // New commands discard the redo steps.
+        assert!(history.can_redo());
+        history.execute(Add(10), &mut x);
+        assert!(!history.can_redo());
+
+        // Nested groups form a single step.
+        history.begin_group();
+        history.execute(Add(100), &mut x);
+        history.begin_group();
+        history.execute(Add(1000), &mut x);
+        history.end_group();
+        history.end_group();
+        assert_eq!(x, 1111);
+        history.undo(&mut x);
+        assert_eq!(x, 11);
+
+        // Empty groups don't create steps.
+        history.begin_group();
+        history.end_group();
+        history.undo(&mut x);
+        assert_eq!(x, 1);
+    }
+}
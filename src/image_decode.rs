@@ -0,0 +1,240 @@
+//! Image file decoding
+
+use image::{self, gif, ImageDecoder, ImageError, ImageFormat, RgbaImage};
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io::{self, Cursor};
+use std::path::Path;
+
+/// Error from decoding an image file.
+#[derive(Debug)]
+pub enum DecodeError {
+    /// Reading the image file failed.
+    Io(io::Error),
+    /// The image codec rejected the data.
+    Image(ImageError),
+    /// Malformed QOI data.
+    Qoi(&'static str),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DecodeError::Io(ref e) => write!(f, "Image file IO error: {}", e),
+            DecodeError::Image(ref e) => write!(f, "Failed to decode image: {}", e),
+            DecodeError::Qoi(e) => write!(f, "Failed to decode QOI image: {}", e),
+        }
+    }
+}
+
+impl Error for DecodeError {}
+
+impl From<io::Error> for DecodeError {
+    fn from(e: io::Error) -> DecodeError { DecodeError::Io(e) }
+}
+
+impl From<ImageError> for DecodeError {
+    fn from(e: ImageError) -> DecodeError { DecodeError::Image(e) }
+}
+
+/// Single frame of an animation clip.
+#[derive(Clone)]
+pub struct AnimationFrame {
+    pub image: RgbaImage,
+    /// How long the frame is shown in seconds.
+    pub delay_s: f32,
+}
+
+/// Load an image file into RGBA pixels.
+///
+/// Supports PNG, BMP, TGA, GIF, QOI and the rest of the formats of the `image` crate. The format
+/// is detected from the file contents. Only the first frame of an animated GIF is loaded, use
+/// `load_animation` to get all of them.
+pub fn load_image<P: AsRef<Path>>(path: P) -> Result<RgbaImage, DecodeError> {
+    decode_image(&fs::read(path)?)
+}
+
+/// Load all the frames of an animated GIF file.
+///
+/// Still images are loaded as a single frame clip.
+pub fn load_animation<P: AsRef<Path>>(path: P) -> Result<Vec<AnimationFrame>, DecodeError> {
+    decode_animation(&fs::read(path)?)
+}
+
+/// Decode image file contents into RGBA pixels.
+///
+/// TGA files have no magic number, so data that isn't recognized as any other format is tried
+/// as TGA.
+///
+/// # Examples
+///
+/// ```
+/// use calx::decode_image;
+///
+/// // 1x1 QOI image with a single opaque red pixel.
+/// let mut qoi = b"qoif\0\0\0\x01\0\0\0\x01\x04\0".to_vec();
+/// qoi.extend_from_slice(&[0xfe, 0xff, 0x00, 0x00]);
+/// qoi.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 1]);
+///
+/// let img = decode_image(&qoi).unwrap();
+/// assert_eq!(img.dimensions(), (1, 1));
+/// assert_eq!(img.get_pixel(0, 0).data, [0xff, 0x00, 0x00, 0xff]);
+///
+/// assert!(decode_image(b"qoif").is_err());
+/// ```
+pub fn decode_image(bytes: &[u8]) -> Result<RgbaImage, DecodeError> {
+    if bytes.starts_with(QOI_MAGIC) {
+        return decode_qoi(bytes);
+    }
+
+    match image::guess_format(bytes) {
+        Ok(format) => Ok(image::load_from_memory_with_format(bytes, format)?.to_rgba()),
+        Err(e) => match image::load_from_memory_with_format(bytes, ImageFormat::TGA) {
+            Ok(img) => Ok(img.to_rgba()),
+            // Report the unrecognized format instead of the TGA decoder's complaints.
+            Err(_) => Err(e.into()),
+        },
+    }
+}
+
+/// Decode all the frames of animated GIF file contents.
+///
+/// Still images are decoded as a single frame clip.
+pub fn decode_animation(bytes: &[u8]) -> Result<Vec<AnimationFrame>, DecodeError> {
+    if image::guess_format(bytes).ok() != Some(ImageFormat::GIF) {
+        return Ok(vec![AnimationFrame {
+            image: decode_image(bytes)?,
+            delay_s: 0.0,
+        }]);
+    }
+
+    let frames = gif::Decoder::new(Cursor::new(bytes)).into_frames()?;
+    Ok(frames
+        .map(|frame| {
+            // The delay ratio is in milliseconds.
+            let delay = frame.delay();
+            AnimationFrame {
+                delay_s: *delay.numer() as f32 / *delay.denom() as f32 / 1000.0,
+                image: frame.into_buffer(),
+            }
+        })
+        .collect())
+}
+
+const QOI_MAGIC: &[u8] = b"qoif";
+
+/// Decode a Quite OK Image format file.
+fn decode_qoi(bytes: &[u8]) -> Result<RgbaImage, DecodeError> {
+    const HEADER_LEN: usize = 14;
+
+    if bytes.len() < HEADER_LEN {
+        return Err(DecodeError::Qoi("truncated header"));
+    }
+    let read_u32 = |i: usize| {
+        u32::from(bytes[i]) << 24
+            | u32::from(bytes[i + 1]) << 16
+            | u32::from(bytes[i + 2]) << 8
+            | u32::from(bytes[i + 3])
+    };
+    let (width, height) = (read_u32(4), read_u32(8));
+    let n_pixels = width as usize * height as usize;
+    // A single run byte covers at most 62 pixels.
+    if n_pixels / 62 > bytes.len() {
+        return Err(DecodeError::Qoi("image size too large for data"));
+    }
+
+    let mut data = bytes[HEADER_LEN..].iter().cloned();
+    let mut next = || data.next().ok_or(DecodeError::Qoi("truncated pixel data"));
+
+    let mut pixels = Vec::with_capacity(n_pixels * 4);
+    let mut index = [[0u8; 4]; 64];
+    let mut px = [0u8, 0, 0, 255];
+    let mut run = 0;
+
+    while pixels.len() < n_pixels * 4 {
+        if run > 0 {
+            run -= 1;
+        } else {
+            let op = next()?;
+            match op {
+                0xfe => {
+                    px[0] = next()?;
+                    px[1] = next()?;
+                    px[2] = next()?;
+                }
+                0xff => {
+                    px[0] = next()?;
+                    px[1] = next()?;
+                    px[2] = next()?;
+                    px[3] = next()?;
+                }
+                _ => match op >> 6 {
+                    0 => px = index[op as usize],
+                    1 => {
+                        px[0] = px[0].wrapping_add((op >> 4) & 3).wrapping_sub(2);
+                        px[1] = px[1].wrapping_add((op >> 2) & 3).wrapping_sub(2);
+                        px[2] = px[2].wrapping_add(op & 3).wrapping_sub(2);
+                    }
+                    2 => {
+                        let dg = (op & 0x3f).wrapping_sub(32);
+                        let rb = next()?;
+                        px[0] = px[0].wrapping_add(dg).wrapping_add(rb >> 4).wrapping_sub(8);
+                        px[1] = px[1].wrapping_add(dg);
+                        px[2] = px[2]
+                            .wrapping_add(dg)
+                            .wrapping_add(rb & 0xf)
+                            .wrapping_sub(8);
+                    }
+                    _ => run = op & 0x3f,
+                },
+            }
+        }
+
+        let hash =
+            px[0] as usize * 3 + px[1] as usize * 5 + px[2] as usize * 7 + px[3] as usize * 11;
+        index[hash % 64] = px;
+        pixels.extend_from_slice(&px);
+    }
+
+    Ok(RgbaImage::from_raw(width, height, pixels).expect("QOI pixel buffer size mismatch"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::decode_image;
+
+    #[test]
+    fn test_qoi_ops() {
+        let red = [0xff, 0x00, 0x00, 0xff];
+        let green = [0x00, 0xff, 0x00, 0xff];
+        let mut qoi = b"qoif\0\0\0\x03\0\0\0\x02\x04\0".to_vec();
+        qoi.extend_from_slice(&[
+            0xff, 0xff, 0x00, 0x00, 0xff, // RGBA, red
+            0xc0, // Run of 1, red
+            0xfe, 0x00, 0xff, 0x00, // RGB, green
+            0x32, // Index 50, red
+            0x7b, // Diff (+1, 0, +1), wraps to (0, 0, 1)
+            0xa2, 0x99, // Luma dg=+2, dr-dg=+1, db-dg=+1, (3, 2, 4)
+        ]);
+        qoi.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 1]);
+
+        let img = decode_image(&qoi).unwrap();
+        assert_eq!(img.dimensions(), (3, 2));
+        let pixels: Vec<[u8; 4]> = img.pixels().map(|p| p.data).collect();
+        assert_eq!(
+            pixels,
+            vec![
+                red,
+                red,
+                green,
+                red,
+                [0x00, 0x00, 0x01, 0xff],
+                [0x03, 0x02, 0x04, 0xff],
+            ]
+        );
+
+        // Missing pixel data.
+        assert!(decode_image(&qoi[..17]).is_err());
+    }
+}
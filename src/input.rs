@@ -0,0 +1,208 @@
+//! Mapping user input to game actions
+
+use std::fmt;
+
+/// Modifier keys held down with a key press.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Default, Debug, Serialize, Deserialize)]
+pub struct Modifiers {
+    #[serde(default)]
+    pub shift: bool,
+    #[serde(default)]
+    pub ctrl: bool,
+    #[serde(default)]
+    pub alt: bool,
+}
+
+impl Modifiers {
+    /// Return modifiers with none of the keys held.
+    pub fn none() -> Modifiers { Default::default() }
+
+    pub fn shift() -> Modifiers {
+        Modifiers {
+            shift: true,
+            ..Default::default()
+        }
+    }
+
+    pub fn ctrl() -> Modifiers {
+        Modifiers {
+            ctrl: true,
+            ..Default::default()
+        }
+    }
+
+    pub fn alt() -> Modifiers {
+        Modifiers {
+            alt: true,
+            ..Default::default()
+        }
+    }
+}
+
+impl fmt::Display for Modifiers {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.ctrl {
+            write!(f, "Ctrl+")?;
+        }
+        if self.alt {
+            write!(f, "Alt+")?;
+        }
+        if self.shift {
+            write!(f, "Shift+")?;
+        }
+        Ok(())
+    }
+}
+
+/// Key or button pressed with a set of modifiers.
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Serialize, Deserialize)]
+pub struct Chord<K> {
+    pub key: K,
+    #[serde(default)]
+    pub modifiers: Modifiers,
+}
+
+impl<K> Chord<K> {
+    pub fn new(key: K, modifiers: Modifiers) -> Chord<K> { Chord { key, modifiers } }
+}
+
+impl<K> From<K> for Chord<K> {
+    fn from(key: K) -> Chord<K> { Chord::new(key, Modifiers::none()) }
+}
+
+impl<K: fmt::Display> fmt::Display for Chord<K> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}", self.modifiers, self.key)
+    }
+}
+
+/// Translation from raw key and button events to logical game actions.
+///
+/// The map is generic over the key type, so the same map works with the key codes of any
+/// backend. An action can have several bindings, but each chord maps to at most one action.
+/// Binding a chord that is already in use moves it to the new action.
+///
+/// Serialize the map to save player key configuration.
+///
+/// # Examples
+///
+/// ```
+/// # #[macro_use] extern crate serde_derive;
+/// # extern crate serde_json;
+/// # extern crate calx;
+/// # fn main() {
+/// use calx::{Chord, InputMap, Modifiers};
+///
+/// #[derive(Copy, Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+/// enum Action {
+///     MoveNW,
+///     OpenInventory,
+///     Quit,
+/// }
+///
+/// let mut map = InputMap::new()
+///     .bind(Action::MoveNW, 'y')
+///     .bind(Action::MoveNW, '7')
+///     .bind(Action::OpenInventory, 'i')
+///     .bind(Action::Quit, Chord::new('q', Modifiers::ctrl()));
+///
+/// assert_eq!(map.action(&'7', Modifiers::none()), Some(Action::MoveNW));
+/// assert_eq!(map.action(&'q', Modifiers::none()), None);
+/// assert_eq!(map.action(&'q', Modifiers::ctrl()), Some(Action::Quit));
+///
+/// // Player rebinds the inventory key.
+/// map.unbind_action(Action::OpenInventory);
+/// map = map.bind(Action::OpenInventory, 'e');
+///
+/// let saved = serde_json::to_string(&map).unwrap();
+/// let loaded: InputMap<char, Action> = serde_json::from_str(&saved).unwrap();
+/// assert_eq!(loaded.action(&'e', Modifiers::none()), Some(Action::OpenInventory));
+/// assert_eq!(loaded.chords(Action::MoveNW).len(), 2);
+/// # }
+/// ```
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct InputMap<K, A> {
+    bindings: Vec<(Chord<K>, A)>,
+}
+
+impl<K: PartialEq, A: Copy + PartialEq> InputMap<K, A> {
+    pub fn new() -> InputMap<K, A> {
+        InputMap {
+            bindings: Vec::new(),
+        }
+    }
+
+    /// Bind a chord to an action.
+    pub fn bind(mut self, action: A, chord: impl Into<Chord<K>>) -> InputMap<K, A> {
+        self.set(action, chord);
+        self
+    }
+
+    /// Bind a chord to an action in place.
+    pub fn set(&mut self, action: A, chord: impl Into<Chord<K>>) {
+        let chord = chord.into();
+        self.bindings.retain(|(c, _)| *c != chord);
+        self.bindings.push((chord, action));
+    }
+
+    /// Remove the binding of a chord.
+    pub fn unbind(&mut self, chord: &Chord<K>) { self.bindings.retain(|(c, _)| c != chord); }
+
+    /// Remove all the bindings of an action.
+    pub fn unbind_action(&mut self, action: A) { self.bindings.retain(|&(_, a)| a != action); }
+
+    /// Return the action bound to a key press.
+    pub fn action(&self, key: &K, modifiers: Modifiers) -> Option<A> {
+        self.bindings
+            .iter()
+            .find(|(c, _)| c.key == *key && c.modifiers == modifiers)
+            .map(|&(_, a)| a)
+    }
+
+    /// Return the chords bound to an action, for showing them in help and settings screens.
+    pub fn chords(&self, action: A) -> Vec<&Chord<K>> {
+        self.bindings
+            .iter()
+            .filter(|&&(_, a)| a == action)
+            .map(|(c, _)| c)
+            .collect()
+    }
+}
+
+impl<K: PartialEq, A: Copy + PartialEq> Default for InputMap<K, A> {
+    fn default() -> Self { InputMap::new() }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Chord, InputMap, Modifiers};
+    use serde_json;
+
+    #[test]
+    fn test_input_map() {
+        let mut map = InputMap::new()
+            .bind(1, "Up")
+            .bind(2, "Down")
+            .bind(3, Chord::new("Up", Modifiers::shift()));
+        assert_eq!(map.action(&"Up", Modifiers::none()), Some(1));
+        assert_eq!(map.action(&"Up", Modifiers::shift()), Some(3));
+        assert_eq!(map.action(&"Up", Modifiers::alt()), None);
+
+        // Rebinding a chord moves it to the new action.
+        map.set(2, "Up");
+        assert_eq!(map.action(&"Up", Modifiers::none()), Some(2));
+        assert!(map.chords(1).is_empty());
+        assert_eq!(map.chords(2).len(), 2);
+
+        map.unbind(&"Down".into());
+        assert_eq!(map.action(&"Down", Modifiers::none()), None);
+
+        let chord = Chord::new("X", Modifiers::ctrl());
+        assert_eq!(chord.to_string(), "Ctrl+X");
+
+        // Modifiers can be left out in config files.
+        let map: InputMap<String, u32> =
+            serde_json::from_str(r#"{"bindings": [[{"key": "a"}, 7]]}"#).unwrap();
+        assert_eq!(map.action(&"a".to_string(), Modifiers::none()), Some(7));
+    }
+}
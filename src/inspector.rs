@@ -0,0 +1,188 @@
+//! Debug overlay that shows what is in a map cell
+
+use euclid::{point2, rect, Point2D, Rect};
+use gui::{GuiDraw, GuiStyle};
+use CellVector;
+
+type Provider<'a> = Box<dyn Fn(CellVector) -> Vec<String> + 'a>;
+
+/// Hover panel that shows the debug data of a map cell.
+///
+/// Data providers are registered with a section name and a function that returns the lines to
+/// show for a cell, such as the terrain, the entities found in a spatial hash, the pathfinding
+/// cost or whether the cell is in view. The inspector is drawn as a panel next to the mouse
+/// cursor for the cell under it, with a section for each provider that has something to say.
+///
+/// Providers can borrow the game state, so an inspector can be built every frame, or kept
+/// around if the providers only capture owned or shared data.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate euclid;
+/// # extern crate calx;
+/// # fn main() {
+/// use std::collections::HashMap;
+/// use euclid::{point2, rect, vec2};
+/// use calx::{CellVector, GuiStyle, Inspector};
+///
+/// let mut terrain = HashMap::new();
+/// terrain.insert(vec2(1, 2), "wall");
+/// let mobs = vec![(vec2(1, 2), "ghost")];
+///
+/// let mut inspector = Inspector::new();
+/// inspector.register("terrain", |p| terrain.get(&p).map(|t| t.to_string()).into_iter().collect());
+/// inspector.register("mobs", |p| {
+///     mobs.iter().filter(|m| m.0 == p).map(|m| m.1.to_string()).collect()
+/// });
+///
+/// let cell: CellVector = vec2(1, 2);
+/// assert_eq!(
+///     inspector.inspect(cell),
+///     vec![("terrain", vec!["wall".to_string()]), ("mobs", vec!["ghost".to_string()])]
+/// );
+///
+/// // Draw the panel next to the mouse, kept inside the screen.
+/// let screen = rect(0.0, 0.0, 640.0, 360.0);
+/// let _draw = inspector.draw(cell, point2(600.0, 20.0), screen, &GuiStyle::default());
+/// # }
+/// ```
+#[derive(Default)]
+pub struct Inspector<'a> {
+    providers: Vec<(String, Provider<'a>)>,
+}
+
+impl<'a> Inspector<'a> {
+    pub fn new() -> Inspector<'a> { Default::default() }
+
+    /// Register a data provider for a panel section.
+    ///
+    /// The provider returns the lines to show for a cell, no lines hides the section.
+    /// Registering a name again replaces the old provider.
+    pub fn register<F>(&mut self, name: &str, provider: F)
+    where
+        F: Fn(CellVector) -> Vec<String> + 'a,
+    {
+        let provider: Provider<'a> = Box::new(provider);
+        match self.providers.iter_mut().find(|(n, _)| n == name) {
+            Some(slot) => slot.1 = provider,
+            None => self.providers.push((name.to_string(), provider)),
+        }
+    }
+
+    /// Query the providers for a cell.
+    ///
+    /// Returns the section names and lines in registration order, sections without lines are
+    /// left out.
+    pub fn inspect(&self, cell: CellVector) -> Vec<(&str, Vec<String>)> {
+        self.providers
+            .iter()
+            .map(|(name, f)| (&name[..], f(cell)))
+            .filter(|(_, lines)| !lines.is_empty())
+            .collect()
+    }
+
+    /// Draw the panel for a cell next to a point.
+    ///
+    /// The panel has the cell coordinates as a title, followed by the section names and their
+    /// indented lines. It is placed to the lower right of `pos` and moved to stay inside
+    /// `bounds` if it would stick out.
+    pub fn draw(
+        &self,
+        cell: CellVector,
+        pos: Point2D<f32>,
+        bounds: Rect<f32>,
+        style: &GuiStyle,
+    ) -> Vec<GuiDraw> {
+        let mut rows = vec![(format!("{}, {}", cell.x, cell.y), style.text)];
+        for (name, lines) in self.inspect(cell) {
+            rows.push((format!("{}:", name), style.highlight));
+            rows.extend(lines.into_iter().map(|l| (format!("  {}", l), style.text)));
+        }
+
+        let (cw, row_h) = (style.char_width, style.row_height);
+        let max_len = rows
+            .iter()
+            .map(|(s, _)| s.chars().count())
+            .max()
+            .unwrap_or(0);
+        let (w, h) = ((max_len + 1) as f32 * cw, rows.len() as f32 * row_h);
+        let x = (pos.x + cw).min(bounds.max_x() - w).max(bounds.origin.x);
+        let y = (pos.y + row_h).min(bounds.max_y() - h).max(bounds.origin.y);
+
+        let mut ret = vec![GuiDraw::Fill {
+            area: rect(x, y, w, h),
+            color: style.background,
+        }];
+        for (i, (text, color)) in rows.into_iter().enumerate() {
+            ret.push(GuiDraw::Text {
+                pos: point2(x + cw / 2.0, y + i as f32 * row_h),
+                text,
+                color,
+            });
+        }
+        ret
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Inspector;
+    use euclid::{point2, rect, vec2};
+    use gui::{GuiDraw, GuiStyle};
+    use CellVector;
+
+    #[test]
+    fn test_inspector() {
+        let walls: [CellVector; 2] = [vec2(0, 0), vec2(1, 0)];
+        let mut inspector = Inspector::new();
+        inspector.register("terrain", |p: CellVector| {
+            vec![if walls.contains(&p) { "wall" } else { "floor" }.to_string()]
+        });
+        inspector.register("cost", |p: CellVector| {
+            if walls.contains(&p) {
+                Vec::new()
+            } else {
+                vec![format!("{}", p.x.abs() + p.y.abs())]
+            }
+        });
+        inspector.register("terrain", |p: CellVector| {
+            vec![if walls.contains(&p) { "rock" } else { "grass" }.to_string()]
+        });
+
+        assert_eq!(
+            inspector.inspect(vec2(0, 0)),
+            vec![("terrain", vec!["rock".to_string()])]
+        );
+
+        let style = GuiStyle::default();
+        let (cw, row_h) = (style.char_width, style.row_height);
+        let screen = rect(0.0, 0.0, 320.0, 200.0);
+        let draw = inspector.draw(vec2(3, -2), point2(0.0, 0.0), screen, &style);
+        let texts: Vec<&str> = draw
+            .iter()
+            .filter_map(|d| match d {
+                GuiDraw::Text { text, .. } => Some(&text[..]),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(texts, vec!["3, -2", "terrain:", "  grass", "cost:", "  5"]);
+        assert_eq!(
+            draw[0],
+            GuiDraw::Fill {
+                area: rect(cw, row_h, 9.0 * cw, 5.0 * row_h),
+                color: style.background,
+            }
+        );
+
+        // The panel stays inside the bounds near the edges.
+        let draw = inspector.draw(vec2(3, -2), point2(310.0, 190.0), screen, &style);
+        assert_eq!(
+            draw[0],
+            GuiDraw::Fill {
+                area: rect(320.0 - 9.0 * cw, 200.0 - 5.0 * row_h, 9.0 * cw, 5.0 * row_h),
+                color: style.background,
+            }
+        );
+    }
+}
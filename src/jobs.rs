@@ -0,0 +1,287 @@
+//! Shared worker thread pool
+
+use euclid::{vec2, TypedRect};
+use std::mem;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use CellSpace;
+use CellVector;
+
+type Job = Box<dyn FnOnce() + Send + 'static>;
+type ScopedJob<'a> = Box<dyn FnOnce() + Send + 'a>;
+
+/// Number of unfinished jobs and the number of jobs that panicked since the last wait.
+type Pending = Arc<(Mutex<(usize, usize)>, Condvar)>;
+
+/// Worker thread pool for background jobs and parallel loops over cells.
+///
+/// Fire-and-forget jobs are queued for the persistent worker threads. The parallel loops split
+/// the work into chunks for the same workers and block until all the chunks are done, so they
+/// can borrow local data.
+///
+/// A panic in a job is caught on the worker and raised again on the thread that waits for the
+/// job, either by calling `wait` or by running a parallel loop.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate euclid;
+/// # extern crate calx;
+/// # fn main() {
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+/// use std::sync::Arc;
+/// use euclid::rect;
+/// use calx::JobPool;
+///
+/// let pool = JobPool::new(4);
+/// let counter = Arc::new(AtomicUsize::new(0));
+/// for _ in 0..10 {
+///     let counter = counter.clone();
+///     pool.spawn(move || {
+///         counter.fetch_add(1, Ordering::SeqCst);
+///     });
+/// }
+/// pool.wait();
+/// assert_eq!(counter.load(Ordering::SeqCst), 10);
+///
+/// let sums = pool.map_cells(rect(0, 0, 3, 2), |p| p.x + p.y);
+/// assert_eq!(sums, vec![0, 1, 2, 1, 2, 3]);
+/// # }
+/// ```
+pub struct JobPool {
+    sender: Option<Sender<Job>>,
+    workers: Vec<JoinHandle<()>>,
+    pending: Pending,
+}
+
+impl JobPool {
+    /// Create a pool with the given number of worker threads.
+    pub fn new(n_threads: usize) -> JobPool {
+        debug_assert!(n_threads > 0);
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        let pending: Pending = Arc::new((Mutex::new((0, 0)), Condvar::new()));
+
+        let workers = (0..n_threads)
+            .map(|_| {
+                let receiver = receiver.clone();
+                let pending = pending.clone();
+                thread::spawn(move || loop {
+                    let job = match receiver.lock().unwrap().recv() {
+                        Ok(job) => job,
+                        // Pool was dropped.
+                        Err(_) => return,
+                    };
+                    // Keep the worker alive and the pending count correct if a job panics.
+                    let panicked = panic::catch_unwind(AssertUnwindSafe(job)).is_err();
+
+                    let (ref count, ref cvar) = *pending;
+                    let mut count = count.lock().unwrap();
+                    count.0 -= 1;
+                    if panicked {
+                        count.1 += 1;
+                    }
+                    cvar.notify_all();
+                })
+            })
+            .collect();
+
+        JobPool {
+            sender: Some(sender),
+            workers,
+            pending,
+        }
+    }
+
+    /// Return the number of worker threads.
+    pub fn n_threads(&self) -> usize { self.workers.len() }
+
+    /// Queue a job to run on a worker thread.
+    pub fn spawn<F: FnOnce() + Send + 'static>(&self, job: F) { self.send(Box::new(job)); }
+
+    fn send(&self, job: Job) {
+        self.pending.0.lock().unwrap().0 += 1;
+        self.sender
+            .as_ref()
+            .expect("Job pool has shut down")
+            .send(job)
+            .expect("Job pool workers have died");
+    }
+
+    /// Block until all the spawned jobs have finished.
+    ///
+    /// Panics if any of the jobs spawned since the last wait panicked.
+    pub fn wait(&self) {
+        let (ref count, ref cvar) = *self.pending;
+        let mut count = count.lock().unwrap();
+        while count.0 > 0 {
+            count = cvar.wait(count).unwrap();
+        }
+        let panics = mem::replace(&mut count.1, 0);
+        drop(count);
+        if panics > 0 {
+            panic!("{} spawned jobs panicked", panics);
+        }
+    }
+
+    /// Compute a value for each cell in a rectangle in parallel.
+    ///
+    /// The values are returned in row-major order. If `f` panics, the panic is raised again on
+    /// the calling thread once all the chunks have finished. Must not be called from a job
+    /// running on the same pool, the loop would wait for the worker it is blocking.
+    pub fn map_cells<T, F>(&self, area: TypedRect<i32, CellSpace>, f: F) -> Vec<T>
+    where
+        T: Send,
+        F: Fn(CellVector) -> T + Sync,
+    {
+        let (w, h) = (area.size.width.max(0), area.size.height.max(0));
+        let n_cells = (w * h) as usize;
+        let chunk = (n_cells + self.n_threads() - 1) / self.n_threads().max(1);
+        if chunk == 0 {
+            return Vec::new();
+        }
+
+        let origin = area.origin.to_vector();
+        let f = &f;
+        let results: Vec<Mutex<Option<thread::Result<Vec<T>>>>> = (0..n_cells)
+            .step_by(chunk)
+            .map(|_| Mutex::new(None))
+            .collect();
+        {
+            // Blocks until every queued chunk has finished, also when unwinding, because the
+            // chunks borrow from this stack frame.
+            let latch = Latch::default();
+            for (i, slot) in results.iter().enumerate() {
+                let (start, end) = (i * chunk, ((i + 1) * chunk).min(n_cells));
+                let done = latch.add();
+                let job: ScopedJob = Box::new(move || {
+                    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                        (start..end)
+                            .map(|i| f(origin + vec2(i as i32 % w, i as i32 / w)))
+                            .collect::<Vec<T>>()
+                    }));
+                    *slot.lock().unwrap() = Some(result);
+                    drop(done);
+                });
+                // Safe because the latch outlives every use of the borrowed data.
+                self.send(unsafe { mem::transmute::<ScopedJob<'_>, Job>(job) });
+            }
+        }
+
+        let mut ret = Vec::with_capacity(n_cells);
+        for slot in results {
+            match slot.into_inner().unwrap() {
+                Some(Ok(values)) => ret.extend(values),
+                Some(Err(e)) => panic::resume_unwind(e),
+                None => unreachable!("Parallel cell chunk never ran"),
+            }
+        }
+        ret
+    }
+
+    /// Run a function for each cell in a rectangle in parallel.
+    pub fn for_each_cell<F>(&self, area: TypedRect<i32, CellSpace>, f: F)
+    where
+        F: Fn(CellVector) + Sync,
+    {
+        self.map_cells(area, f);
+    }
+}
+
+/// Count of unfinished chunks of a parallel loop, waits for them to finish when dropped.
+#[derive(Default)]
+struct Latch(Arc<(Mutex<usize>, Condvar)>);
+
+impl Latch {
+    /// Register a chunk and return the handle that marks it finished when dropped.
+    fn add(&self) -> ChunkDone {
+        *(self.0).0.lock().unwrap() += 1;
+        ChunkDone(self.0.clone())
+    }
+}
+
+struct ChunkDone(Arc<(Mutex<usize>, Condvar)>);
+
+impl Drop for ChunkDone {
+    fn drop(&mut self) {
+        let (ref count, ref cvar) = *self.0;
+        *count.lock().unwrap() -= 1;
+        cvar.notify_all();
+    }
+}
+
+impl Drop for Latch {
+    fn drop(&mut self) {
+        let (ref count, ref cvar) = *self.0;
+        let mut count = count.lock().unwrap();
+        while *count > 0 {
+            count = cvar.wait(count).unwrap();
+        }
+    }
+}
+
+impl Default for JobPool {
+    /// Create a pool with one worker for each available CPU core.
+    fn default() -> Self { JobPool::new(thread::available_parallelism().map_or(1, |n| n.get())) }
+}
+
+impl Drop for JobPool {
+    fn drop(&mut self) {
+        // Closing the channel makes the workers exit once the queue is empty.
+        self.sender = None;
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::JobPool;
+    use euclid::{rect, vec2};
+    use std::collections::HashSet;
+    use std::panic::{self, AssertUnwindSafe};
+    use std::sync::Mutex;
+    use std::thread;
+
+    #[test]
+    fn test_job_pool() {
+        let pool = JobPool::new(3);
+        pool.spawn(|| panic!("Job failure"));
+        pool.spawn(|| {});
+        assert!(panic::catch_unwind(AssertUnwindSafe(|| pool.wait())).is_err());
+        // The panic is only reported once.
+        pool.wait();
+
+        let seen = Mutex::new(Vec::new());
+        pool.for_each_cell(rect(-2, 5, 7, 5), |p| seen.lock().unwrap().push(p));
+        let seen = seen.into_inner().unwrap();
+        assert_eq!(seen.len(), 35);
+        assert!(seen.contains(&vec2(4, 9)));
+
+        assert!(pool.map_cells(rect(0, 0, 0, 10), |p| p).is_empty());
+
+        // Loops run on the pool's own workers.
+        let threads = Mutex::new(HashSet::new());
+        for _ in 0..10 {
+            pool.for_each_cell(rect(0, 0, 10, 10), |_| {
+                threads.lock().unwrap().insert(thread::current().id());
+            });
+        }
+        let threads = threads.into_inner().unwrap();
+        assert!(threads.len() <= 3);
+        assert!(!threads.contains(&thread::current().id()));
+
+        let failed = panic::catch_unwind(AssertUnwindSafe(|| {
+            pool.map_cells(rect(0, 0, 10, 10), |p| {
+                assert!(p != vec2(5, 5), "Cell failure");
+                p
+            })
+        }));
+        assert!(failed.is_err());
+        pool.wait();
+        assert_eq!(pool.map_cells(rect(0, 0, 2, 1), |p| p.x), vec![0, 1]);
+    }
+}
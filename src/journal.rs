@@ -0,0 +1,236 @@
+//! Append-only event journal for world state
+
+use incremental::Incremental;
+use serde::Deserialize;
+
+/// Event journal that is the canonical record of changes to a world state.
+///
+/// All changes to the state go through `record`, which applies the event and appends it to the
+/// journal with the game tick it happened on. Every event gets a running sequence number, so
+/// other systems can pick up the events they haven't seen yet: an autosave can write only the
+/// events after the last save and a multiplayer host can send clients the events after their
+/// last acknowledged one.
+///
+/// The journal is periodically compacted into a snapshot of the state, after which the earlier
+/// events are no longer available. Use `Replay` instead if you need to keep every event.
+///
+/// # Examples
+///
+/// ```
+/// # #[macro_use] extern crate serde_derive;
+/// # extern crate serde_json;
+/// # extern crate calx;
+/// # fn main() {
+/// use calx::{Incremental, Journal};
+///
+/// #[derive(Clone, Default, Debug, PartialEq, Serialize, Deserialize)]
+/// struct Gold(i32);
+///
+/// impl Incremental<i32> for Gold {
+///     fn update(self, e: &i32) -> Gold { Gold(self.0 + *e) }
+/// }
+///
+/// let mut journal = Journal::new(Gold::default()).compact_every(3);
+/// journal.record(1, 10);
+/// journal.record(1, -3);
+/// assert_eq!(journal.state(), &Gold(7));
+///
+/// // Network client has seen the first event.
+/// let unseen: Vec<_> = journal.events_since(1).unwrap().map(|(_, e)| *e).collect();
+/// assert_eq!(unseen, vec![-3]);
+///
+/// // Third event triggers compaction.
+/// journal.record(5, 100);
+/// assert_eq!(journal.snapshot_seq(), 3);
+/// assert!(journal.events_since(1).is_none());
+///
+/// let saved = serde_json::to_string(&journal).unwrap();
+/// let loaded: Journal<Gold, i32> = serde_json::from_str(&saved).unwrap();
+/// assert_eq!(loaded.state(), &Gold(107));
+/// # }
+/// ```
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(from = "JournalData<T, E>")]
+#[serde(bound(deserialize = "T: Incremental<E> + Clone + Deserialize<'de>, E: Deserialize<'de>"))]
+pub struct Journal<T, E> {
+    /// State before the first event in `events`.
+    snapshot: T,
+    /// Sequence number of the first event in `events`.
+    snapshot_seq: u64,
+    /// Tick of the latest event compacted into the snapshot.
+    snapshot_tick: u64,
+    /// Events with their ticks since the snapshot.
+    events: Vec<(u64, E)>,
+    /// Compact when this many events have accumulated, 0 for never.
+    compact_every: usize,
+    /// Current state, rebuilt from the snapshot and the events on load.
+    ///
+    /// Always `Some`, the option is only for moving the state through `Incremental::update`.
+    #[serde(skip_serializing)]
+    state: Option<T>,
+}
+
+/// Serialized fields of a `Journal`.
+#[derive(Deserialize)]
+struct JournalData<T, E> {
+    snapshot: T,
+    snapshot_seq: u64,
+    snapshot_tick: u64,
+    events: Vec<(u64, E)>,
+    compact_every: usize,
+}
+
+impl<T: Incremental<E> + Clone, E> From<JournalData<T, E>> for Journal<T, E> {
+    fn from(data: JournalData<T, E>) -> Journal<T, E> {
+        let state = data
+            .events
+            .iter()
+            .fold(data.snapshot.clone(), |state, (_, e)| state.update(e));
+        Journal {
+            snapshot: data.snapshot,
+            snapshot_seq: data.snapshot_seq,
+            snapshot_tick: data.snapshot_tick,
+            events: data.events,
+            compact_every: data.compact_every,
+            state: Some(state),
+        }
+    }
+}
+
+impl<T: Incremental<E> + Clone, E> Journal<T, E> {
+    /// Start a journal from an initial state.
+    pub fn new(initial: T) -> Journal<T, E> {
+        Journal {
+            snapshot: initial.clone(),
+            snapshot_seq: 0,
+            snapshot_tick: 0,
+            events: Vec::new(),
+            compact_every: 0,
+            state: Some(initial),
+        }
+    }
+
+    /// Set the journal to compact automatically when the given number of events have been
+    /// recorded since the last snapshot.
+    pub fn compact_every(mut self, events: usize) -> Journal<T, E> {
+        self.compact_every = events;
+        self
+    }
+
+    /// Apply an event to the state and append it to the journal.
+    ///
+    /// Ticks must not decrease between events.
+    pub fn record(&mut self, tick: u64, e: E) {
+        debug_assert!(tick >= self.last_tick(), "Event recorded out of order");
+        let state = self.state.take().expect("Missing journal state");
+        self.state = Some(state.update(&e));
+        self.events.push((tick, e));
+        if self.compact_every > 0 && self.events.len() >= self.compact_every {
+            self.compact();
+        }
+    }
+
+    /// Return the current state.
+    pub fn state(&self) -> &T { self.state.as_ref().expect("Missing journal state") }
+
+    /// Fold the journaled events into a new snapshot.
+    pub fn compact(&mut self) {
+        self.snapshot = self.state().clone();
+        self.snapshot_tick = self.last_tick();
+        self.snapshot_seq += self.events.len() as u64;
+        self.events.clear();
+    }
+
+    /// Return the state stored in the latest snapshot.
+    pub fn snapshot(&self) -> &T { &self.snapshot }
+
+    /// Return the sequence number of the first event after the snapshot.
+    ///
+    /// This is also the number of events folded into the snapshot.
+    pub fn snapshot_seq(&self) -> u64 { self.snapshot_seq }
+
+    /// Return the sequence number the next recorded event will get.
+    pub fn next_seq(&self) -> u64 { self.snapshot_seq + self.events.len() as u64 }
+
+    /// Return the tick of the most recent event.
+    pub fn last_tick(&self) -> u64 {
+        self.events.last().map_or(self.snapshot_tick, |e| e.0)
+    }
+
+    /// Return the events since the snapshot with their ticks.
+    pub fn events(&self) -> &[(u64, E)] { &self.events }
+
+    /// Iterate the events with sequence numbers starting from `seq`.
+    ///
+    /// Returns `None` if some of the events have already been compacted away, in which case the
+    /// receiver needs the whole snapshot and `events()` instead.
+    pub fn events_since(&self, seq: u64) -> Option<impl Iterator<Item = &(u64, E)>> {
+        if seq < self.snapshot_seq {
+            return None;
+        }
+        let skip = ((seq - self.snapshot_seq) as usize).min(self.events.len());
+        Some(self.events[skip..].iter())
+    }
+
+    /// Reconstruct the state as it was at the end of a tick.
+    ///
+    /// Returns `None` if the tick is before the snapshot.
+    pub fn state_at(&self, tick: u64) -> Option<T> {
+        if tick < self.snapshot_tick {
+            return None;
+        }
+        Some(
+            self.events
+                .iter()
+                .take_while(|e| e.0 <= tick)
+                .fold(self.snapshot.clone(), |state, (_, e)| state.update(e)),
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Journal;
+    use incremental::Incremental;
+    use serde_json;
+
+    #[derive(Clone, Default, Debug, PartialEq, Serialize, Deserialize)]
+    struct Log(Vec<char>);
+
+    impl Incremental<char> for Log {
+        fn update(mut self, e: &char) -> Log {
+            self.0.push(*e);
+            self
+        }
+    }
+
+    #[test]
+    fn test_journal() {
+        let mut journal = Journal::new(Log::default());
+        for (tick, c) in "abcde".chars().enumerate() {
+            journal.record(tick as u64 / 2, c);
+        }
+        assert_eq!(journal.next_seq(), 5);
+        assert_eq!(journal.last_tick(), 2);
+        assert_eq!(journal.state_at(0), Some(Log(vec!['a', 'b'])));
+        assert_eq!(journal.events_since(9).unwrap().count(), 0);
+
+        journal.compact();
+        assert_eq!(journal.snapshot(), &Log("abcde".chars().collect()));
+        assert!(journal.events().is_empty());
+        assert_eq!(journal.state_at(1), None);
+        assert_eq!(journal.events_since(5).unwrap().count(), 0);
+
+        journal.record(3, 'f');
+        assert_eq!(journal.state_at(2), Some(Log("abcde".chars().collect())));
+        assert_eq!(journal.state().0.len(), 6);
+
+        // The current state is rebuilt after loading.
+        let saved = serde_json::to_string(&journal).unwrap();
+        let mut loaded: Journal<Log, char> = serde_json::from_str(&saved).unwrap();
+        assert_eq!(loaded.state(), journal.state());
+        loaded.record(4, 'g');
+        assert_eq!(loaded.state().0.len(), 7);
+        assert_eq!(loaded.events_since(5).unwrap().count(), 2);
+    }
+}
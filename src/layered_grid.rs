@@ -0,0 +1,304 @@
+use euclid::TypedRect;
+use grid::Grid;
+use hex::{hex_neighbors, HexGeom};
+use hex_fov::{HexFov, Sight};
+use mem_stats::HeapSize;
+use std::collections::HashSet;
+use std::ops::{Index, IndexMut, Range};
+use CellSpace;
+use CellVector;
+
+/// Cell position on a multi-level map.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Serialize, Deserialize)]
+pub struct LevelPos {
+    /// Position on the level.
+    pub pos: CellVector,
+    /// Level index, larger values are higher up.
+    pub z: i32,
+}
+
+impl LevelPos {
+    pub fn new(pos: CellVector, z: i32) -> LevelPos { LevelPos { pos, z } }
+
+    /// Hex distance with level changes counting as single steps.
+    ///
+    /// Never overestimates the number of moves between positions, so it's usable as the A* search
+    /// heuristic.
+    pub fn dist(&self, other: &LevelPos) -> i32 {
+        (other.pos - self.pos)
+            .hex_dist()
+            .max((other.z - self.z).abs())
+    }
+}
+
+/// Stack of map levels with stair and ramp connections between them.
+///
+/// All levels have the same bounds. Moving between levels is only possible through the links
+/// made with `connect`, so a bridge over a river is a cell on the upper level and the river flows
+/// under it on the lower level without the two interacting.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate euclid;
+/// # extern crate calx;
+/// # fn main() {
+/// use euclid::{rect, vec2};
+/// use calx::{astar_path, LayeredGrid, LevelPos};
+///
+/// // Two floors of a tower with walls everywhere except for one corridor on each floor.
+/// let mut tower = LayeredGrid::new(rect(0, 0, 8, 8), 0..2, '#');
+/// for x in 0..8 {
+///     tower[LevelPos::new(vec2(x, 0), 0)] = '.';
+///     tower[LevelPos::new(vec2(x, 4), 1)] = '.';
+/// }
+/// tower[LevelPos::new(vec2(7, 4), 0)] = '.';
+/// // Stairs at the east end.
+/// tower.connect(LevelPos::new(vec2(7, 4), 0), LevelPos::new(vec2(7, 4), 1));
+/// for y in 0..4 {
+///     tower[LevelPos::new(vec2(7, y), 0)] = '.';
+/// }
+///
+/// let start = LevelPos::new(vec2(0, 0), 0);
+/// let end = LevelPos::new(vec2(0, 4), 1);
+/// let path = astar_path(start, &end, |p| {
+///     tower
+///         .neighbors(*p, |&c| c == '.')
+///         .into_iter()
+///         .map(move |n| (n, n.dist(&end) as f32))
+/// })
+/// .unwrap();
+/// assert!(path.contains(&LevelPos::new(vec2(7, 4), 1)));
+/// assert_eq!(path.last(), Some(&end));
+/// # }
+/// ```
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct LayeredGrid<T> {
+    min_z: i32,
+    levels: Vec<Grid<T>>,
+    /// One-way links between levels, sorted for lookup.
+    links: Vec<(LevelPos, LevelPos)>,
+}
+
+impl<T> LayeredGrid<T> {
+    /// Create a multi-level grid by computing the value for each cell.
+    pub fn from_fn<F>(bounds: TypedRect<i32, CellSpace>, z_range: Range<i32>, mut f: F) -> Self
+    where
+        F: FnMut(LevelPos) -> T,
+    {
+        let levels = z_range
+            .clone()
+            .map(|z| Grid::from_fn(bounds, |pos| f(LevelPos { pos, z })))
+            .collect();
+        LayeredGrid {
+            min_z: z_range.start,
+            levels,
+            links: Vec::new(),
+        }
+    }
+
+    /// Create a multi-level grid filled with a value.
+    pub fn new(bounds: TypedRect<i32, CellSpace>, z_range: Range<i32>, value: T) -> Self
+    where
+        T: Clone,
+    {
+        LayeredGrid::from_fn(bounds, z_range, |_| value.clone())
+    }
+
+    /// Return the rectangle covered by each level.
+    pub fn bounds(&self) -> TypedRect<i32, CellSpace> {
+        self.levels
+            .first()
+            .map_or_else(TypedRect::zero, |level| level.bounds())
+    }
+
+    /// Return the range of level indices.
+    pub fn z_range(&self) -> Range<i32> { self.min_z..self.min_z + self.levels.len() as i32 }
+
+    /// Return the grid of a single level.
+    pub fn level(&self, z: i32) -> Option<&Grid<T>> { self.levels.get(self.level_index(z)?) }
+
+    pub fn level_mut(&mut self, z: i32) -> Option<&mut Grid<T>> {
+        let idx = self.level_index(z)?;
+        self.levels.get_mut(idx)
+    }
+
+    pub fn contains(&self, pos: LevelPos) -> bool { self.get(pos).is_some() }
+
+    pub fn get(&self, pos: LevelPos) -> Option<&T> { self.level(pos.z)?.get(pos.pos) }
+
+    pub fn get_mut(&mut self, pos: LevelPos) -> Option<&mut T> {
+        self.level_mut(pos.z)?.get_mut(pos.pos)
+    }
+
+    /// Iterate the cell positions and values level by level from the bottom up.
+    pub fn iter<'a>(&'a self) -> impl Iterator<Item = (LevelPos, &'a T)> + 'a {
+        let min_z = self.min_z;
+        self.levels.iter().enumerate().flat_map(move |(i, level)| {
+            let z = min_z + i as i32;
+            level.iter().map(move |(pos, t)| (LevelPos { pos, z }, t))
+        })
+    }
+
+    /// Connect two positions in both directions, like the ends of a staircase.
+    pub fn connect(&mut self, a: LevelPos, b: LevelPos) {
+        self.connect_one_way(a, b);
+        self.connect_one_way(b, a);
+    }
+
+    /// Make a link that can only be moved along from `a` to `b`, like a hole in the floor.
+    pub fn connect_one_way(&mut self, a: LevelPos, b: LevelPos) {
+        debug_assert!(self.contains(a) && self.contains(b));
+        let link = (a, b);
+        if let Err(idx) = self.links.binary_search_by_key(&key(&link), key) {
+            self.links.insert(idx, link);
+        }
+    }
+
+    /// Remove the links between two positions in both directions.
+    pub fn disconnect(&mut self, a: LevelPos, b: LevelPos) {
+        self.links.retain(|&link| link != (a, b) && link != (b, a));
+    }
+
+    /// Return the positions a position is linked to.
+    pub fn links(&self, pos: LevelPos) -> impl Iterator<Item = LevelPos> + '_ {
+        let k = pos_key(&pos);
+        let start = self.links.partition_point(|l| pos_key(&l.0) < k);
+        self.links[start..]
+            .iter()
+            .take_while(move |l| l.0 == pos)
+            .map(|l| l.1)
+    }
+
+    /// Return the passable hex neighbors and linked positions of a position.
+    pub fn neighbors<F>(&self, pos: LevelPos, is_passable: F) -> Vec<LevelPos>
+    where
+        F: Fn(&T) -> bool,
+    {
+        hex_neighbors(pos.pos)
+            .map(|p| LevelPos { pos: p, z: pos.z })
+            .chain(self.links(pos))
+            .filter(|&p| self.get(p).is_some_and(&is_passable))
+            .collect()
+    }
+
+    /// Compute the hex field of view of a position.
+    ///
+    /// Sight is blocked by cells that aren't transparent, but the blocking cells themselves are
+    /// seen. Seen cells that are open to the level below, such as chasms and the sides of a
+    /// bridge, also show the cell right below them, and so on further down if that cell is open
+    /// as well.
+    pub fn fov<F, G>(
+        &self,
+        origin: LevelPos,
+        range: i32,
+        is_transparent: F,
+        is_open_below: G,
+    ) -> HashSet<LevelPos>
+    where
+        F: Fn(&T) -> bool,
+        G: Fn(&T) -> bool,
+    {
+        let mut ret = HashSet::new();
+        let level = match self.level(origin.z) {
+            Some(level) => level,
+            None => return ret,
+        };
+        let is_transparent = |p: CellVector| level.get(p).is_some_and(&is_transparent);
+        for (offset, _) in HexFov::new(Sight::new(origin.pos, range, &is_transparent)) {
+            let mut pos = LevelPos {
+                pos: origin.pos + offset,
+                z: origin.z,
+            };
+            while let Some(cell) = self.get(pos) {
+                ret.insert(pos);
+                if !is_open_below(cell) {
+                    break;
+                }
+                pos.z -= 1;
+            }
+        }
+        ret
+    }
+
+    fn level_index(&self, z: i32) -> Option<usize> {
+        let idx = z - self.min_z;
+        if idx >= 0 && (idx as usize) < self.levels.len() {
+            Some(idx as usize)
+        } else {
+            None
+        }
+    }
+}
+
+fn pos_key(pos: &LevelPos) -> (i32, i32, i32) { (pos.z, pos.pos.y, pos.pos.x) }
+
+fn key(link: &(LevelPos, LevelPos)) -> ((i32, i32, i32), (i32, i32, i32)) {
+    (pos_key(&link.0), pos_key(&link.1))
+}
+
+impl<T> Index<LevelPos> for LayeredGrid<T> {
+    type Output = T;
+
+    fn index(&self, pos: LevelPos) -> &T { self.get(pos).expect("Position outside grid") }
+}
+
+impl<T> IndexMut<LevelPos> for LayeredGrid<T> {
+    fn index_mut(&mut self, pos: LevelPos) -> &mut T {
+        self.get_mut(pos).expect("Position outside grid")
+    }
+}
+
+impl<T> HeapSize for LayeredGrid<T> {
+    fn heap_size(&self) -> usize { self.levels.heap_size() + self.links.heap_size() }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{LayeredGrid, LevelPos};
+    use euclid::{rect, vec2};
+
+    fn at(x: i32, y: i32, z: i32) -> LevelPos { LevelPos::new(vec2(x, y), z) }
+
+    #[test]
+    fn test_bridge() {
+        // River level with a bridge level above it.
+        let mut map = LayeredGrid::from_fn(rect(0, 0, 9, 9), -1..1, |p| {
+            if p.z < 0 {
+                '~'
+            } else if p.pos.x == 4 {
+                '='
+            } else {
+                ' '
+            }
+        });
+        map[at(0, 4, 0)] = '#';
+        assert_eq!(map.z_range(), -1..1);
+        assert_eq!(map.iter().count(), 162);
+        assert!(!map.contains(at(0, 0, 1)));
+
+        // Can walk along the bridge but not off it.
+        let on_bridge = map.neighbors(at(4, 4, 0), |&c| c == '=');
+        assert_eq!(on_bridge.len(), 2);
+        assert!(on_bridge.contains(&at(4, 3, 0)));
+
+        map.connect_one_way(at(4, 8, 0), at(4, 8, -1));
+        map.connect(at(5, 5, 0), at(5, 5, -1));
+        assert_eq!(
+            map.links(at(5, 5, -1)).collect::<Vec<_>>(),
+            vec![at(5, 5, 0)]
+        );
+        assert_eq!(map.links(at(4, 8, -1)).count(), 0);
+        assert!(map.neighbors(at(4, 8, 0), |_| true).contains(&at(4, 8, -1)));
+        map.disconnect(at(5, 5, -1), at(5, 5, 0));
+        assert_eq!(map.links(at(5, 5, 0)).count(), 0);
+
+        // Looking down from the bridge sees the river, the wall blocks sight.
+        let seen = map.fov(at(4, 4, 0), 8, |&c| c != '#', |&c| c == ' ');
+        assert!(seen.contains(&at(2, 4, 0)));
+        assert!(seen.contains(&at(2, 4, -1)));
+        assert!(!seen.contains(&at(4, 4, -1)));
+        assert!(seen.contains(&at(0, 4, 0)));
+        assert!(!seen.contains(&at(0, 4, -1)));
+    }
+}
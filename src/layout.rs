@@ -0,0 +1,186 @@
+//! Rectangle arithmetic for laying out UI panels
+
+use euclid::{point2, rect, Rect, SideOffsets2D, Size2D};
+
+/// Point of a rectangle that a sub-rectangle is attached to.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Serialize, Deserialize)]
+pub enum Anchor {
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    Center,
+    Right,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+
+impl Anchor {
+    /// Return the anchor position as fractions of the width and height of a rectangle.
+    pub fn fraction(self) -> (f32, f32) {
+        use Anchor::*;
+        match self {
+            TopLeft => (0.0, 0.0),
+            Top => (0.5, 0.0),
+            TopRight => (1.0, 0.0),
+            Left => (0.0, 0.5),
+            Center => (0.5, 0.5),
+            Right => (1.0, 0.5),
+            BottomLeft => (0.0, 1.0),
+            Bottom => (0.5, 1.0),
+            BottomRight => (1.0, 1.0),
+        }
+    }
+}
+
+/// Layout operations for UI rectangles.
+///
+/// Intersection, union, containment and uniform inflation are already provided by euclid's
+/// `Rect`. Splitting and edge operations never produce negative sizes, a split past the edge of
+/// the rectangle gives an empty part.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate euclid;
+/// # extern crate calx;
+/// # fn main() {
+/// use euclid::{rect, size2, SideOffsets2D};
+/// use calx::{Anchor, RectExt};
+///
+/// let screen = rect(0.0, 0.0, 800.0, 600.0);
+/// // Status bar along the bottom.
+/// let (main, status) = screen.split_v(-20.0);
+/// assert_eq!(status, rect(0.0, 580.0, 800.0, 20.0));
+/// // Map view takes three quarters of the width, sidebar gets the rest.
+/// let (map, sidebar) = main.split_h_ratio(0.75);
+/// assert_eq!(sidebar, rect(600.0, 0.0, 200.0, 580.0));
+/// let sidebar = sidebar.deflate_edges(SideOffsets2D::new_all_same(4.0));
+/// assert_eq!(sidebar, rect(604.0, 4.0, 192.0, 572.0));
+///
+/// // Dialog box in the middle of the map.
+/// let dialog = map.anchored_sub(Anchor::Center, size2(200.0, 100.0));
+/// assert_eq!(dialog, rect(200.0, 240.0, 200.0, 100.0));
+/// # }
+/// ```
+pub trait RectExt: Sized {
+    /// Move the edges of the rectangle inwards by the offsets.
+    ///
+    /// The size is clamped to zero if the offsets are larger than the rectangle.
+    fn deflate_edges(&self, offsets: SideOffsets2D<f32>) -> Self;
+
+    /// Move the edges of the rectangle outwards by the offsets.
+    fn inflate_edges(&self, offsets: SideOffsets2D<f32>) -> Self;
+
+    /// Split the rectangle into left and right parts.
+    ///
+    /// The left part gets a width of `x`. If `x` is negative, the right part gets a width of
+    /// `-x` instead.
+    fn split_h(&self, x: f32) -> (Self, Self);
+
+    /// Split the rectangle into top and bottom parts.
+    ///
+    /// The top part gets a height of `y`. If `y` is negative, the bottom part gets a height of
+    /// `-y` instead.
+    fn split_v(&self, y: f32) -> (Self, Self);
+
+    /// Split the rectangle into left and right parts with the left part getting fraction `t`
+    /// of the width.
+    fn split_h_ratio(&self, t: f32) -> (Self, Self);
+
+    /// Split the rectangle into top and bottom parts with the top part getting fraction `t` of
+    /// the height.
+    fn split_v_ratio(&self, t: f32) -> (Self, Self);
+
+    /// Return a rectangle of the given size placed inside this one at the anchor.
+    ///
+    /// The anchor point of the sub-rectangle is placed on the same anchor point of this
+    /// rectangle, so `Anchor::BottomRight` puts the sub-rectangle in the bottom right corner.
+    fn anchored_sub(&self, anchor: Anchor, size: Size2D<f32>) -> Self;
+}
+
+impl RectExt for Rect<f32> {
+    fn deflate_edges(&self, offsets: SideOffsets2D<f32>) -> Rect<f32> {
+        let width = (self.size.width - offsets.horizontal()).max(0.0);
+        let height = (self.size.height - offsets.vertical()).max(0.0);
+        let x = (self.origin.x + offsets.left).min(self.max_x() - width);
+        let y = (self.origin.y + offsets.top).min(self.max_y() - height);
+        rect(x, y, width, height)
+    }
+
+    fn inflate_edges(&self, offsets: SideOffsets2D<f32>) -> Rect<f32> {
+        self.outer_rect(offsets)
+    }
+
+    fn split_h(&self, x: f32) -> (Rect<f32>, Rect<f32>) {
+        let (w, h) = (self.size.width, self.size.height);
+        let left = if x < 0.0 { w + x } else { x }.max(0.0).min(w);
+        (
+            rect(self.origin.x, self.origin.y, left, h),
+            rect(self.origin.x + left, self.origin.y, w - left, h),
+        )
+    }
+
+    fn split_v(&self, y: f32) -> (Rect<f32>, Rect<f32>) {
+        let (w, h) = (self.size.width, self.size.height);
+        let top = if y < 0.0 { h + y } else { y }.max(0.0).min(h);
+        (
+            rect(self.origin.x, self.origin.y, w, top),
+            rect(self.origin.x, self.origin.y + top, w, h - top),
+        )
+    }
+
+    fn split_h_ratio(&self, t: f32) -> (Rect<f32>, Rect<f32>) {
+        self.split_h(self.size.width * t.max(0.0))
+    }
+
+    fn split_v_ratio(&self, t: f32) -> (Rect<f32>, Rect<f32>) {
+        self.split_v(self.size.height * t.max(0.0))
+    }
+
+    fn anchored_sub(&self, anchor: Anchor, size: Size2D<f32>) -> Rect<f32> {
+        let (u, v) = anchor.fraction();
+        Rect::new(
+            point2(
+                self.origin.x + (self.size.width - size.width) * u,
+                self.origin.y + (self.size.height - size.height) * v,
+            ),
+            size,
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Anchor, RectExt};
+    use euclid::{rect, size2, SideOffsets2D};
+
+    #[test]
+    fn test_rect_layout() {
+        let r = rect(10.0, 10.0, 100.0, 50.0);
+
+        assert_eq!(
+            r.split_h(30.0),
+            (rect(10.0, 10.0, 30.0, 50.0), rect(40.0, 10.0, 70.0, 50.0))
+        );
+        assert_eq!(r.split_h(-30.0).1, rect(80.0, 10.0, 30.0, 50.0));
+        assert_eq!(r.split_h(500.0).1.size, size2(0.0, 50.0));
+        assert_eq!(r.split_v_ratio(0.2).0, rect(10.0, 10.0, 100.0, 10.0));
+        assert_eq!(r.split_v(-1000.0).0.size, size2(100.0, 0.0));
+
+        let m = SideOffsets2D::new(1.0, 2.0, 3.0, 4.0);
+        assert_eq!(r.deflate_edges(m), rect(14.0, 11.0, 94.0, 46.0));
+        assert_eq!(r.deflate_edges(m).inflate_edges(m), r);
+        let tiny = rect(0.0, 0.0, 2.0, 2.0);
+        assert_eq!(
+            tiny.deflate_edges(SideOffsets2D::new_all_same(5.0)).size,
+            size2(0.0, 0.0)
+        );
+
+        let sub = |anchor| r.anchored_sub(anchor, size2(20.0, 10.0));
+        assert_eq!(sub(Anchor::TopLeft), rect(10.0, 10.0, 20.0, 10.0));
+        assert_eq!(sub(Anchor::Bottom), rect(50.0, 50.0, 20.0, 10.0));
+        assert_eq!(sub(Anchor::Right), rect(90.0, 30.0, 20.0, 10.0));
+    }
+}
@@ -1,50 +1,199 @@
+extern crate deflate;
 extern crate euclid;
+extern crate gif;
 extern crate image;
+extern crate inflate;
 extern crate num;
 extern crate rand;
 extern crate serde;
 #[macro_use]
 extern crate serde_derive;
+extern crate serde_json;
 extern crate tempdir;
 extern crate time;
 extern crate vec_map;
 
 mod alg_misc;
+mod angle;
+mod anim;
+mod annotations;
+mod ascii_map;
+mod atlas;
+mod autotile;
+mod balance;
+mod build_info;
+mod climate;
 mod colors;
+mod config;
+mod console;
+mod danger;
+mod decoration;
 pub mod ease;
+mod event_bus;
+mod field_gif;
+pub mod fixed;
 mod fov;
+mod framing;
+mod golden;
+mod grid;
+mod grid_overlay;
+mod gui;
 mod hex;
 mod hex_fov;
+mod history;
+mod image_decode;
 mod incremental;
+mod input;
+mod inspector;
+mod jobs;
+mod journal;
+mod layered_grid;
+mod layout;
 mod legend_builder;
+mod lighting;
+mod loader;
+mod map_memory;
+pub mod mapgen;
+mod mem_stats;
+mod mods;
+mod ninepatch;
+pub mod noise;
+mod packed_field;
 mod parser;
+mod pool;
 mod prefab;
+mod profiler;
+mod query;
+mod rect_pack;
+mod replay;
+mod reservation;
 mod rng;
+mod save;
+mod schedule;
+mod scheduler;
 mod search;
+mod sites;
+mod sound;
 mod space;
+mod sprite_sheet;
+mod square_fov;
+mod stats;
+mod stealth;
 mod system;
+mod templates;
+pub mod test_maps;
 mod text;
+mod tile_registry;
+mod tilemap;
 mod timing;
+mod traffic;
+mod tuning;
+mod vaults;
+mod viewport;
+mod watch;
 
 pub use alg_misc::{
-    bounding_rect, clamp, compact_bits_by_2, lerp, retry_gen, spread_bits_by_2, Deciban,
-    GenericError, LerpPath, Noise, WeightedChoice,
+    bounding_rect, clamp, compact_bits_by_2, from_hilbert, from_morton, hilbert_cells, lerp,
+    retry_gen, spread_bits_by_2, to_hilbert, to_morton, Deciban, GenericError, LerpPath, Noise,
+    WeightedChoice,
 };
+pub use angle::Angle;
+pub use anim::{AnimState, Animation, AnimationError, AnimationSet, AnimationSpec, LoopMode};
+pub use annotations::{Annotation, Annotations};
+pub use ascii_map::{render_ansi, render_ascii};
+pub use atlas::{AtlasItem, DynamicAtlas};
+pub use autotile::{
+    square_neighbors, Autotiler, Connection, SquareKernel, TileMask, TileRuleSpec, TileRules,
+    TileRulesError,
+};
+pub use balance::{simulate_encounters, BalanceReport, Encounter};
+pub use build_info::{emit_build_info, BuildInfo};
+pub use climate::{Biome, BiomeSpawns, Climate, ClimateModel};
 pub use colors::{
-    color, scolor, term_color, to_linear, to_srgb, BaseTermColor, PseudoTermColor, Rgba, SRgba,
-    TermColor, Xterm256Color, NAMED_COLORS,
+    color, gradient, scolor, term_color, to_linear, to_srgb, BaseTermColor, ColorRamp, Hsla, Hsva,
+    PseudoTermColor, Rgba, SRgba, TermColor, Xterm256Color, NAMED_COLORS,
+};
+pub use config::{Config, ConfigError};
+pub use console::Console;
+pub use danger::{DangerMap, Threat};
+pub use decoration::{
+    automaton_decoration, noise_decoration, scatter_decoration, DecorationPass, Decorator,
 };
+pub use event_bus::{EventBus, Subscription};
+pub use field_gif::{FieldGif, FrameRecorder};
+pub use fixed::Fixed;
 pub use fov::{Fov, FovValue, PolarPoint};
-pub use hex::{hex_disc, hex_neighbors, taxicab_neighbors, Dir12, Dir6, HexDisc, HexGeom};
-pub use hex_fov::{AddFakeIsometricCorners, HexFov, HexFovIter, HexPolarPoint};
+pub use framing::{FrameCodec, FrameDecoder, FrameError};
+pub use golden::{assert_golden, snapshot, UPDATE_GOLDENS_VAR};
+pub use grid::Grid;
+pub use grid_overlay::GridOverlay;
+pub use gui::{bar_graph, Gui, GuiDraw, GuiEvent, GuiStyle};
+pub use hex::{
+    hex_disc, hex_from_cube, hex_from_offset, hex_line, hex_mirror, hex_neighbors, hex_ring,
+    hex_rotate, hex_to_cube, hex_to_offset, line_of_sight, taxicab_neighbors, Dir12, Dir6,
+    DirSet12, DirSet6, HexDisc, HexGeom,
+};
+pub use hex_fov::{AddFakeIsometricCorners, HexFov, HexFovIter, HexPolarPoint, Sight};
+pub use history::{Command, History};
+pub use image_decode::{
+    decode_animation, decode_image, load_animation, load_image, AnimationFrame, DecodeError,
+};
 pub use incremental::{Incremental, IncrementalState};
+pub use input::{Chord, InputMap, Modifiers};
+pub use inspector::Inspector;
+pub use jobs::JobPool;
+pub use journal::Journal;
+pub use layered_grid::{LayeredGrid, LevelPos};
+pub use layout::{Anchor, RectExt};
 pub use legend_builder::LegendBuilder;
+pub use lighting::{illuminate, Light, LightMap};
+pub use loader::{AssetLoader, LoadHandle, LoadResult};
+pub use map_memory::{CellMemory, MapMemory};
+pub use mem_stats::{HeapSize, MemoryReport};
+pub use mods::ContentOverlay;
+pub use ninepatch::{ninepatch, tile_rect};
+pub use packed_field::PackedField;
+pub use pool::{Pool, PoolKey};
 pub use prefab::{
     DenseTextMap, FromPrefab, IntoPrefab, MinimapSpace, PrefabError, ProjectedImage, TextSpace,
 };
-pub use rng::{seeded_rng, RandomPermutation, RngExt};
-pub use search::{astar_path, Dijkstra, GridNode};
-pub use space::{CellSpace, CellVector, Space, Transformation};
-pub use system::{app_data_path, save_screenshot, TimeLogItem};
-pub use text::{split_line, templatize};
-pub use timing::{cycle_anim, single_anim, spike, TimestepLoop};
+pub use profiler::{with_profiler, ProfileScope, Profiler};
+pub use query::{query, CellQuery};
+pub use rect_pack::pack_rects;
+pub use replay::{Replay, ReplayPlayer};
+pub use reservation::{MoveOutcome, MoveRequest, MoveResolver, MoveResult};
+pub use rng::{
+    jittered_grid, poisson_disk, seeded_rng, AliasTable, RandomPermutation, RngExt, SeedTree,
+    SerRng, WeightedTable,
+};
+pub use save::{Migration, SaveError, SaveFile, SaveFormat, SaveHeader, SaveInfo, SlotInfo};
+pub use schedule::Schedule;
+pub use scheduler::{Scheduler, NORMAL_SPEED};
+pub use search::{
+    astar_path, astar_path_in, astar_path_observed, astar_path_weighted, astar_path_weighted_in,
+    autoexplore, connected_components, expand_weights, flood_fill, Dijkstra, DijkstraMap,
+    FlowField, GridNode, SearchBuffers, SearchObserver, SearchTrace,
+};
+pub use sites::{dungeon_score, settlement_score, Site, SiteKind, SitePlacer, WorldSites};
+pub use sound::SoundField;
+pub use space::{
+    depth_z, AffineSpace, CellShape, CellSpace, CellVector, IsoSpace, PixelSpace, ScreenProjection,
+    Space, Transformation,
+};
+pub use sprite_sheet::{tile_sheet, Sprite, SpriteSheet, SpriteSheetError, SpriteSpec};
+pub use square_fov::{SquareFov, SquarePolarPoint};
+pub use stats::{percentile, Histogram, RunningStats};
+pub use stealth::{Observer, Perception, StealthModel};
+pub use system::{app_data_path, save_screenshot};
+pub use templates::{Grammar, TemplateError};
+pub use text::{layout_text, split_line, templatize, TextFragment};
+pub use tile_registry::{TileGraphics, TileId, TileRegistry, TileRegistryError, TileSpec};
+pub use tilemap::{TileIndexError, TileIndexMap, TILEMAP_FRAGMENT_SHADER};
+pub use timing::{
+    cycle_anim, single_anim, spike, AnimClock, FrameCounter, GameClocks, GameLoop, TimestepLoop,
+};
+pub use traffic::TrafficMap;
+pub use tuning::TuningPanel;
+pub use vaults::{Orientation, Placement, PlacementFailure, PlacementReport, Vault, VaultPlacer};
+pub use viewport::{ScaleMode, Viewport};
+pub use watch::WatchHud;
@@ -0,0 +1,385 @@
+//! Colored light propagation over hex maps
+
+use hex::HexGeom;
+use hex_fov::{HexFov, Sight};
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::hash::Hash;
+use time;
+use {CellVector, Rgba};
+
+/// Colored point light source.
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Light {
+    pub pos: CellVector,
+    /// Color and brightness of the light at the source cell.
+    ///
+    /// Components above 1.0 make a light that stays bright further out.
+    pub color: Rgba,
+    /// Distance in cells the light reaches.
+    pub radius: i32,
+}
+
+impl Light {
+    pub fn new(pos: CellVector, color: impl Into<Rgba>, radius: i32) -> Light {
+        Light {
+            pos,
+            color: color.into(),
+            radius,
+        }
+    }
+
+    /// Return the light level at a distance from the source, ignoring occlusion.
+    ///
+    /// The intensity falls off linearly from full at the source to zero just past the radius.
+    pub fn intensity_at(&self, dist: i32) -> f32 {
+        if dist > self.radius {
+            0.0
+        } else {
+            1.0 - dist as f32 / (self.radius + 1) as f32
+        }
+    }
+}
+
+/// Compute the light falling on map cells from a set of light sources.
+///
+/// Light is occluded like sight in `HexFov`. Opaque cells are lit but cast shadows behind them.
+/// Overlapping lights add up. The returned map only has the cells that get some light, and the
+/// light values have an alpha of 1. Multiply tile colors with the light value of the cell,
+/// using an ambient light color for cells missing from the map.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate euclid;
+/// # extern crate calx;
+/// # fn main() {
+/// use euclid::vec2;
+/// use calx::{illuminate, Light, Rgba};
+///
+/// let torch = Light::new(vec2(0, 0), Rgba::new(1.0, 0.5, 0.0, 1.0), 3);
+/// // Pillar at (1, 1).
+/// let light = illuminate(&[torch], |p| p != vec2(1, 1));
+///
+/// assert_eq!(light[&vec2(0, 0)], Rgba::new(1.0, 0.5, 0.0, 1.0));
+/// assert_eq!(light[&vec2(1, 1)], Rgba::new(0.75, 0.375, 0.0, 1.0));
+/// // Shadow behind the pillar.
+/// assert!(!light.contains_key(&vec2(2, 2)));
+/// // Out of range.
+/// assert!(!light.contains_key(&vec2(0, 4)));
+/// # }
+/// ```
+pub fn illuminate<'a, I, F>(lights: I, is_transparent: F) -> HashMap<CellVector, Rgba>
+where
+    I: IntoIterator<Item = &'a Light>,
+    F: Fn(CellVector) -> bool,
+{
+    let mut ret: HashMap<CellVector, Rgba> = HashMap::new();
+    for light in lights {
+        for (pos, c) in light_cells(light, &is_transparent) {
+            let e = ret.entry(pos).or_insert(Rgba::new(0.0, 0.0, 0.0, 1.0));
+            e.r += c.r;
+            e.g += c.g;
+            e.b += c.b;
+        }
+    }
+    ret
+}
+
+/// Compute the light a single light source casts on the cells it reaches.
+fn light_cells(
+    light: &Light,
+    is_transparent: &dyn Fn(CellVector) -> bool,
+) -> Vec<(CellVector, Rgba)> {
+    if light.radius < 0 {
+        return Vec::new();
+    }
+    // Collect into a set first, the FOV can visit cells on arc boundaries more than once.
+    let cells: HashSet<CellVector> =
+        HexFov::new(Sight::new(light.pos, light.radius, is_transparent))
+            .map(|(offset, _)| offset)
+            .collect();
+    cells
+        .into_iter()
+        .map(|offset| {
+            (
+                light.pos + offset,
+                light.color * light.intensity_at(offset.hex_dist()),
+            )
+        })
+        .collect()
+}
+
+/// Light map that is updated incrementally as lights and walls change.
+///
+/// Changing a light or the occlusion of a cell only marks the affected lights dirty, and
+/// `update` recomputes dirty lights until a time budget runs out. The lights that didn't fit
+/// in the budget keep their old contribution in the map until a later frame gets to them, so
+/// scenes with many lights can be kept up to date without frame drops.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate euclid;
+/// # extern crate calx;
+/// # fn main() {
+/// use euclid::vec2;
+/// use calx::{Light, LightMap};
+///
+/// let mut door_open = false;
+///
+/// let mut light = LightMap::new();
+/// light.insert("torch", Light::new(vec2(0, 0), [1.0, 0.5, 0.0], 4));
+/// // Door at (2, 0).
+/// light.update_all(|p| door_open || p != vec2(2, 0));
+/// assert!(light.get(vec2(3, 0)).is_none());
+///
+/// door_open = true;
+/// light.occlusion_changed(vec2(2, 0));
+/// assert_eq!(light.dirty_count(), 1);
+/// // Spend at most 2 ms on lighting this frame.
+/// while !light.update(0.002, |p| door_open || p != vec2(2, 0)) {}
+/// assert!(light.get(vec2(3, 0)).is_some());
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct LightMap<K> {
+    lights: HashMap<K, LightState>,
+    /// Summed light of every lit cell with the number of lights reaching it.
+    cells: HashMap<CellVector, (Rgba, usize)>,
+    /// Lights waiting to be recomputed, oldest first.
+    dirty: VecDeque<K>,
+}
+
+#[derive(Clone, Debug)]
+struct LightState {
+    light: Light,
+    is_dirty: bool,
+    /// Contribution of the light that is currently summed into the map.
+    cells: Vec<(CellVector, Rgba)>,
+}
+
+impl<K: Eq + Hash + Clone> LightMap<K> {
+    pub fn new() -> LightMap<K> {
+        LightMap {
+            lights: HashMap::new(),
+            cells: HashMap::new(),
+            dirty: VecDeque::new(),
+        }
+    }
+
+    /// Add or change a light.
+    ///
+    /// A changed light keeps lighting the map as it was until it is recomputed.
+    pub fn insert(&mut self, key: K, light: Light) {
+        let state = self.lights.entry(key.clone()).or_insert(LightState {
+            light,
+            is_dirty: false,
+            cells: Vec::new(),
+        });
+        state.light = light;
+        if !state.is_dirty {
+            state.is_dirty = true;
+            self.dirty.push_back(key);
+        }
+    }
+
+    /// Remove a light, its light disappears from the map immediately.
+    pub fn remove(&mut self, key: &K) -> Option<Light> {
+        let state = self.lights.remove(key)?;
+        if state.is_dirty {
+            self.dirty.retain(|k| k != key);
+        }
+        self.subtract(&state.cells);
+        Some(state.light)
+    }
+
+    /// Mark the lights that can reach a cell dirty after the cell's transparency changed.
+    pub fn occlusion_changed(&mut self, pos: CellVector) {
+        for (key, state) in self.lights.iter_mut() {
+            if !state.is_dirty && (pos - state.light.pos).hex_dist() <= state.light.radius {
+                state.is_dirty = true;
+                self.dirty.push_back(key.clone());
+            }
+        }
+    }
+
+    /// Recompute dirty lights until the time budget in seconds runs out.
+    ///
+    /// At least one dirty light is always recomputed so that updates make progress with any
+    /// budget. Returns whether all lights are up to date.
+    pub fn update<F>(&mut self, budget_s: f64, is_transparent: F) -> bool
+    where
+        F: Fn(CellVector) -> bool,
+    {
+        let end = time::precise_time_s() + budget_s;
+        while self.update_next(&is_transparent) {
+            if time::precise_time_s() >= end {
+                break;
+            }
+        }
+        self.dirty.is_empty()
+    }
+
+    /// Recompute all dirty lights.
+    pub fn update_all<F>(&mut self, is_transparent: F)
+    where
+        F: Fn(CellVector) -> bool,
+    {
+        while self.update_next(&is_transparent) {}
+    }
+
+    /// Recompute the oldest dirty light, return false if there were none.
+    fn update_next(&mut self, is_transparent: &dyn Fn(CellVector) -> bool) -> bool {
+        let key = match self.dirty.pop_front() {
+            Some(key) => key,
+            None => return false,
+        };
+        let mut state = self.lights.remove(&key).expect("Dirty light missing");
+        self.subtract(&state.cells);
+        state.cells = light_cells(&state.light, is_transparent);
+        state.is_dirty = false;
+        for &(pos, c) in &state.cells {
+            let e = self
+                .cells
+                .entry(pos)
+                .or_insert((Rgba::new(0.0, 0.0, 0.0, 1.0), 0));
+            e.0.r += c.r;
+            e.0.g += c.g;
+            e.0.b += c.b;
+            e.1 += 1;
+        }
+        self.lights.insert(key, state);
+        true
+    }
+
+    /// Take a light's contribution out of the map.
+    fn subtract(&mut self, cells: &[(CellVector, Rgba)]) {
+        for &(pos, c) in cells {
+            if let Entry::Occupied(mut e) = self.cells.entry(pos) {
+                if e.get().1 <= 1 {
+                    // Remove the cell entirely instead of leaving rounding errors behind.
+                    e.remove();
+                } else {
+                    let e = e.get_mut();
+                    e.0.r -= c.r;
+                    e.0.g -= c.g;
+                    e.0.b -= c.b;
+                    e.1 -= 1;
+                }
+            }
+        }
+    }
+
+    /// Return the light at a cell, or `None` if no light reaches it.
+    pub fn get(&self, pos: CellVector) -> Option<Rgba> { self.cells.get(&pos).map(|e| e.0) }
+
+    /// Iterate the lit cells.
+    pub fn iter(&self) -> impl Iterator<Item = (CellVector, Rgba)> + '_ {
+        self.cells.iter().map(|(&p, e)| (p, e.0))
+    }
+
+    /// Return the number of lights waiting to be recomputed.
+    pub fn dirty_count(&self) -> usize { self.dirty.len() }
+}
+
+impl<K: Eq + Hash + Clone> Default for LightMap<K> {
+    fn default() -> LightMap<K> { LightMap::new() }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{illuminate, Light, LightMap};
+    use euclid::vec2;
+    use hex::{hex_disc, HexGeom};
+    use std::collections::{HashMap, HashSet};
+    use Rgba;
+
+    #[test]
+    fn test_illuminate() {
+        let red = Light::new(vec2(0, 0), [1.0, 0.0, 0.0], 4);
+        let blue = Light::new(vec2(4, 0), [0.0, 0.0, 2.0], 2);
+        assert_eq!(red.intensity_at(0), 1.0);
+        assert!((red.intensity_at(4) - 0.2).abs() < 1e-6);
+        assert_eq!(red.intensity_at(5), 0.0);
+
+        let light = illuminate(&[red, blue], |_| true);
+        // Each light covers its whole disc in open space.
+        assert_eq!(
+            light.len(),
+            hex_disc(vec2(0, 0), 4)
+                .chain(hex_disc(vec2(4, 0), 2))
+                .collect::<HashSet<_>>()
+                .len()
+        );
+        for (&p, c) in &light {
+            assert!(p.hex_dist() <= 4 || (p - vec2(4, 0)).hex_dist() <= 2);
+            assert_eq!(c.a, 1.0);
+        }
+
+        // Lights mix.
+        let c = light[&vec2(3, 0)];
+        assert!((c.r - 0.4).abs() < 1e-6);
+        assert!((c.b - 4.0 / 3.0).abs() < 1e-6);
+        assert_eq!(light[&vec2(-1, 0)], Rgba::new(0.8, 0.0, 0.0, 1.0));
+        // Cells on FOV arc boundaries are only lit once.
+        assert_eq!(light[&vec2(-1, -1)], Rgba::new(0.8, 0.0, 0.0, 1.0));
+
+        // Wall at x = 2 keeps the lights apart except at the opening.
+        let light = illuminate(&[red, blue], |p| p.x != 2 || p.y == 5);
+        assert_eq!(light[&vec2(3, 0)].r, 0.0);
+        assert!(light[&vec2(2, 0)].r > 0.0 && light[&vec2(2, 0)].b > 0.0);
+
+        let single = |radius| illuminate(&[Light::new(vec2(0, 0), [1.0; 3], radius)], |_| true);
+        assert_eq!(single(0).len(), 1);
+        assert!(single(-1).is_empty());
+    }
+
+    #[test]
+    fn test_light_map() {
+        let red = Light::new(vec2(0, 0), [1.0, 0.0, 0.0], 4);
+        let blue = Light::new(vec2(4, 0), [0.0, 0.0, 2.0], 2);
+        let wall = |p: ::CellVector| p.x != 2 || p.y == 5;
+
+        let mut map = LightMap::new();
+        map.insert(1, red);
+        map.insert(2, blue);
+        map.insert(2, blue);
+        assert_eq!(map.dirty_count(), 2);
+        // Zero budget still makes progress one light at a time.
+        assert!(!map.update(0.0, |_| true));
+        assert!(map.update(0.0, |_| true));
+        assert_eq!(
+            map.iter().collect::<HashMap<_, _>>(),
+            illuminate(&[red, blue], |_| true)
+        );
+
+        // Wall changes only affect the lights that reach them.
+        map.occlusion_changed(vec2(-4, 0));
+        assert_eq!(map.dirty_count(), 1);
+        map.occlusion_changed(vec2(2, 0));
+        assert_eq!(map.dirty_count(), 2);
+        map.update_all(wall);
+        assert_eq!(
+            map.iter().collect::<HashMap<_, _>>(),
+            illuminate(&[red, blue], wall)
+        );
+
+        // Changed light keeps its old light until updated.
+        let moved = Light::new(vec2(-10, 0), [1.0, 0.0, 0.0], 4);
+        map.insert(1, moved);
+        assert!(map.get(vec2(0, 0)).is_some());
+        map.update_all(wall);
+        assert!(map.get(vec2(0, 0)).is_none());
+        assert!(map.get(vec2(-10, 0)).is_some());
+
+        map.insert(2, blue);
+        assert_eq!(map.remove(&2), Some(blue));
+        assert_eq!(map.remove(&2), None);
+        assert_eq!(map.dirty_count(), 0);
+        assert_eq!(
+            map.iter().collect::<HashMap<_, _>>(),
+            illuminate(&[moved], wall)
+        );
+    }
+}
@@ -0,0 +1,180 @@
+//! Background asset loading
+
+use image::RgbaImage;
+use image_decode;
+use jobs::JobPool;
+use std::error::Error;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::Arc;
+
+/// Result of loading an asset.
+pub type LoadResult<T> = Result<T, Box<dyn Error + Send + Sync>>;
+
+/// Asset loader that decodes assets on the worker threads of a `JobPool`.
+///
+/// Each load returns a handle that can be polled once per frame without blocking. The loader
+/// keeps count of the started and finished loads for showing a progress bar. The job pool can
+/// be shared with the rest of the game, loading many assets at once then doesn't start more
+/// threads than the pool has.
+///
+/// # Examples
+///
+/// ```
+/// use std::sync::Arc;
+/// use calx::{AssetLoader, JobPool};
+///
+/// let pool = Arc::new(JobPool::new(2));
+/// let mut loader = AssetLoader::new(pool);
+/// let mut handle = loader.load(|| Ok("level data".len()));
+///
+/// let size = loop {
+///     if let Some(result) = handle.poll() {
+///         break result.unwrap();
+///     }
+///     // Draw loading screen with loader.progress() here.
+/// };
+/// assert_eq!(size, 10);
+/// assert!(loader.is_done());
+/// assert_eq!(loader.progress(), 1.0);
+/// ```
+pub struct AssetLoader {
+    pool: Arc<JobPool>,
+    started: usize,
+    finished: Arc<AtomicUsize>,
+}
+
+impl AssetLoader {
+    /// Create a loader that runs the loads on a job pool.
+    pub fn new(pool: Arc<JobPool>) -> AssetLoader {
+        AssetLoader {
+            pool,
+            started: 0,
+            finished: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// Queue a decoding function to run on a worker thread.
+    ///
+    /// A panic in the function is turned into an error result.
+    pub fn load<T, F>(&mut self, f: F) -> LoadHandle<T>
+    where
+        T: Send + 'static,
+        F: FnOnce() -> LoadResult<T> + Send + 'static,
+    {
+        let (sender, receiver) = mpsc::channel();
+        let finished = self.finished.clone();
+        self.started += 1;
+        self.pool.spawn(move || {
+            let result = panic::catch_unwind(AssertUnwindSafe(f))
+                .unwrap_or_else(|_| Err("Asset load panicked".into()));
+            // Count the load as finished before the handle can see the result.
+            finished.fetch_add(1, Ordering::SeqCst);
+            // The receiving handle may have been dropped, the result is just discarded then.
+            let _ = sender.send(result);
+        });
+        LoadHandle {
+            receiver,
+            is_received: false,
+        }
+    }
+
+    /// Load and decode an image file on a worker thread.
+    ///
+    /// Accepts all the formats supported by `load_image`.
+    pub fn load_image<P: AsRef<Path>>(&mut self, path: P) -> LoadHandle<RgbaImage> {
+        let path = path.as_ref().to_path_buf();
+        self.load(move || Ok(image_decode::load_image(path)?))
+    }
+
+    /// Return the fraction of started loads that have finished.
+    pub fn progress(&self) -> f32 {
+        if self.started == 0 {
+            1.0
+        } else {
+            self.finished.load(Ordering::SeqCst) as f32 / self.started as f32
+        }
+    }
+
+    /// Return whether all started loads have finished.
+    pub fn is_done(&self) -> bool { self.finished.load(Ordering::SeqCst) >= self.started }
+}
+
+impl Default for AssetLoader {
+    /// Create a loader with its own job pool with one worker for each available CPU core.
+    fn default() -> Self { AssetLoader::new(Arc::new(JobPool::default())) }
+}
+
+/// Completion handle for an asset being loaded by `AssetLoader`.
+pub struct LoadHandle<T> {
+    receiver: Receiver<LoadResult<T>>,
+    is_received: bool,
+}
+
+impl<T> LoadHandle<T> {
+    /// Return the load result if loading has finished.
+    ///
+    /// The result is only returned once, later polls return `None`.
+    pub fn poll(&mut self) -> Option<LoadResult<T>> {
+        if self.is_received {
+            return None;
+        }
+        let result = match self.receiver.try_recv() {
+            Ok(result) => result,
+            Err(TryRecvError::Empty) => return None,
+            Err(TryRecvError::Disconnected) => Err("Asset load was dropped unfinished".into()),
+        };
+        self.is_received = true;
+        Some(result)
+    }
+
+    /// Block until loading has finished.
+    pub fn wait(self) -> LoadResult<T> {
+        self.receiver
+            .recv()
+            .map_err(|_| "Asset load was dropped unfinished".into())
+            .and_then(|result| result)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::AssetLoader;
+    use jobs::JobPool;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_panicking_load() {
+        let pool = Arc::new(JobPool::new(1));
+        let mut loader = AssetLoader::new(pool.clone());
+        let mut handle = loader.load::<i32, _>(|| panic!("Malformed asset"));
+        let result = loop {
+            if let Some(result) = handle.poll() {
+                break result;
+            }
+        };
+        assert!(result.is_err());
+        assert!(handle.poll().is_none());
+        assert!(loader.is_done());
+
+        let handle = loader.load::<i32, _>(|| panic!("Malformed asset"));
+        assert!(handle.wait().is_err());
+        assert_eq!(loader.progress(), 1.0);
+
+        // Load panics are caught before they reach the pool.
+        pool.wait();
+    }
+
+    #[test]
+    fn test_shared_pool() {
+        let pool = Arc::new(JobPool::new(2));
+        let mut loader = AssetLoader::new(pool.clone());
+        let handles: Vec<_> = (0..20).map(|i| loader.load(move || Ok(i * 2))).collect();
+        pool.wait();
+        assert!(loader.is_done());
+        let results: Vec<i32> = handles.into_iter().map(|h| h.wait().unwrap()).collect();
+        assert_eq!(results, (0..20).map(|i| i * 2).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,239 @@
+//! Remembering the parts of the map that have been seen
+
+use std::collections::{HashMap, HashSet};
+use CellVector;
+
+/// What is known about a map cell.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum CellMemory<'a, T: 'a> {
+    /// The cell is in view right now.
+    Visible(&'a T),
+    /// The cell has been seen before and this is how it looked the last time.
+    Remembered(&'a T),
+    /// The cell has never been seen.
+    Unknown,
+}
+
+impl<'a, T> CellMemory<'a, T> {
+    /// Return the known value of a visible or remembered cell.
+    pub fn value(self) -> Option<&'a T> {
+        match self {
+            CellMemory::Visible(t) | CellMemory::Remembered(t) => Some(t),
+            CellMemory::Unknown => None,
+        }
+    }
+}
+
+/// Explored map memory built from field of view results.
+///
+/// Feed the `HexFov` from the viewer's position to `update` every turn. Cells in the field of
+/// view become visible and their terrain is recorded. Cells that drop out of view stay
+/// remembered with the terrain they had when they were last seen, even if the map has changed
+/// since.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate euclid;
+/// # extern crate serde_json;
+/// # extern crate calx;
+/// # fn main() {
+/// use euclid::vec2;
+/// use calx::{CellMemory, CellVector, FovValue, HexFov, HexGeom, MapMemory};
+///
+/// #[derive(PartialEq, Clone)]
+/// struct Sight(i32);
+///
+/// impl FovValue for Sight {
+///     fn advance(&self, offset: CellVector) -> Option<Self> {
+///         if offset.hex_dist() < self.0 { Some(self.clone()) } else { None }
+///     }
+/// }
+///
+/// let mut memory = MapMemory::new();
+///
+/// let terrain = |p: CellVector| if p == vec2(1, 0) { '+' } else { '.' };
+/// assert_eq!(memory.update(vec2(0, 0), HexFov::new(Sight(3)), terrain), 19);
+/// assert_eq!(memory.get(vec2(1, 0)), CellMemory::Visible(&'+'));
+///
+/// // Walk away while the door gets opened, it's still remembered as closed.
+/// let opened = |p: CellVector| if p == vec2(1, 0) { '\'' } else { '.' };
+/// memory.update(vec2(10, 0), HexFov::new(Sight(3)), opened);
+/// assert_eq!(memory.get(vec2(1, 0)), CellMemory::Remembered(&'+'));
+/// assert_eq!(memory.get(vec2(5, 0)), CellMemory::Unknown);
+///
+/// let saved = serde_json::to_string(&memory).unwrap();
+/// let loaded: MapMemory<char> = serde_json::from_str(&saved).unwrap();
+/// assert_eq!(loaded, memory);
+/// # }
+/// ```
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(from = "MapMemoryData<T>", into = "MapMemoryData<T>")]
+pub struct MapMemory<T: Clone> {
+    seen: HashMap<CellVector, T>,
+    visible: HashSet<CellVector>,
+}
+
+/// Serialized form of a `MapMemory`.
+///
+/// JSON maps can't have vector keys, so the cells are stored as lists sorted into row-major
+/// order to keep save files stable.
+#[derive(Serialize, Deserialize)]
+struct MapMemoryData<T> {
+    seen: Vec<(CellVector, T)>,
+    visible: Vec<CellVector>,
+}
+
+impl<T: Clone> From<MapMemoryData<T>> for MapMemory<T> {
+    fn from(data: MapMemoryData<T>) -> MapMemory<T> {
+        MapMemory {
+            seen: data.seen.into_iter().collect(),
+            visible: data.visible.into_iter().collect(),
+        }
+    }
+}
+
+impl<T: Clone> From<MapMemory<T>> for MapMemoryData<T> {
+    fn from(memory: MapMemory<T>) -> MapMemoryData<T> {
+        let mut seen: Vec<_> = memory.seen.into_iter().collect();
+        seen.sort_by_key(|&(p, _)| (p.y, p.x));
+        let mut visible: Vec<_> = memory.visible.into_iter().collect();
+        visible.sort_by_key(|p| (p.y, p.x));
+        MapMemoryData { seen, visible }
+    }
+}
+
+impl<T: Clone> Default for MapMemory<T> {
+    fn default() -> MapMemory<T> { MapMemory::new() }
+}
+
+impl<T: Clone> MapMemory<T> {
+    pub fn new() -> MapMemory<T> {
+        MapMemory {
+            seen: HashMap::new(),
+            visible: HashSet::new(),
+        }
+    }
+
+    /// Replace the visible cells with a new field of view.
+    ///
+    /// The field of view yields cell offsets from `origin` like `HexFov` does, and `terrain`
+    /// gives the value to remember for each visible cell from its absolute position. Returns the
+    /// number of cells that were seen for the first time.
+    pub fn update<I, V, F>(&mut self, origin: CellVector, fov: I, mut terrain: F) -> usize
+    where
+        I: IntoIterator<Item = (CellVector, V)>,
+        F: FnMut(CellVector) -> T,
+    {
+        self.visible.clear();
+        let mut discovered = 0;
+        for (offset, _) in fov {
+            let pos = origin + offset;
+            if !self.visible.insert(pos) {
+                continue;
+            }
+            if self.seen.insert(pos, terrain(pos)).is_none() {
+                discovered += 1;
+            }
+        }
+        discovered
+    }
+
+    /// Mark all cells as out of view without forgetting them.
+    pub fn clear_visible(&mut self) { self.visible.clear(); }
+
+    /// Return what is known about a cell.
+    pub fn get(&self, pos: CellVector) -> CellMemory<'_, T> {
+        match self.seen.get(&pos) {
+            Some(t) if self.visible.contains(&pos) => CellMemory::Visible(t),
+            Some(t) => CellMemory::Remembered(t),
+            None => CellMemory::Unknown,
+        }
+    }
+
+    pub fn is_visible(&self, pos: CellVector) -> bool { self.visible.contains(&pos) }
+
+    /// Return whether the cell is visible or has been seen before.
+    pub fn is_seen(&self, pos: CellVector) -> bool { self.seen.contains_key(&pos) }
+
+    /// Forget a cell so it becomes unknown again.
+    pub fn forget(&mut self, pos: CellVector) {
+        self.seen.remove(&pos);
+        self.visible.remove(&pos);
+    }
+
+    /// Forget everything.
+    pub fn clear(&mut self) {
+        self.seen.clear();
+        self.visible.clear();
+    }
+
+    /// Iterate all the visible and remembered cells in arbitrary order.
+    pub fn iter(&self) -> impl Iterator<Item = (CellVector, CellMemory<'_, T>)> {
+        self.seen.keys().map(move |&p| (p, self.get(p)))
+    }
+
+    /// Iterate the currently visible cells in arbitrary order.
+    pub fn visible(&self) -> impl Iterator<Item = CellVector> + '_ { self.visible.iter().cloned() }
+
+    /// Return the number of cells that have been seen.
+    pub fn len(&self) -> usize { self.seen.len() }
+
+    pub fn is_empty(&self) -> bool { self.seen.is_empty() }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{CellMemory, MapMemory};
+    use euclid::vec2;
+    use hex::HexGeom;
+    use hex_fov::{HexFov, Sight};
+    use serde_json;
+    use test_maps::TestMap;
+    use CellVector;
+
+    #[test]
+    fn test_map_memory() {
+        let map = TestMap::scattered(&2, 20, 0.3);
+        let is_transparent = |p: CellVector| !map.is_wall(p);
+        let terrain = |p: CellVector| if map.is_wall(p) { '#' } else { '.' };
+        let mut memory = MapMemory::new();
+
+        let fov = |origin| HexFov::new(Sight::new(origin, 6, &is_transparent));
+        let a = map.floor().next().unwrap();
+        let first = memory.update(a, fov(a), terrain);
+        assert_eq!(first, memory.len());
+        assert_eq!(memory.visible().count(), first);
+        assert_eq!(memory.get(a), CellMemory::Visible(&'.'));
+        // Walls blocking the view are remembered too.
+        assert!(memory.iter().any(|(_, m)| m.value() == Some(&'#')));
+        assert!(memory.iter().all(|(p, _)| (p - a).hex_dist() <= 6));
+
+        // Looking again from the same spot finds nothing new.
+        assert_eq!(memory.update(a, fov(a), terrain), 0);
+
+        let b = map.floor().find(|&p| (p - a).hex_dist() > 12).unwrap();
+        let second = memory.update(b, fov(b), terrain);
+        assert!(second > 0);
+        assert_eq!(memory.get(a), CellMemory::Remembered(&'.'));
+        assert_eq!(memory.get(b), CellMemory::Visible(&'.'));
+        assert!(!memory.is_visible(a));
+        assert!(memory.is_seen(a));
+        assert_eq!(memory.len(), first + second);
+
+        memory.forget(b);
+        assert_eq!(memory.get(b), CellMemory::Unknown);
+        assert_eq!(memory.get(vec2(100, 100)), CellMemory::Unknown);
+
+        let saved = serde_json::to_string(&memory).unwrap();
+        let loaded: MapMemory<char> = serde_json::from_str(&saved).unwrap();
+        assert_eq!(loaded, memory);
+        // Save files don't depend on hash order.
+        assert_eq!(serde_json::to_string(&loaded).unwrap(), saved);
+
+        memory.clear_visible();
+        assert_eq!(memory.visible().count(), 0);
+        memory.clear();
+        assert!(memory.is_empty());
+    }
+}
@@ -0,0 +1,680 @@
+//! Procedural map generators
+//!
+//! The generators produce a `Grid<bool>` covering the requested area with `true` for floor and
+//! `false` for wall cells. Convert the result into actual terrain with `Grid::map`, or combine
+//! several generators by running them on different parts of a map. Connectivity is measured
+//! with hex grid adjacency, and every generator leaves the edge cells of the area as walls.
+//!
+//! The morphological operations `erode`, `dilate`, `open` and `close` clean up and reshape the
+//! generated masks. `room_graph` and `locks_and_keys` build lock and key puzzles over rooms.
+
+use euclid::{rect, vec2, TypedRect};
+use grid::Grid;
+use hex::{Dir6, HexGeom};
+use rand::Rng;
+use std::collections::HashSet;
+use {CellSpace, CellVector};
+
+/// Generate a cave with a cellular automaton.
+///
+/// The area starts as random noise with `floor_chance` probability for floor cells and is then
+/// smoothed for the given number of iterations. Only the largest connected cave is kept, smaller
+/// pockets are filled in.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate euclid;
+/// # extern crate calx;
+/// # fn main() {
+/// use euclid::rect;
+/// use calx::{mapgen, seeded_rng};
+///
+/// let cave = mapgen::cave(&mut seeded_rng(&1), rect(0, 0, 40, 30), 0.55, 4);
+/// assert!(cave.iter().filter(|&(_, &floor)| floor).count() > 100);
+/// # }
+/// ```
+pub fn cave<R: Rng + ?Sized>(
+    rng: &mut R,
+    area: TypedRect<i32, CellSpace>,
+    floor_chance: f32,
+    iterations: usize,
+) -> Grid<bool> {
+    let is_edge = |p: CellVector| {
+        p.x == area.min_x()
+            || p.y == area.min_y()
+            || p.x == area.max_x() - 1
+            || p.y == area.max_y() - 1
+    };
+
+    let mut grid = Grid::from_fn(area, |p| !is_edge(p) && rng.gen::<f32>() < floor_chance);
+
+    for _ in 0..iterations {
+        grid = grid.map(|p, &floor| {
+            if is_edge(p) {
+                return false;
+            }
+            let walls = Dir6::iter()
+                .filter(|d| !grid.get(p + d.to_v2()).cloned().unwrap_or(false))
+                .count();
+            match walls {
+                0..=2 => true,
+                3 => floor,
+                _ => false,
+            }
+        });
+    }
+
+    let keep = largest_region(&grid);
+    grid.map(|p, _| keep.contains(&p))
+}
+
+/// Generate rooms connected with corridors using binary space partitioning.
+///
+/// The area is split recursively into parts no smaller than `min_size` cells along either axis,
+/// each part gets a room and the rooms of sibling parts are joined with corridors. Returns the
+/// map and the floor rectangles of the rooms.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate euclid;
+/// # extern crate calx;
+/// # fn main() {
+/// use euclid::rect;
+/// use calx::{mapgen, seeded_rng};
+///
+/// let (map, rooms) = mapgen::rooms(&mut seeded_rng(&1), rect(0, 0, 40, 30), 8);
+/// assert!(rooms.len() >= 4);
+/// assert!(map[rooms[0].origin.to_vector()]);
+/// # }
+/// ```
+pub fn rooms<R: Rng + ?Sized>(
+    rng: &mut R,
+    area: TypedRect<i32, CellSpace>,
+    min_size: i32,
+) -> (Grid<bool>, Vec<TypedRect<i32, CellSpace>>) {
+    assert!(min_size >= 4, "Minimum room area size must be at least 4");
+    let mut grid = Grid::new(area, false);
+    let mut rooms = Vec::new();
+    split_rooms(rng, &mut grid, &mut rooms, area, min_size);
+    (grid, rooms)
+}
+
+/// Build rooms in a BSP node and return a floor cell to connect the node from.
+fn split_rooms<R: Rng + ?Sized>(
+    rng: &mut R,
+    grid: &mut Grid<bool>,
+    rooms: &mut Vec<TypedRect<i32, CellSpace>>,
+    area: TypedRect<i32, CellSpace>,
+    min_size: i32,
+) -> CellVector {
+    let (w, h) = (area.size.width, area.size.height);
+    let can_split_x = w >= 2 * min_size;
+    let can_split_y = h >= 2 * min_size;
+
+    if !can_split_x && !can_split_y {
+        // Leaf, place a room with at least one cell of wall around it.
+        let room_w = rng.gen_range((w - 2) / 2, w - 1);
+        let room_h = rng.gen_range((h - 2) / 2, h - 1);
+        let x = area.min_x() + rng.gen_range(1, w - room_w);
+        let y = area.min_y() + rng.gen_range(1, h - room_h);
+        let room = rect(x, y, room_w, room_h);
+        carve_rect(grid, room);
+        rooms.push(room);
+        return vec2(x + room_w / 2, y + room_h / 2);
+    }
+
+    // Split across the longer axis.
+    let split_x = can_split_x && (!can_split_y || w > h || (w == h && rng.gen()));
+    let (a, b) = if split_x {
+        let cut = rng.gen_range(min_size, w - min_size + 1);
+        (
+            rect(area.min_x(), area.min_y(), cut, h),
+            rect(area.min_x() + cut, area.min_y(), w - cut, h),
+        )
+    } else {
+        let cut = rng.gen_range(min_size, h - min_size + 1);
+        (
+            rect(area.min_x(), area.min_y(), w, cut),
+            rect(area.min_x(), area.min_y() + cut, w, h - cut),
+        )
+    };
+
+    let p1 = split_rooms(rng, grid, rooms, a, min_size);
+    let p2 = split_rooms(rng, grid, rooms, b, min_size);
+    carve_corridor(rng, grid, p1, p2);
+    if rng.gen() {
+        p1
+    } else {
+        p2
+    }
+}
+
+fn carve_rect(grid: &mut Grid<bool>, area: TypedRect<i32, CellSpace>) {
+    for y in area.min_y()..area.max_y() {
+        for x in area.min_x()..area.max_x() {
+            grid[vec2(x, y)] = true;
+        }
+    }
+}
+
+/// Carve a corridor with one bend between two points.
+fn carve_corridor<R: Rng + ?Sized>(
+    rng: &mut R,
+    grid: &mut Grid<bool>,
+    a: CellVector,
+    b: CellVector,
+) {
+    let bend = if rng.gen() {
+        vec2(b.x, a.y)
+    } else {
+        vec2(a.x, b.y)
+    };
+    for &(start, end) in &[(a, bend), (bend, b)] {
+        let (x1, x2) = (start.x.min(end.x), start.x.max(end.x));
+        let (y1, y2) = (start.y.min(end.y), start.y.max(end.y));
+        carve_rect(grid, rect(x1, y1, x2 - x1 + 1, y2 - y1 + 1));
+    }
+}
+
+/// Generate a perfect maze.
+///
+/// The maze has exactly one path between any two of its floor cells. The passages run along the
+/// x and y axes of the cell space and the rooms of the maze are at the odd offsets from the
+/// area origin.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate euclid;
+/// # extern crate calx;
+/// # fn main() {
+/// use euclid::{rect, vec2};
+/// use calx::{mapgen, seeded_rng};
+///
+/// let maze = mapgen::maze(&mut seeded_rng(&1), rect(0, 0, 21, 21));
+/// assert!(maze[vec2(1, 1)]);
+/// assert!(!maze[vec2(2, 2)]);
+/// # }
+/// ```
+pub fn maze<R: Rng + ?Sized>(rng: &mut R, area: TypedRect<i32, CellSpace>) -> Grid<bool> {
+    let mut grid = Grid::new(area, false);
+    let origin = area.origin.to_vector();
+    let (rooms_w, rooms_h) = ((area.size.width - 1) / 2, (area.size.height - 1) / 2);
+    if rooms_w <= 0 || rooms_h <= 0 {
+        return grid;
+    }
+
+    let room = |x: i32, y: i32| origin + vec2(2 * x + 1, 2 * y + 1);
+    let in_maze = |x: i32, y: i32| x >= 0 && y >= 0 && x < rooms_w && y < rooms_h;
+    let start = (rng.gen_range(0, rooms_w), rng.gen_range(0, rooms_h));
+    grid[room(start.0, start.1)] = true;
+
+    // Randomized depth-first search.
+    let mut stack = vec![start];
+    while let Some(&(x, y)) = stack.last() {
+        let mut dirs = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+        rng.shuffle(&mut dirs);
+        let next = dirs
+            .iter()
+            .map(|&(dx, dy)| (x + dx, y + dy))
+            .find(|&(x, y)| in_maze(x, y) && !grid[room(x, y)]);
+
+        match next {
+            Some((nx, ny)) => {
+                grid[room(nx, ny)] = true;
+                // Open the wall between the rooms.
+                grid[origin + vec2(x + nx + 1, y + ny + 1)] = true;
+                stack.push((nx, ny));
+            }
+            None => {
+                stack.pop();
+            }
+        }
+    }
+
+    grid
+}
+
+/// Neighborhood shape for the morphological mask operations.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum StructuringElement {
+    /// Cells within the given hex distance.
+    Hex(i32),
+    /// Cells within the given distance along both axes on a square grid.
+    Square(i32),
+}
+
+impl StructuringElement {
+    /// Return the offsets of the cells in the neighborhood, including the origin.
+    pub fn offsets(self) -> Vec<CellVector> {
+        let r = match self {
+            StructuringElement::Hex(r) | StructuringElement::Square(r) => r.max(0),
+        };
+        let mut ret = Vec::new();
+        for y in -r..=r {
+            for x in -r..=r {
+                let v = vec2(x, y);
+                if let StructuringElement::Hex(_) = self {
+                    if v.hex_dist() > r {
+                        continue;
+                    }
+                }
+                ret.push(v);
+            }
+        }
+        ret
+    }
+}
+
+/// Shrink the `true` areas of a mask.
+///
+/// A cell stays `true` only if every cell of the neighborhood around it is `true`. Cells outside
+/// the grid are ignored, so areas touching the grid edge don't shrink from that side.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate euclid;
+/// # extern crate calx;
+/// # fn main() {
+/// use euclid::{rect, vec2};
+/// use calx::Grid;
+/// use calx::mapgen::{self, StructuringElement};
+///
+/// let mut mask = Grid::new(rect(0, 0, 9, 9), false);
+/// mask[vec2(4, 4)] = true;
+///
+/// // Grow the single cell into a hex of radius 2.
+/// let blob = mapgen::dilate(&mask, StructuringElement::Hex(2));
+/// assert_eq!(blob.iter().filter(|&(_, &b)| b).count(), 19);
+///
+/// // Shrinking it back gives the original cell.
+/// assert_eq!(mapgen::erode(&blob, StructuringElement::Hex(2)), mask);
+///
+/// // Opening removes features smaller than the element.
+/// assert!(mapgen::open(&mask, StructuringElement::Square(1))
+///     .iter()
+///     .all(|(_, &b)| !b));
+/// # }
+/// ```
+pub fn erode(mask: &Grid<bool>, element: StructuringElement) -> Grid<bool> {
+    let offsets = element.offsets();
+    mask.map(|p, &b| b && offsets.iter().all(|&d| mask.get(p + d) != Some(&false)))
+}
+
+/// Grow the `true` areas of a mask.
+///
+/// A cell becomes `true` if any cell of the neighborhood around it is `true`.
+pub fn dilate(mask: &Grid<bool>, element: StructuringElement) -> Grid<bool> {
+    let offsets = element.offsets();
+    mask.map(|p, &b| b || offsets.iter().any(|&d| mask.get(p + d) == Some(&true)))
+}
+
+/// Erode and then dilate a mask.
+///
+/// Removes `true` areas and protrusions too small to hold the structuring element, such as
+/// single floor cells in cellular automaton cave output.
+pub fn open(mask: &Grid<bool>, element: StructuringElement) -> Grid<bool> {
+    dilate(&erode(mask, element), element)
+}
+
+/// Dilate and then erode a mask.
+///
+/// Fills `false` gaps and notches too small to hold the structuring element, such as lone
+/// pillars in a cave.
+pub fn close(mask: &Grid<bool>, element: StructuringElement) -> Grid<bool> {
+    erode(&dilate(mask, element), element)
+}
+
+/// Return the `false` cells that are next to a `true` cell.
+///
+/// With a floor mask, these are the wall cells that face the floor.
+pub fn border(mask: &Grid<bool>, element: StructuringElement) -> Grid<bool> {
+    let grown = dilate(mask, element);
+    grown.map(|p, &b| b && !mask[p])
+}
+
+/// Find which rooms of a map are connected to each other.
+///
+/// Rooms are connected if they touch or if a corridor of floor cells outside the rooms leads from
+/// one to the other. Returns the connected room index pairs with the smaller index first.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate euclid;
+/// # extern crate calx;
+/// # fn main() {
+/// use euclid::rect;
+/// use calx::{mapgen, seeded_rng};
+///
+/// let (map, rooms) = mapgen::rooms(&mut seeded_rng(&1), rect(0, 0, 40, 30), 8);
+/// let graph = mapgen::room_graph(&map, &rooms);
+/// assert!(graph.len() >= rooms.len() - 1);
+/// # }
+/// ```
+pub fn room_graph(map: &Grid<bool>, rooms: &[TypedRect<i32, CellSpace>]) -> Vec<(usize, usize)> {
+    let room_at = |p: CellVector| rooms.iter().position(|r| r.contains(&p.to_point()));
+    let mut edges = Vec::new();
+
+    for (i, room) in rooms.iter().enumerate() {
+        let mut seen = HashSet::new();
+        let mut stack: Vec<CellVector> = Vec::new();
+        for y in room.min_y()..room.max_y() {
+            for x in room.min_x()..room.max_x() {
+                stack.push(vec2(x, y));
+            }
+        }
+        seen.extend(stack.iter().cloned());
+
+        while let Some(p) = stack.pop() {
+            for d in Dir6::iter() {
+                let q = p + d.to_v2();
+                if seen.contains(&q) || map.get(q) != Some(&true) {
+                    continue;
+                }
+                seen.insert(q);
+                match room_at(q) {
+                    Some(j) if j != i => edges.push((i.min(j), i.max(j))),
+                    Some(_) => {}
+                    None => stack.push(q),
+                }
+            }
+        }
+    }
+    edges.sort();
+    edges.dedup();
+    edges
+}
+
+/// Locked doors and key locations for a graph of rooms.
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct LockPlan {
+    /// Number of keys needed to reach each room.
+    pub levels: Vec<usize>,
+    /// Locked doors as room index pairs and the index of the lock.
+    pub doors: Vec<(usize, usize, usize)>,
+    /// Room holding the key of each lock.
+    pub keys: Vec<usize>,
+}
+
+impl LockPlan {
+    /// Return whether every room can be reached from the start room.
+    ///
+    /// The player is assumed to pick up every key in the rooms they can reach.
+    pub fn is_solvable(&self, edges: &[(usize, usize)], start: usize) -> bool {
+        let lock_of = |a: usize, b: usize| {
+            self.doors
+                .iter()
+                .find(|&&(x, y, _)| (x, y) == (a, b) || (x, y) == (b, a))
+                .map(|&(_, _, lock)| lock)
+        };
+        let mut reached = vec![false; self.levels.len()];
+        let mut has_key = vec![false; self.keys.len()];
+        reached[start] = true;
+
+        loop {
+            let mut changed = false;
+            for (k, &room) in self.keys.iter().enumerate() {
+                if reached[room] && !has_key[k] {
+                    has_key[k] = true;
+                    changed = true;
+                }
+            }
+            for &(a, b) in edges {
+                if reached[a] == reached[b] {
+                    continue;
+                }
+                if lock_of(a, b).map_or(true, |lock| has_key[lock]) {
+                    reached[a] = true;
+                    reached[b] = true;
+                    changed = true;
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+        reached.iter().all(|&r| r)
+    }
+}
+
+/// Assign locked doors and keys to a graph of rooms.
+///
+/// The rooms are split into `n_locks + 1` areas grown outwards from the start room, each area
+/// needing one more key to enter than the previous one. Doors leading into an area are locked
+/// with the area's lock and the key is placed in a random room of the previous area, so the
+/// layout is always solvable.
+///
+/// Returns `None` if some rooms can't be reached from the start room or if there are fewer
+/// rooms than areas.
+///
+/// # Examples
+///
+/// ```
+/// use calx::{mapgen, seeded_rng};
+///
+/// // Ring of six rooms with a dead end branch.
+/// let edges = vec![(0, 1), (1, 2), (2, 3), (3, 4), (4, 5), (0, 5), (3, 6)];
+/// let plan = mapgen::locks_and_keys(&mut seeded_rng(&1), 7, &edges, 0, 2).unwrap();
+/// assert_eq!(plan.keys.len(), 2);
+/// assert!(plan.is_solvable(&edges, 0));
+///
+/// // The key of the first lock must be reachable without keys.
+/// assert_eq!(plan.levels[plan.keys[0]], 0);
+/// ```
+pub fn locks_and_keys<R: Rng + ?Sized>(
+    rng: &mut R,
+    n_rooms: usize,
+    edges: &[(usize, usize)],
+    start: usize,
+    n_locks: usize,
+) -> Option<LockPlan> {
+    let mut neighbors = vec![Vec::new(); n_rooms];
+    for &(a, b) in edges {
+        neighbors[a].push(b);
+        neighbors[b].push(a);
+    }
+
+    // Grow outwards from the start in random order, so every room is discovered from a room
+    // earlier in the order.
+    let mut order = vec![start];
+    let mut seen = vec![false; n_rooms];
+    seen[start] = true;
+    let mut frontier: Vec<usize> = neighbors[start].clone();
+    while !frontier.is_empty() {
+        let room = frontier.swap_remove(rng.gen_range(0, frontier.len()));
+        if seen[room] {
+            continue;
+        }
+        seen[room] = true;
+        order.push(room);
+        frontier.extend(neighbors[room].iter().filter(|&&n| !seen[n]));
+    }
+    if order.len() < n_rooms || n_rooms < n_locks + 1 {
+        return None;
+    }
+
+    // Cut the order into consecutive areas.
+    let mut cuts: Vec<usize> = (1..n_rooms).collect();
+    rng.shuffle(&mut cuts);
+    let mut cuts = cuts[..n_locks].to_vec();
+    cuts.sort();
+    let mut levels = vec![0; n_rooms];
+    for (i, &room) in order.iter().enumerate() {
+        levels[room] = cuts.iter().filter(|&&c| c <= i).count();
+    }
+
+    let doors = edges
+        .iter()
+        .filter(|&&(a, b)| levels[a] != levels[b])
+        .map(|&(a, b)| (a, b, levels[a].max(levels[b]) - 1))
+        .collect();
+
+    let keys = (0..n_locks)
+        .map(|k| {
+            let area: Vec<usize> = (0..n_rooms).filter(|&r| levels[r] == k).collect();
+            area[rng.gen_range(0, area.len())]
+        })
+        .collect();
+
+    Some(LockPlan {
+        levels,
+        doors,
+        keys,
+    })
+}
+
+/// Return the cells of the largest hex-connected floor region.
+fn largest_region(grid: &Grid<bool>) -> HashSet<CellVector> {
+    let mut seen = HashSet::new();
+    let mut best = HashSet::new();
+
+    for (p, &floor) in grid.iter() {
+        if !floor || seen.contains(&p) {
+            continue;
+        }
+
+        let mut region = HashSet::new();
+        let mut stack = vec![p];
+        region.insert(p);
+        while let Some(p) = stack.pop() {
+            for d in Dir6::iter() {
+                let q = p + d.to_v2();
+                if grid.get(q) == Some(&true) && region.insert(q) {
+                    stack.push(q);
+                }
+            }
+        }
+
+        seen.extend(region.iter().cloned());
+        if region.len() > best.len() {
+            best = region;
+        }
+    }
+    best
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        border, cave, close, dilate, erode, largest_region, locks_and_keys, maze, open, room_graph,
+        rooms, StructuringElement,
+    };
+    use euclid::{rect, vec2};
+    use grid::Grid;
+    use rand::Rng;
+    use rng::seeded_rng;
+
+    #[test]
+    fn test_generators_connected() {
+        for seed in 0..8 {
+            let mut rng = seeded_rng(&seed);
+            let area = rect(-5, 3, 31, 23);
+
+            for grid in &[
+                cave(&mut rng, area, 0.55, 4),
+                rooms(&mut rng, area, 6).0,
+                maze(&mut rng, area),
+            ] {
+                assert_eq!(grid.bounds(), area);
+                let floor = grid.iter().filter(|&(_, &f)| f).count();
+                assert!(floor > 0);
+                assert_eq!(largest_region(grid).len(), floor);
+                // Edges are walls.
+                assert!(grid
+                    .iter()
+                    .filter(|&(p, _)| p.x == area.min_x() || p.y == area.max_y() - 1)
+                    .all(|(_, &f)| !f));
+            }
+
+            // Perfect maze on 15 x 11 rooms has the rooms and the 164 passages between them.
+            let m = maze(&mut rng, area);
+            assert_eq!(m.iter().filter(|&(_, &f)| f).count(), 15 * 11 * 2 - 1);
+        }
+    }
+
+    #[test]
+    fn test_deterministic() {
+        let area = rect(0, 0, 30, 20);
+        assert_eq!(
+            cave(&mut seeded_rng(&"a"), area, 0.5, 3),
+            cave(&mut seeded_rng(&"a"), area, 0.5, 3)
+        );
+        assert_eq!(
+            rooms(&mut seeded_rng(&"a"), area, 5),
+            rooms(&mut seeded_rng(&"a"), area, 5)
+        );
+    }
+
+    #[test]
+    fn test_morphology() {
+        use self::StructuringElement::*;
+
+        assert_eq!(Hex(1).offsets().len(), 7);
+        assert_eq!(Square(1).offsets().len(), 9);
+        assert_eq!(Square(0).offsets(), vec![vec2(0, 0)]);
+
+        let mut rng = seeded_rng(&"morph");
+        let area = rect(0, 0, 30, 20);
+        for &element in &[Hex(1), Hex(2), Square(1)] {
+            let mask = cave(&mut rng, area, 0.55, 2);
+            let count = |g: &Grid<bool>| g.iter().filter(|&(_, &b)| b).count();
+            let subset = |a: &Grid<bool>, b: &Grid<bool>| a.iter().all(|(p, &x)| !x || b[p]);
+
+            let opened = open(&mask, element);
+            let closed = close(&mask, element);
+            assert!(subset(&erode(&mask, element), &mask));
+            assert!(subset(&mask, &dilate(&mask, element)));
+            assert!(subset(&opened, &mask));
+            assert!(subset(&mask, &closed));
+            // Opening and closing are idempotent.
+            assert_eq!(open(&opened, element), opened);
+            assert_eq!(close(&closed, element), closed);
+
+            let edge = border(&mask, element);
+            assert!(edge.iter().all(|(p, &b)| !(b && mask[p])));
+            assert_eq!(count(&edge) + count(&mask), count(&dilate(&mask, element)));
+        }
+    }
+
+    #[test]
+    fn test_locks_and_keys() {
+        let mut rng = seeded_rng(&"locks");
+        for seed in 0..20 {
+            let (map, room_rects) = rooms(&mut seeded_rng(&seed), rect(0, 0, 60, 40), 6);
+            let n = room_rects.len();
+            let mut edges = room_graph(&map, &room_rects);
+            // Extra random connections to get some loops.
+            for _ in 0..3 {
+                let (a, b) = (rng.gen_range(0, n), rng.gen_range(0, n));
+                if a != b {
+                    edges.push((a.min(b), a.max(b)));
+                }
+            }
+
+            let n_locks = rng.gen_range(0, 5);
+            let start = rng.gen_range(0, n);
+            let mut plan = locks_and_keys(&mut rng, n, &edges, start, n_locks).unwrap();
+            assert!(plan.is_solvable(&edges, start));
+            assert_eq!(plan.levels[start], 0);
+            for lock in 0..n_locks {
+                assert!(plan.doors.iter().any(|&(_, _, l)| l == lock));
+            }
+
+            // Hiding a key behind its own lock makes the map unsolvable.
+            if n_locks > 0 {
+                let k = rng.gen_range(0, n_locks);
+                plan.keys[k] = (0..n).find(|&r| plan.levels[r] == k + 1).unwrap();
+                assert!(!plan.is_solvable(&edges, start));
+            }
+        }
+
+        // Disconnected rooms.
+        assert!(locks_and_keys(&mut rng, 3, &[(0, 1)], 0, 1).is_none());
+        // Too many locks.
+        assert!(locks_and_keys(&mut rng, 2, &[(0, 1)], 0, 2).is_none());
+    }
+}
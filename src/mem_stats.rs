@@ -0,0 +1,113 @@
+//! Memory usage accounting
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::mem;
+
+/// Estimate of the heap memory owned by a value.
+///
+/// Implementations count the allocated capacity of the containers directly owned by the value,
+/// not the size of the value itself. The estimates are for spotting growth over long sessions
+/// and don't need to match the allocator's bookkeeping exactly.
+pub trait HeapSize {
+    /// Return the approximate number of heap bytes owned by the value.
+    fn heap_size(&self) -> usize;
+}
+
+impl<T> HeapSize for Vec<T> {
+    fn heap_size(&self) -> usize { self.capacity() * mem::size_of::<T>() }
+}
+
+impl HeapSize for String {
+    fn heap_size(&self) -> usize { self.capacity() }
+}
+
+impl<K, V, S> HeapSize for HashMap<K, V, S> {
+    fn heap_size(&self) -> usize {
+        // Each bucket holds the entry and one control byte.
+        self.capacity() * (mem::size_of::<(K, V)>() + 1)
+    }
+}
+
+impl<T, S> HeapSize for HashSet<T, S> {
+    fn heap_size(&self) -> usize { self.capacity() * (mem::size_of::<T>() + 1) }
+}
+
+/// Memory usage tallied by category.
+///
+/// Collect the sizes of the large containers of a program into a report to show in a stats
+/// display.
+///
+/// # Examples
+///
+/// ```
+/// use calx::MemoryReport;
+///
+/// let terrain: Vec<u32> = Vec::with_capacity(1024);
+/// let particles: Vec<[f32; 4]> = Vec::with_capacity(256);
+///
+/// let mut report = MemoryReport::new();
+/// report.add("terrain", &terrain);
+/// report.add("particles", &particles);
+/// report.add_bytes("particles", 100);
+///
+/// assert_eq!(report.get("terrain"), 4096);
+/// assert_eq!(report.get("particles"), 4196);
+/// assert_eq!(report.total(), 8292);
+/// assert_eq!(report.to_string(), "particles: 4.1 KiB\nterrain: 4.0 KiB\ntotal: 8.1 KiB");
+/// ```
+#[derive(Clone, Default, Debug)]
+pub struct MemoryReport {
+    categories: BTreeMap<String, usize>,
+}
+
+impl MemoryReport {
+    pub fn new() -> MemoryReport { MemoryReport::default() }
+
+    /// Add the heap size of a value to a category.
+    pub fn add(&mut self, category: &str, item: &impl HeapSize) {
+        self.add_bytes(category, item.heap_size());
+    }
+
+    /// Add a byte count to a category.
+    pub fn add_bytes(&mut self, category: &str, bytes: usize) {
+        *self.categories.entry(category.to_string()).or_insert(0) += bytes;
+    }
+
+    /// Return the bytes counted for a category.
+    pub fn get(&self, category: &str) -> usize {
+        self.categories.get(category).cloned().unwrap_or(0)
+    }
+
+    /// Return the bytes counted for all categories.
+    pub fn total(&self) -> usize { self.categories.values().sum() }
+
+    /// Iterate the categories in alphabetical order with their byte counts.
+    pub fn iter<'a>(&'a self) -> impl Iterator<Item = (&'a str, usize)> + 'a {
+        self.categories.iter().map(|(k, &v)| (&k[..], v))
+    }
+}
+
+impl fmt::Display for MemoryReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fn human(bytes: usize) -> String {
+            const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+            let mut x = bytes as f64;
+            let mut unit = 0;
+            while x >= 1024.0 && unit < UNITS.len() - 1 {
+                x /= 1024.0;
+                unit += 1;
+            }
+            if unit == 0 {
+                format!("{} B", bytes)
+            } else {
+                format!("{:.1} {}", x, UNITS[unit])
+            }
+        }
+
+        for (category, bytes) in self.iter() {
+            writeln!(f, "{}: {}", category, human(bytes))?;
+        }
+        write!(f, "total: {}", human(self.total()))
+    }
+}
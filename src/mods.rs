@@ -0,0 +1,262 @@
+//! Layered game content for mod support
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+/// Game data directories layered over each other.
+///
+/// The base content is the bottom layer and mods are added on top of it. A data file path
+/// relative to the layer root resolves to the file in the topmost layer that has it, so mods can
+/// replace any base content file and add new ones.
+///
+/// # Examples
+///
+/// ```no_run
+/// use calx::{app_data_path, ContentOverlay};
+///
+/// let content = ContentOverlay::new("data")
+///     .discover_mods(&app_data_path("mygame").join("mods"))
+///     .unwrap();
+/// for conflict in content.conflicts().unwrap() {
+///     eprintln!("{:?} is provided by several mods: {:?}", conflict.0, conflict.1);
+/// }
+/// let prefabs = content.read("prefabs/town.txt").unwrap();
+/// ```
+#[derive(Clone, Debug)]
+pub struct ContentOverlay {
+    /// Names and root directories of the layers from bottom to top.
+    layers: Vec<(String, PathBuf)>,
+}
+
+impl ContentOverlay {
+    /// Create an overlay with the base content directory as the only layer.
+    pub fn new(base: impl Into<PathBuf>) -> ContentOverlay {
+        ContentOverlay {
+            layers: vec![("base".to_string(), base.into())],
+        }
+    }
+
+    /// Add a content layer above the existing ones.
+    pub fn layer(mut self, name: &str, root: impl Into<PathBuf>) -> ContentOverlay {
+        self.layers.push((name.to_string(), root.into()));
+        self
+    }
+
+    /// Add every subdirectory of `mods_dir` as a mod layer.
+    ///
+    /// The mods are layered in the alphabetical order of their directory names, later mods
+    /// override earlier ones. Prefix the names with numbers to control the order. A missing mods
+    /// directory counts as having no mods.
+    pub fn discover_mods(mut self, mods_dir: &Path) -> io::Result<ContentOverlay> {
+        let entries = match fs::read_dir(mods_dir) {
+            Ok(entries) => entries,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(self),
+            Err(e) => return Err(e),
+        };
+
+        let mut mods = Vec::new();
+        for entry in entries {
+            let entry = entry?;
+            if entry.file_type()?.is_dir() {
+                let name = entry.file_name().to_string_lossy().into_owned();
+                mods.push((name, entry.path()));
+            }
+        }
+        mods.sort();
+        self.layers.extend(mods);
+        Ok(self)
+    }
+
+    /// Return the names of the layers from bottom to top.
+    pub fn layer_names(&self) -> Vec<&str> { self.layers.iter().map(|(n, _)| &n[..]).collect() }
+
+    /// Return the actual location of a data file.
+    ///
+    /// The path must be relative and stay inside the layer roots, absolute paths and `..`
+    /// components are an error.
+    pub fn resolve(&self, path: impl AsRef<Path>) -> io::Result<PathBuf> {
+        let path = path.as_ref();
+        check_data_path(path)?;
+        self.layers
+            .iter()
+            .rev()
+            .map(|(_, root)| root.join(path))
+            .find(|p| p.is_file())
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("{} not found in any content layer", path.display()),
+                )
+            })
+    }
+
+    /// Read a data file from the topmost layer that has it.
+    pub fn read(&self, path: impl AsRef<Path>) -> io::Result<Vec<u8>> {
+        fs::read(self.resolve(path)?)
+    }
+
+    /// Return every data file path and the names of the layers that provide it, bottom first.
+    pub fn files(&self) -> io::Result<BTreeMap<PathBuf, Vec<String>>> {
+        Ok(self
+            .layer_files()?
+            .into_iter()
+            .map(|(path, layers)| (path, self.names(&layers)))
+            .collect())
+    }
+
+    /// Return the data files provided by more than one mod layer.
+    ///
+    /// Mods replacing base content is the intended use, so only files that two or more mods
+    /// provide count as conflicts. The topmost mod in the list wins.
+    pub fn conflicts(&self) -> io::Result<Vec<(PathBuf, Vec<String>)>> {
+        Ok(self
+            .layer_files()?
+            .into_iter()
+            .map(|(path, mut layers)| {
+                // The base layer is always at index 0, mods can reuse its name.
+                layers.retain(|&i| i != 0);
+                (path, layers)
+            })
+            .filter(|(_, layers)| layers.len() > 1)
+            .map(|(path, layers)| (path, self.names(&layers)))
+            .collect())
+    }
+
+    /// Return every data file path and the indices of the layers that provide it.
+    fn layer_files(&self) -> io::Result<BTreeMap<PathBuf, Vec<usize>>> {
+        let mut ret: BTreeMap<PathBuf, Vec<usize>> = BTreeMap::new();
+        for (i, (_, root)) in self.layers.iter().enumerate() {
+            if !root.is_dir() {
+                continue;
+            }
+            for path in walk(root)? {
+                let rel = path
+                    .strip_prefix(root)
+                    .expect("Walk left root")
+                    .to_path_buf();
+                ret.entry(rel).or_default().push(i);
+            }
+        }
+        Ok(ret)
+    }
+
+    fn names(&self, layers: &[usize]) -> Vec<String> {
+        layers.iter().map(|&i| self.layers[i].0.clone()).collect()
+    }
+}
+
+/// Data file paths are joined to the layer roots, so they can't point outside them.
+fn check_data_path(path: &Path) -> io::Result<()> {
+    let is_inside = path
+        .components()
+        .all(|c| matches!(c, Component::Normal(_) | Component::CurDir));
+    if path.as_os_str().is_empty() || !is_inside {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Invalid data file path '{}'", path.display()),
+        ));
+    }
+    Ok(())
+}
+
+/// List the files under a directory recursively.
+fn walk(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut ret = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            ret.extend(walk(&entry.path())?);
+        } else {
+            ret.push(entry.path());
+        }
+    }
+    Ok(ret)
+}
+
+#[cfg(test)]
+mod test {
+    use super::ContentOverlay;
+    use std::fs;
+    use std::io;
+    use std::path::{Path, PathBuf};
+    use tempdir::TempDir;
+
+    fn write(root: &Path, path: &str, text: &str) {
+        let path = root.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, text).unwrap();
+    }
+
+    #[test]
+    fn test_overlay() {
+        let dir = TempDir::new("calx-mods").unwrap();
+        let base = dir.path().join("data");
+        let mods = dir.path().join("mods");
+        write(&base, "monsters.txt", "base");
+        write(&base, "maps/town.txt", "base");
+        write(&mods.join("2-b"), "monsters.txt", "b");
+        write(&mods.join("1-a"), "monsters.txt", "a");
+        write(&mods.join("1-a"), "maps/town.txt", "a");
+        write(&mods.join("1-a"), "maps/cave.txt", "a");
+
+        let content = ContentOverlay::new(&base).discover_mods(&mods).unwrap();
+        assert_eq!(content.layer_names(), vec!["base", "1-a", "2-b"]);
+        assert_eq!(content.read("monsters.txt").unwrap(), b"b");
+        assert_eq!(content.read("maps/town.txt").unwrap(), b"a");
+        assert_eq!(content.read("maps/cave.txt").unwrap(), b"a");
+        assert!(content.read("items.txt").is_err());
+        assert_eq!(
+            content.resolve("maps/town.txt").unwrap(),
+            mods.join("1-a").join("maps/town.txt")
+        );
+
+        // Paths can't escape the layer roots.
+        write(dir.path(), "secret.txt", "secret");
+        for path in &["../secret.txt", "maps/../../secret.txt", ""] {
+            let e = content.read(path).unwrap_err();
+            assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+        }
+        let absolute = dir.path().join("secret.txt");
+        assert_eq!(
+            content.read(&absolute).unwrap_err().kind(),
+            io::ErrorKind::InvalidInput
+        );
+
+        assert_eq!(content.files().unwrap().len(), 3);
+        assert_eq!(
+            content.conflicts().unwrap(),
+            vec![(
+                PathBuf::from("monsters.txt"),
+                vec!["1-a".to_string(), "2-b".to_string()]
+            )]
+        );
+
+        let no_mods = ContentOverlay::new(&base)
+            .discover_mods(&dir.path().join("nothing"))
+            .unwrap();
+        assert_eq!(no_mods.read("monsters.txt").unwrap(), b"base");
+    }
+
+    #[test]
+    fn test_mod_named_base() {
+        let dir = TempDir::new("calx-mods").unwrap();
+        let base = dir.path().join("data");
+        let mods = dir.path().join("mods");
+        write(&base, "monsters.txt", "base");
+        write(&mods.join("a"), "monsters.txt", "a");
+        write(&mods.join("base"), "monsters.txt", "mod");
+
+        let content = ContentOverlay::new(&base).discover_mods(&mods).unwrap();
+        assert_eq!(content.layer_names(), vec!["base", "a", "base"]);
+        assert_eq!(content.read("monsters.txt").unwrap(), b"mod");
+        assert_eq!(
+            content.conflicts().unwrap(),
+            vec![(
+                PathBuf::from("monsters.txt"),
+                vec!["a".to_string(), "base".to_string()]
+            )]
+        );
+    }
+}
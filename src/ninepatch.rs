@@ -0,0 +1,163 @@
+//! Layout of stretched and tiled images for UI drawing
+
+use euclid::{rect, Rect, SideOffsets2D};
+
+/// Split a nine-patch image into source and destination rectangle pairs for drawing.
+///
+/// The margins cut the source image into corners that are drawn as is, edges that stretch
+/// along one axis and a center that stretches along both, so a single atlas tile can be used
+/// to draw panels and window frames of any size. If the destination is smaller than the
+/// margins, the margins shrink to fit. Pieces with zero area are left out.
+///
+/// Draw each returned source area of the image into the paired destination area.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate euclid;
+/// # extern crate calx;
+/// # fn main() {
+/// use euclid::{rect, SideOffsets2D};
+/// use calx::ninepatch;
+///
+/// // 12x12 frame image with 4 pixel borders stretched into a 100x40 panel.
+/// let pieces = ninepatch(
+///     rect(0.0, 0.0, 12.0, 12.0),
+///     rect(10.0, 10.0, 100.0, 40.0),
+///     SideOffsets2D::new_all_same(4.0),
+/// );
+/// assert_eq!(pieces.len(), 9);
+/// // Top left corner is not stretched.
+/// assert_eq!(pieces[0], (rect(0.0, 0.0, 4.0, 4.0), rect(10.0, 10.0, 4.0, 4.0)));
+/// // Center fills the middle.
+/// assert_eq!(pieces[4], (rect(4.0, 4.0, 4.0, 4.0), rect(14.0, 14.0, 92.0, 32.0)));
+/// # }
+/// ```
+pub fn ninepatch(
+    src: Rect<f32>,
+    dst: Rect<f32>,
+    margins: SideOffsets2D<f32>,
+) -> Vec<(Rect<f32>, Rect<f32>)> {
+    fn stops(start: f32, size: f32, head: f32, tail: f32) -> [f32; 4] {
+        let shrink = if head + tail > size {
+            size / (head + tail)
+        } else {
+            1.0
+        };
+        [
+            start,
+            start + head * shrink,
+            start + size - tail * shrink,
+            start + size,
+        ]
+    }
+
+    let sx = stops(src.origin.x, src.size.width, margins.left, margins.right);
+    let sy = stops(src.origin.y, src.size.height, margins.top, margins.bottom);
+    let dx = stops(dst.origin.x, dst.size.width, margins.left, margins.right);
+    let dy = stops(dst.origin.y, dst.size.height, margins.top, margins.bottom);
+
+    let span = |s: &[f32; 4], i: usize| (s[i], s[i + 1] - s[i]);
+    let mut ret = Vec::with_capacity(9);
+    for row in 0..3 {
+        for col in 0..3 {
+            let ((sx0, sw), (sy0, sh)) = (span(&sx, col), span(&sy, row));
+            let ((dx0, dw), (dy0, dh)) = (span(&dx, col), span(&dy, row));
+            if sw > 0.0 && sh > 0.0 && dw > 0.0 && dh > 0.0 {
+                ret.push((rect(sx0, sy0, sw, sh), rect(dx0, dy0, dw, dh)));
+            }
+        }
+    }
+    ret
+}
+
+/// Split an area into source and destination rectangle pairs for tiling an image over it.
+///
+/// The image is repeated at its original size starting from the top left corner of the
+/// destination. Tiles at the right and bottom edges are cropped to fit.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate euclid;
+/// # extern crate calx;
+/// # fn main() {
+/// use euclid::rect;
+/// use calx::tile_rect;
+///
+/// let tiles = tile_rect(rect(0.0, 0.0, 8.0, 8.0), rect(0.0, 0.0, 20.0, 8.0));
+/// assert_eq!(
+///     tiles,
+///     vec![
+///         (rect(0.0, 0.0, 8.0, 8.0), rect(0.0, 0.0, 8.0, 8.0)),
+///         (rect(0.0, 0.0, 8.0, 8.0), rect(8.0, 0.0, 8.0, 8.0)),
+///         (rect(0.0, 0.0, 4.0, 8.0), rect(16.0, 0.0, 4.0, 8.0)),
+///     ]
+/// );
+/// # }
+/// ```
+pub fn tile_rect(src: Rect<f32>, dst: Rect<f32>) -> Vec<(Rect<f32>, Rect<f32>)> {
+    let mut ret = Vec::new();
+    if src.size.width <= 0.0 || src.size.height <= 0.0 {
+        return ret;
+    }
+
+    let mut y = dst.origin.y;
+    while y < dst.max_y() {
+        let h = src.size.height.min(dst.max_y() - y);
+        let mut x = dst.origin.x;
+        while x < dst.max_x() {
+            let w = src.size.width.min(dst.max_x() - x);
+            ret.push((rect(src.origin.x, src.origin.y, w, h), rect(x, y, w, h)));
+            x += src.size.width;
+        }
+        y += src.size.height;
+    }
+    ret
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ninepatch, tile_rect};
+    use euclid::{rect, SideOffsets2D};
+
+    #[test]
+    fn test_ninepatch() {
+        let src = rect(16.0, 0.0, 16.0, 16.0);
+        let margins = SideOffsets2D::new(2.0, 4.0, 6.0, 8.0);
+
+        let pieces = ninepatch(src, rect(0.0, 0.0, 40.0, 30.0), margins);
+        assert_eq!(pieces.len(), 9);
+        let area: f32 = pieces
+            .iter()
+            .map(|(_, d)| d.size.width * d.size.height)
+            .sum();
+        assert_eq!(area, 40.0 * 30.0);
+        // Bottom right corner.
+        assert_eq!(
+            pieces[8],
+            (rect(28.0, 10.0, 4.0, 6.0), rect(36.0, 24.0, 4.0, 6.0))
+        );
+
+        // Too small destination squeezes out the center.
+        let pieces = ninepatch(src, rect(0.0, 0.0, 6.0, 8.0), margins);
+        assert_eq!(pieces.len(), 4);
+        assert_eq!(pieces[0].1, rect(0.0, 0.0, 4.0, 2.0));
+
+        // No margins is plain stretching.
+        let pieces = ninepatch(src, rect(0.0, 0.0, 5.0, 5.0), SideOffsets2D::zero());
+        assert_eq!(pieces, vec![(src, rect(0.0, 0.0, 5.0, 5.0))]);
+    }
+
+    #[test]
+    fn test_tile_rect() {
+        let tiles = tile_rect(rect(4.0, 4.0, 4.0, 4.0), rect(1.0, 1.0, 10.0, 6.0));
+        assert_eq!(tiles.len(), 6);
+        assert_eq!(
+            tiles[5],
+            (rect(4.0, 4.0, 2.0, 2.0), rect(9.0, 5.0, 2.0, 2.0))
+        );
+        assert!(tile_rect(rect(0.0, 0.0, 0.0, 4.0), rect(0.0, 0.0, 10.0, 10.0)).is_empty());
+        assert!(tile_rect(rect(0.0, 0.0, 4.0, 4.0), rect(0.0, 0.0, 0.0, 10.0)).is_empty());
+    }
+}
@@ -0,0 +1,179 @@
+//! Coherent gradient noise
+//!
+//! The noise is computed with plain floating point arithmetic and a permutation table built
+//! with integer operations, so the same seed gives the same values on every platform.
+
+/// Seeded Perlin gradient noise in two and three dimensions.
+///
+/// Noise values are in [-1, 1] and are zero at the integer lattice points. Features of the
+/// noise are about one unit wide, scale the input coordinates to change the feature size.
+///
+/// # Examples
+///
+/// ```
+/// use calx::noise::Perlin;
+///
+/// let noise = Perlin::new(1234);
+/// let height = noise.fbm2(12.3, 4.56, 4, 0.5);
+/// assert!(height >= -1.0 && height <= 1.0);
+/// assert_eq!(noise.noise2(3.0, 4.0), 0.0);
+/// assert_eq!(height, Perlin::new(1234).fbm2(12.3, 4.56, 4, 0.5));
+/// ```
+#[derive(Clone)]
+pub struct Perlin {
+    perm: [u8; 512],
+}
+
+impl Perlin {
+    /// Create a noise generator with a permutation table shuffled by the seed.
+    pub fn new(seed: u64) -> Perlin {
+        // SplitMix64, fully specified here to keep the table stable.
+        let mut state = seed;
+        let mut next = || {
+            state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+            let mut z = state;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+            z ^ (z >> 31)
+        };
+
+        let mut table = [0u8; 256];
+        for (i, x) in table.iter_mut().enumerate() {
+            *x = i as u8;
+        }
+        for i in (1..256).rev() {
+            let j = (next() % (i as u64 + 1)) as usize;
+            table.swap(i, j);
+        }
+
+        let mut perm = [0u8; 512];
+        for i in 0..512 {
+            perm[i] = table[i % 256];
+        }
+        Perlin { perm }
+    }
+
+    fn hash(&self, i: i64) -> usize { self.perm[(i & 0xff) as usize] as usize }
+
+    /// Sample two-dimensional noise.
+    pub fn noise2(&self, x: f64, y: f64) -> f64 {
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let (ix, iy) = (x0 as i64, y0 as i64);
+
+        let grad = |dx: i64, dy: i64, fx: f64, fy: f64| {
+            let h = self.hash(ix + dx + self.hash(iy + dy) as i64);
+            // Diagonal gradients keep the value range at [-1, 1].
+            let gx = if h & 1 == 0 { fx } else { -fx };
+            let gy = if h & 2 == 0 { fy } else { -fy };
+            gx + gy
+        };
+
+        let (u, v) = (fade(fx), fade(fy));
+        let a = lerp(grad(0, 0, fx, fy), grad(1, 0, fx - 1.0, fy), u);
+        let b = lerp(grad(0, 1, fx, fy - 1.0), grad(1, 1, fx - 1.0, fy - 1.0), u);
+        lerp(a, b, v).clamp(-1.0, 1.0)
+    }
+
+    /// Sample three-dimensional noise.
+    pub fn noise3(&self, x: f64, y: f64, z: f64) -> f64 {
+        let (x0, y0, z0) = (x.floor(), y.floor(), z.floor());
+        let (fx, fy, fz) = (x - x0, y - y0, z - z0);
+        let (ix, iy, iz) = (x0 as i64, y0 as i64, z0 as i64);
+
+        let grad = |dx: i64, dy: i64, dz: i64| {
+            let h = self.hash(ix + dx + self.hash(iy + dy + self.hash(iz + dz) as i64) as i64);
+            let (x, y, z) = (fx - dx as f64, fy - dy as f64, fz - dz as f64);
+            // The 12 cube edge directions, with 4 repeated to fill 16 slots.
+            match h & 15 {
+                0 | 12 => x + y,
+                1 | 13 => -x + y,
+                2 => x - y,
+                3 => -x - y,
+                4 => x + z,
+                5 => -x + z,
+                6 => x - z,
+                7 => -x - z,
+                8 => y + z,
+                9 | 14 => -y + z,
+                10 => y - z,
+                _ => -y - z,
+            }
+        };
+
+        let (u, v, w) = (fade(fx), fade(fy), fade(fz));
+        let x00 = lerp(grad(0, 0, 0), grad(1, 0, 0), u);
+        let x10 = lerp(grad(0, 1, 0), grad(1, 1, 0), u);
+        let x01 = lerp(grad(0, 0, 1), grad(1, 0, 1), u);
+        let x11 = lerp(grad(0, 1, 1), grad(1, 1, 1), u);
+        let value = lerp(lerp(x00, x10, v), lerp(x01, x11, v), w);
+
+        // Scale the theoretical maximum of sqrt(3/2) to 1.
+        (value * 0.816_496_580_927_726).clamp(-1.0, 1.0)
+    }
+
+    /// Sample fractal Brownian motion from stacked octaves of two-dimensional noise.
+    ///
+    /// Each octave has double the frequency of the previous one and its amplitude multiplied
+    /// by `persistence`. The sum is normalized back into [-1, 1].
+    pub fn fbm2(&self, x: f64, y: f64, octaves: u32, persistence: f64) -> f64 {
+        self.fbm(octaves, persistence, |f| self.noise2(x * f, y * f))
+    }
+
+    /// Sample fractal Brownian motion from stacked octaves of three-dimensional noise.
+    pub fn fbm3(&self, x: f64, y: f64, z: f64, octaves: u32, persistence: f64) -> f64 {
+        self.fbm(octaves, persistence, |f| self.noise3(x * f, y * f, z * f))
+    }
+
+    fn fbm<F: Fn(f64) -> f64>(&self, octaves: u32, persistence: f64, sample: F) -> f64 {
+        let (mut sum, mut total_amplitude) = (0.0, 0.0);
+        let (mut frequency, mut amplitude) = (1.0, 1.0);
+        for _ in 0..octaves {
+            sum += sample(frequency) * amplitude;
+            total_amplitude += amplitude;
+            frequency *= 2.0;
+            amplitude *= persistence;
+        }
+        if total_amplitude > 0.0 {
+            sum / total_amplitude
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Quintic smoothing curve with zero first and second derivatives at 0 and 1.
+fn fade(t: f64) -> f64 { t * t * t * (t * (t * 6.0 - 15.0) + 10.0) }
+
+fn lerp(a: f64, b: f64, t: f64) -> f64 { a + (b - a) * t }
+
+#[cfg(test)]
+mod test {
+    use super::Perlin;
+
+    #[test]
+    fn test_noise_range() {
+        let noise = Perlin::new(7);
+        let mut max = 0.0f64;
+        for i in 0..2000 {
+            let (x, y, z) = (i as f64 * 0.173, i as f64 * 0.311 - 50.0, i as f64 * 0.057);
+            for &v in &[
+                noise.noise2(x, y),
+                noise.noise3(x, y, z),
+                noise.fbm3(x, y, z, 5, 0.5),
+            ] {
+                assert!((-1.0..=1.0).contains(&v));
+                max = max.max(v.abs());
+            }
+
+            // Continuity.
+            assert!((noise.noise2(x, y) - noise.noise2(x + 0.001, y)).abs() < 0.01);
+            assert!((noise.noise3(x, y, z) - noise.noise3(x, y, z + 0.001)).abs() < 0.01);
+        }
+        // Not degenerate.
+        assert!(max > 0.5);
+
+        // Different seeds give different noise.
+        assert!(noise.noise2(0.5, 0.5) != Perlin::new(8).noise2(0.5, 0.5));
+    }
+}
@@ -0,0 +1,344 @@
+use alg_misc::bounding_rect;
+use euclid::{point2, vec2, TypedPoint2D, TypedRect};
+use grid::Grid;
+use mem_stats::HeapSize;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::hash::Hash;
+use std::iter::FromIterator;
+use CellSpace;
+use CellVector;
+
+/// Compact storage for a cell field.
+///
+/// The cells are stored as a palette of the distinct values in the field and a run-length
+/// encoded byte string of palette indices over the bounding rectangle of the field. Maps with
+/// large uniform areas pack into a small fraction of the size of a plain list of cells, which is
+/// useful for keeping serialized save files small.
+///
+/// Sparse fields are packed from a `HashMap` with `new` and dense ones from a `Grid` with `From`.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate euclid;
+/// # extern crate calx;
+/// # fn main() {
+/// use std::collections::HashMap;
+/// use euclid::vec2;
+/// use calx::{CellVector, PackedField};
+///
+/// let mut map = HashMap::new();
+/// for y in 0..64 {
+///     for x in 0..64 {
+///         map.insert(vec2(x, y), if x == 0 || y == 0 { '#' } else { '.' });
+///     }
+/// }
+///
+/// let packed: PackedField<char> = map.clone().into_iter().collect();
+/// assert!(packed.encoded_len() < 400);
+///
+/// let unpacked: HashMap<CellVector, char> = packed.iter().collect();
+/// assert_eq!(map, unpacked);
+/// # }
+/// ```
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(try_from = "PackedFieldData<T>")]
+pub struct PackedField<T> {
+    origin: [i32; 2],
+    width: u32,
+    height: u32,
+    palette: Vec<T>,
+    /// Pairs of varint encoded run lengths and palette indices.
+    ///
+    /// Palette index 0 marks empty cells, other indices are offset by one.
+    runs: Vec<u8>,
+}
+
+/// Serialized fields of a `PackedField`, validated before use.
+#[derive(Deserialize)]
+struct PackedFieldData<T> {
+    origin: [i32; 2],
+    width: u32,
+    height: u32,
+    palette: Vec<T>,
+    runs: Vec<u8>,
+}
+
+impl<T> TryFrom<PackedFieldData<T>> for PackedField<T> {
+    type Error = &'static str;
+
+    fn try_from(data: PackedFieldData<T>) -> Result<PackedField<T>, &'static str> {
+        let fits = |origin: i32, size: u32| i64::from(origin) + i64::from(size) <= 1 << 31;
+        if !fits(data.origin[0], data.width) || !fits(data.origin[1], data.height) {
+            return Err("Packed field extends outside the cell coordinate range");
+        }
+
+        let area = u64::from(data.width) * u64::from(data.height);
+        let mut total = 0u64;
+        let mut bytes = data.runs.iter().cloned().peekable();
+        while bytes.peek().is_some() {
+            let (len, idx) = match (read_varint(&mut bytes), read_varint(&mut bytes)) {
+                (Some(len), Some(idx)) => (len, idx),
+                _ => return Err("Malformed packed field run"),
+            };
+            if idx as usize > data.palette.len() {
+                return Err("Packed field palette index out of range");
+            }
+            total += u64::from(len);
+            if total > area {
+                return Err("Packed field runs overflow the field area");
+            }
+        }
+
+        Ok(PackedField {
+            origin: data.origin,
+            width: data.width,
+            height: data.height,
+            palette: data.palette,
+            runs: data.runs,
+        })
+    }
+}
+
+impl<T: Clone + Eq + Hash> PackedField<T> {
+    /// Pack a cell field.
+    ///
+    /// Only the cells in the field are visited, the empty space between them is encoded as
+    /// runs, so sparse fields with a large bounding rectangle pack quickly.
+    pub fn new(field: &HashMap<CellVector, T>) -> PackedField<T> {
+        let points: Vec<TypedPoint2D<i32, CellSpace>> =
+            field.keys().map(|p| p.to_point()).collect();
+        let mut cells: Vec<(CellVector, &T)> = field.iter().map(|(&p, t)| (p, t)).collect();
+        cells.sort_by_key(|&(p, _)| (p.y, p.x));
+        PackedField::pack(bounding_rect(&points), cells)
+    }
+
+    /// Pack cells in row-major order inside the bounds.
+    fn pack<'a, I>(bounds: TypedRect<i32, CellSpace>, cells: I) -> PackedField<T>
+    where
+        I: IntoIterator<Item = (CellVector, &'a T)>,
+        T: 'a,
+    {
+        let width = bounds.size.width as u64;
+        let mut palette = Vec::new();
+        let mut palette_index = HashMap::new();
+        let mut runs = Vec::new();
+        // Length and palette index of the run being built.
+        let mut current = (0u64, 0u32);
+        let mut pos = 0u64;
+
+        for (p, value) in cells {
+            let i = (p.y - bounds.origin.y) as u64 * width + (p.x - bounds.origin.x) as u64;
+            let idx = *palette_index.entry(value).or_insert_with(|| {
+                palette.push(value.clone());
+                palette.len() as u32
+            });
+
+            for &(len, run_idx) in &[(i - pos, 0), (1, idx)] {
+                if len == 0 {
+                    continue;
+                }
+                if current.1 == run_idx {
+                    current.0 += len;
+                } else {
+                    push_run(&mut runs, current.0, current.1);
+                    current = (len, run_idx);
+                }
+            }
+            pos = i + 1;
+        }
+        push_run(&mut runs, current.0, current.1);
+
+        PackedField {
+            origin: [bounds.origin.x, bounds.origin.y],
+            width: bounds.size.width as u32,
+            height: bounds.size.height as u32,
+            palette,
+            runs,
+        }
+    }
+}
+
+impl<T> PackedField<T> {
+    /// Return the distinct values in the field.
+    pub fn palette(&self) -> &[T] { &self.palette }
+
+    /// Return the size of the run-length encoded cell data in bytes.
+    pub fn encoded_len(&self) -> usize { self.runs.len() }
+
+    /// Iterate the non-empty cells of the field.
+    pub fn iter<'a>(&'a self) -> impl Iterator<Item = (CellVector, T)> + 'a
+    where
+        T: Clone,
+    {
+        let origin = point2::<i32, CellSpace>(self.origin[0], self.origin[1]);
+        let width = u64::from(self.width.max(1));
+        let mut bytes = self.runs.iter().cloned();
+
+        let runs = ::std::iter::from_fn(move || {
+            let len = read_varint(&mut bytes)?;
+            let idx = read_varint(&mut bytes)?;
+            Some((len, idx))
+        });
+
+        runs.scan(0u64, |pos, (len, idx)| {
+            let start = *pos;
+            *pos += u64::from(len);
+            Some((start, len, idx))
+        })
+        .filter(|&(_, _, idx)| idx != 0)
+        .flat_map(move |(start, len, idx)| {
+            (start..start + u64::from(len)).map(move |i| {
+                let p = origin + vec2((i % width) as i32, (i / width) as i32);
+                (p.to_vector(), self.palette[idx as usize - 1].clone())
+            })
+        })
+    }
+}
+
+impl<T: Clone + Eq + Hash> FromIterator<(CellVector, T)> for PackedField<T> {
+    fn from_iter<I: IntoIterator<Item = (CellVector, T)>>(iter: I) -> Self {
+        let field: HashMap<CellVector, T> = iter.into_iter().collect();
+        PackedField::new(&field)
+    }
+}
+
+impl<'a, T: Clone + Eq + Hash> From<&'a Grid<T>> for PackedField<T> {
+    fn from(grid: &'a Grid<T>) -> Self { PackedField::pack(grid.bounds(), grid.iter()) }
+}
+
+impl<T> HeapSize for PackedField<T> {
+    fn heap_size(&self) -> usize { self.palette.heap_size() + self.runs.heap_size() }
+}
+
+/// Append an unsigned LEB128 encoded integer.
+fn push_varint(buf: &mut Vec<u8>, mut x: u32) {
+    while x >= 0x80 {
+        buf.push((x as u8) | 0x80);
+        x >>= 7;
+    }
+    buf.push(x as u8);
+}
+
+/// Append a run of cells, split into several runs if it's too long for a varint.
+fn push_run(buf: &mut Vec<u8>, mut len: u64, idx: u32) {
+    while len > 0 {
+        let n = len.min(u64::from(u32::MAX)) as u32;
+        push_varint(buf, n);
+        push_varint(buf, idx);
+        len -= u64::from(n);
+    }
+}
+
+/// Read an unsigned LEB128 encoded integer.
+///
+/// Returns `None` if the data ends or the value doesn't fit in 32 bits.
+fn read_varint<I: Iterator<Item = u8>>(bytes: &mut I) -> Option<u32> {
+    let mut ret = 0;
+    let mut shift = 0;
+    loop {
+        let b = bytes.next()?;
+        if shift >= 32 || (shift == 28 && b & 0x7f > 0xf) {
+            return None;
+        }
+        ret |= ((b & 0x7f) as u32) << shift;
+        if b & 0x80 == 0 {
+            return Some(ret);
+        }
+        shift += 7;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{push_varint, read_varint, PackedField};
+    use euclid::{rect, vec2};
+    use grid::Grid;
+    use serde_json;
+    use std::collections::HashMap;
+    use CellVector;
+
+    #[test]
+    fn test_varint() {
+        for &x in &[0, 1, 127, 128, 300, 16383, 16384, u32::MAX] {
+            let mut buf = Vec::new();
+            push_varint(&mut buf, x);
+            assert_eq!(Some(x), read_varint(&mut buf.into_iter()));
+        }
+        assert_eq!(None, read_varint(&mut vec![0x80].into_iter()));
+        assert_eq!(None, read_varint(&mut vec![0xff; 5].into_iter()));
+        let too_long = vec![0x80, 0x80, 0x80, 0x80, 0x80, 0x01];
+        assert_eq!(None, read_varint(&mut too_long.into_iter()));
+    }
+
+    #[test]
+    fn test_sparse_field() {
+        let mut map: HashMap<CellVector, u8> = HashMap::new();
+        map.insert(vec2(-5, 3), 1);
+        map.insert(vec2(7, -2), 2);
+        map.insert(vec2(0, 0), 1);
+
+        let packed = PackedField::new(&map);
+        assert_eq!(packed.palette().len(), 2);
+        let unpacked: HashMap<CellVector, u8> = packed.iter().collect();
+        assert_eq!(map, unpacked);
+
+        let empty: PackedField<u8> = PackedField::new(&HashMap::new());
+        assert_eq!(0, empty.iter().count());
+
+        let saved = serde_json::to_string(&packed).unwrap();
+        let loaded: PackedField<u8> = serde_json::from_str(&saved).unwrap();
+        assert_eq!(loaded, packed);
+
+        // Far apart cells don't make packing walk the whole bounding rectangle.
+        let mut map: HashMap<CellVector, u8> = HashMap::new();
+        map.insert(vec2(-100_000, -100_000), 1);
+        map.insert(vec2(100_000, 100_000), 2);
+        let packed = PackedField::new(&map);
+        let unpacked: HashMap<CellVector, u8> = packed.iter().collect();
+        assert_eq!(map, unpacked);
+        let saved = serde_json::to_string(&packed).unwrap();
+        assert!(serde_json::from_str::<PackedField<u8>>(&saved).is_ok());
+    }
+
+    #[test]
+    fn test_pack_grid() {
+        let grid = Grid::from_fn(rect(-3, 2, 10, 6), |p| (p.x / 4 + p.y) as u8);
+        let packed = PackedField::from(&grid);
+        assert_eq!(packed.palette().len(), 7);
+        assert!(packed
+            .iter()
+            .zip(grid.iter())
+            .all(|((p1, a), (p2, &b))| p1 == p2 && a == b));
+        assert_eq!(packed.iter().count(), grid.len());
+
+        let map: HashMap<CellVector, u8> = grid.iter().map(|(p, &x)| (p, x)).collect();
+        assert_eq!(packed, PackedField::new(&map));
+    }
+
+    #[test]
+    fn test_corrupt_field() {
+        let load = |json: &str| serde_json::from_str::<PackedField<u8>>(json);
+        let field = |runs: &str| {
+            format!(
+                r#"{{ "origin": [0, 0], "width": 4, "height": 4, "palette": [1], "runs": {} }}"#,
+                runs
+            )
+        };
+        assert!(load(&field("[16, 1]")).is_ok());
+        assert!(load(&field("[10, 0, 6, 1]")).is_ok());
+        // Palette index past the palette.
+        assert!(load(&field("[16, 2]")).is_err());
+        // Runs longer than the field.
+        assert!(load(&field("[16, 1, 1, 0]")).is_err());
+        // Truncated and oversized varints.
+        assert!(load(&field("[16]")).is_err());
+        assert!(load(&field("[255, 255, 255, 255, 255, 1, 1]")).is_err());
+
+        // Cells past the largest coordinate.
+        let far =
+            r#"{"origin": [2147483647, 0], "width": 4, "height": 1, "palette": [], "runs": []}"#;
+        assert!(load(far).is_err());
+    }
+}
@@ -0,0 +1,224 @@
+use mem_stats::HeapSize;
+use std::ops::{Index, IndexMut};
+
+/// Stable handle to a value stored in a `Pool`.
+///
+/// The key remembers the generation of the slot it was issued for, so a key to a removed value
+/// will not access a different value that later reuses the same slot.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Ord, PartialOrd, Debug, Serialize, Deserialize)]
+pub struct PoolKey {
+    index: u32,
+    generation: u32,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct Slot<T> {
+    generation: u32,
+    value: Option<T>,
+}
+
+/// Object pool with generational keys.
+///
+/// Values are stored in a flat vector and addressed with `PoolKey` handles that stay valid until
+/// the value is removed. Slots of removed values are reused for new ones.
+///
+/// # Examples
+///
+/// ```
+/// use calx::Pool;
+///
+/// let mut pool = Pool::new();
+/// let a = pool.insert("a");
+/// let b = pool.insert("b");
+/// assert_eq!(pool[a], "a");
+///
+/// assert_eq!(pool.remove(a), Some("a"));
+/// let c = pool.insert("c");
+/// assert_eq!(pool.get(a), None);
+/// assert_eq!(pool.get(c), Some(&"c"));
+///
+/// let mut values: Vec<&str> = pool.iter().map(|(_, &v)| v).collect();
+/// values.sort();
+/// assert_eq!(values, vec!["b", "c"]);
+/// # let _ = b;
+/// ```
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Pool<T> {
+    slots: Vec<Slot<T>>,
+    free: Vec<u32>,
+    len: usize,
+}
+
+impl<T> Pool<T> {
+    /// Create an empty pool.
+    pub fn new() -> Pool<T> {
+        Pool {
+            slots: Vec::new(),
+            free: Vec::new(),
+            len: 0,
+        }
+    }
+
+    /// Store a value in the pool and return the key for it.
+    pub fn insert(&mut self, value: T) -> PoolKey {
+        self.len += 1;
+        if let Some(index) = self.free.pop() {
+            let slot = &mut self.slots[index as usize];
+            debug_assert!(slot.value.is_none());
+            slot.value = Some(value);
+            PoolKey {
+                index,
+                generation: slot.generation,
+            }
+        } else {
+            self.slots.push(Slot {
+                generation: 0,
+                value: Some(value),
+            });
+            PoolKey {
+                index: self.slots.len() as u32 - 1,
+                generation: 0,
+            }
+        }
+    }
+
+    /// Remove a value from the pool.
+    ///
+    /// Returns `None` if the key is no longer valid.
+    pub fn remove(&mut self, key: PoolKey) -> Option<T> {
+        let slot = self.slots.get_mut(key.index as usize)?;
+        if slot.generation != key.generation {
+            return None;
+        }
+        let ret = slot.value.take()?;
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free.push(key.index);
+        self.len -= 1;
+        Some(ret)
+    }
+
+    /// Return the value for a key if the key is still valid.
+    pub fn get(&self, key: PoolKey) -> Option<&T> {
+        match self.slots.get(key.index as usize) {
+            Some(slot) if slot.generation == key.generation => slot.value.as_ref(),
+            _ => None,
+        }
+    }
+
+    /// Return a mutable reference to the value for a key if the key is still valid.
+    pub fn get_mut(&mut self, key: PoolKey) -> Option<&mut T> {
+        match self.slots.get_mut(key.index as usize) {
+            Some(slot) if slot.generation == key.generation => slot.value.as_mut(),
+            _ => None,
+        }
+    }
+
+    /// Return whether the key points to a value in the pool.
+    pub fn contains(&self, key: PoolKey) -> bool { self.get(key).is_some() }
+
+    /// Return the number of values in the pool.
+    pub fn len(&self) -> usize { self.len }
+
+    /// Return whether the pool is empty.
+    pub fn is_empty(&self) -> bool { self.len == 0 }
+
+    /// Remove all values from the pool.
+    ///
+    /// Keys issued before clearing will not be valid afterwards.
+    pub fn clear(&mut self) {
+        let keys: Vec<PoolKey> = self.keys().collect();
+        for k in keys {
+            self.remove(k);
+        }
+    }
+
+    /// Iterate the keys of the values in the pool.
+    pub fn keys<'a>(&'a self) -> impl Iterator<Item = PoolKey> + 'a {
+        self.iter().map(|(k, _)| k)
+    }
+
+    /// Iterate the keys and values in the pool.
+    pub fn iter<'a>(&'a self) -> impl Iterator<Item = (PoolKey, &'a T)> + 'a {
+        self.slots.iter().enumerate().filter_map(|(i, slot)| {
+            slot.value.as_ref().map(|v| {
+                (
+                    PoolKey {
+                        index: i as u32,
+                        generation: slot.generation,
+                    },
+                    v,
+                )
+            })
+        })
+    }
+
+    /// Iterate the keys and mutable values in the pool.
+    pub fn iter_mut<'a>(&'a mut self) -> impl Iterator<Item = (PoolKey, &'a mut T)> + 'a {
+        self.slots.iter_mut().enumerate().filter_map(|(i, slot)| {
+            let generation = slot.generation;
+            slot.value.as_mut().map(|v| {
+                (
+                    PoolKey {
+                        index: i as u32,
+                        generation,
+                    },
+                    v,
+                )
+            })
+        })
+    }
+}
+
+impl<T> Default for Pool<T> {
+    fn default() -> Self { Pool::new() }
+}
+
+impl<T> Index<PoolKey> for Pool<T> {
+    type Output = T;
+
+    fn index(&self, key: PoolKey) -> &T { self.get(key).expect("Invalid pool key") }
+}
+
+impl<T> IndexMut<PoolKey> for Pool<T> {
+    fn index_mut(&mut self, key: PoolKey) -> &mut T { self.get_mut(key).expect("Invalid pool key") }
+}
+
+impl<T> HeapSize for Pool<T> {
+    fn heap_size(&self) -> usize { self.slots.heap_size() + self.free.heap_size() }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Pool;
+    use serde_json;
+
+    #[test]
+    fn test_pool() {
+        let mut pool = Pool::new();
+        let keys: Vec<_> = (0..10).map(|i| pool.insert(i)).collect();
+        for &k in &keys[..5] {
+            pool.remove(k);
+        }
+        assert_eq!(pool.len(), 5);
+        assert_eq!(pool.remove(keys[0]), None);
+
+        for (_, v) in pool.iter_mut() {
+            *v *= 10;
+        }
+        assert_eq!(pool[keys[7]], 70);
+
+        let saved = serde_json::to_string(&pool).unwrap();
+        let mut pool: Pool<i32> = serde_json::from_str(&saved).unwrap();
+        assert_eq!(pool.get(keys[9]), Some(&90));
+
+        // Reuse freed slots without reviving stale keys.
+        let new_key = pool.insert(100);
+        assert_eq!(pool.len(), 6);
+        assert!(keys.iter().all(|&k| pool.get(k) != Some(&100)));
+        assert!(pool.contains(new_key));
+
+        pool.clear();
+        assert!(pool.is_empty());
+        assert!(!pool.contains(new_key));
+    }
+}
@@ -0,0 +1,310 @@
+//! Hierarchical scope timing
+
+use rand::{self, Rng};
+use serde_json;
+use stats::{percentile, RunningStats};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{self, Write};
+use std::marker::PhantomData;
+use time;
+
+/// Number of durations kept for each scope for estimating percentiles.
+const SAMPLE_SIZE: usize = 1024;
+
+/// Collected timings of nested program scopes.
+///
+/// Scopes are identified by their path, the names of the enclosing scopes and the scope itself
+/// separated by slashes. Timings accumulate until `reset` is called, so a game can reset the
+/// profiler every frame to look at per-frame timings or let it run to get long term averages.
+/// Each scope keeps running statistics and a fixed size random sample of its durations for the
+/// percentiles, so a long-running profiler doesn't keep growing. Trace events for every scope
+/// are only recorded while tracing is enabled with `set_tracing`.
+///
+/// Usually the profiler is used through the thread-local instance with `ProfileScope` guards and
+/// `with_profiler`.
+///
+/// # Examples
+///
+/// ```
+/// use calx::Profiler;
+///
+/// let mut profiler = Profiler::new();
+/// for _ in 0..3 {
+///     profiler.begin("frame");
+///     profiler.begin("physics");
+///     profiler.end();
+///     profiler.begin("render");
+///     profiler.end();
+///     profiler.end();
+/// }
+///
+/// assert_eq!(profiler.scopes(), vec!["frame", "frame/physics", "frame/render"]);
+/// assert_eq!(profiler.stats("frame/render").unwrap().count(), 3);
+/// assert!(profiler.percentile("frame", 0.9).unwrap() >= 0.0);
+///
+/// profiler.reset();
+/// assert!(profiler.stats("frame").is_none());
+/// ```
+#[derive(Clone, Default, Debug)]
+pub struct Profiler {
+    /// Paths and start times of the currently open scopes.
+    stack: Vec<(String, f64)>,
+    scopes: BTreeMap<String, ScopeTimes>,
+    is_tracing: bool,
+    trace: Vec<TraceEvent>,
+}
+
+/// Collected durations of a single scope.
+#[derive(Clone, Default, Debug)]
+struct ScopeTimes {
+    stats: RunningStats,
+    /// Uniform random sample of the durations.
+    sample: Vec<f64>,
+}
+
+impl ScopeTimes {
+    fn push(&mut self, t: f64) {
+        self.stats.push(t);
+        if self.sample.len() < SAMPLE_SIZE {
+            self.sample.push(t);
+        } else {
+            // Reservoir sampling, every duration so far has the same chance of being kept.
+            let i = rand::thread_rng().gen_range(0, self.stats.count());
+            if i < SAMPLE_SIZE {
+                self.sample[i] = t;
+            }
+        }
+    }
+}
+
+/// Complete event in the Chrome trace event format.
+#[derive(Clone, Debug, Serialize)]
+struct TraceEvent {
+    name: String,
+    cat: String,
+    ph: &'static str,
+    /// Start time in microseconds.
+    ts: f64,
+    /// Duration in microseconds.
+    dur: f64,
+    pid: u32,
+    tid: u32,
+}
+
+#[derive(Serialize)]
+struct Trace<'a> {
+    #[serde(rename = "traceEvents")]
+    trace_events: &'a [TraceEvent],
+}
+
+impl Profiler {
+    pub fn new() -> Profiler { Default::default() }
+
+    /// Start timing a scope inside the current scope.
+    pub fn begin(&mut self, name: &str) {
+        let path = match self.stack.last() {
+            Some((parent, _)) => format!("{}/{}", parent, name),
+            None => name.to_string(),
+        };
+        self.stack.push((path, time::precise_time_s()));
+    }
+
+    /// Stop timing the innermost open scope.
+    pub fn end(&mut self) {
+        let end = time::precise_time_s();
+        let (path, begin) = self
+            .stack
+            .pop()
+            .expect("Profiler scope ended without begin");
+        self.record(path, begin, end);
+    }
+
+    fn record(&mut self, path: String, begin: f64, end: f64) {
+        if self.is_tracing {
+            let name = path.rsplit('/').next().unwrap_or("").to_string();
+            self.trace.push(TraceEvent {
+                name,
+                cat: path.clone(),
+                ph: "X",
+                ts: begin * 1e6,
+                dur: (end - begin) * 1e6,
+                pid: 0,
+                tid: 0,
+            });
+        }
+        self.scopes.entry(path).or_default().push(end - begin);
+    }
+
+    /// Start or stop recording trace events for `write_chrome_trace`.
+    ///
+    /// The trace keeps every scope that ends while tracing is on, so only leave it on for the
+    /// frames you want to look at.
+    pub fn set_tracing(&mut self, is_tracing: bool) { self.is_tracing = is_tracing; }
+
+    /// Clear the collected timings.
+    ///
+    /// Scopes that are open when the profiler is reset are still recorded when they end.
+    pub fn reset(&mut self) {
+        self.scopes.clear();
+        self.trace.clear();
+    }
+
+    /// Return the paths of the scopes that have timings.
+    pub fn scopes(&self) -> Vec<&str> { self.scopes.keys().map(|s| &s[..]).collect() }
+
+    /// Return the duration statistics of a scope in seconds.
+    pub fn stats(&self, path: &str) -> Option<RunningStats> {
+        self.scopes.get(path).map(|t| t.stats)
+    }
+
+    /// Return a percentile of the durations of a scope in seconds.
+    ///
+    /// The percentile is exact for up to 1024 durations and estimated from a random sample of
+    /// them after that.
+    pub fn percentile(&self, path: &str, p: f64) -> Option<f64> {
+        let mut times = self.scopes.get(path)?.sample.clone();
+        percentile(&mut times, p)
+    }
+
+    /// Write the timings recorded while tracing since the last reset as a Chrome trace event
+    /// file.
+    ///
+    /// The file can be viewed with the `chrome://tracing` page of Chrome or with Perfetto.
+    pub fn write_chrome_trace<W: Write>(&self, writer: W) -> io::Result<()> {
+        let trace = Trace {
+            trace_events: &self.trace,
+        };
+        serde_json::to_writer(writer, &trace).map_err(io::Error::from)
+    }
+}
+
+impl fmt::Display for Profiler {
+    /// Show a table of the scope timings in milliseconds.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<32} {:>6} {:>9} {:>9} {:>9} {:>9}",
+            "scope", "count", "total", "avg", "min", "max"
+        )?;
+        for (path, times) in &self.scopes {
+            let stats = &times.stats;
+            let depth = path.matches('/').count();
+            let name = path.rsplit('/').next().unwrap_or("");
+            writeln!(
+                f,
+                "{:<32} {:>6} {:>9.3} {:>9.3} {:>9.3} {:>9.3}",
+                format!("{}{}", "  ".repeat(depth), name),
+                stats.count(),
+                stats.mean() * stats.count() as f64 * 1e3,
+                stats.mean() * 1e3,
+                stats.min() * 1e3,
+                stats.max() * 1e3
+            )?;
+        }
+        Ok(())
+    }
+}
+
+thread_local!(static PROFILER: RefCell<Profiler> = RefCell::new(Profiler::new()));
+
+/// Access the thread-local profiler.
+///
+/// Returns `None` if the thread is shutting down and the profiler is already gone.
+pub fn with_profiler<F, T>(f: F) -> Option<T>
+where
+    F: FnOnce(&mut Profiler) -> T,
+{
+    PROFILER.try_with(|p| f(&mut p.borrow_mut())).ok()
+}
+
+/// Guard that times the scope it lives in with the thread-local profiler.
+///
+/// # Examples
+///
+/// ```
+/// use calx::{with_profiler, ProfileScope};
+///
+/// fn update() {
+///     let _scope = ProfileScope::new("update");
+///     // ...
+/// }
+///
+/// {
+///     let _scope = ProfileScope::new("frame");
+///     update();
+/// }
+///
+/// let avg = with_profiler(|p| p.stats("frame/update").unwrap().mean()).unwrap();
+/// println!("Update took {:.3} ms", avg * 1e3);
+/// with_profiler(|p| p.reset());
+/// ```
+#[must_use]
+pub struct ProfileScope {
+    /// The scope must end on the thread whose profiler it began in, so it can't be `Send`.
+    _not_send: PhantomData<*const ()>,
+}
+
+impl ProfileScope {
+    pub fn new(name: &str) -> ProfileScope {
+        with_profiler(|p| p.begin(name));
+        ProfileScope {
+            _not_send: PhantomData,
+        }
+    }
+}
+
+impl Drop for ProfileScope {
+    fn drop(&mut self) { with_profiler(|p| p.end()); }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Profiler, SAMPLE_SIZE};
+    use serde_json::{self, Value};
+
+    #[test]
+    fn test_profiler() {
+        let mut profiler = Profiler::new();
+        profiler.set_tracing(true);
+        profiler.record("frame".to_string(), 1.0, 1.5);
+        profiler.record("frame/ai".to_string(), 1.0, 1.25);
+        profiler.record("frame".to_string(), 2.0, 3.0);
+
+        let stats = profiler.stats("frame").unwrap();
+        assert_eq!(stats.count(), 2);
+        assert_eq!((stats.min(), stats.max()), (0.5, 1.0));
+        assert_eq!(profiler.percentile("frame", 0.5), Some(0.75));
+        assert!(profiler.to_string().contains("\n  ai "));
+
+        let mut trace = Vec::new();
+        profiler.write_chrome_trace(&mut trace).unwrap();
+        let trace: Value = serde_json::from_slice(&trace).unwrap();
+        let events = trace["traceEvents"].as_array().unwrap();
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[1]["name"], "ai");
+        assert_eq!(events[1]["dur"], 250_000.0);
+
+        // Scopes open across a reset still get recorded.
+        profiler.begin("long");
+        profiler.reset();
+        profiler.end();
+        assert_eq!(profiler.scopes(), vec!["long"]);
+    }
+
+    #[test]
+    fn test_bounded_memory() {
+        let mut profiler = Profiler::new();
+        for i in 0..10 * SAMPLE_SIZE {
+            profiler.record("frame".to_string(), 0.0, i as f64);
+        }
+        assert_eq!(profiler.stats("frame").unwrap().count(), 10 * SAMPLE_SIZE);
+        assert_eq!(profiler.scopes["frame"].sample.len(), SAMPLE_SIZE);
+        let median = profiler.percentile("frame", 0.5).unwrap() / (10 * SAMPLE_SIZE) as f64;
+        assert!(median > 0.3 && median < 0.7);
+
+        // Nothing is traced unless asked for.
+        assert!(profiler.trace.is_empty());
+    }
+}
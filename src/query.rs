@@ -0,0 +1,167 @@
+//! Chained queries over cell fields
+
+use hex::HexGeom;
+use rand::{seq, Rng};
+use std::collections::HashSet;
+use CellVector;
+
+/// Start a query over the cells of a field.
+///
+/// The cells can come from anything that iterates position and value pairs, such as
+/// `Grid::iter` or a `HashMap<CellVector, T>`. The query steps are lazy iterator adapters, so a
+/// chain of them doesn't allocate intermediate collections.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate euclid;
+/// # extern crate calx;
+/// # fn main() {
+/// use std::collections::HashSet;
+/// use euclid::{rect, vec2};
+/// use calx::{query, seeded_rng, Grid};
+///
+/// #[derive(Copy, Clone, PartialEq, Debug)]
+/// enum Terrain {
+///     Floor,
+///     Lava,
+/// }
+///
+/// let map = Grid::from_fn(rect(0, 0, 10, 10), |p| {
+///     if p.x == 5 { Terrain::Lava } else { Terrain::Floor }
+/// });
+/// let fov: HashSet<_> = map.iter().map(|(p, _)| p).filter(|p| p.y < 3).collect();
+///
+/// // Find all lava cells visible to the player.
+/// let visible_lava: Vec<_> = query(map.iter())
+///     .matching(|&&t| t == Terrain::Lava)
+///     .in_set(&fov)
+///     .positions()
+///     .collect();
+/// assert_eq!(visible_lava, vec![vec2(5, 0), vec2(5, 1), vec2(5, 2)]);
+///
+/// // Pick spawn points near the player.
+/// let spawns = query(map.iter())
+///     .matching(|&&t| t == Terrain::Floor)
+///     .within(vec2(2, 2), 2)
+///     .sample(&mut seeded_rng(&1), 3);
+/// assert_eq!(spawns.len(), 3);
+/// # }
+/// ```
+pub fn query<I, T>(cells: I) -> CellQuery<I::IntoIter>
+where
+    I: IntoIterator<Item = (CellVector, T)>,
+{
+    CellQuery {
+        iter: cells.into_iter(),
+    }
+}
+
+/// Lazy query over position and value pairs of a cell field.
+///
+/// Created with `query`. The query is an iterator itself, so regular iterator methods can be
+/// mixed in with the query steps.
+pub struct CellQuery<I> {
+    iter: I,
+}
+
+impl<T, I: Iterator<Item = (CellVector, T)>> CellQuery<I> {
+    /// Keep the cells whose value matches a predicate.
+    pub fn matching<F>(self, mut pred: F) -> CellQuery<impl Iterator<Item = (CellVector, T)>>
+    where
+        F: FnMut(&T) -> bool,
+    {
+        query(self.iter.filter(move |(_, x)| pred(x)))
+    }
+
+    /// Keep the cells whose position matches a predicate.
+    pub fn at<F>(self, mut pred: F) -> CellQuery<impl Iterator<Item = (CellVector, T)>>
+    where
+        F: FnMut(CellVector) -> bool,
+    {
+        query(self.iter.filter(move |&(p, _)| pred(p)))
+    }
+
+    /// Keep the cells within a hex distance of a point.
+    pub fn within(
+        self,
+        center: CellVector,
+        radius: i32,
+    ) -> CellQuery<impl Iterator<Item = (CellVector, T)>> {
+        self.at(move |p| (p - center).hex_dist() <= radius)
+    }
+
+    /// Keep the cells in a set of positions, such as a field of view.
+    pub fn in_set<'a>(
+        self,
+        set: &'a HashSet<CellVector>,
+    ) -> CellQuery<impl Iterator<Item = (CellVector, T)> + 'a>
+    where
+        I: 'a,
+        T: 'a,
+    {
+        self.at(move |p| set.contains(&p))
+    }
+
+    /// Drop the values and iterate the positions of the remaining cells.
+    pub fn positions(self) -> impl Iterator<Item = CellVector> { self.iter.map(|(p, _)| p) }
+
+    /// Pick up to `n` random cells from the query.
+    ///
+    /// Returns all the cells if there are fewer than `n`. The order of the returned cells is
+    /// not specified.
+    pub fn sample<R: Rng + ?Sized>(self, rng: &mut R, n: usize) -> Vec<(CellVector, T)> {
+        seq::sample_iter(rng, self.iter, n).unwrap_or_else(|all| all)
+    }
+}
+
+impl<T, I: Iterator<Item = (CellVector, T)>> Iterator for CellQuery<I> {
+    type Item = (CellVector, T);
+
+    fn next(&mut self) -> Option<Self::Item> { self.iter.next() }
+
+    fn size_hint(&self) -> (usize, Option<usize>) { self.iter.size_hint() }
+}
+
+#[cfg(test)]
+mod test {
+    use super::query;
+    use euclid::{rect, vec2};
+    use grid::Grid;
+    use hex::HexGeom;
+    use rng::seeded_rng;
+    use std::collections::{HashMap, HashSet};
+
+    #[test]
+    fn test_query() {
+        let grid = Grid::from_fn(rect(-5, -5, 11, 11), |p| p.x + p.y);
+
+        let near: HashSet<_> = query(grid.iter())
+            .within(vec2(0, 0), 2)
+            .positions()
+            .collect();
+        assert_eq!(near.len(), 19);
+        assert!(near.iter().all(|p| p.hex_dist() <= 2));
+
+        let total: i32 = query(grid.iter())
+            .matching(|&&x| x > 0)
+            .at(|p| p.x == 0)
+            .map(|(_, &x)| x)
+            .sum();
+        assert_eq!(total, 1 + 2 + 3 + 4 + 5);
+
+        // Works on hash maps with owned values too.
+        let map: HashMap<_, _> = grid.iter().map(|(p, &x)| (p, x)).collect();
+        let in_set: Vec<_> = query(map).in_set(&near).collect();
+        assert_eq!(in_set.len(), 19);
+
+        let mut rng = seeded_rng(&3);
+        let sample = query(grid.iter())
+            .matching(|&&x| x == 0)
+            .sample(&mut rng, 4);
+        assert_eq!(sample.len(), 4);
+        assert!(sample.iter().all(|(p, _)| p.x == -p.y));
+        let sample = query(grid.iter()).within(vec2(0, 0), 0).sample(&mut rng, 4);
+        assert_eq!(sample, vec![(vec2(0, 0), &0)]);
+    }
+}
@@ -0,0 +1,119 @@
+use euclid::{point2, Point2D, Size2D};
+
+/// Pack rectangles into as few fixed-size pages as possible.
+///
+/// Returns the page index and the top left position for each rectangle in the order of the
+/// input sizes, or `None` if some rectangle is too large to fit on a page. Uses a shelf packer
+/// that places the rectangles in rows from the tallest to the shortest, which works well for
+/// sprite and glyph sets where the heights don't vary wildly.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate euclid;
+/// # extern crate calx;
+/// # fn main() {
+/// use euclid::{point2, size2};
+/// use calx::pack_rects;
+///
+/// let sizes = vec![size2(8, 8), size2(16, 4), size2(10, 16)];
+/// let placed = pack_rects(&sizes, size2(32, 32)).unwrap();
+/// assert_eq!(placed, vec![(0, point2(10, 0)), (0, point2(0, 16)), (0, point2(0, 0))]);
+///
+/// assert!(pack_rects(&[size2(33, 1)], size2(32, 32)).is_none());
+/// # }
+/// ```
+pub fn pack_rects(
+    sizes: &[Size2D<i32>],
+    page_size: Size2D<i32>,
+) -> Option<Vec<(usize, Point2D<i32>)>> {
+    struct Shelf {
+        y: i32,
+        height: i32,
+        x: i32,
+    }
+
+    if sizes
+        .iter()
+        .any(|s| s.width > page_size.width || s.height > page_size.height)
+    {
+        return None;
+    }
+
+    let mut order: Vec<usize> = (0..sizes.len()).collect();
+    // Stable sort, so equal height rectangles stay in input order.
+    order.sort_by_key(|&i| (-sizes[i].height, -sizes[i].width));
+
+    let mut pages: Vec<Vec<Shelf>> = Vec::new();
+    let mut ret = vec![(0, point2(0, 0)); sizes.len()];
+
+    'rects: for i in order {
+        let size = sizes[i];
+        if size.width <= 0 || size.height <= 0 {
+            continue;
+        }
+
+        for (page_idx, shelves) in pages.iter_mut().enumerate() {
+            // Try existing shelves first.
+            for shelf in shelves.iter_mut() {
+                if size.height <= shelf.height && shelf.x + size.width <= page_size.width {
+                    ret[i] = (page_idx, point2(shelf.x, shelf.y));
+                    shelf.x += size.width;
+                    continue 'rects;
+                }
+            }
+
+            // Then open a new shelf below the existing ones.
+            let y = shelves.last().map_or(0, |s| s.y + s.height);
+            if y + size.height <= page_size.height {
+                ret[i] = (page_idx, point2(0, y));
+                shelves.push(Shelf {
+                    y,
+                    height: size.height,
+                    x: size.width,
+                });
+                continue 'rects;
+            }
+        }
+
+        // Start a new page.
+        ret[i] = (pages.len(), point2(0, 0));
+        pages.push(vec![Shelf {
+            y: 0,
+            height: size.height,
+            x: size.width,
+        }]);
+    }
+
+    Some(ret)
+}
+
+#[cfg(test)]
+mod test {
+    use super::pack_rects;
+    use euclid::{rect, size2, Rect};
+    use rand::Rng;
+    use rng::seeded_rng;
+
+    #[test]
+    fn test_no_overlap() {
+        let mut rng = seeded_rng(&"pack");
+        let sizes: Vec<_> = (0..200)
+            .map(|_| size2(rng.gen_range(1, 20), rng.gen_range(1, 20)))
+            .collect();
+        let page = size2(64, 64);
+        let placed = pack_rects(&sizes, page).unwrap();
+
+        let rects: Vec<(usize, Rect<i32>)> = placed
+            .iter()
+            .zip(&sizes)
+            .map(|(&(p, pos), s)| (p, rect(pos.x, pos.y, s.width, s.height)))
+            .collect();
+        for (i, &(page_a, a)) in rects.iter().enumerate() {
+            assert!(rect(0, 0, page.width, page.height).contains_rect(&a));
+            for &(page_b, b) in &rects[i + 1..] {
+                assert!(page_a != page_b || !a.intersects(&b));
+            }
+        }
+    }
+}
@@ -0,0 +1,193 @@
+//! Recorded event sequences that can be played back and scrubbed
+
+use incremental::Incremental;
+
+/// Event log of a game session with periodic state snapshots.
+///
+/// The state of any turn can be reconstructed by running the events from the nearest earlier
+/// snapshot, so seeking around in a long replay doesn't need to run the whole log. The snapshots
+/// are serialized along with the events.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Replay<T, E> {
+    events: Vec<E>,
+    /// Number of events between snapshots.
+    interval: usize,
+    /// Snapshot `i` is the state before event `i * interval`.
+    snapshots: Vec<T>,
+}
+
+impl<T: Incremental<E> + Clone, E> Replay<T, E> {
+    /// Start recording from an initial state, with a snapshot every `interval` events.
+    pub fn new(initial: T, interval: usize) -> Replay<T, E> {
+        assert!(interval > 0, "Snapshot interval must be positive");
+        Replay {
+            events: Vec::new(),
+            interval,
+            snapshots: vec![initial],
+        }
+    }
+
+    /// Record the next event.
+    pub fn push(&mut self, e: E) {
+        self.events.push(e);
+        let n = self.events.len();
+        if n % self.interval == 0 {
+            let snapshot = self.build(n - self.interval, n);
+            self.snapshots.push(snapshot);
+        }
+    }
+
+    /// Return the number of recorded events.
+    pub fn len(&self) -> usize { self.events.len() }
+
+    pub fn is_empty(&self) -> bool { self.events.is_empty() }
+
+    pub fn events(&self) -> &[E] { &self.events }
+
+    /// Reconstruct the state after the given number of events.
+    pub fn state_at(&self, turn: usize) -> T {
+        let turn = turn.min(self.events.len());
+        self.build(turn - turn % self.interval, turn)
+    }
+
+    /// Run events from the snapshot at `start` up to `end`.
+    fn build(&self, start: usize, end: usize) -> T {
+        let snapshot = self.snapshots[start / self.interval].clone();
+        self.events[start..end]
+            .iter()
+            .fold(snapshot, |state, e| state.update(e))
+    }
+}
+
+/// Playback controller for a replay.
+///
+/// Call `tick` once per playback frame to advance the unpaused replay and draw `state` to show
+/// the reconstructed game.
+///
+/// # Examples
+///
+/// ```
+/// use calx::{Incremental, Replay, ReplayPlayer};
+///
+/// #[derive(Clone, Default)]
+/// struct Score(u32);
+///
+/// impl Incremental<u32> for Score {
+///     fn update(self, e: &u32) -> Score { Score(self.0 + *e) }
+/// }
+///
+/// let mut replay = Replay::new(Score::default(), 16);
+/// for i in 1..=100 {
+///     replay.push(i);
+/// }
+///
+/// let mut player = ReplayPlayer::new(&replay);
+/// player.tick();
+/// assert_eq!(player.state().0, 1);
+///
+/// player.set_paused(true);
+/// player.tick();
+/// assert_eq!(player.turn(), 1);
+/// player.step();
+/// assert_eq!(player.state().0, 3);
+///
+/// player.seek(50);
+/// assert_eq!(player.state().0, 1275);
+/// ```
+pub struct ReplayPlayer<'a, T: 'a, E: 'a> {
+    replay: &'a Replay<T, E>,
+    turn: usize,
+    state: T,
+    paused: bool,
+}
+
+impl<'a, T: Incremental<E> + Clone, E> ReplayPlayer<'a, T, E> {
+    /// Start playing a replay from the beginning.
+    pub fn new(replay: &'a Replay<T, E>) -> ReplayPlayer<'a, T, E> {
+        ReplayPlayer {
+            replay,
+            turn: 0,
+            state: replay.snapshots[0].clone(),
+            paused: false,
+        }
+    }
+
+    /// Return the current state of the replayed game.
+    pub fn state(&self) -> &T { &self.state }
+
+    /// Return the number of events played so far.
+    pub fn turn(&self) -> usize { self.turn }
+
+    /// Return whether all the events have been played.
+    pub fn is_finished(&self) -> bool { self.turn >= self.replay.len() }
+
+    pub fn is_paused(&self) -> bool { self.paused }
+
+    pub fn set_paused(&mut self, paused: bool) { self.paused = paused; }
+
+    /// Advance the replay by one event unless paused.
+    pub fn tick(&mut self) {
+        if !self.paused {
+            self.step();
+        }
+    }
+
+    /// Advance the replay by one event even when paused.
+    ///
+    /// Returns false if the replay was already at the end.
+    pub fn step(&mut self) -> bool {
+        if self.is_finished() {
+            return false;
+        }
+        let state = self.state.clone();
+        self.state = state.update(&self.replay.events[self.turn]);
+        self.turn += 1;
+        true
+    }
+
+    /// Jump to the state after the given number of events.
+    pub fn seek(&mut self, turn: usize) {
+        let turn = turn.min(self.replay.len());
+        if turn < self.turn || turn - self.turn > self.replay.interval {
+            self.state = self.replay.state_at(turn);
+            self.turn = turn;
+        }
+        while self.turn < turn {
+            self.step();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Replay, ReplayPlayer};
+    use incremental::Incremental;
+
+    #[derive(Clone, PartialEq, Debug)]
+    struct Log(Vec<u32>);
+
+    impl Incremental<u32> for Log {
+        fn update(mut self, e: &u32) -> Log {
+            self.0.push(*e);
+            self
+        }
+    }
+
+    #[test]
+    fn test_seek() {
+        let mut replay = Replay::new(Log(vec![]), 7);
+        for i in 0..50 {
+            replay.push(i);
+        }
+        assert_eq!(replay.snapshots.len(), 8);
+
+        let mut player = ReplayPlayer::new(&replay);
+        for &turn in &[0, 13, 12, 50, 3, 4, 40, 100] {
+            player.seek(turn);
+            let expected: Vec<u32> = (0..turn.min(50) as u32).collect();
+            assert_eq!(player.state(), &Log(expected));
+        }
+        assert!(player.is_finished());
+        assert!(!player.step());
+    }
+}
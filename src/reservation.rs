@@ -0,0 +1,276 @@
+//! Cell reservation for simultaneous movement
+
+use rand::Rng;
+use std::collections::HashMap;
+use CellVector;
+
+/// Request of an actor to move to a cell during a simultaneous turn.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct MoveRequest<A> {
+    pub actor: A,
+    pub from: CellVector,
+    pub to: CellVector,
+    /// Requests with higher priority win contested cells.
+    pub priority: i32,
+}
+
+impl<A> MoveRequest<A> {
+    pub fn new(actor: A, from: CellVector, to: CellVector) -> MoveRequest<A> {
+        MoveRequest {
+            actor,
+            from,
+            to,
+            priority: 0,
+        }
+    }
+
+    pub fn priority(mut self, priority: i32) -> MoveRequest<A> {
+        self.priority = priority;
+        self
+    }
+}
+
+/// What happened to a move request.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum MoveOutcome<A> {
+    /// The actor did not try to move.
+    Stayed,
+    /// The actor moved to its destination.
+    Moved,
+    /// The actor traded places with another actor.
+    Swapped(A),
+    /// The actor could not move because another actor took or kept the destination cell.
+    Blocked(A),
+}
+
+/// Resolved move of an actor.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct MoveResult<A> {
+    pub actor: A,
+    /// Where the actor is after the turn.
+    pub pos: CellVector,
+    pub outcome: MoveOutcome<A>,
+}
+
+/// Resolver for moves that all actors make at the same time.
+///
+/// Every actor makes a move request, including the ones that stay in place, and the resolver
+/// works out who gets to move so that no two actors end up in the same cell. When several
+/// actors want the same cell, the one with the highest priority wins and ties are broken with
+/// the random number generator, so a seeded generator gives reproducible results. An actor
+/// can move into a cell vacated by another actor on the same turn, and lines of actors
+/// following each other all move. Actors can't move into cells of actors that end up staying.
+///
+/// The start cells of the requests must all be distinct.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate euclid;
+/// # extern crate calx;
+/// # fn main() {
+/// use euclid::vec2;
+/// use calx::{seeded_rng, MoveOutcome, MoveRequest, MoveResolver};
+///
+/// let requests = vec![
+///     // Two actors go for the same cell, the player has the right of way.
+///     MoveRequest::new("player", vec2(0, 0), vec2(1, 0)).priority(10),
+///     MoveRequest::new("goblin", vec2(2, 0), vec2(1, 0)),
+///     // Orc walks into the goblin's cell, but the goblin didn't get to leave.
+///     MoveRequest::new("orc", vec2(3, 0), vec2(2, 0)),
+/// ];
+///
+/// let results = MoveResolver::new().resolve(&mut seeded_rng(&1), &requests);
+/// assert_eq!(results[0].pos, vec2(1, 0));
+/// assert_eq!(results[0].outcome, MoveOutcome::Moved);
+/// assert_eq!(results[1].outcome, MoveOutcome::Blocked("player"));
+/// assert_eq!(results[2].outcome, MoveOutcome::Blocked("goblin"));
+/// assert_eq!(results[2].pos, vec2(3, 0));
+/// # }
+/// ```
+#[derive(Copy, Clone, Debug)]
+pub struct MoveResolver {
+    allow_swaps: bool,
+}
+
+impl MoveResolver {
+    pub fn new() -> MoveResolver { MoveResolver { allow_swaps: true } }
+
+    /// Set whether two actors moving into each other's cells trade places.
+    ///
+    /// If swaps are not allowed, both actors are blocked. Rotations of three or more actors
+    /// are always allowed. Swaps are allowed by default.
+    pub fn allow_swaps(mut self, allow_swaps: bool) -> MoveResolver {
+        self.allow_swaps = allow_swaps;
+        self
+    }
+
+    /// Resolve a turn of moves.
+    ///
+    /// Returns the results in the order of the requests.
+    pub fn resolve<A, R>(&self, rng: &mut R, requests: &[MoveRequest<A>]) -> Vec<MoveResult<A>>
+    where
+        A: Clone,
+        R: Rng + ?Sized,
+    {
+        let occupant: HashMap<CellVector, usize> = requests
+            .iter()
+            .enumerate()
+            .map(|(i, r)| (r.from, i))
+            .collect();
+        debug_assert_eq!(occupant.len(), requests.len(), "Actors share a start cell");
+
+        let mut blocked_by: Vec<Option<usize>> = vec![None; requests.len()];
+
+        // Pick the winner for each contested cell.
+        let mut contenders: HashMap<CellVector, Vec<(i32, u32, usize)>> = HashMap::new();
+        for (i, r) in requests.iter().enumerate() {
+            if r.to != r.from {
+                let tiebreak = rng.gen();
+                contenders
+                    .entry(r.to)
+                    .or_default()
+                    .push((r.priority, tiebreak, i));
+            }
+        }
+        // Go through the cells in request order so that the RNG use doesn't depend on hashing.
+        for r in requests {
+            if let Some(mut list) = contenders.remove(&r.to) {
+                list.sort();
+                let winner = list.pop().unwrap().2;
+                for (_, _, i) in list {
+                    blocked_by[i] = Some(winner);
+                }
+            }
+        }
+
+        let is_moving = |i: usize, blocked_by: &[Option<usize>]| {
+            requests[i].to != requests[i].from && blocked_by[i].is_none()
+        };
+
+        // Block moves into cells whose occupants stay put until nothing changes.
+        loop {
+            let mut changed = false;
+            for i in 0..requests.len() {
+                if !is_moving(i, &blocked_by) {
+                    continue;
+                }
+                if let Some(&j) = occupant.get(&requests[i].to) {
+                    let is_swap = requests[j].to == requests[i].from;
+                    if !is_moving(j, &blocked_by) || (is_swap && !self.allow_swaps) {
+                        blocked_by[i] = Some(j);
+                        changed = true;
+                    }
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+
+        requests
+            .iter()
+            .enumerate()
+            .map(|(i, r)| {
+                let (pos, outcome) = if r.to == r.from {
+                    (r.from, MoveOutcome::Stayed)
+                } else if let Some(j) = blocked_by[i] {
+                    (r.from, MoveOutcome::Blocked(requests[j].actor.clone()))
+                } else {
+                    match occupant.get(&r.to) {
+                        Some(&j) if requests[j].to == r.from => {
+                            (r.to, MoveOutcome::Swapped(requests[j].actor.clone()))
+                        }
+                        _ => (r.to, MoveOutcome::Moved),
+                    }
+                };
+                MoveResult {
+                    actor: r.actor.clone(),
+                    pos,
+                    outcome,
+                }
+            })
+            .collect()
+    }
+}
+
+impl Default for MoveResolver {
+    fn default() -> Self { MoveResolver::new() }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{MoveOutcome, MoveRequest, MoveResolver};
+    use euclid::vec2;
+    use rng::seeded_rng;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_move_resolver() {
+        let mut rng = seeded_rng(&1);
+        let resolver = MoveResolver::new();
+
+        // A line of actors all step forward.
+        let line: Vec<_> = (0..4)
+            .map(|x| MoveRequest::new(x, vec2(x, 0), vec2(x + 1, 0)))
+            .collect();
+        let results = resolver.resolve(&mut rng, &line);
+        assert!(results.iter().all(|r| r.outcome == MoveOutcome::Moved));
+
+        // A line of actors walking into a wall.
+        let mut line = line;
+        line[3].to = line[3].from;
+        let results = resolver.resolve(&mut rng, &line);
+        assert_eq!(results[3].outcome, MoveOutcome::Stayed);
+        assert_eq!(results[0].outcome, MoveOutcome::Blocked(1));
+        assert!(results.iter().all(|r| r.pos == vec2(r.actor, 0)));
+
+        // Swaps and rotations.
+        let swap = vec![
+            MoveRequest::new('a', vec2(0, 0), vec2(1, 0)),
+            MoveRequest::new('b', vec2(1, 0), vec2(0, 0)),
+        ];
+        let results = resolver.resolve(&mut rng, &swap);
+        assert_eq!(results[0].outcome, MoveOutcome::Swapped('b'));
+        assert_eq!(results[1].pos, vec2(0, 0));
+        let results = MoveResolver::new()
+            .allow_swaps(false)
+            .resolve(&mut rng, &swap);
+        assert_eq!(results[0].outcome, MoveOutcome::Blocked('b'));
+        assert_eq!(results[1].outcome, MoveOutcome::Blocked('a'));
+
+        let rotation = vec![
+            MoveRequest::new('a', vec2(0, 0), vec2(1, 0)),
+            MoveRequest::new('b', vec2(1, 0), vec2(1, 1)),
+            MoveRequest::new('c', vec2(1, 1), vec2(0, 0)),
+        ];
+        let results = MoveResolver::new()
+            .allow_swaps(false)
+            .resolve(&mut rng, &rotation);
+        assert!(results.iter().all(|r| r.outcome == MoveOutcome::Moved));
+
+        // Crowd rushing into one cell never stacks and ties are deterministic.
+        let crowd: Vec<_> = [vec2(-1, 0), vec2(1, 0), vec2(0, -1), vec2(0, 1)]
+            .iter()
+            .enumerate()
+            .map(|(i, &p)| MoveRequest::new(i, p, vec2(0, 0)))
+            .collect();
+        let results = resolver.resolve(&mut seeded_rng(&2), &crowd);
+        assert_eq!(results, resolver.resolve(&mut seeded_rng(&2), &crowd));
+        let cells: HashSet<_> = results.iter().map(|r| r.pos).collect();
+        assert_eq!(cells.len(), 4);
+        assert_eq!(
+            results
+                .iter()
+                .filter(|r| r.outcome == MoveOutcome::Moved)
+                .count(),
+            1
+        );
+
+        let mut crowd = crowd;
+        crowd[2].priority = 1;
+        let results = resolver.resolve(&mut rng, &crowd);
+        assert_eq!(results[2].outcome, MoveOutcome::Moved);
+        assert_eq!(results[0].outcome, MoveOutcome::Blocked(2));
+    }
+}
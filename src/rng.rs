@@ -1,23 +1,187 @@
-use rand::{seq, Rng, SeedableRng, XorShiftRng};
-use std::hash::Hash;
+use euclid::{point2, TypedPoint2D, TypedRect};
+use rand::distributions::Distribution;
+use rand::{seq, Error, Rng, RngCore, SeedableRng, XorShiftRng};
+use serde::de::{self, Deserialize, Deserializer};
+use serde::{Serialize, Serializer};
+use std::cell::RefCell;
+use std::f32::consts::PI;
+use std::hash::{Hash, Hasher};
 use vec_map::VecMap;
 use Deciban;
 
 /// Seed a RNG from any hashable value.
-pub fn seeded_rng(seed: &impl Hash) -> XorShiftRng {
-    use std::collections::hash_map::DefaultHasher;
-    use std::hash::Hasher;
+///
+/// The seed is hashed with a fixed algorithm that doesn't depend on the Rust version or the
+/// platform, so the same seed gives the same random numbers everywhere as long as the `Hash`
+/// implementation of the seed type stays the same. Integers, strings and tuples and arrays of
+/// them are safe to use.
+pub fn seeded_rng(seed: &impl Hash) -> XorShiftRng { rng_from_hash(stable_hash(seed)) }
 
-    let mut hasher = DefaultHasher::new();
-    seed.hash(&mut hasher);
-    let hash = hasher.finish().to_be();
+fn rng_from_hash(hash: u64) -> XorShiftRng {
+    let mut seed = [0u8; 16];
+    seed[..8].copy_from_slice(&splitmix(hash).to_le_bytes());
+    seed[8..].copy_from_slice(&splitmix(hash ^ 0x5eed_5eed_5eed_5eed).to_le_bytes());
     // XorShift seed mustn't be all-0.
-    let hash = if hash == 0 { 1 } else { hash };
-
-    let seed = unsafe { ::std::mem::transmute::<[u64; 2], [u8; 16]>([hash, hash]) };
+    if seed.iter().all(|&b| b == 0) {
+        seed[0] = 1;
+    }
     SeedableRng::from_seed(seed)
 }
 
+/// Hash a value with the platform independent FNV-1a hash.
+fn stable_hash(value: &impl Hash) -> u64 {
+    let mut hasher = StableHasher(0xcbf2_9ce4_8422_2325);
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+struct StableHasher(u64);
+
+impl Hasher for StableHasher {
+    fn finish(&self) -> u64 { self.0 }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.0 = (self.0 ^ b as u64).wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    // Fix the byte order and the width of usize so that the hash is the same everywhere.
+    fn write_u16(&mut self, i: u16) { self.write(&i.to_le_bytes()) }
+
+    fn write_u32(&mut self, i: u32) { self.write(&i.to_le_bytes()) }
+
+    fn write_u64(&mut self, i: u64) { self.write(&i.to_le_bytes()) }
+
+    fn write_u128(&mut self, i: u128) { self.write(&i.to_le_bytes()) }
+
+    fn write_usize(&mut self, i: usize) { self.write_u64(i as u64) }
+
+    fn write_isize(&mut self, i: isize) { self.write_u64(i as i64 as u64) }
+}
+
+/// SplitMix64 output function, spreads the weakly mixed FNV bits over the whole word.
+fn splitmix(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// Hierarchy of seeds for independent random number streams.
+///
+/// Child seeds are derived from the parent seed and a key, so different parts of the game can
+/// have their own random number generators that don't affect each other. Adding a new random
+/// feature in one part won't change the output of the others.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate rand;
+/// # extern crate calx;
+/// # fn main() {
+/// use rand::Rng;
+/// use calx::SeedTree;
+///
+/// let world = SeedTree::new(&12345);
+/// let level_3 = world.child("mapgen").child(3);
+/// let x: u32 = level_3.rng().gen();
+/// assert_eq!(x, SeedTree::new(&12345).child("mapgen").child(3).rng().gen::<u32>());
+/// assert!(x != world.child("mapgen").child(4).rng().gen::<u32>());
+/// # }
+/// ```
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Serialize, Deserialize)]
+pub struct SeedTree(u64);
+
+impl SeedTree {
+    /// Create a root seed from any hashable value.
+    pub fn new(seed: &impl Hash) -> SeedTree { SeedTree(splitmix(stable_hash(seed))) }
+
+    /// Derive a child seed for the given key.
+    pub fn child(&self, key: impl Hash) -> SeedTree {
+        SeedTree(splitmix(stable_hash(&(self.0, key))))
+    }
+
+    /// Create a random number generator for this seed.
+    pub fn rng(&self) -> XorShiftRng { rng_from_hash(self.0) }
+}
+
+/// Random number generator wrapper that can be stored in save files.
+///
+/// The generator is serialized as a seed, the byte sequence of `R::Seed` in a serde sequence
+/// (16 bytes for `XorShiftRng`). Serializing draws a fresh seed from the generator and reseeds
+/// it, so a game that keeps running after saving produces the same random numbers as one that
+/// is loaded from the save. The format only depends on the generator algorithm, old saves keep
+/// replaying identically as long as `R` stays the same.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate rand;
+/// # extern crate serde_json;
+/// # extern crate calx;
+/// # fn main() {
+/// use rand::{Rng, XorShiftRng};
+/// use calx::{seeded_rng, SerRng};
+///
+/// let mut rng = SerRng::new(seeded_rng(&1));
+/// let save = serde_json::to_string(&rng).unwrap();
+/// let mut loaded: SerRng<XorShiftRng> = serde_json::from_str(&save).unwrap();
+/// assert_eq!(rng.gen::<u64>(), loaded.gen::<u64>());
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct SerRng<R>(RefCell<R>);
+
+impl<R: SeedableRng + RngCore> SerRng<R> {
+    pub fn new(rng: R) -> SerRng<R> { SerRng(RefCell::new(rng)) }
+
+    pub fn into_inner(self) -> R { self.0.into_inner() }
+
+    /// Replace the generator with one seeded from its own output and return the seed bytes.
+    fn reseed(&self) -> Vec<u8> {
+        let mut rng = self.0.borrow_mut();
+        let mut seed = R::Seed::default();
+        rng.fill_bytes(seed.as_mut());
+        let ret = seed.as_mut().to_vec();
+        *rng = R::from_seed(seed);
+        ret
+    }
+}
+
+impl<R: SeedableRng + RngCore> RngCore for SerRng<R> {
+    fn next_u32(&mut self) -> u32 { self.0.get_mut().next_u32() }
+
+    fn next_u64(&mut self) -> u64 { self.0.get_mut().next_u64() }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) { self.0.get_mut().fill_bytes(dest) }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.0.get_mut().try_fill_bytes(dest)
+    }
+}
+
+impl<R: SeedableRng + RngCore> Serialize for SerRng<R> {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        self.reseed().serialize(s)
+    }
+}
+
+impl<'a, R: SeedableRng + RngCore> Deserialize<'a> for SerRng<R> {
+    fn deserialize<D: Deserializer<'a>>(d: D) -> Result<Self, D::Error> {
+        let bytes: Vec<u8> = Vec::deserialize(d)?;
+        let mut seed = R::Seed::default();
+        if bytes.len() != seed.as_mut().len() {
+            return Err(de::Error::invalid_length(
+                bytes.len(),
+                &"seed length of the generator",
+            ));
+        }
+        seed.as_mut().copy_from_slice(&bytes);
+        Ok(SerRng::new(R::from_seed(seed)))
+    }
+}
+
 /// Additional methods for random number generators.
 pub trait RngExt {
     /// Return true with 50 % probability.
@@ -98,3 +262,318 @@ impl<'a, R: Rng + 'static> Iterator for RandomPermutation<'a, R> {
         Some(self.shuffle.insert(swap_idx, head).unwrap_or(swap_idx))
     }
 }
+
+/// Generate random points in an area that are at least `min_dist` apart.
+///
+/// Uses Bridson's algorithm to fill the area with points until there is no more room for new
+/// ones. The result is an even scattering without the clusters and gaps of uniformly random
+/// points.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate euclid;
+/// # extern crate calx;
+/// # fn main() {
+/// use euclid::{rect, UnknownUnit};
+/// use calx::{poisson_disk, seeded_rng};
+///
+/// let area = rect::<_, UnknownUnit>(0.0, 0.0, 40.0, 30.0);
+/// let points = poisson_disk(&mut seeded_rng(&1), area, 4.0);
+/// assert!(points.len() > 30);
+/// assert!((points[0] - points[1]).length() >= 4.0);
+/// # }
+/// ```
+pub fn poisson_disk<R: Rng + ?Sized, U>(
+    rng: &mut R,
+    bounds: TypedRect<f32, U>,
+    min_dist: f32,
+) -> Vec<TypedPoint2D<f32, U>> {
+    // Number of candidate points tried around an active point before giving up on it.
+    const ATTEMPTS: usize = 30;
+
+    assert!(min_dist > 0.0, "Minimum distance must be positive");
+    let mut ret = Vec::new();
+    if bounds.size.width <= 0.0 || bounds.size.height <= 0.0 {
+        return ret;
+    }
+
+    // Background grid cells are small enough to hold at most one point each.
+    let cell = min_dist / 2.0f32.sqrt();
+    let w = (bounds.size.width / cell).ceil() as usize;
+    let h = (bounds.size.height / cell).ceil() as usize;
+    let mut grid: Vec<Option<usize>> = vec![None; w * h];
+    let grid_pos = |p: TypedPoint2D<f32, U>| {
+        let x = ((p.x - bounds.min_x()) / cell) as usize;
+        let y = ((p.y - bounds.min_y()) / cell) as usize;
+        (x.min(w - 1), y.min(h - 1))
+    };
+
+    let first = point2(
+        rng.gen_range(bounds.min_x(), bounds.max_x()),
+        rng.gen_range(bounds.min_y(), bounds.max_y()),
+    );
+    let (x, y) = grid_pos(first);
+    grid[x + y * w] = Some(0);
+    ret.push(first);
+    let mut active = vec![0];
+
+    while !active.is_empty() {
+        let i = rng.gen_range(0, active.len());
+        let origin = ret[active[i]];
+
+        let candidate = (0..ATTEMPTS)
+            .map(|_| {
+                let angle = rng.gen_range(0.0, 2.0 * PI);
+                let dist = rng.gen_range(min_dist, 2.0 * min_dist);
+                point2(origin.x + angle.cos() * dist, origin.y + angle.sin() * dist)
+            })
+            .find(|&p| {
+                if !bounds.contains(&p) {
+                    return false;
+                }
+                let (x, y) = grid_pos(p);
+                // Points closer than min_dist can be at most two grid cells away.
+                (y.saturating_sub(2)..(y + 3).min(h)).all(|y| {
+                    (x.saturating_sub(2)..(x + 3).min(w)).all(|x| match grid[x + y * w] {
+                        Some(j) => (ret[j] - p).length() >= min_dist,
+                        None => true,
+                    })
+                })
+            });
+
+        match candidate {
+            Some(p) => {
+                let (x, y) = grid_pos(p);
+                grid[x + y * w] = Some(ret.len());
+                active.push(ret.len());
+                ret.push(p);
+            }
+            None => {
+                active.swap_remove(i);
+            }
+        }
+    }
+
+    ret
+}
+
+/// Generate one random point in each `spacing` sized square of a grid covering the area.
+///
+/// A cheaper alternative to `poisson_disk` that still avoids large clusters and gaps, but points
+/// in adjacent grid squares can end up arbitrarily close to each other.
+pub fn jittered_grid<R: Rng + ?Sized, U>(
+    rng: &mut R,
+    bounds: TypedRect<f32, U>,
+    spacing: f32,
+) -> Vec<TypedPoint2D<f32, U>> {
+    assert!(spacing > 0.0, "Grid spacing must be positive");
+    let w = (bounds.size.width / spacing).ceil().max(0.0) as usize;
+    let h = (bounds.size.height / spacing).ceil().max(0.0) as usize;
+
+    let mut ret = Vec::with_capacity(w * h);
+    for y in 0..h {
+        for x in 0..w {
+            // Clip the squares at the far edges to stay inside the bounds.
+            let x0 = bounds.min_x() + x as f32 * spacing;
+            let y0 = bounds.min_y() + y as f32 * spacing;
+            let x1 = (x0 + spacing).min(bounds.max_x());
+            let y1 = (y0 + spacing).min(bounds.max_y());
+            ret.push(point2(rng.gen_range(x0, x1), rng.gen_range(y0, y1)));
+        }
+    }
+    ret
+}
+
+/// Discrete distribution of indices with given relative weights.
+///
+/// Uses Vose's alias method, sampling takes constant time regardless of the number of weights
+/// after the table has been built in linear time.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate rand;
+/// # extern crate calx;
+/// # fn main() {
+/// use rand::distributions::Distribution;
+/// use calx::{seeded_rng, AliasTable};
+///
+/// let table = AliasTable::new(&[1.0, 0.0, 3.0]);
+/// let mut rng = seeded_rng(&1);
+/// let n = (0..1000).filter(|_| table.sample(&mut rng) == 2).count();
+/// assert!(n > 700 && n < 800);
+/// # }
+/// ```
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AliasTable {
+    prob: Vec<f64>,
+    alias: Vec<usize>,
+}
+
+impl AliasTable {
+    /// Build a table from non-negative weights with a positive sum.
+    pub fn new(weights: &[f64]) -> AliasTable {
+        assert!(
+            weights.iter().all(|&w| w >= 0.0 && w.is_finite()),
+            "Invalid weight"
+        );
+        let total: f64 = weights.iter().sum();
+        assert!(total > 0.0, "Weights must not all be zero");
+
+        let n = weights.len();
+        // Scale the weights so that the average is 1.
+        let mut prob: Vec<f64> = weights.iter().map(|&w| w * n as f64 / total).collect();
+        let mut alias: Vec<usize> = (0..n).collect();
+
+        let (mut small, mut large): (Vec<usize>, Vec<usize>) = (0..n).partition(|&i| prob[i] < 1.0);
+        while let (Some(s), Some(&l)) = (small.pop(), large.last()) {
+            // Fill the rest of the small slot with the large item.
+            alias[s] = l;
+            prob[l] -= 1.0 - prob[s];
+            if prob[l] < 1.0 {
+                large.pop();
+                small.push(l);
+            }
+        }
+        // Anything left over is at 1 up to rounding errors.
+        for i in small.into_iter().chain(large) {
+            prob[i] = 1.0;
+        }
+
+        AliasTable { prob, alias }
+    }
+
+    pub fn len(&self) -> usize { self.prob.len() }
+
+    pub fn is_empty(&self) -> bool { self.prob.is_empty() }
+}
+
+impl Distribution<usize> for AliasTable {
+    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> usize {
+        let i = rng.gen_range(0, self.prob.len());
+        if rng.gen::<f64>() < self.prob[i] {
+            i
+        } else {
+            self.alias[i]
+        }
+    }
+}
+
+/// Distribution of values with given relative weights.
+///
+/// Intended for loot tables and spawn lists. Unlike the `WeightedChoice` trait that walks the
+/// whole item list on every pick, sampling takes constant time, so large tables are fine.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate rand;
+/// # extern crate calx;
+/// # fn main() {
+/// use rand::distributions::Distribution;
+/// use calx::{seeded_rng, WeightedTable};
+///
+/// let loot = WeightedTable::new(vec![(10.0, "gold"), (3.0, "potion"), (0.5, "sword")]);
+/// let item = loot.sample(&mut seeded_rng(&1));
+/// assert!(["gold", "potion", "sword"].contains(&item));
+/// # }
+/// ```
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct WeightedTable<T> {
+    items: Vec<T>,
+    table: AliasTable,
+}
+
+impl<T> WeightedTable<T> {
+    /// Build a distribution from weight and value pairs.
+    pub fn new(items: impl IntoIterator<Item = (f64, T)>) -> WeightedTable<T> {
+        let (weights, items): (Vec<f64>, Vec<T>) = items.into_iter().unzip();
+        WeightedTable {
+            items,
+            table: AliasTable::new(&weights),
+        }
+    }
+
+    /// Pick a reference to a random value.
+    pub fn pick<R: Rng + ?Sized>(&self, rng: &mut R) -> &T { &self.items[self.table.sample(rng)] }
+
+    pub fn items(&self) -> &[T] { &self.items }
+}
+
+impl<T: Clone> Distribution<T> for WeightedTable<T> {
+    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> T { self.pick(rng).clone() }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        jittered_grid, poisson_disk, seeded_rng, stable_hash, AliasTable, SerRng, WeightedTable,
+    };
+    use euclid::{rect, UnknownUnit};
+    use rand::distributions::Distribution;
+    use rand::{Rng, XorShiftRng};
+    use serde_json;
+
+    #[test]
+    fn test_poisson_disk() {
+        let bounds = rect(-10.0, 5.0, 50.0, 20.0);
+        let points = poisson_disk::<_, UnknownUnit>(&mut seeded_rng(&1), bounds, 3.0);
+        for (i, a) in points.iter().enumerate() {
+            assert!(bounds.contains(a));
+            for b in &points[i + 1..] {
+                assert!((*a - *b).length() >= 3.0);
+            }
+        }
+        // Max packing with hexagonal tiling would be around 130 points. Maximal random
+        // packing gets roughly half of that.
+        assert!(points.len() > 50);
+
+        let points = jittered_grid::<_, UnknownUnit>(&mut seeded_rng(&1), bounds, 3.0);
+        assert_eq!(points.len(), 17 * 7);
+        assert!(points.iter().all(|p| bounds.contains(p)));
+    }
+
+    #[test]
+    fn test_alias_table() {
+        let weights = [5.0, 0.0, 1.0, 2.0, 0.5, 1.5];
+        let table = AliasTable::new(&weights);
+        let mut rng = seeded_rng(&1);
+        let mut counts = [0; 6];
+        let n = 100_000;
+        for _ in 0..n {
+            counts[table.sample(&mut rng)] += 1;
+        }
+        for (&w, &c) in weights.iter().zip(&counts) {
+            let expected = w / 10.0 * n as f64;
+            assert!((c as f64 - expected).abs() < 0.02 * n as f64);
+        }
+        assert_eq!(counts[1], 0);
+
+        let single = WeightedTable::new(vec![(0.0, 'a'), (1.0, 'b')]);
+        assert!((0..100).all(|_| single.sample(&mut rng) == 'b'));
+    }
+
+    #[test]
+    fn test_ser_rng() {
+        let mut rng = SerRng::new(seeded_rng(&"save"));
+        for _ in 0..10 {
+            rng.gen::<u32>();
+        }
+        let save = serde_json::to_string(&rng).unwrap();
+        let mut loaded: SerRng<XorShiftRng> = serde_json::from_str(&save).unwrap();
+        for _ in 0..100 {
+            assert_eq!(rng.gen::<u32>(), loaded.gen::<u32>());
+        }
+        assert!(serde_json::from_str::<SerRng<XorShiftRng>>("[1, 2, 3]").is_err());
+    }
+
+    #[test]
+    fn test_stable_seed() {
+        // Exact values, these must not change between platforms or Rust versions.
+        assert_eq!(stable_hash(&(1u8, "a")), 0xd113_0e18_6785_4ff8);
+        assert_eq!(stable_hash(&1usize), stable_hash(&1u64));
+        assert_eq!(stable_hash(&0x1234_5678u32), 0xcccf_d053_e47c_3365);
+        assert_eq!(seeded_rng(&"calx").gen::<u32>(), 4_016_844_366);
+    }
+}
@@ -0,0 +1,512 @@
+//! Versioned save files
+
+use deflate;
+use inflate;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{self, Value};
+use std::error::Error;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use system::app_data_path;
+
+/// Metadata stored in front of the saved game data.
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct SaveHeader {
+    /// Version of the save data layout, incremented each time a migration is added.
+    pub format_version: u32,
+    /// Free-form version string of the game that wrote the save.
+    pub game_version: String,
+}
+
+/// Error from loading or storing a save.
+#[derive(Debug)]
+pub enum SaveError {
+    /// Reading or writing the save failed.
+    Io(io::Error),
+    /// The save data could not be encoded or decoded.
+    Serialization(serde_json::Error),
+    /// The save was written by a newer version of the game than the one trying to load it.
+    TooNew {
+        header: SaveHeader,
+        supported_version: u32,
+    },
+    /// A migration function failed to convert the save data.
+    Migration { from_version: u32, message: String },
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SaveError::Io(ref e) => write!(f, "Save file IO error: {}", e),
+            SaveError::Serialization(ref e) => write!(f, "Malformed save data: {}", e),
+            SaveError::TooNew {
+                ref header,
+                supported_version,
+            } => write!(
+                f,
+                "Save is from a newer game version {} (format {}), this version only supports \
+                 formats up to {}",
+                header.game_version, header.format_version, supported_version
+            ),
+            SaveError::Migration {
+                from_version,
+                ref message,
+            } => write!(
+                f,
+                "Failed to migrate save from format {}: {}",
+                from_version, message
+            ),
+        }
+    }
+}
+
+impl Error for SaveError {}
+
+impl From<io::Error> for SaveError {
+    fn from(e: io::Error) -> SaveError { SaveError::Io(e) }
+}
+
+impl From<serde_json::Error> for SaveError {
+    fn from(e: serde_json::Error) -> SaveError { SaveError::Serialization(e) }
+}
+
+/// A migration converts save data from one format version to the next one.
+pub type Migration = Box<dyn Fn(Value) -> Result<Value, String>>;
+
+/// Save data layout description with migrations from older versions.
+///
+/// The format version is the number of registered migrations. When loading an older save, the
+/// migrations starting from the save's format version are run in order on the generic JSON
+/// representation of the save data before it is deserialized into the actual game type.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate serde_json;
+/// # extern crate calx;
+/// # fn main() {
+/// use calx::SaveFormat;
+///
+/// let old_format = SaveFormat::new("0.1.0");
+/// let mut file = Vec::new();
+/// old_format.save(&mut file, &vec![1, 2, 3]).unwrap();
+///
+/// // The game data type changed from a list to a total sum.
+/// let new_format = SaveFormat::new("0.2.0").migration(|v| {
+///     let sum: u64 = v.as_array()
+///         .ok_or("Expected a list")?
+///         .iter()
+///         .filter_map(|x| x.as_u64())
+///         .sum();
+///     Ok(serde_json::Value::from(sum))
+/// });
+///
+/// let (header, total): (_, u64) = new_format.load(&file[..]).unwrap();
+/// assert_eq!(header.game_version, "0.1.0");
+/// assert_eq!(total, 6);
+///
+/// // Newer saves can't be loaded with the older format.
+/// let mut file = Vec::new();
+/// new_format.save(&mut file, &6).unwrap();
+/// assert!(old_format.load::<_, u64>(&file[..]).is_err());
+/// # }
+/// ```
+pub struct SaveFormat {
+    game_version: String,
+    migrations: Vec<Migration>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SaveContents<T> {
+    header: SaveHeader,
+    data: T,
+}
+
+impl SaveFormat {
+    /// Create a save format with no migrations.
+    pub fn new(game_version: &str) -> SaveFormat {
+        SaveFormat {
+            game_version: game_version.to_string(),
+            migrations: Vec::new(),
+        }
+    }
+
+    /// Add a migration from the current format version to the next one.
+    pub fn migration<F>(mut self, f: F) -> SaveFormat
+    where
+        F: Fn(Value) -> Result<Value, String> + 'static,
+    {
+        self.migrations.push(Box::new(f));
+        self
+    }
+
+    /// Return the format version new saves are written with.
+    pub fn version(&self) -> u32 { self.migrations.len() as u32 }
+
+    /// Return the header for new saves.
+    pub fn header(&self) -> SaveHeader {
+        SaveHeader {
+            format_version: self.version(),
+            game_version: self.game_version.clone(),
+        }
+    }
+
+    /// Write save data with the current format header.
+    pub fn save<W: Write, T: Serialize>(&self, writer: W, data: &T) -> Result<(), SaveError> {
+        serde_json::to_writer(
+            writer,
+            &SaveContents {
+                header: self.header(),
+                data,
+            },
+        )?;
+        Ok(())
+    }
+
+    /// Read the header and the raw save data without migrating it.
+    pub fn load_raw<R: Read>(&self, reader: R) -> Result<(SaveHeader, Value), SaveError> {
+        let file: SaveContents<Value> = serde_json::from_reader(reader)?;
+        Ok((file.header, file.data))
+    }
+
+    /// Run the migrations needed to bring raw save data up to the current format version.
+    pub fn migrate(&self, header: &SaveHeader, mut data: Value) -> Result<Value, SaveError> {
+        if header.format_version > self.version() {
+            return Err(SaveError::TooNew {
+                header: header.clone(),
+                supported_version: self.version(),
+            });
+        }
+
+        for (i, migration) in self
+            .migrations
+            .iter()
+            .enumerate()
+            .skip(header.format_version as usize)
+        {
+            data = migration(data).map_err(|message| SaveError::Migration {
+                from_version: i as u32,
+                message,
+            })?;
+        }
+
+        Ok(data)
+    }
+
+    /// Load a save, migrating it from an older format version if needed.
+    ///
+    /// Returns the header the save was written with along with the loaded data.
+    pub fn load<R: Read, T: DeserializeOwned>(
+        &self,
+        reader: R,
+    ) -> Result<(SaveHeader, T), SaveError> {
+        let (header, data) = self.load_raw(reader)?;
+        let data = self.migrate(&header, data)?;
+        Ok((header, serde_json::from_value(data)?))
+    }
+
+    /// Migrate a save to the current format version without loading it as game data.
+    ///
+    /// Lets a tool bring old saves up to date offline. Returns the header of the original save.
+    pub fn upgrade<R: Read, W: Write>(
+        &self,
+        reader: R,
+        writer: W,
+    ) -> Result<SaveHeader, SaveError> {
+        let (header, data) = self.load_raw(reader)?;
+        let data = self.migrate(&header, data)?;
+        self.save(writer, &data)?;
+        Ok(header)
+    }
+}
+
+/// Summary of a save file for diagnosing broken saves.
+///
+/// Reads the generic save structure, so it works without the game's data types and the save
+/// format migrations.
+///
+/// # Examples
+///
+/// ```
+/// # #[macro_use] extern crate serde_json;
+/// # extern crate calx;
+/// # fn main() {
+/// use calx::{SaveFormat, SaveInfo};
+///
+/// let mut file = Vec::new();
+/// let data = json!({ "world": [1, 2, 3], "player": "Alice" });
+/// SaveFormat::new("0.1.0").save(&mut file, &data).unwrap();
+///
+/// let info = SaveInfo::read(&file[..]).unwrap();
+/// assert_eq!(info.header.game_version, "0.1.0");
+/// assert_eq!(info.size, file.len());
+/// assert_eq!(info.sections, vec![("player".to_string(), 7), ("world".to_string(), 7)]);
+/// println!("{}", info);
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct SaveInfo {
+    pub header: SaveHeader,
+    /// Size of the whole save file in bytes.
+    pub size: usize,
+    /// Names and encoded sizes in bytes of the top-level fields of the save data.
+    ///
+    /// Save data that isn't a map shows up as a single section named "data".
+    pub sections: Vec<(String, usize)>,
+    /// The save data as generic JSON.
+    pub data: Value,
+}
+
+impl SaveInfo {
+    pub fn read<R: Read>(mut reader: R) -> Result<SaveInfo, SaveError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        let file: SaveContents<Value> = serde_json::from_slice(&bytes)?;
+
+        let size = |v: &Value| serde_json::to_vec(v).map(|b| b.len());
+        let sections = match file.data {
+            Value::Object(ref map) => map
+                .iter()
+                .map(|(k, v)| Ok((k.clone(), size(v)?)))
+                .collect::<Result<Vec<_>, serde_json::Error>>()?,
+            ref data => vec![("data".to_string(), size(data)?)],
+        };
+
+        Ok(SaveInfo {
+            header: file.header,
+            size: bytes.len(),
+            sections,
+            data: file.data,
+        })
+    }
+
+    /// Write the save data as indented JSON for reading.
+    pub fn write_pretty<W: Write>(&self, writer: W) -> Result<(), SaveError> {
+        serde_json::to_writer_pretty(
+            writer,
+            &SaveContents {
+                header: self.header.clone(),
+                data: &self.data,
+            },
+        )?;
+        Ok(())
+    }
+}
+
+impl fmt::Display for SaveInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "game version {}, save format {}, {} bytes",
+            self.header.game_version, self.header.format_version, self.size
+        )?;
+        for (name, size) in &self.sections {
+            writeln!(f, "  {}: {} bytes", name, size)?;
+        }
+        Ok(())
+    }
+}
+
+/// Metadata of a save slot.
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct SlotInfo {
+    /// Name of the slot, also the file name of the save without the extension.
+    #[serde(skip)]
+    pub name: String,
+    pub header: SaveHeader,
+    /// Time the save was written in seconds since the Unix epoch.
+    pub timestamp: u64,
+    /// Game-defined preview for save menus, like a thumbnail image or a summary text.
+    pub preview: Vec<u8>,
+    /// Whether the save data is deflate compressed.
+    pub compressed: bool,
+}
+
+/// Directory of save slots that are written atomically.
+///
+/// Each slot is a file with a line of `SlotInfo` metadata followed by the save data written
+/// with a `SaveFormat`, so older saves are migrated when loading. The metadata can be read
+/// without loading the save data for listing the slots in a menu.
+///
+/// Saves are first written to a temporary file which is then renamed over the old save, so a
+/// crash in the middle of saving leaves the previous save intact.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate tempdir;
+/// # extern crate calx;
+/// # fn main() {
+/// use calx::{SaveFile, SaveFormat};
+///
+/// let dir = tempdir::TempDir::new("saves").unwrap();
+/// // Use `SaveFile::in_app_data` in a game.
+/// let saves = SaveFile::new(dir.path(), SaveFormat::new("0.1.0")).compressed(true);
+///
+/// saves.save("autosave", &vec![1, 2, 3], b"Dungeon level 3").unwrap();
+/// saves.save("manual", &vec![4, 5], b"Town").unwrap();
+///
+/// let slots = saves.slots().unwrap();
+/// assert_eq!(slots.len(), 2);
+/// assert!(slots.iter().any(|s| s.name == "manual" && s.preview == b"Town"));
+///
+/// let (info, data): (_, Vec<u32>) = saves.load("autosave").unwrap();
+/// assert_eq!(info.header.game_version, "0.1.0");
+/// assert_eq!(data, vec![1, 2, 3]);
+///
+/// saves.delete("autosave").unwrap();
+/// assert!(saves.load::<Vec<u32>>("autosave").is_err());
+/// # }
+/// ```
+pub struct SaveFile {
+    dir: PathBuf,
+    format: SaveFormat,
+    compress: bool,
+}
+
+impl SaveFile {
+    /// Create save slots in a directory.
+    pub fn new(dir: impl Into<PathBuf>, format: SaveFormat) -> SaveFile {
+        SaveFile {
+            dir: dir.into(),
+            format,
+            compress: false,
+        }
+    }
+
+    /// Create save slots in the `saves` subdirectory of the application data directory.
+    pub fn in_app_data(app_name: &str, format: SaveFormat) -> SaveFile {
+        SaveFile::new(app_data_path(app_name).join("saves"), format)
+    }
+
+    /// Set whether new saves are compressed.
+    ///
+    /// Both compressed and uncompressed saves can be loaded either way.
+    pub fn compressed(mut self, compress: bool) -> SaveFile {
+        self.compress = compress;
+        self
+    }
+
+    /// Return the path of a slot's save file.
+    pub fn path(&self, slot: &str) -> PathBuf { self.dir.join(format!("{}.{}", slot, EXTENSION)) }
+
+    /// Write a save into a slot, replacing any previous save in it.
+    pub fn save<T: Serialize>(
+        &self,
+        slot: &str,
+        data: &T,
+        preview: &[u8],
+    ) -> Result<(), SaveError> {
+        check_slot_name(slot)?;
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        let info = SlotInfo {
+            name: slot.to_string(),
+            header: self.format.header(),
+            timestamp,
+            preview: preview.to_vec(),
+            compressed: self.compress,
+        };
+
+        let mut payload = Vec::new();
+        self.format.save(&mut payload, data)?;
+        if self.compress {
+            payload = deflate::deflate_bytes(&payload);
+        }
+
+        fs::create_dir_all(&self.dir)?;
+        let path = self.path(slot);
+        let tmp_path = path.with_extension(format!("{}.tmp", EXTENSION));
+        {
+            let mut file = File::create(&tmp_path)?;
+            serde_json::to_writer(&mut file, &info)?;
+            file.write_all(b"\n")?;
+            file.write_all(&payload)?;
+            file.sync_all()?;
+        }
+        fs::rename(&tmp_path, &path)?;
+        Ok(())
+    }
+
+    /// Load the save in a slot, migrating it from an older format version if needed.
+    pub fn load<T: DeserializeOwned>(&self, slot: &str) -> Result<(SlotInfo, T), SaveError> {
+        check_slot_name(slot)?;
+        let mut reader = BufReader::new(File::open(self.path(slot))?);
+        let info = read_info(&mut reader, slot)?;
+        let mut payload = Vec::new();
+        reader.read_to_end(&mut payload)?;
+        if info.compressed {
+            payload = inflate::inflate_bytes(&payload)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        }
+        let (_, data) = self.format.load(&payload[..])?;
+        Ok((info, data))
+    }
+
+    /// Return the metadata of the existing save slots, newest first.
+    ///
+    /// Files that can't be read as saves are left out.
+    pub fn slots(&self) -> Result<Vec<SlotInfo>, SaveError> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut ret = Vec::new();
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().map_or(true, |e| e != EXTENSION) {
+                continue;
+            }
+            let name = match path.file_stem().and_then(|s| s.to_str()) {
+                Some(name) => name.to_string(),
+                None => continue,
+            };
+            let info = File::open(&path)
+                .map_err(SaveError::from)
+                .and_then(|f| read_info(&mut BufReader::new(f), &name));
+            if let Ok(info) = info {
+                ret.push(info);
+            }
+        }
+        ret.sort_by(|a, b| b.timestamp.cmp(&a.timestamp).then(a.name.cmp(&b.name)));
+        Ok(ret)
+    }
+
+    /// Delete the save in a slot.
+    pub fn delete(&self, slot: &str) -> Result<(), SaveError> {
+        check_slot_name(slot)?;
+        fs::remove_file(self.path(slot))?;
+        Ok(())
+    }
+}
+
+/// File extension of save slot files.
+const EXTENSION: &str = "sav";
+
+/// Slot names become file names, so they can't point outside the save directory.
+fn check_slot_name(slot: &str) -> Result<(), SaveError> {
+    if slot.is_empty() || Path::new(slot).file_name() != Some(slot.as_ref()) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Invalid save slot name '{}'", slot),
+        )
+        .into());
+    }
+    Ok(())
+}
+
+/// Read the metadata line at the start of a save slot file.
+fn read_info<R: BufRead>(reader: &mut R, slot: &str) -> Result<SlotInfo, SaveError> {
+    let mut line = Vec::new();
+    reader.read_until(b'\n', &mut line)?;
+    let mut info: SlotInfo = serde_json::from_slice(&line)?;
+    info.name = slot.to_string();
+    Ok(info)
+}
@@ -0,0 +1,189 @@
+//! Daily routines for NPCs
+
+use annotations::Annotations;
+use hex::{hex_neighbors, HexGeom};
+use search::astar_path;
+use CellVector;
+
+/// Daily routine that sends an actor between named map locations.
+///
+/// The schedule repeats every `day_length` time units, use whatever tick unit the game clock
+/// runs on. Each entry names an annotation in the map's annotation layer that the actor should
+/// be at from the start time of the entry until the start of the next one. The last entry of
+/// the day carries over past midnight until the first entry of the next day.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate euclid;
+/// # extern crate calx;
+/// # fn main() {
+/// use euclid::vec2;
+/// use calx::{Annotation, Annotations, Schedule};
+///
+/// let mut town = Annotations::new();
+/// town.insert(Annotation::new("home", vec2(0, 0)));
+/// town.insert(Annotation::region("smithy", vec![vec2(5, 0), vec2(6, 0)]));
+/// town.insert(Annotation::new("tavern", vec2(0, 4)));
+///
+/// // Day is 24 hours long.
+/// let smith = Schedule::new(24)
+///     .at(8, "smithy")
+///     .at(18, "tavern")
+///     .at(22, "home");
+/// assert_eq!(smith.place_at(3), Some("home"));
+/// assert_eq!(smith.place_at(24 + 9), Some("smithy"));
+///
+/// // Walk to work in the morning.
+/// let mut pos = vec2(0, 0);
+/// while let Some(next) = smith.step(9, pos, &town, |p| p.x.abs() < 10 && p.y.abs() < 10) {
+///     pos = next;
+/// }
+/// assert_eq!(pos, vec2(5, 0));
+/// # }
+/// ```
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Schedule {
+    day_length: u64,
+    entries: Vec<(u64, String)>,
+}
+
+impl Schedule {
+    /// Create an empty schedule with the length of the repeating day.
+    pub fn new(day_length: u64) -> Schedule {
+        assert!(day_length > 0, "Day length must be positive");
+        Schedule {
+            day_length,
+            entries: Vec::new(),
+        }
+    }
+
+    /// Add an entry for going to a named location at a time of day.
+    ///
+    /// An existing entry for the same time of day is replaced.
+    pub fn at(mut self, time_of_day: u64, place: impl Into<String>) -> Schedule {
+        let time_of_day = time_of_day % self.day_length;
+        let place = place.into();
+        match self.entries.binary_search_by_key(&time_of_day, |e| e.0) {
+            Ok(i) => self.entries[i].1 = place,
+            Err(i) => self.entries.insert(i, (time_of_day, place)),
+        }
+        self
+    }
+
+    /// Return the name of the location the actor should be at, at a given time.
+    ///
+    /// Returns `None` for an empty schedule.
+    pub fn place_at(&self, time: u64) -> Option<&str> {
+        let time_of_day = time % self.day_length;
+        let idx = self.entries.partition_point(|e| e.0 <= time_of_day);
+        // Before the first entry of the day, the last one from the previous day holds.
+        let entry = if idx == 0 {
+            self.entries.last()
+        } else {
+            self.entries.get(idx - 1)
+        };
+        entry.map(|e| &e.1[..])
+    }
+
+    /// Find the path from a position to the scheduled location at a given time.
+    ///
+    /// The path starts from `pos` and ends at the nearest passable cell of the location. The
+    /// `is_passable` function must block off an area around the map so that searches for
+    /// unreachable locations terminate. Returns `None` if the location doesn't exist in the
+    /// annotation layer or can't be reached.
+    pub fn path<F>(
+        &self,
+        time: u64,
+        pos: CellVector,
+        places: &Annotations,
+        is_passable: F,
+    ) -> Option<Vec<CellVector>>
+    where
+        F: Fn(CellVector) -> bool,
+    {
+        let place = places.get(self.place_at(time)?)?;
+        if place.contains(pos) {
+            return Some(vec![pos]);
+        }
+        let goal = place
+            .cells
+            .iter()
+            .filter(|&&p| is_passable(p))
+            .min_by_key(|&&p| (p - pos).hex_dist())?;
+
+        let is_passable = &is_passable;
+        astar_path(pos, goal, |&p| {
+            hex_neighbors(p)
+                .filter(move |&q| is_passable(q))
+                .map(move |q| (q, (*goal - q).hex_dist() as f32))
+        })
+    }
+
+    /// Return the next cell to move to for following the schedule at a given time.
+    ///
+    /// Returns `None` if the actor is already at the scheduled location or the location can't be
+    /// reached.
+    pub fn step<F>(
+        &self,
+        time: u64,
+        pos: CellVector,
+        places: &Annotations,
+        is_passable: F,
+    ) -> Option<CellVector>
+    where
+        F: Fn(CellVector) -> bool,
+    {
+        self.path(time, pos, places, is_passable)?.get(1).cloned()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Schedule;
+    use annotations::{Annotation, Annotations};
+    use euclid::vec2;
+    use CellVector;
+
+    #[test]
+    fn test_schedule() {
+        assert_eq!(Schedule::new(10).place_at(5), None);
+
+        let schedule = Schedule::new(100)
+            .at(20, "field")
+            .at(60, "home")
+            .at(190, "well");
+        assert_eq!(schedule.place_at(0), Some("well"));
+        assert_eq!(schedule.place_at(19), Some("well"));
+        assert_eq!(schedule.place_at(20), Some("field"));
+        assert_eq!(schedule.place_at(59), Some("field"));
+        assert_eq!(schedule.place_at(89), Some("home"));
+        assert_eq!(schedule.place_at(99), Some("well"));
+        assert_eq!(schedule.place_at(1020), Some("field"));
+
+        let mut places = Annotations::new();
+        places.insert(Annotation::new("home", vec2(0, 0)));
+        places.insert(Annotation::region("field", vec![vec2(6, 0), vec2(7, 0)]));
+        // Well is behind a wall.
+        places.insert(Annotation::new("well", vec2(0, 8)));
+
+        // Wall across the map with a gap at x = 4.
+        let passable = |p: CellVector| p.x.abs() <= 10 && p.y.abs() <= 10 && (p.x == 4 || p.y != 4);
+        let path = schedule.path(30, vec2(0, 0), &places, passable).unwrap();
+        assert_eq!(path[0], vec2(0, 0));
+        assert_eq!(*path.last().unwrap(), vec2(6, 0));
+        let path = schedule.path(30, vec2(7, 0), &places, passable);
+        assert_eq!(path, Some(vec![vec2(7, 0)]));
+        assert_eq!(schedule.step(30, vec2(7, 0), &places, passable), None);
+
+        let path = schedule.path(10, vec2(0, 0), &places, passable).unwrap();
+        assert!(path.contains(&vec2(4, 4)));
+
+        // Well is outside the passable area.
+        let blocked = |p: CellVector| p.x.abs() <= 10 && p.y.abs() <= 3;
+        assert_eq!(schedule.step(10, vec2(0, 0), &places, blocked), None);
+        // Unknown location.
+        let schedule = schedule.at(0, "nowhere");
+        assert_eq!(schedule.step(0, vec2(0, 0), &places, passable), None);
+    }
+}
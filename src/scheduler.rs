@@ -0,0 +1,262 @@
+//! Speed-based turn order for actors
+
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+/// Speed of an actor that acts at the normal rate.
+///
+/// An action with cost `NORMAL_SPEED` takes one normal turn of game time, twice as long for an
+/// actor with half the normal speed and half as long for one with double the normal speed.
+pub const NORMAL_SPEED: u32 = 100;
+
+/// Queue of actors ordered by when they get to act next.
+///
+/// Actors are added with a speed value. `next` returns the actor whose turn it is and advances
+/// the game clock to the time of the turn. The acting actor then spends time on its action with
+/// `spend`, and the time is scaled by the actor's speed, so that fast actors get more turns
+/// than slow ones. Actors scheduled for the same time act in the order they were queued, so the
+/// turn order is deterministic and the scheduler can be saved and loaded with the rest of the
+/// game state.
+///
+/// # Examples
+///
+/// ```
+/// # #[macro_use] extern crate serde_derive;
+/// # extern crate serde_json;
+/// # extern crate calx;
+/// # fn main() {
+/// use calx::{Scheduler, NORMAL_SPEED};
+///
+/// #[derive(Copy, Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+/// enum Actor {
+///     Player,
+///     Bat,
+/// }
+///
+/// let mut scheduler = Scheduler::new();
+/// scheduler.add(Actor::Player, NORMAL_SPEED);
+/// scheduler.add(Actor::Bat, 2 * NORMAL_SPEED);
+///
+/// let mut turns = Vec::new();
+/// for _ in 0..6 {
+///     let actor = scheduler.next().unwrap();
+///     turns.push(actor);
+///     // Everyone does regular actions.
+///     scheduler.spend(NORMAL_SPEED);
+/// }
+/// let (p, b) = (Actor::Player, Actor::Bat);
+/// assert_eq!(turns, vec![p, b, b, p, b, b]);
+/// assert_eq!(scheduler.now(), 150);
+///
+/// let saved = serde_json::to_string(&scheduler).unwrap();
+/// let mut loaded: Scheduler<Actor> = serde_json::from_str(&saved).unwrap();
+/// assert_eq!(loaded.next(), Some(Actor::Player));
+/// # }
+/// ```
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Scheduler<A> {
+    /// Time of the current turn.
+    now: u64,
+    /// Running count of queued turns, used to keep the order of simultaneous turns stable.
+    seq: u64,
+    /// Actors waiting for their turn.
+    queue: BinaryHeap<Turn<A>>,
+    /// Actor whose turn it is, if it hasn't spent time on an action yet.
+    current: Option<Turn<A>>,
+}
+
+/// Queued turn of an actor.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct Turn<A> {
+    time: u64,
+    seq: u64,
+    actor: A,
+    speed: u32,
+}
+
+impl<A> Turn<A> {
+    fn key(&self) -> (u64, u64) { (self.time, self.seq) }
+}
+
+impl<A> PartialEq for Turn<A> {
+    fn eq(&self, other: &Turn<A>) -> bool { self.key() == other.key() }
+}
+
+impl<A> Eq for Turn<A> {}
+
+impl<A> PartialOrd for Turn<A> {
+    fn partial_cmp(&self, other: &Turn<A>) -> Option<Ordering> { Some(self.cmp(other)) }
+}
+
+impl<A> Ord for Turn<A> {
+    fn cmp(&self, other: &Turn<A>) -> Ordering {
+        // Reversed, BinaryHeap returns the largest item first and the earliest turn should go
+        // first.
+        other.key().cmp(&self.key())
+    }
+}
+
+impl<A: Clone + PartialEq> Scheduler<A> {
+    pub fn new() -> Scheduler<A> {
+        Scheduler {
+            now: 0,
+            seq: 0,
+            queue: BinaryHeap::new(),
+            current: None,
+        }
+    }
+
+    /// Add an actor to act at the current time, after the actors already waiting to act.
+    pub fn add(&mut self, actor: A, speed: u32) {
+        let now = self.now;
+        self.push(now, actor, speed);
+    }
+
+    /// Add an actor to act after a delay in game time.
+    pub fn add_after(&mut self, delay: u64, actor: A, speed: u32) {
+        let time = self.now + delay;
+        self.push(time, actor, speed);
+    }
+
+    fn push(&mut self, time: u64, actor: A, speed: u32) {
+        assert!(speed > 0, "Actor speed must be positive");
+        self.queue.push(Turn {
+            time,
+            seq: self.seq,
+            actor,
+            speed,
+        });
+        self.seq += 1;
+    }
+
+    /// Remove an actor from the schedule.
+    ///
+    /// Returns whether the actor was found.
+    pub fn remove(&mut self, actor: &A) -> bool {
+        if self.current.as_ref().is_some_and(|t| &t.actor == actor) {
+            self.current = None;
+            return true;
+        }
+        let len = self.queue.len();
+        self.queue.retain(|t| &t.actor != actor);
+        self.queue.len() != len
+    }
+
+    /// Advance to the next actor's turn and return the actor.
+    ///
+    /// If the previous actor didn't call `spend`, it is treated as having waited for a turn
+    /// with the cost of `NORMAL_SPEED`.
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Option<A> {
+        if self.current.is_some() {
+            self.spend(NORMAL_SPEED);
+        }
+        let turn = self.queue.pop()?;
+        self.now = turn.time;
+        let actor = turn.actor.clone();
+        self.current = Some(turn);
+        Some(actor)
+    }
+
+    /// Return the actor whose turn it currently is.
+    pub fn current(&self) -> Option<&A> { self.current.as_ref().map(|t| &t.actor) }
+
+    /// End the current actor's turn with an action of the given cost.
+    ///
+    /// The actor's next turn is `cost * NORMAL_SPEED / speed` ticks in the future, rounded up.
+    /// Does nothing if there is no current actor.
+    pub fn spend(&mut self, cost: u32) {
+        if let Some(turn) = self.current.take() {
+            let (ticks, speed) = (cost as u64 * NORMAL_SPEED as u64, turn.speed as u64);
+            let delay = (ticks + speed - 1) / speed;
+            self.add_after(delay, turn.actor, turn.speed);
+        }
+    }
+
+    /// Change the speed of an actor, starting from its next action.
+    ///
+    /// Returns whether the actor was found.
+    pub fn set_speed(&mut self, actor: &A, speed: u32) -> bool {
+        assert!(speed > 0, "Actor speed must be positive");
+        if let Some(turn) = self.current.as_mut().filter(|t| &t.actor == actor) {
+            turn.speed = speed;
+            return true;
+        }
+        // BinaryHeap doesn't allow mutable access to items, speed doesn't affect the ordering
+        // so the heap can be rebuilt as is.
+        let mut queue = ::std::mem::take(&mut self.queue).into_vec();
+        let mut found = false;
+        for t in queue.iter_mut().filter(|t| &t.actor == actor) {
+            t.speed = speed;
+            found = true;
+        }
+        self.queue = queue.into();
+        found
+    }
+
+    /// Return the game time of the current turn.
+    pub fn now(&self) -> u64 { self.now }
+
+    /// Return the number of scheduled actors, including the current one.
+    pub fn len(&self) -> usize { self.queue.len() + self.current.iter().count() }
+
+    pub fn is_empty(&self) -> bool { self.len() == 0 }
+
+    /// Iterate the scheduled actors with the times of their next turns in no particular order.
+    ///
+    /// The current actor is not included.
+    pub fn iter(&self) -> impl Iterator<Item = (u64, &A)> {
+        self.queue.iter().map(|t| (t.time, &t.actor))
+    }
+}
+
+impl<A: Clone + PartialEq> Default for Scheduler<A> {
+    fn default() -> Scheduler<A> { Scheduler::new() }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Scheduler, NORMAL_SPEED};
+    use serde_json;
+
+    #[test]
+    fn test_scheduler() {
+        let mut s = Scheduler::new();
+        assert_eq!(s.next(), None);
+
+        s.add('a', NORMAL_SPEED);
+        s.add('b', NORMAL_SPEED);
+        s.add_after(30, 'c', 3 * NORMAL_SPEED);
+        assert_eq!(s.len(), 3);
+
+        // Simultaneous actors go in insertion order.
+        assert_eq!(s.next(), Some('a'));
+        assert_eq!(s.current(), Some(&'a'));
+        s.spend(50);
+        assert_eq!(s.next(), Some('b'));
+        // Skipping spend takes a normal turn.
+        assert_eq!(s.next(), Some('c'));
+        assert_eq!(s.now(), 30);
+        assert_eq!(s.len(), 3);
+        // 100 / 3 rounds up.
+        s.spend(NORMAL_SPEED);
+        let mut times: Vec<_> = s.iter().map(|(t, &a)| (t, a)).collect();
+        times.sort();
+        assert_eq!(times, vec![(50, 'a'), (64, 'c'), (100, 'b')]);
+
+        assert!(s.set_speed(&'a', NORMAL_SPEED / 2));
+        assert_eq!(s.next(), Some('a'));
+        s.spend(NORMAL_SPEED);
+        assert!(s.iter().any(|(t, &a)| (t, a) == (250, 'a')));
+
+        let saved = serde_json::to_string(&s).unwrap();
+        let mut loaded: Scheduler<char> = serde_json::from_str(&saved).unwrap();
+        assert_eq!(loaded.next(), Some('c'));
+        assert!(loaded.remove(&'c'));
+        assert!(loaded.remove(&'a'));
+        assert!(!loaded.remove(&'a'));
+        assert_eq!(loaded.next(), Some('b'));
+        assert_eq!(loaded.now(), 100);
+        assert!(!loaded.set_speed(&'x', 1));
+    }
+}
@@ -1,6 +1,9 @@
-use std::cmp::Ordering;
+use hex::Dir6;
+use mem_stats::HeapSize;
+use std::cmp::Reverse;
 use std::collections::{BinaryHeap, HashMap, HashSet};
 use std::hash::Hash;
+use CellVector;
 
 /// A node in a graph with a regular grid.
 pub trait GridNode: PartialEq + Eq + Clone + Hash + PartialOrd + Ord {
@@ -8,6 +11,158 @@ pub trait GridNode: PartialEq + Eq + Clone + Hash + PartialOrd + Ord {
     fn neighbors(&self) -> Vec<Self>;
 }
 
+/// Receiver for the progress of a search algorithm.
+///
+/// Observers are intended for debugging and visualizing searches, all methods have empty default
+/// implementations. The unit type is the no-op observer.
+pub trait SearchObserver<N> {
+    /// A node was added to the search frontier.
+    fn frontier(&mut self, _node: &N) {}
+
+    /// The neighbors of a node were expanded.
+    fn expanded(&mut self, _node: &N) {}
+
+    /// The search finished with a path.
+    fn path(&mut self, _path: &[N]) {}
+}
+
+impl<N> SearchObserver<N> for () {}
+
+/// Search observer that records the events it sees.
+#[derive(Clone, Debug)]
+pub struct SearchTrace<N> {
+    /// Nodes added to the frontier in the order they were added.
+    pub frontier: Vec<N>,
+    /// Nodes expanded in the order they were expanded.
+    pub expanded: Vec<N>,
+    /// Path found by the search, empty if no path was found.
+    pub path: Vec<N>,
+}
+
+impl<N> Default for SearchTrace<N> {
+    fn default() -> Self {
+        SearchTrace {
+            frontier: Vec::new(),
+            expanded: Vec::new(),
+            path: Vec::new(),
+        }
+    }
+}
+
+impl<N: Clone> SearchObserver<N> for SearchTrace<N> {
+    fn frontier(&mut self, node: &N) { self.frontier.push(node.clone()); }
+
+    fn expanded(&mut self, node: &N) { self.expanded.push(node.clone()); }
+
+    fn path(&mut self, path: &[N]) { self.path = path.to_vec(); }
+}
+
+/// Reusable scratch memory for the search algorithms.
+///
+/// The search functions store the nodes they visit in an index-based arena instead of building
+/// new maps for every query. Keep a `SearchBuffers` value around between queries and pass it to
+/// the `_in` variants of the search functions to reuse the allocated memory.
+pub struct SearchBuffers<N> {
+    /// Arena index for every node seen during the current search.
+    index: HashMap<N, usize>,
+    /// The node arena.
+    nodes: Vec<SearchNode<N>>,
+    /// Open set of the best-first searches.
+    open: BinaryHeap<OpenItem>,
+    /// Current and next frontier of breadth-first searches.
+    frontier: (Vec<N>, Vec<N>),
+}
+
+struct SearchNode<N> {
+    item: N,
+    come_from: Option<usize>,
+    closed: bool,
+    /// Lowest known path cost from the search origin.
+    cost: f32,
+}
+
+/// Convert a distance into an open set priority.
+fn priority(dist: f32) -> u32 {
+    debug_assert!(dist >= 0.0);
+    // Convert dist to integers so we can push items into BinaryHeap that expects Ord.
+    // The trick here is that non-negative IEEE 754 floats have the same ordering as their
+    // binary representations interpreted as integers.
+    //
+    // Also flip the sign on the value, shorter distance means bigger value, since BinaryHeap
+    // returns the largest item first.
+    ::std::u32::MAX - dist.to_bits()
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+struct OpenItem {
+    /// Priority, larger values are popped first.
+    value: u32,
+    idx: usize,
+    come_from: Option<usize>,
+}
+
+impl<N: Eq + Hash + Clone> SearchBuffers<N> {
+    pub fn new() -> SearchBuffers<N> {
+        SearchBuffers {
+            index: HashMap::new(),
+            nodes: Vec::new(),
+            open: BinaryHeap::new(),
+            frontier: (Vec::new(), Vec::new()),
+        }
+    }
+
+    /// Clear the buffers for a new search while keeping the allocated memory.
+    fn clear(&mut self) {
+        self.index.clear();
+        self.nodes.clear();
+        self.open.clear();
+        self.frontier.0.clear();
+        self.frontier.1.clear();
+    }
+
+    /// Return the arena index of a node, adding it to the arena if it isn't there yet.
+    fn node_index(&mut self, item: &N) -> usize {
+        if let Some(&idx) = self.index.get(item) {
+            return idx;
+        }
+
+        let idx = self.nodes.len();
+        self.nodes.push(SearchNode {
+            item: item.clone(),
+            come_from: None,
+            closed: false,
+            cost: f32::INFINITY,
+        });
+        self.index.insert(item.clone(), idx);
+        idx
+    }
+
+    /// Return the path from the search origin to an arena node.
+    fn path_to(&self, mut idx: usize) -> Vec<N> {
+        let mut path = vec![self.nodes[idx].item.clone()];
+        while let Some(prev) = self.nodes[idx].come_from {
+            path.push(self.nodes[prev].item.clone());
+            idx = prev;
+        }
+        path.reverse();
+        path
+    }
+}
+
+impl<N: Eq + Hash + Clone> Default for SearchBuffers<N> {
+    fn default() -> Self { SearchBuffers::new() }
+}
+
+impl<N> HeapSize for SearchBuffers<N> {
+    fn heap_size(&self) -> usize {
+        self.index.heap_size()
+            + self.nodes.heap_size()
+            + self.open.capacity() * ::std::mem::size_of::<OpenItem>()
+            + self.frontier.0.heap_size()
+            + self.frontier.1.heap_size()
+    }
+}
+
 /// A pathfinding map structure.
 ///
 /// A Dijkstra map lets you run pathfinding from any graph node it covers
@@ -22,37 +177,136 @@ impl<N: GridNode> Dijkstra<N> {
     /// Create a new Dijkstra map up to limit distance from goals, omitting
     /// nodes for which the is_valid predicate returns false.
     pub fn new<F: Fn(&N) -> bool>(goals: Vec<N>, is_valid: F, limit: u32) -> Dijkstra<N> {
-        assert!(!goals.is_empty());
+        let mut ret = Dijkstra {
+            weights: HashMap::new(),
+        };
+        ret.recompute_in(goals, is_valid, limit, &mut SearchBuffers::new());
+        ret
+    }
+
+    /// Recompute the map in place for new goals, reusing the memory of the map and the buffers.
+    pub fn recompute_in<F: Fn(&N) -> bool>(
+        &mut self,
+        goals: Vec<N>,
+        is_valid: F,
+        limit: u32,
+        buffers: &mut SearchBuffers<N>,
+    ) {
+        self.recompute_observed(goals, is_valid, limit, buffers, &mut ());
+    }
 
-        let mut weights = HashMap::new();
-        let mut edge = HashSet::new();
+    /// Recompute the map and report the progress to an observer.
+    pub fn recompute_observed<F: Fn(&N) -> bool>(
+        &mut self,
+        goals: Vec<N>,
+        is_valid: F,
+        limit: u32,
+        buffers: &mut SearchBuffers<N>,
+        observer: &mut impl SearchObserver<N>,
+    ) {
+        assert!(!goals.is_empty());
+        buffers.clear();
+        self.weights.clear();
 
-        for n in goals {
-            edge.insert(n);
+        if limit == 0 {
+            return;
         }
 
-        for dist in 0..(limit) {
-            for n in &edge {
-                weights.insert(n.clone(), dist);
+        let (ref mut edge, ref mut new_edge) = buffers.frontier;
+
+        for n in goals {
+            if !self.weights.contains_key(&n) {
+                observer.frontier(&n);
+                self.weights.insert(n.clone(), 0);
+                edge.push(n);
             }
+        }
 
-            let mut new_edge = HashSet::new();
-            for n in &edge {
+        for dist in 1..limit {
+            new_edge.clear();
+            for n in edge.iter() {
+                observer.expanded(n);
                 for m in n.neighbors() {
-                    if is_valid(&m) && !weights.contains_key(&m) {
-                        new_edge.insert(m);
+                    if is_valid(&m) && !self.weights.contains_key(&m) {
+                        observer.frontier(&m);
+                        self.weights.insert(m.clone(), dist);
+                        new_edge.push(m);
                     }
                 }
             }
 
-            edge = new_edge;
+            ::std::mem::swap(edge, new_edge);
 
             if edge.is_empty() {
                 break;
             }
         }
+    }
+
+    /// Repair the map after the validity of some nodes has changed.
+    ///
+    /// Nodes that can no longer reach the goals the same way as before are removed and the
+    /// remaining map is expanded again from the edges of the changed area, so only the affected
+    /// part of the map gets recomputed. The result is the same as building the map from scratch
+    /// with the same goals, validity predicate and limit, as long as the neighbor relation of
+    /// the nodes is symmetric.
+    pub fn update<F: Fn(&N) -> bool>(&mut self, changed: &[N], is_valid: F, limit: u32) {
+        let mut removed = Vec::new();
+
+        // Remove changed nodes that became invalid. Goal nodes stay on the map regardless.
+        for n in changed {
+            if let Some(&w) = self.weights.get(n) {
+                if w > 0 && !is_valid(n) {
+                    self.weights.remove(n);
+                    removed.push((n.clone(), w));
+                }
+            }
+        }
+
+        // Remove the nodes that were reached through removed nodes and have no other neighbor
+        // one step closer to the goals.
+        let mut i = 0;
+        while i < removed.len() {
+            let w = removed[i].1;
+            for m in removed[i].0.neighbors() {
+                if self.weights.get(&m) != Some(&(w + 1)) {
+                    continue;
+                }
+                let is_supported = m
+                    .neighbors()
+                    .iter()
+                    .any(|k| self.weights.get(k) == Some(&w));
+                if !is_supported {
+                    self.weights.remove(&m);
+                    removed.push((m, w + 1));
+                }
+            }
+            i += 1;
+        }
 
-        Dijkstra { weights }
+        // Expand the remaining map into the cleared area and the newly valid nodes.
+        let mut open = BinaryHeap::new();
+        for n in changed.iter().chain(removed.iter().map(|(n, _)| n)) {
+            for m in n.neighbors() {
+                if let Some(&w) = self.weights.get(&m) {
+                    open.push(Reverse((w, m)));
+                }
+            }
+        }
+
+        while let Some(Reverse((w, n))) = open.pop() {
+            if self.weights[&n] < w || w + 1 >= limit {
+                continue;
+            }
+
+            for m in n.neighbors() {
+                if !is_valid(&m) || self.weights.get(&m).is_some_and(|&old| old <= w + 1) {
+                    continue;
+                }
+                self.weights.insert(m.clone(), w + 1);
+                open.push(Reverse((w + 1, m)));
+            }
+        }
     }
 
     /// Return the neighbors of a cell (if any), sorted from downhill to
@@ -69,91 +323,621 @@ impl<N: GridNode> Dijkstra<N> {
     }
 }
 
-/// Find A* path in freeform graph.
+/// A Dijkstra map with weighted goals.
 ///
-/// The `neighbors` function returns neighboring nodes and their estimated distance from the goal.
-/// The search will treat any node whose distance is zero as a goal and return a path leading to
-/// it.
-pub fn astar_path<N, F>(start: N, end: &N, neighbors: F) -> Option<Vec<N>>
+/// Unlike `Dijkstra`, every goal node starts with its own weight, which may be negative, and
+/// every other node gets the lowest goal weight plus distance to that goal. Monsters that roll
+/// downhill on the map move towards the most attractive goal that they can reach soonest.
+///
+/// # Examples
+///
+/// ```
+/// use calx::{DijkstraMap, GridNode};
+///
+/// #[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
+/// struct Pos(i32);
+///
+/// impl GridNode for Pos {
+///     fn neighbors(&self) -> Vec<Pos> { vec![Pos(self.0 - 1), Pos(self.0 + 1)] }
+/// }
+///
+/// // A small treasure at -2 and a big treasure at 5.
+/// let valid = |p: &Pos| p.0.abs() <= 10;
+/// let map = DijkstraMap::new(vec![(Pos(-2), 0), (Pos(5), -4)], valid, 20);
+/// assert_eq!(Some(1), map.get(&Pos(0)));
+/// assert_eq!(Some(Pos(1)), map.roll_downhill(&Pos(0)));
+///
+/// // The flee map leads away from the player at 0.
+/// let player = DijkstraMap::new(vec![(Pos(0), 0)], valid, 20);
+/// let flee = player.flee(valid, 1.2, 20);
+/// assert_eq!(Some(Pos(3)), flee.roll_downhill(&Pos(2)));
+/// ```
+#[derive(Clone, Debug)]
+pub struct DijkstraMap<N> {
+    pub weights: HashMap<N, i32>,
+}
+
+impl<N: GridNode> DijkstraMap<N> {
+    /// Build a map from goal nodes with initial weights.
+    ///
+    /// Nodes for which `is_valid` returns false are omitted, and the expansion stops at nodes
+    /// whose weight would exceed `max_weight`.
+    pub fn new<F>(goals: Vec<(N, i32)>, is_valid: F, max_weight: i32) -> DijkstraMap<N>
+    where
+        F: Fn(&N) -> bool,
+    {
+        let weights = expand_weights(
+            goals,
+            |n| n.neighbors(),
+            |n| Some(1).filter(|_| is_valid(n)),
+            max_weight,
+        );
+        DijkstraMap { weights }
+    }
+
+    /// Return the weight of a node if the node is on the map.
+    pub fn get(&self, node: &N) -> Option<i32> { self.weights.get(node).cloned() }
+
+    /// Return the neighbor with the lowest weight if it is lower than the weight of the node.
+    ///
+    /// Returns `None` when the node is at a local minimum of the map.
+    pub fn roll_downhill(&self, node: &N) -> Option<N> {
+        let (w, n) = node
+            .neighbors()
+            .into_iter()
+            .filter_map(|n| self.get(&n).map(|w| (w, n)))
+            .min()?;
+
+        match self.get(node) {
+            Some(current) if current <= w => None,
+            _ => Some(n),
+        }
+    }
+
+    /// Build a map for fleeing from the goals of this map.
+    ///
+    /// The weights of the map are multiplied by `-coefficient` and used as the goals of a new
+    /// map. With coefficients somewhat above 1, fleeing creatures prefer running past the
+    /// threat into open areas to getting cornered in dead ends.
+    pub fn flee<F>(&self, is_valid: F, coefficient: f32, max_weight: i32) -> DijkstraMap<N>
+    where
+        F: Fn(&N) -> bool,
+    {
+        let mut goals: Vec<(N, i32)> = self
+            .weights
+            .iter()
+            .map(|(n, &w)| (n.clone(), (w as f32 * -coefficient).round() as i32))
+            .collect();
+        // Keep the result independent of hash map iteration order.
+        goals.sort();
+        DijkstraMap::new(goals, is_valid, max_weight)
+    }
+}
+
+/// Expand weights outwards from goal nodes in a graph with costs for entering nodes.
+///
+/// Every reached node gets the lowest goal weight plus path cost to that goal. The `cost`
+/// function gives the non-negative cost of entering a node or `None` if the node can't be
+/// entered. The expansion stops at nodes whose weight reaches `max_weight`.
+///
+/// On an unbounded graph, costs must be positive. With zero or negative costs the weights may
+/// never reach `max_weight` and the expansion doesn't terminate.
+pub fn expand_weights<N, I, F, C>(
+    goals: impl IntoIterator<Item = (N, i32)>,
+    neighbors: F,
+    cost: C,
+    max_weight: i32,
+) -> HashMap<N, i32>
 where
     N: Eq + Hash + Clone,
-    F: Fn(&N) -> Vec<(N, f32)>,
+    F: Fn(&N) -> I,
+    I: IntoIterator<Item = N>,
+    C: Fn(&N) -> Option<i32>,
 {
-    #[derive(Eq, PartialEq)]
-    struct MetricNode<N> {
-        value: u32,
-        item: N,
-        come_from: Option<N>,
+    let mut weights: HashMap<N, i32> = HashMap::new();
+    // Nodes are stored separately so that they don't need to be ordered for the heap.
+    let mut nodes = Vec::new();
+    let mut open = BinaryHeap::new();
+
+    for (n, w) in goals {
+        if weights.get(&n).map_or(true, |&old| w < old) {
+            weights.insert(n.clone(), w);
+            open.push(Reverse((w, nodes.len())));
+            nodes.push(n);
+        }
     }
-    impl<N: Eq> Ord for MetricNode<N> {
-        fn cmp(&self, other: &Self) -> Ordering { self.value.cmp(&other.value) }
+
+    while let Some(Reverse((w, idx))) = open.pop() {
+        let n = nodes[idx].clone();
+        if weights[&n] < w {
+            // Already reached through a better path.
+            continue;
+        }
+        if w >= max_weight {
+            continue;
+        }
+
+        for m in neighbors(&n) {
+            let w = match cost(&m) {
+                Some(c) => w + c,
+                None => continue,
+            };
+            if weights.get(&m).is_some_and(|&old| old <= w) {
+                continue;
+            }
+            weights.insert(m.clone(), w);
+            open.push(Reverse((w, nodes.len())));
+            nodes.push(m);
+        }
     }
-    impl<N: Eq> PartialOrd for MetricNode<N> {
-        fn partial_cmp(&self, other: &Self) -> Option<Ordering> { Some(self.cmp(other)) }
+
+    weights
+}
+
+/// Find a path to the nearest unexplored node.
+///
+/// Unexplored nodes are assumed to be passable. The search expands from `start` through explored
+/// passable nodes up to `limit` steps and returns the path to the closest unexplored node it
+/// reaches, ending with that node. Returns `None` if there are no reachable unexplored nodes.
+///
+/// # Examples
+///
+/// ```
+/// use calx::{autoexplore, GridNode};
+///
+/// #[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
+/// struct Pos(i32);
+///
+/// impl GridNode for Pos {
+///     fn neighbors(&self) -> Vec<Pos> { vec![Pos(self.0 - 1), Pos(self.0 + 1)] }
+/// }
+///
+/// // Explored corridor from -2 to 5, with a wall at 1.
+/// let explored = |p: &Pos| p.0 >= -2 && p.0 <= 5;
+/// let passable = |p: &Pos| p.0 != 1;
+///
+/// assert_eq!(
+///     Some(vec![Pos(0), Pos(-1), Pos(-2), Pos(-3)]),
+///     autoexplore(&Pos(0), explored, passable, 100)
+/// );
+/// assert_eq!(None, autoexplore(&Pos(0), explored, passable, 2));
+/// ```
+pub fn autoexplore<N, E, P>(start: &N, is_explored: E, is_passable: P, limit: u32) -> Option<Vec<N>>
+where
+    N: GridNode,
+    E: Fn(&N) -> bool,
+    P: Fn(&N) -> bool,
+{
+    let map = Dijkstra::new(
+        vec![start.clone()],
+        |n| !is_explored(n) || is_passable(n),
+        limit.saturating_add(1),
+    );
+
+    // Everything closer than the nearest unexplored node is explored, so the downhill path from
+    // it back to start only goes through explored nodes.
+    let (mut dist, target) = map
+        .weights
+        .iter()
+        .filter(|&(n, _)| !is_explored(n))
+        .map(|(n, &w)| (w, n))
+        .min()?;
+
+    let mut path = vec![target.clone()];
+    while dist > 0 {
+        let next = map
+            .sorted_neighbors(&path[path.len() - 1])
+            .into_iter()
+            .find(|n| map.weights[n] == dist - 1)
+            .expect("Broken Dijkstra map");
+        path.push(next);
+        dist -= 1;
     }
+    path.reverse();
+    Some(path)
+}
 
-    fn node<N: Eq>(item: N, dist: f32, come_from: Option<N>) -> MetricNode<N> {
-        debug_assert!(dist >= 0.0);
-        // Convert dist to integers so we can push MetricNodes into BinaryHeap that expects Ord.
-        // The trick here is that non-negative IEEE 754 floats have the same ordering as their
-        // binary representations interpreted as integers.
-        //
-        // Also flip the sign on the value, shorter distance means bigger value, since BinaryHeap
-        // returns the largest item first.
-        let value = ::std::u32::MAX - dist.to_bits();
-        MetricNode {
-            item,
-            value,
-            come_from,
-        }
+/// Shared movement directions towards a set of goals on a hex grid.
+///
+/// The field is computed once with a breadth-first search from the goals and stores the
+/// direction of the next step for every reached cell, so any number of agents can follow it
+/// with a single lookup per move instead of searching paths of their own.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate euclid;
+/// # extern crate calx;
+/// # fn main() {
+/// use euclid::vec2;
+/// use calx::{hex_disc, Dir6, FlowField};
+///
+/// let arena: Vec<_> = hex_disc(vec2(0, 0), 5).collect();
+/// let wall = vec2(-1, -1);
+/// let field = FlowField::new(&[vec2(0, 0)], |p| *p != wall && arena.contains(p), 10);
+///
+/// assert_eq!(field.direction(vec2(2, 0)), Some(Dir6::Northwest));
+/// assert_eq!(field.direction(vec2(0, 0)), None);
+/// assert_eq!(field.distance(vec2(-2, -2)), Some(3));
+///
+/// // Follow the field to the goal.
+/// let mut pos = vec2(-2, -2);
+/// while let Some(next) = field.step(pos) {
+///     pos = next;
+/// }
+/// assert_eq!(pos, vec2(0, 0));
+/// # }
+/// ```
+#[derive(Clone, Default, Debug)]
+pub struct FlowField {
+    cells: HashMap<CellVector, (u32, Option<Dir6>)>,
+}
+
+impl FlowField {
+    /// Compute a flow field up to limit distance from goals, omitting cells for which the
+    /// is_valid predicate returns false.
+    pub fn new<F: Fn(&CellVector) -> bool>(
+        goals: &[CellVector],
+        is_valid: F,
+        limit: u32,
+    ) -> FlowField {
+        let mut ret = FlowField::default();
+        ret.recompute(goals, is_valid, limit);
+        ret
     }
 
-    let mut come_from = HashMap::new();
+    /// Recompute the field in place for new goals, reusing the memory of the field.
+    pub fn recompute<F: Fn(&CellVector) -> bool>(
+        &mut self,
+        goals: &[CellVector],
+        is_valid: F,
+        limit: u32,
+    ) {
+        self.cells.clear();
+        if limit == 0 {
+            return;
+        }
 
-    let mut open = BinaryHeap::new();
-    open.push(node(start.clone(), ::std::f32::MAX, None));
+        let mut edge = Vec::new();
+        for &p in goals {
+            if self.cells.insert(p, (0, None)).is_none() {
+                edge.push(p);
+            }
+        }
 
-    // Find shortest path.
-    let mut goal = loop {
-        if let Some(closest) = open.pop() {
-            if come_from.contains_key(&closest.item) {
-                // Already saw it through a presumably shorter path...
-                continue;
+        let mut new_edge = Vec::new();
+        for dist in 1..limit {
+            for &p in &edge {
+                for &dir in Dir6::iter() {
+                    let q = p + dir.to_v2();
+                    if is_valid(&q) && !self.cells.contains_key(&q) {
+                        // Moving from the new cell back towards the cell it was reached from.
+                        self.cells.insert(q, (dist, Some(dir + 3)));
+                        new_edge.push(q);
+                    }
+                }
             }
 
-            if let Some(from) = closest.come_from {
-                come_from.insert(closest.item.clone(), from);
+            ::std::mem::swap(&mut edge, &mut new_edge);
+            new_edge.clear();
+            if edge.is_empty() {
+                break;
             }
+        }
+    }
+
+    /// Return the direction to move from a cell towards the nearest goal.
+    ///
+    /// Returns `None` for goal cells and cells outside the field.
+    pub fn direction(&self, pos: CellVector) -> Option<Dir6> {
+        self.cells.get(&pos).and_then(|&(_, dir)| dir)
+    }
+
+    /// Return the number of steps from a cell to the nearest goal.
+    pub fn distance(&self, pos: CellVector) -> Option<u32> {
+        self.cells.get(&pos).map(|&(dist, _)| dist)
+    }
+
+    /// Return the cell to move to from a cell towards the nearest goal.
+    pub fn step(&self, pos: CellVector) -> Option<CellVector> {
+        self.direction(pos).map(|dir| pos + dir.to_v2())
+    }
+
+    /// Return whether the cell is covered by the field.
+    pub fn contains(&self, pos: CellVector) -> bool { self.cells.contains_key(&pos) }
+}
+
+/// Find all the nodes reachable from origin through passable nodes.
+///
+/// The origin is included in the result if it is passable, otherwise the result is empty.
+///
+/// # Examples
+///
+/// ```
+/// use calx::{flood_fill, GridNode};
+///
+/// #[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
+/// struct Pos(i32);
+///
+/// impl GridNode for Pos {
+///     fn neighbors(&self) -> Vec<Pos> { vec![Pos(self.0 - 1), Pos(self.0 + 1)] }
+/// }
+///
+/// // Room from -3 to 3 with walls on both sides.
+/// let passable = |p: &Pos| p.0.abs() <= 3;
+/// assert_eq!(flood_fill(Pos(1), passable).len(), 7);
+/// assert!(flood_fill(Pos(5), passable).is_empty());
+/// ```
+pub fn flood_fill<N, F>(origin: N, is_passable: F) -> HashSet<N>
+where
+    N: GridNode,
+    F: Fn(&N) -> bool,
+{
+    let mut ret = HashSet::new();
+    if !is_passable(&origin) {
+        return ret;
+    }
 
-            if &closest.item == end {
-                break Some(closest.item);
+    let mut stack = vec![origin.clone()];
+    ret.insert(origin);
+    while let Some(node) = stack.pop() {
+        for n in node.neighbors() {
+            if !ret.contains(&n) && is_passable(&n) {
+                ret.insert(n.clone());
+                stack.push(n);
             }
+        }
+    }
+    ret
+}
 
-            for (item, dist) in neighbors(&closest.item) {
-                let already_seen = come_from.contains_key(&item) || item == start;
-                if already_seen {
-                    continue;
-                }
-                open.push(node(item, dist, Some(closest.item.clone())));
+/// Split a set of nodes into connected regions of passable nodes.
+///
+/// Only the nodes in `bounds` are considered, so the passability function doesn't need to be
+/// limited to a finite area. The regions are ordered by their first node in `bounds`. A map is
+/// fully connected if there is at most one region.
+pub fn connected_components<N, I, F>(bounds: I, is_passable: F) -> Vec<HashSet<N>>
+where
+    N: GridNode,
+    I: IntoIterator<Item = N>,
+    F: Fn(&N) -> bool,
+{
+    let nodes: Vec<N> = bounds.into_iter().filter(|n| is_passable(n)).collect();
+    let mut unassigned: HashSet<N> = nodes.iter().cloned().collect();
+    let mut ret = Vec::new();
+
+    for origin in nodes {
+        if unassigned.contains(&origin) {
+            let region = flood_fill(origin, |n| unassigned.contains(n));
+            for n in &region {
+                unassigned.remove(n);
             }
-        } else {
-            break None;
+            ret.push(region);
+        }
+    }
+    ret
+}
+
+/// Find A* path in freeform graph.
+///
+/// The `neighbors` function returns neighboring nodes and their estimated distance from the goal.
+/// The search will treat any node whose distance is zero as a goal and return a path leading to
+/// it.
+///
+/// The neighbors can be returned as any iterable. On hex maps with precomputed exits, mapping
+/// the iterator from `DirSet6::neighbors` avoids allocating a collection for every node.
+pub fn astar_path<N, F, I>(start: N, end: &N, neighbors: F) -> Option<Vec<N>>
+where
+    N: Eq + Hash + Clone,
+    F: Fn(&N) -> I,
+    I: IntoIterator<Item = (N, f32)>,
+{
+    astar_path_in(start, end, neighbors, &mut SearchBuffers::new())
+}
+
+/// Find A* path in freeform graph using preallocated search buffers.
+///
+/// See `astar_path` for details.
+pub fn astar_path_in<N, F, I>(
+    start: N,
+    end: &N,
+    neighbors: F,
+    buffers: &mut SearchBuffers<N>,
+) -> Option<Vec<N>>
+where
+    N: Eq + Hash + Clone,
+    F: Fn(&N) -> I,
+    I: IntoIterator<Item = (N, f32)>,
+{
+    astar_path_observed(start, end, neighbors, buffers, &mut ())
+}
+
+/// Find A* path in freeform graph and report the search progress to an observer.
+///
+/// See `astar_path` for details.
+///
+/// # Examples
+///
+/// ```
+/// use calx::{astar_path_observed, SearchBuffers, SearchTrace};
+///
+/// let mut trace = SearchTrace::default();
+/// let path = astar_path_observed(
+///     0,
+///     &3,
+///     |&x| vec![(x - 1, (3 - (x - 1)) as f32), (x + 1, (3 - (x + 1)) as f32)],
+///     &mut SearchBuffers::new(),
+///     &mut trace,
+/// );
+///
+/// assert_eq!(Some(vec![0, 1, 2, 3]), path);
+/// assert_eq!(vec![0, 1, 2], trace.expanded);
+/// assert_eq!(vec![0, 1, 2, 3], trace.path);
+/// ```
+pub fn astar_path_observed<N, F, I>(
+    start: N,
+    end: &N,
+    neighbors: F,
+    buffers: &mut SearchBuffers<N>,
+    observer: &mut impl SearchObserver<N>,
+) -> Option<Vec<N>>
+where
+    N: Eq + Hash + Clone,
+    F: Fn(&N) -> I,
+    I: IntoIterator<Item = (N, f32)>,
+{
+    buffers.clear();
+    let start_idx = buffers.node_index(&start);
+    observer.frontier(&start);
+    buffers.open.push(OpenItem {
+        value: priority(::std::f32::MAX),
+        idx: start_idx,
+        come_from: None,
+    });
+
+    // Find shortest path.
+    while let Some(closest) = buffers.open.pop() {
+        if buffers.nodes[closest.idx].closed {
+            // Already saw it through a presumably shorter path...
+            continue;
+        }
+
+        buffers.nodes[closest.idx].closed = true;
+        buffers.nodes[closest.idx].come_from = closest.come_from;
+
+        if &buffers.nodes[closest.idx].item == end {
+            let path = buffers.path_to(closest.idx);
+            observer.path(&path);
+            return Some(path);
         }
-    };
 
-    // Extract path from the graph structure.
-    let mut path = Vec::new();
-    while let Some(x) = goal {
-        goal = come_from.remove(&x);
-        path.push(x);
+        let item = buffers.nodes[closest.idx].item.clone();
+        observer.expanded(&item);
+        for (next, dist) in neighbors(&item) {
+            let idx = buffers.node_index(&next);
+            if buffers.nodes[idx].closed || idx == start_idx {
+                continue;
+            }
+            observer.frontier(&next);
+            buffers.open.push(OpenItem {
+                value: priority(dist),
+                idx,
+                come_from: Some(closest.idx),
+            });
+        }
     }
-    path.reverse();
 
-    if path.is_empty() {
-        None
-    } else {
-        Some(path)
+    None
+}
+
+/// Find the lowest cost A* path in a graph with weighted edges.
+///
+/// The `neighbors` function returns neighboring nodes and the non-negative cost of moving to
+/// them. The `heuristic` function estimates the remaining cost from a node to `end`, and must
+/// never overestimate it for the returned path to be the cheapest one. Returns the path along
+/// with its total cost.
+///
+/// Nodes that were already expanded are expanded again if a cheaper route to them turns up,
+/// which can only happen when the heuristic drops by more than the edge cost along some edge.
+/// Heuristics like the distance to `end` never do this and expand every node at most once.
+///
+/// # Examples
+///
+/// ```
+/// use calx::astar_path_weighted;
+///
+/// // A road along the even numbers costs less to travel than the direct route.
+/// let neighbors = |&x: &i32| {
+///     let mut ret = vec![(x + 1, 3.0), (x - 1, 3.0)];
+///     if x % 2 == 0 {
+///         ret.push((x + 2, 1.0));
+///     }
+///     ret
+/// };
+///
+/// let heuristic = |&x: &i32| (5 - x).abs() as f32 * 0.5;
+///
+/// let (path, cost) = astar_path_weighted(0, &5, neighbors, heuristic).unwrap();
+/// assert_eq!(path, vec![0, 2, 4, 5]);
+/// assert_eq!(cost, 5.0);
+/// ```
+pub fn astar_path_weighted<N, F, I, H>(
+    start: N,
+    end: &N,
+    neighbors: F,
+    heuristic: H,
+) -> Option<(Vec<N>, f32)>
+where
+    N: Eq + Hash + Clone,
+    F: Fn(&N) -> I,
+    I: IntoIterator<Item = (N, f32)>,
+    H: Fn(&N) -> f32,
+{
+    astar_path_weighted_in(start, end, neighbors, heuristic, &mut SearchBuffers::new())
+}
+
+/// Find the lowest cost A* path in a weighted graph using preallocated search buffers.
+///
+/// See `astar_path_weighted` for details.
+pub fn astar_path_weighted_in<N, F, I, H>(
+    start: N,
+    end: &N,
+    neighbors: F,
+    heuristic: H,
+    buffers: &mut SearchBuffers<N>,
+) -> Option<(Vec<N>, f32)>
+where
+    N: Eq + Hash + Clone,
+    F: Fn(&N) -> I,
+    I: IntoIterator<Item = (N, f32)>,
+    H: Fn(&N) -> f32,
+{
+    buffers.clear();
+    let start_idx = buffers.node_index(&start);
+    buffers.nodes[start_idx].cost = 0.0;
+    buffers.open.push(OpenItem {
+        value: priority(heuristic(&start)),
+        idx: start_idx,
+        come_from: None,
+    });
+
+    while let Some(closest) = buffers.open.pop() {
+        if buffers.nodes[closest.idx].closed {
+            continue;
+        }
+        buffers.nodes[closest.idx].closed = true;
+
+        if &buffers.nodes[closest.idx].item == end {
+            return Some((
+                buffers.path_to(closest.idx),
+                buffers.nodes[closest.idx].cost,
+            ));
+        }
+
+        let item = buffers.nodes[closest.idx].item.clone();
+        let cost = buffers.nodes[closest.idx].cost;
+        for (next, edge_cost) in neighbors(&item) {
+            debug_assert!(edge_cost >= 0.0);
+            let idx = buffers.node_index(&next);
+            let next_cost = cost + edge_cost;
+            if next_cost >= buffers.nodes[idx].cost {
+                continue;
+            }
+            // Unlike the unweighted search, the parent is updated whenever a cheaper route is
+            // found instead of when the node is popped. An inconsistent heuristic can lead to
+            // a cheaper route to a closed node, which then needs to be reopened.
+            buffers.nodes[idx].cost = next_cost;
+            buffers.nodes[idx].closed = false;
+            buffers.nodes[idx].come_from = Some(closest.idx);
+            buffers.open.push(OpenItem {
+                value: priority(next_cost + heuristic(&next)),
+                idx,
+                come_from: Some(closest.idx),
+            });
+        }
     }
+
+    None
 }
 
 #[cfg(test)]
@@ -179,5 +963,167 @@ mod test {
             Some(vec![8, 9, 10, 11, 12]),
             astar_path(8, &12, |x| neighbors(8, x))
         );
+
+        // Reusing the buffers gives the same results.
+        let mut buffers = SearchBuffers::new();
+        for _ in 0..2 {
+            assert_eq!(
+                Some(vec![8, 7, 6]),
+                astar_path_in(8, &6, |x| neighbors(8, x), &mut buffers)
+            );
+        }
+    }
+
+    #[test]
+    fn test_astar_exits() {
+        use euclid::vec2;
+        use hex::{Dir6, DirSet6, HexGeom};
+        use std::collections::HashMap;
+        use CellVector;
+
+        // Corridor of cells that only connect along the x axis, and a one-way exit north.
+        let mut exits: HashMap<CellVector, DirSet6> = HashMap::new();
+        let east_west = DirSet6::from(Dir6::Southeast) | DirSet6::from(Dir6::Northwest);
+        for x in 0..5 {
+            exits.insert(vec2(x, 0), east_west);
+        }
+        exits.get_mut(&vec2(2, 0)).unwrap().insert(Dir6::North);
+        exits.insert(vec2(1, -1), DirSet6::default());
+
+        let path = |start: CellVector, goal: CellVector| {
+            astar_path(start, &goal, |&p| {
+                let dirs = exits.get(&p).cloned().unwrap_or_default();
+                dirs.neighbors(p)
+                    .map(move |q| (q, (goal - q).hex_dist() as f32))
+            })
+        };
+        assert_eq!(
+            path(vec2(0, 0), vec2(3, 0)),
+            Some(vec![vec2(0, 0), vec2(1, 0), vec2(2, 0), vec2(3, 0)])
+        );
+        assert_eq!(
+            path(vec2(0, 0), vec2(1, -1)),
+            Some(vec![vec2(0, 0), vec2(1, 0), vec2(2, 0), vec2(1, -1)])
+        );
+        assert_eq!(path(vec2(1, -1), vec2(0, 0)), None);
+    }
+
+    #[test]
+    fn test_astar_inconsistent_heuristic() {
+        // The cheap route is through 'a', but the heuristic makes 'a' look expensive, so 'c' is
+        // first reached through 'b'.
+        let neighbors = |&n: &char| match n {
+            's' => vec![('a', 1.0), ('b', 1.0)],
+            'a' => vec![('c', 1.0)],
+            'b' => vec![('c', 3.0)],
+            'c' => vec![('g', 3.0)],
+            _ => vec![],
+        };
+        // Never overestimates, but drops by more than the edge cost from 'a' to 'c'.
+        let heuristic = |&n: &char| if n == 'a' { 4.0 } else { 0.0 };
+        assert_eq!(
+            astar_path_weighted('s', &'g', neighbors, heuristic),
+            Some((vec!['s', 'a', 'c', 'g'], 5.0))
+        );
+    }
+
+    #[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
+    struct Line(i32);
+
+    impl GridNode for Line {
+        fn neighbors(&self) -> Vec<Line> { vec![Line(self.0 - 1), Line(self.0 + 1)] }
+    }
+
+    #[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
+    struct Ring(i32);
+
+    impl GridNode for Ring {
+        fn neighbors(&self) -> Vec<Ring> { vec![Ring((self.0 + 9) % 10), Ring((self.0 + 1) % 10)] }
+    }
+
+    #[test]
+    fn test_autoexplore_no_limit() {
+        let explored = |p: &Ring| p.0 != 3;
+        assert_eq!(
+            autoexplore(&Ring(0), explored, |_| true, u32::MAX),
+            Some(vec![Ring(0), Ring(1), Ring(2), Ring(3)])
+        );
+    }
+
+    #[test]
+    fn test_dijkstra() {
+        let map = Dijkstra::new(vec![Line(0), Line(10)], |n| n.0 >= -3, 4);
+        assert_eq!(Some(&0), map.weights.get(&Line(10)));
+        assert_eq!(Some(&3), map.weights.get(&Line(-3)));
+        assert_eq!(Some(&2), map.weights.get(&Line(8)));
+        assert_eq!(None, map.weights.get(&Line(-4)));
+        assert_eq!(None, map.weights.get(&Line(14)));
+        assert_eq!(vec![Line(1), Line(3)], map.sorted_neighbors(&Line(2)));
+
+        let mut buffers = SearchBuffers::new();
+        let mut map2 = Dijkstra::new(vec![Line(100)], |_| true, 2);
+        map2.recompute_in(vec![Line(0), Line(10)], |n| n.0 >= -3, 4, &mut buffers);
+        assert_eq!(map.weights, map2.weights);
+    }
+
+    #[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
+    struct Square(i32, i32);
+
+    impl GridNode for Square {
+        fn neighbors(&self) -> Vec<Square> {
+            vec![
+                Square(self.0, self.1 - 1),
+                Square(self.0 + 1, self.1),
+                Square(self.0, self.1 + 1),
+                Square(self.0 - 1, self.1),
+            ]
+        }
+    }
+
+    #[test]
+    fn test_dijkstra_update() {
+        use rand::Rng;
+        use rng::seeded_rng;
+        use std::collections::HashSet;
+
+        let mut rng = seeded_rng(&"dijkstra update");
+        let mut walls: HashSet<Square> = (0..120)
+            .map(|_| Square(rng.gen_range(0, 20), rng.gen_range(0, 20)))
+            .collect();
+        let goals = vec![Square(0, 0), Square(19, 19)];
+
+        for _ in 0..20 {
+            let valid = |walls: &HashSet<Square>, n: &Square| {
+                n.0 >= 0 && n.1 >= 0 && n.0 < 20 && n.1 < 20 && !walls.contains(n)
+            };
+            let mut map = Dijkstra::new(goals.clone(), |n| valid(&walls, n), 30);
+
+            let changed: Vec<Square> = (0..8)
+                .map(|_| Square(rng.gen_range(0, 20), rng.gen_range(0, 20)))
+                .collect();
+            for n in &changed {
+                if !walls.remove(n) {
+                    walls.insert(n.clone());
+                }
+            }
+
+            map.update(&changed, |n| valid(&walls, n), 30);
+            let fresh = Dijkstra::new(goals.clone(), |n| valid(&walls, n), 30);
+            assert_eq!(map.weights, fresh.weights);
+        }
+    }
+
+    #[test]
+    fn test_connected_components() {
+        // Walls at 3 and 7.
+        let passable = |n: &Line| n.0 != 3 && n.0 != 7;
+        let regions = connected_components((0..10).map(Line), passable);
+        assert_eq!(regions.len(), 3);
+        assert_eq!(regions[0], (0..3).map(Line).collect());
+        assert_eq!(regions[1], (4..7).map(Line).collect());
+        assert_eq!(regions[2], (8..10).map(Line).collect());
+
+        assert_eq!(connected_components((0..3).map(Line), passable).len(), 1);
+        assert!(connected_components((3..4).map(Line), passable).is_empty());
     }
 }
@@ -0,0 +1,291 @@
+//! Overworld settlement and dungeon placement
+
+use climate::{Biome, Climate};
+use grid::Grid;
+use hex::{hex_disc, hex_neighbors, HexGeom};
+use rand::Rng;
+use CellVector;
+
+/// Type of a placed overworld feature.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Serialize, Deserialize)]
+pub enum SiteKind {
+    Town,
+    DungeonEntrance,
+}
+
+/// Overworld feature placed by `SitePlacer`.
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Site {
+    pub kind: SiteKind,
+    pub pos: CellVector,
+    /// Suitability score of the location in [0, 1].
+    pub score: f32,
+}
+
+/// Sites placed on an overworld and the travel routes between them.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct WorldSites {
+    pub sites: Vec<Site>,
+    /// Pairs of site indices connected by a route.
+    ///
+    /// The routes form a minimum spanning tree over the sites by hex distance, so every site
+    /// can be traveled to from every other one.
+    pub routes: Vec<(usize, usize)>,
+}
+
+type ScoreFn = fn(&Grid<Climate>, CellVector) -> f32;
+
+/// Return the largest altitude difference between a cell and its neighbors.
+fn roughness(climate: &Grid<Climate>, pos: CellVector) -> f32 {
+    let altitude = climate[pos].altitude;
+    hex_neighbors(pos)
+        .filter_map(|p| climate.get(p))
+        .map(|c| (c.altitude - altitude).abs())
+        .fold(0.0, f32::max)
+}
+
+fn is_near(climate: &Grid<Climate>, pos: CellVector, radius: i32, biome: Biome) -> bool {
+    hex_disc(pos, radius).any(|p| climate.get(p).is_some_and(|c| c.biome == biome))
+}
+
+/// Score a cell for building a town in [0, 1].
+///
+/// Towns like habitable biomes, flat land and having water nearby. Ocean and mountain cells
+/// score zero.
+pub fn settlement_score(climate: &Grid<Climate>, pos: CellVector) -> f32 {
+    use Biome::*;
+    let habitability = match climate[pos].biome {
+        Ocean | Mountain | Ice => return 0.0,
+        Grassland => 1.0,
+        TemperateForest => 0.9,
+        Savanna => 0.8,
+        TropicalForest | TemperateRainforest => 0.6,
+        Taiga => 0.5,
+        TropicalRainforest => 0.4,
+        Tundra => 0.2,
+        Desert => 0.15,
+    };
+    let flatness = 1.0 - (roughness(climate, pos) * 10.0).min(1.0);
+    let water = if is_near(climate, pos, 2, Ocean) {
+        1.0
+    } else {
+        0.6
+    };
+    habitability * (0.5 + 0.5 * flatness) * water
+}
+
+/// Score a cell for a dungeon entrance in [0, 1].
+///
+/// Dungeon entrances like rough land at the foot of mountains. Ocean and mountain cells score
+/// zero.
+pub fn dungeon_score(climate: &Grid<Climate>, pos: CellVector) -> f32 {
+    match climate[pos].biome {
+        Biome::Ocean | Biome::Mountain => return 0.0,
+        _ => {}
+    }
+    let ruggedness = (roughness(climate, pos) * 10.0).min(1.0);
+    let mountains = if is_near(climate, pos, 2, Biome::Mountain) {
+        1.0
+    } else {
+        0.3
+    };
+    (0.3 + 0.7 * ruggedness) * mountains
+}
+
+/// Worldgen pass that places towns and dungeon entrances on an overworld.
+///
+/// Sites are chosen greedily from the best scoring cells, with some randomness mixed into the
+/// scores, while keeping every pair of sites at least the minimum spacing apart.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate euclid;
+/// # extern crate calx;
+/// # fn main() {
+/// use euclid::rect;
+/// use calx::noise::Perlin;
+/// use calx::{seeded_rng, ClimateModel, Grid, SiteKind, SitePlacer};
+///
+/// let terrain = Perlin::new(1);
+/// let heightmap = Grid::from_fn(rect(0, 0, 60, 40), |p| {
+///     terrain.fbm2(p.x as f64 / 12.0, p.y as f64 / 12.0, 4, 0.5) as f32 * 0.5 + 0.5
+/// });
+/// let climate = ClimateModel::new(1).climate(&heightmap);
+///
+/// let world = SitePlacer::new()
+///     .towns(5)
+///     .dungeons(3)
+///     .min_spacing(6)
+///     .place(&mut seeded_rng(&1), &climate);
+/// assert_eq!(world.sites.iter().filter(|s| s.kind == SiteKind::Town).count(), 5);
+/// assert_eq!(world.routes.len(), world.sites.len() - 1);
+/// # }
+/// ```
+#[derive(Copy, Clone, Debug)]
+pub struct SitePlacer {
+    towns: usize,
+    dungeons: usize,
+    min_spacing: i32,
+}
+
+impl SitePlacer {
+    pub fn new() -> SitePlacer {
+        SitePlacer {
+            towns: 0,
+            dungeons: 0,
+            min_spacing: 1,
+        }
+    }
+
+    /// Set the number of towns to place.
+    pub fn towns(mut self, n: usize) -> SitePlacer {
+        self.towns = n;
+        self
+    }
+
+    /// Set the number of dungeon entrances to place.
+    pub fn dungeons(mut self, n: usize) -> SitePlacer {
+        self.dungeons = n;
+        self
+    }
+
+    /// Set the smallest hex distance allowed between two sites.
+    pub fn min_spacing(mut self, cells: i32) -> SitePlacer {
+        self.min_spacing = cells;
+        self
+    }
+
+    /// Place the sites.
+    ///
+    /// Towns are placed first. If the map runs out of suitable cells, fewer sites than
+    /// requested are placed.
+    pub fn place<R: Rng + ?Sized>(&self, rng: &mut R, climate: &Grid<Climate>) -> WorldSites {
+        let mut sites: Vec<Site> = Vec::new();
+        let passes: [(SiteKind, usize, ScoreFn); 2] = [
+            (SiteKind::Town, self.towns, settlement_score),
+            (SiteKind::DungeonEntrance, self.dungeons, dungeon_score),
+        ];
+
+        for &(kind, count, score_fn) in &passes {
+            let mut candidates: Vec<(f32, CellVector, f32)> = climate
+                .iter()
+                .map(|(p, _)| (p, score_fn(climate, p)))
+                .filter(|&(_, score)| score > 0.0)
+                .map(|(p, score)| (score * rng.gen_range(0.75, 1.0), p, score))
+                .collect();
+            candidates.sort_by(|a, b| b.0.partial_cmp(&a.0).expect("Bad site score"));
+
+            let mut placed = 0;
+            for (_, pos, score) in candidates {
+                if placed == count {
+                    break;
+                }
+                if sites
+                    .iter()
+                    .all(|s| (s.pos - pos).hex_dist() >= self.min_spacing)
+                {
+                    sites.push(Site { kind, pos, score });
+                    placed += 1;
+                }
+            }
+        }
+
+        let routes = spanning_tree(&sites);
+        WorldSites { sites, routes }
+    }
+}
+
+impl Default for SitePlacer {
+    fn default() -> Self { SitePlacer::new() }
+}
+
+/// Prim's minimum spanning tree over the sites.
+fn spanning_tree(sites: &[Site]) -> Vec<(usize, usize)> {
+    let mut ret = Vec::new();
+    if sites.is_empty() {
+        return ret;
+    }
+    let dist = |a: usize, b: usize| (sites[a].pos - sites[b].pos).hex_dist();
+
+    let mut in_tree = vec![false; sites.len()];
+    // Closest tree node and distance to it for each node.
+    let mut best: Vec<(usize, i32)> = (0..sites.len()).map(|i| (0, dist(0, i))).collect();
+    in_tree[0] = true;
+    for _ in 1..sites.len() {
+        let next = (0..sites.len())
+            .filter(|&i| !in_tree[i])
+            .min_by_key(|&i| best[i].1)
+            .expect("Ran out of sites");
+        in_tree[next] = true;
+        ret.push((best[next].0.min(next), best[next].0.max(next)));
+        for i in 0..sites.len() {
+            if !in_tree[i] && dist(next, i) < best[i].1 {
+                best[i] = (next, dist(next, i));
+            }
+        }
+    }
+    ret
+}
+
+#[cfg(test)]
+mod test {
+    use super::{dungeon_score, settlement_score, SiteKind, SitePlacer};
+    use climate::ClimateModel;
+    use euclid::{rect, vec2};
+    use grid::Grid;
+    use hex::HexGeom;
+    use rng::seeded_rng;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_sites() {
+        // Coast on the west, mountains on the east.
+        let heightmap = Grid::from_fn(rect(0, -20, 40, 40), |p| match p.x {
+            x if x < 5 => 0.1,
+            x if x > 34 => 0.95,
+            x => 0.5 + x as f32 * 0.005,
+        });
+        let climate = ClimateModel::new(1).climate(&heightmap);
+        assert!(settlement_score(&climate, vec2(6, 0)) > settlement_score(&climate, vec2(20, 0)));
+        assert!(dungeon_score(&climate, vec2(33, 0)) > dungeon_score(&climate, vec2(20, 0)));
+        assert_eq!(settlement_score(&climate, vec2(0, 0)), 0.0);
+
+        let world = SitePlacer::new()
+            .towns(4)
+            .dungeons(4)
+            .min_spacing(5)
+            .place(&mut seeded_rng(&1), &climate);
+        assert_eq!(world.sites.len(), 8);
+        for (i, a) in world.sites.iter().enumerate() {
+            for b in &world.sites[i + 1..] {
+                assert!((a.pos - b.pos).hex_dist() >= 5);
+            }
+        }
+        let dungeon_x: Vec<i32> = world
+            .sites
+            .iter()
+            .filter(|s| s.kind == SiteKind::DungeonEntrance)
+            .map(|s| s.pos.x)
+            .collect();
+        assert!(dungeon_x.iter().all(|&x| x >= 32));
+
+        // Routes connect everything.
+        let routes: HashSet<_> = world.routes.iter().cloned().collect();
+        assert_eq!(routes.len(), 7);
+        let mut reached = HashSet::new();
+        let mut stack = vec![0];
+        while let Some(i) = stack.pop() {
+            if reached.insert(i) {
+                for &(a, b) in &routes {
+                    if a == i {
+                        stack.push(b);
+                    } else if b == i {
+                        stack.push(a);
+                    }
+                }
+            }
+        }
+        assert_eq!(reached.len(), 8);
+    }
+}
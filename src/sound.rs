@@ -0,0 +1,161 @@
+//! Sound and scent propagation over hex maps
+
+use hex::{hex_neighbors, Dir6};
+use search::expand_weights;
+use std::collections::HashMap;
+use CellVector;
+
+/// Intensity field of sounds or scents spreading out from sources.
+///
+/// Each source starts with its loudness, and the intensity drops by the damping of every cell
+/// the sound passes through, so walls can muffle sounds more than open floor. A cell gets the
+/// intensity of the loudest path to it from any source. Cells with no intensity left are not
+/// stored.
+///
+/// Monsters can check the field to notice sounds and use `direction` to find out where they
+/// came from. Call `decay` every turn to use the field for scents that linger and fade.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate euclid;
+/// # extern crate calx;
+/// # fn main() {
+/// use euclid::vec2;
+/// use calx::{Dir6, SoundField};
+///
+/// // A wall along x = 2 that muffles sound, a solid pillar at (-1, 0).
+/// let damping = |p: calx::CellVector| match p {
+///     p if p == vec2(-1, 0) => None,
+///     p if p.x == 2 => Some(3),
+///     _ => Some(1),
+/// };
+///
+/// // Door slams at the origin.
+/// let sound = SoundField::new(vec![(vec2(0, 0), 6)], damping);
+/// assert_eq!(sound.level(vec2(0, 0)), 6);
+/// assert_eq!(sound.level(vec2(1, 0)), 5);
+/// // Heard faintly through the wall.
+/// assert_eq!(sound.level(vec2(3, 0)), 1);
+/// assert_eq!(sound.level(vec2(-1, 0)), 0);
+///
+/// // Monster hears it and knows which way to go.
+/// assert_eq!(sound.direction(vec2(1, 0)), Some(Dir6::Northwest));
+/// # }
+/// ```
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct SoundField {
+    levels: HashMap<CellVector, i32>,
+}
+
+impl SoundField {
+    /// Propagate sounds from sources with loudness values.
+    ///
+    /// The `damping` function gives the intensity lost when the sound enters a cell, or `None`
+    /// for cells that block sound completely. Damping values below 1 are treated as 1, so every
+    /// step weakens the sound and the propagation always stops.
+    pub fn new<I, F>(sources: I, damping: F) -> SoundField
+    where
+        I: IntoIterator<Item = (CellVector, i32)>,
+        F: Fn(CellVector) -> Option<i32>,
+    {
+        // Work with negated levels so that the loudest sound is the lowest weight.
+        let weights = expand_weights(
+            sources.into_iter().map(|(p, loudness)| (p, -loudness)),
+            |&p| hex_neighbors(p),
+            |&p| damping(p).map(|d| d.max(1)),
+            0,
+        );
+        SoundField {
+            levels: weights
+                .into_iter()
+                .filter(|&(_, w)| w < 0)
+                .map(|(p, w)| (p, -w))
+                .collect(),
+        }
+    }
+
+    /// Return the intensity at a cell, zero if nothing can be heard.
+    pub fn level(&self, pos: CellVector) -> i32 { self.levels.get(&pos).cloned().unwrap_or(0) }
+
+    /// Return the direction to the loudest neighboring cell if it's louder than this cell.
+    ///
+    /// Following the directions leads to the source of the sound.
+    pub fn direction(&self, pos: CellVector) -> Option<Dir6> {
+        let here = self.level(pos);
+        let (level, dir) = Dir6::iter()
+            .map(|&d| (self.level(pos + d.to_v2()), d))
+            .max_by_key(|&(level, d)| (level, -(d as i32)))?;
+        if level > here {
+            Some(dir)
+        } else {
+            None
+        }
+    }
+
+    /// Iterate the cells that have some intensity.
+    pub fn iter(&self) -> impl Iterator<Item = (CellVector, i32)> + '_ {
+        self.levels.iter().map(|(&p, &l)| (p, l))
+    }
+
+    /// Weaken the whole field by an amount, for making scents fade over time.
+    pub fn decay(&mut self, amount: i32) {
+        self.levels.retain(|_, l| {
+            *l -= amount;
+            *l > 0
+        });
+    }
+
+    /// Add another field into this one, keeping the stronger intensity for each cell.
+    ///
+    /// Use this to lay fresh scent over an old, decaying trail.
+    pub fn merge(&mut self, other: &SoundField) {
+        for (p, l) in other.iter() {
+            let e = self.levels.entry(p).or_insert(0);
+            *e = (*e).max(l);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::SoundField;
+    use euclid::vec2;
+    use hex::{Dir6, HexGeom};
+
+    #[test]
+    fn test_sound_field() {
+        let open = |_| Some(1);
+        let sound = SoundField::new(vec![(vec2(0, 0), 3), (vec2(10, 0), 2)], open);
+        // Sound reaches out to two cells from the source.
+        assert!(sound
+            .iter()
+            .all(|(p, _)| p.hex_dist() <= 2 || (p - vec2(10, 0)).hex_dist() <= 1));
+        assert_eq!(sound.level(vec2(2, 2)), 1);
+        assert_eq!(sound.level(vec2(3, 3)), 0);
+        assert_eq!(sound.level(vec2(10, 0)), 2);
+        assert_eq!(sound.direction(vec2(2, 2)), Some(Dir6::North));
+        assert_eq!(sound.direction(vec2(0, 0)), None);
+        assert_eq!(sound.direction(vec2(5, 0)), None);
+
+        // Louder source overrides a quieter one.
+        let sound = SoundField::new(vec![(vec2(0, 0), 2), (vec2(1, 0), 5)], open);
+        assert_eq!(sound.level(vec2(0, 0)), 4);
+
+        // Zero damping still fades out on an unbounded map.
+        let sound = SoundField::new(vec![(vec2(0, 0), 3)], |_| Some(0));
+        assert_eq!(sound.level(vec2(2, 0)), 1);
+        assert_eq!(sound.iter().count(), 19);
+
+        // Scent trail.
+        let mut trail = SoundField::new(vec![(vec2(0, 0), 4)], open);
+        trail.decay(2);
+        assert_eq!(trail.level(vec2(0, 0)), 2);
+        assert_eq!(trail.level(vec2(1, 0)), 1);
+        assert_eq!(trail.level(vec2(2, 0)), 0);
+        trail.merge(&SoundField::new(vec![(vec2(2, 0), 5)], open));
+        assert_eq!(trail.level(vec2(2, 0)), 5);
+        assert_eq!(trail.level(vec2(0, 0)), 3);
+        assert_eq!(trail.direction(vec2(0, 0)), Some(Dir6::Southeast));
+    }
+}
@@ -1,4 +1,5 @@
-use euclid::{vec3, TypedVector2D, TypedVector3D};
+use euclid::{vec2, vec3, TypedVector2D, TypedVector3D};
+use hex::hex_round;
 
 /// Unit tag for typed euclid structs.
 ///
@@ -42,13 +43,11 @@ pub type CellVector = TypedVector2D<i32, CellSpace>;
 /// ```notrust
 /// [v[0] * M[0] + v[1] * M[1], v[0] * M[2] + v[1] * M[3]]
 /// ```
+///
+/// For floating point spaces that are a linear or affine transformation of `CellSpace`, like
+/// screen coordinates, implement `AffineSpace` instead and let the inverse be derived for you.
 pub trait Transformation {
     type Element: Copy;
-    // To make this even more automagical, could just make the user specify a 2x2 transformation
-    // matrix here, then figure out how to invert the matrix at compile time to keep unprojection
-    // efficient and set up conversions between the probably integer element types and
-    // the transformation matrix that needs to be floating point. LLVM should be able to optimize
-    // away a 2x2 matrix inversion function called with const inputs.
 
     /// Transform `CellSpace` coordinates to this space.
     fn unproject<V: Into<[i32; 2]>>(v: V) -> [Self::Element; 2];
@@ -71,6 +70,34 @@ pub trait Space {
 
     /// Project from `CellSpace` to local space.
     fn from_cell_space(cell: CellVector) -> Self;
+
+    /// Convert to another space by going through `CellSpace`.
+    ///
+    /// The position is snapped to the cell it is in on the way.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate euclid;
+    /// # extern crate calx;
+    /// # fn main() {
+    /// use euclid::{vec2, TypedVector2D};
+    /// use calx::{CellVector, IsoSpace, Space, TextSpace};
+    ///
+    /// // Mouse position on the screen.
+    /// let mouse: TypedVector2D<f32, IsoSpace> = vec2(35.0, 9.0);
+    /// assert_eq!(mouse.convert_to::<CellVector>(), vec2(2, 0));
+    /// // Character position in a text map of the level.
+    /// let text: TypedVector2D<i32, TextSpace> = mouse.convert_to();
+    /// assert_eq!(text, vec2(4, 0));
+    /// # }
+    /// ```
+    fn convert_to<T: Space>(self) -> T
+    where
+        Self: Sized,
+    {
+        T::from_cell_space(self.to_cell_space())
+    }
 }
 
 impl<T, U> Space for TypedVector2D<T, U>
@@ -96,3 +123,298 @@ where
         vec3(v[0], v[1], Default::default())
     }
 }
+
+/// Number of distinct draw layers within a single cell row for `depth_z`.
+const DEPTH_LAYERS: i32 = 256;
+
+/// Compute the draw depth for an object at a cell position on a given layer.
+///
+/// Depth values follow the convention that a smaller z is drawn on top of a larger z. Cells are
+/// sorted by their screen row in the default flat-top hex or isometric projection, where both the
+/// `CellSpace` x and y axes point down on the screen, so cells further south will be drawn over
+/// cells further north. Within a single row, objects on a higher layer are drawn over objects on
+/// a lower layer.
+///
+/// The returned values are exact multiples of a power of two inside the open interval (0, 1), so
+/// the ordering is strict for cells whose `x + y` is in [-8191, 8191].
+///
+/// # Examples
+///
+/// ```
+/// # extern crate euclid;
+/// # extern crate calx;
+/// # fn main() {
+/// use euclid::vec2;
+/// use calx::depth_z;
+///
+/// // Southern cells go on top.
+/// assert!(depth_z(vec2(1, 1), 0) < depth_z(vec2(0, 0), 0));
+/// // Higher layers go on top.
+/// assert!(depth_z(vec2(0, 0), 1) < depth_z(vec2(0, 0), 0));
+/// // But layers never push an object over the next row.
+/// assert!(depth_z(vec2(1, 0), 0) < depth_z(vec2(0, 0), 255));
+/// # }
+/// ```
+pub fn depth_z(pos: CellVector, layer: u8) -> f32 {
+    let row = pos.x + pos.y;
+    debug_assert!(row.abs() < 8192, "Cell row out of depth sorting range");
+    let depth = row * DEPTH_LAYERS + layer as i32;
+    // 2^21 slots on either side of 0.5, each 2^-22 wide.
+    0.5 - depth as f32 / (1 << 22) as f32
+}
+
+/// Shape of the map cells on screen.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub enum CellShape {
+    /// Square or rhombus shaped cells, such as regular square grids and isometric maps.
+    Square,
+    /// Hexagonal cells using the `CellSpace` hex convention.
+    Hex,
+}
+
+/// Mapping between `CellSpace` and floating point screen coordinates.
+///
+/// The projection is defined by the screen vectors that the `CellSpace` unit axes project to and
+/// by the screen position of the center of the origin cell. Scrolling the camera is done by
+/// moving the origin.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate euclid;
+/// # extern crate calx;
+/// # fn main() {
+/// use euclid::vec2;
+/// use calx::{CellShape, ScreenProjection};
+///
+/// // Flat-top hexes with a circumradius of 16 pixels.
+/// let proj = ScreenProjection {
+///     x_axis: [24.0, 13.856],
+///     y_axis: [-24.0, 13.856],
+///     origin: [320.0, 200.0],
+///     shape: CellShape::Hex,
+/// };
+///
+/// assert_eq!(vec2(0, 0), proj.pick([320.0, 200.0]));
+/// assert_eq!(vec2(0, 0), proj.pick([334.0, 200.0]));
+/// assert_eq!(vec2(1, 0), proj.pick([340.0, 210.0]));
+/// assert_eq!(vec2(2, 1), proj.pick(proj.cell_to_screen(vec2(2, 1))));
+/// # }
+/// ```
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct ScreenProjection {
+    /// Screen vector for the `CellSpace` x-axis unit vector.
+    pub x_axis: [f32; 2],
+    /// Screen vector for the `CellSpace` y-axis unit vector.
+    pub y_axis: [f32; 2],
+    /// Screen position of the center of the origin cell.
+    pub origin: [f32; 2],
+    /// Shape of the cells, determines how points between cell centers are assigned.
+    pub shape: CellShape,
+}
+
+impl ScreenProjection {
+    /// Return the screen position of the center of a cell.
+    pub fn cell_to_screen(&self, cell: CellVector) -> [f32; 2] {
+        let (x, y) = (cell.x as f32, cell.y as f32);
+        [
+            self.origin[0] + x * self.x_axis[0] + y * self.y_axis[0],
+            self.origin[1] + x * self.x_axis[1] + y * self.y_axis[1],
+        ]
+    }
+
+    /// Return the fractional `CellSpace` coordinates of a screen position.
+    pub fn screen_to_cell_f32(&self, screen_pos: [f32; 2]) -> [f32; 2] {
+        let (a, c) = (self.x_axis[0], self.x_axis[1]);
+        let (b, d) = (self.y_axis[0], self.y_axis[1]);
+        let det = a * d - b * c;
+        debug_assert!(det != 0.0, "Degenerate screen projection");
+
+        let x = screen_pos[0] - self.origin[0];
+        let y = screen_pos[1] - self.origin[1];
+        [(d * x - b * y) / det, (a * y - c * x) / det]
+    }
+
+    /// Return the cell whose on-screen shape contains the screen position.
+    pub fn pick(&self, screen_pos: [f32; 2]) -> CellVector {
+        let [x, y] = self.screen_to_cell_f32(screen_pos);
+        match self.shape {
+            CellShape::Square => vec2(x.round() as i32, y.round() as i32),
+            CellShape::Hex => hex_round(x, y),
+        }
+    }
+}
+
+/// Floating point space defined by an affine projection from `CellSpace`.
+///
+/// Implementing this trait gives the space a `Transformation` where the inverse projection
+/// comes from the projection matrix and positions are snapped to the cells that contain them
+/// according to the cell shape.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate euclid;
+/// # extern crate calx;
+/// # fn main() {
+/// use euclid::{vec2, TypedVector2D};
+/// use calx::{AffineSpace, CellShape, CellVector, ScreenProjection, Space};
+///
+/// /// Square map with 8x8 tiles and a 4 pixel border.
+/// struct TileSpace;
+///
+/// impl AffineSpace for TileSpace {
+///     const PROJECTION: ScreenProjection = ScreenProjection {
+///         x_axis: [8.0, 0.0],
+///         y_axis: [0.0, 8.0],
+///         origin: [8.0, 8.0],
+///         shape: CellShape::Square,
+///     };
+/// }
+///
+/// let pos: TypedVector2D<f32, TileSpace> = Space::from_cell_space(vec2(1, 2));
+/// assert_eq!(pos, vec2(16.0, 24.0));
+/// assert_eq!(vec2::<f32, TileSpace>(19.0, 20.0).to_cell_space(), vec2(1, 2));
+/// # }
+/// ```
+pub trait AffineSpace {
+    /// Projection from `CellSpace` into this space.
+    ///
+    /// The origin of the projection is the translation part of the transformation.
+    const PROJECTION: ScreenProjection;
+}
+
+impl<S: AffineSpace> Transformation for S {
+    type Element = f32;
+
+    fn unproject<V: Into<[i32; 2]>>(v: V) -> [f32; 2] {
+        let v = v.into();
+        S::PROJECTION.cell_to_screen(vec2(v[0], v[1]))
+    }
+
+    fn project<V: Into<[f32; 2]>>(v: V) -> [i32; 2] { S::PROJECTION.pick(v.into()).to_array() }
+}
+
+/// Screen pixel space for a fake isometric hex map.
+///
+/// The hexes are drawn as 32x16 pixel isometric diamonds with the hex axis that runs north to
+/// south going straight down the screen. The origin cell is centered at the origin.
+pub struct IsoSpace;
+
+impl AffineSpace for IsoSpace {
+    const PROJECTION: ScreenProjection = ScreenProjection {
+        x_axis: [16.0, 8.0],
+        y_axis: [-16.0, 8.0],
+        origin: [0.0, 0.0],
+        shape: CellShape::Hex,
+    };
+}
+
+/// Screen pixel space for a flat-top hex map with regular hexagons.
+///
+/// The hexagons have a circumradius of 16 pixels. The origin cell is centered at the origin.
+pub struct PixelSpace;
+
+impl AffineSpace for PixelSpace {
+    const PROJECTION: ScreenProjection = ScreenProjection {
+        x_axis: [24.0, 13.856],
+        y_axis: [-24.0, 13.856],
+        origin: [0.0, 0.0],
+        shape: CellShape::Hex,
+    };
+}
+
+#[cfg(test)]
+mod test {
+    use super::{CellShape, CellVector, ScreenProjection};
+    use euclid::vec2;
+
+    fn dist2(a: [f32; 2], b: [f32; 2]) -> f32 {
+        (a[0] - b[0]) * (a[0] - b[0]) + (a[1] - b[1]) * (a[1] - b[1])
+    }
+
+    fn check_nearest_center(proj: &ScreenProjection) {
+        // For regular hexagons and squares the cell containing a point is the cell with the
+        // nearest center.
+        for sy in 0..80 {
+            for sx in 0..80 {
+                let p = [sx as f32 * 1.37 - 50.0, sy as f32 * 1.29 - 50.0];
+                let picked = proj.pick(p);
+                let d = dist2(p, proj.cell_to_screen(picked));
+                for y in -8..8 {
+                    for x in -8..8 {
+                        let c: CellVector = vec2(x, y);
+                        assert!(dist2(p, proj.cell_to_screen(c)) >= d - 0.001);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_hex_pick() {
+        let s = 10.0;
+        let proj = ScreenProjection {
+            x_axis: [1.5 * s, 0.866 * s],
+            y_axis: [-1.5 * s, 0.866 * s],
+            origin: [0.0, 0.0],
+            shape: CellShape::Hex,
+        };
+        check_nearest_center(&proj);
+    }
+
+    #[test]
+    fn test_square_pick() {
+        let proj = ScreenProjection {
+            x_axis: [8.0, 0.0],
+            y_axis: [0.0, 8.0],
+            origin: [3.0, -2.0],
+            shape: CellShape::Square,
+        };
+        check_nearest_center(&proj);
+        assert_eq!(vec2(1, 0), proj.pick([3.0 + 4.5, -2.0]));
+        assert_eq!(vec2(0, 0), proj.pick([3.0 + 3.5, -2.0]));
+    }
+
+    #[test]
+    fn test_isometric_pick() {
+        let proj = ScreenProjection {
+            x_axis: [16.0, 8.0],
+            y_axis: [-16.0, 8.0],
+            origin: [0.0, 0.0],
+            shape: CellShape::Square,
+        };
+        for y in -5..5 {
+            for x in -5..5 {
+                let c = vec2(x, y);
+                let p = proj.cell_to_screen(c);
+                assert_eq!(c, proj.pick(p));
+                // Just inside the top and bottom corners of the diamond.
+                assert_eq!(c, proj.pick([p[0], p[1] - 7.9]));
+                assert_eq!(c, proj.pick([p[0], p[1] + 7.9]));
+            }
+        }
+    }
+
+    #[test]
+    fn test_affine_spaces() {
+        use super::{IsoSpace, PixelSpace, Space};
+        use euclid::TypedVector2D;
+        use prefab::TextVector;
+
+        for y in -5..5 {
+            for x in -5..5 {
+                let c: CellVector = vec2(x, y);
+                let iso: TypedVector2D<f32, IsoSpace> = c.convert_to();
+                let pixel: TypedVector2D<f32, PixelSpace> = c.convert_to();
+                assert_eq!(iso.to_cell_space(), c);
+                assert_eq!(pixel.to_cell_space(), c);
+                assert_eq!((iso + vec2(7.0, 3.0)).to_cell_space(), c);
+                assert_eq!(pixel.convert_to::<TextVector>().to_cell_space(), c);
+            }
+        }
+        let c: CellVector = vec2(1, 0);
+        let iso: TypedVector2D<f32, IsoSpace> = c.convert_to();
+        assert_eq!(iso, vec2(16.0, 8.0));
+    }
+}
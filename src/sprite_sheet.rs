@@ -0,0 +1,250 @@
+//! Slicing sprite sheet images and naming the sprites
+
+use atlas::{AtlasItem, DynamicAtlas};
+use euclid::{vec2, Size2D, Vector2D};
+use image::RgbaImage;
+use serde_json;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+
+/// Cut a sheet of equally sized tiles into separate images.
+///
+/// The tiles start `margin` pixels from the top left corner of the sheet and have `spacing`
+/// pixels between them. The tiles are returned in row-major order and partial tiles at the
+/// right and bottom edges are left out.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate euclid;
+/// # extern crate image;
+/// # extern crate calx;
+/// # fn main() {
+/// use euclid::size2;
+/// use image::RgbaImage;
+/// use calx::{tile_sheet, DynamicAtlas};
+///
+/// // 3x2 tiles of 8x8 pixels with 1 pixel borders.
+/// let sheet = RgbaImage::new(1 + 3 * 9, 1 + 2 * 9);
+/// let tiles = tile_sheet(&sheet, size2(8, 8), 1, 1);
+/// assert_eq!(tiles.len(), 6);
+///
+/// let mut atlas = DynamicAtlas::new(size2(64, 64));
+/// let items: Vec<_> = tiles.iter().map(|t| atlas.insert(t).unwrap()).collect();
+/// assert_eq!(items[5].rect.size, size2(8, 8));
+/// # }
+/// ```
+pub fn tile_sheet(
+    image: &RgbaImage,
+    tile_size: Size2D<u32>,
+    margin: u32,
+    spacing: u32,
+) -> Vec<RgbaImage> {
+    let (w, h) = (tile_size.width, tile_size.height);
+    if w == 0 || h == 0 {
+        return Vec::new();
+    }
+    let count = |size: u32, tile: u32| (size.saturating_sub(margin) + spacing) / (tile + spacing);
+    let columns = count(image.width(), w);
+    let rows = count(image.height(), h);
+
+    let mut ret = Vec::new();
+    for row in 0..rows {
+        for column in 0..columns {
+            let x = margin + column * (w + spacing);
+            let y = margin + row * (h + spacing);
+            ret.push(crop(image, x, y, w, h));
+        }
+    }
+    ret
+}
+
+fn crop(image: &RgbaImage, x: u32, y: u32, w: u32, h: u32) -> RgbaImage {
+    RgbaImage::from_fn(w, h, |u, v| *image.get_pixel(x + u, y + v))
+}
+
+/// Sprite description in a sprite sheet data file.
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct SpriteSpec {
+    /// Area of the sprite on the sheet as x, y, width and height in pixels.
+    pub rect: [u32; 4],
+    /// Point of the sprite that is placed at the drawing position, relative to the top left
+    /// corner of the sprite.
+    #[serde(default)]
+    pub anchor: [i32; 2],
+}
+
+/// Sprite added to the atlas from a sprite sheet.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Sprite {
+    pub item: AtlasItem,
+    /// Offset from the drawing position to the top left corner of the sprite.
+    pub offset: Vector2D<i32>,
+}
+
+/// Error from loading a `SpriteSheet`.
+#[derive(Debug)]
+pub enum SpriteSheetError {
+    /// The data file could not be parsed.
+    Parse(serde_json::Error),
+    /// A sprite's area extends outside the sheet image.
+    OutOfBounds { name: String },
+    /// A sprite is too large to fit on an atlas page.
+    TooLarge { name: String },
+}
+
+impl fmt::Display for SpriteSheetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SpriteSheetError::Parse(ref e) => write!(f, "Malformed sprite sheet data: {}", e),
+            SpriteSheetError::OutOfBounds { ref name } => {
+                write!(f, "Sprite '{}' is outside the sheet image", name)
+            }
+            SpriteSheetError::TooLarge { ref name } => {
+                write!(f, "Sprite '{}' doesn't fit in the atlas", name)
+            }
+        }
+    }
+}
+
+impl Error for SpriteSheetError {}
+
+impl From<serde_json::Error> for SpriteSheetError {
+    fn from(e: serde_json::Error) -> SpriteSheetError { SpriteSheetError::Parse(e) }
+}
+
+/// Named sprites cut from a sheet image into the texture atlas.
+///
+/// The data file is a JSON object from sprite names to `SpriteSpec` values, so drawing code can
+/// refer to sprites by name instead of by their position on the sheet.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate euclid;
+/// # extern crate image;
+/// # extern crate calx;
+/// # fn main() {
+/// use euclid::{size2, vec2};
+/// use image::RgbaImage;
+/// use calx::{DynamicAtlas, SpriteSheet};
+///
+/// let sheet = RgbaImage::new(32, 16);
+/// let data = r#"{
+///     "floor": { "rect": [0, 0, 16, 8] },
+///     "tree": { "rect": [16, 0, 16, 16], "anchor": [8, 14] }
+/// }"#;
+///
+/// let mut atlas = DynamicAtlas::new(size2(64, 64));
+/// let sprites = SpriteSheet::from_json(data, &sheet, &mut atlas).unwrap();
+///
+/// let tree = sprites.get("tree").unwrap();
+/// assert_eq!(tree.item.rect.size, size2(16, 16));
+/// assert_eq!(tree.offset, vec2(-8, -14));
+/// assert!(sprites.get("rock").is_none());
+///
+/// // Sprites outside the sheet are caught when loading.
+/// let bad = r#"{ "rock": { "rect": [24, 8, 16, 16] } }"#;
+/// assert!(SpriteSheet::from_json(bad, &sheet, &mut atlas).is_err());
+/// # }
+/// ```
+#[derive(Clone, Default, Debug)]
+pub struct SpriteSheet {
+    sprites: BTreeMap<String, Sprite>,
+}
+
+impl SpriteSheet {
+    /// Load sprites from a JSON data file and the sheet image.
+    pub fn from_json(
+        json: &str,
+        image: &RgbaImage,
+        atlas: &mut DynamicAtlas,
+    ) -> Result<SpriteSheet, SpriteSheetError> {
+        let specs: BTreeMap<String, SpriteSpec> = serde_json::from_str(json)?;
+        SpriteSheet::from_specs(&specs, image, atlas)
+    }
+
+    /// Load sprites from sprite descriptions and the sheet image.
+    pub fn from_specs(
+        specs: &BTreeMap<String, SpriteSpec>,
+        image: &RgbaImage,
+        atlas: &mut DynamicAtlas,
+    ) -> Result<SpriteSheet, SpriteSheetError> {
+        let mut sprites = BTreeMap::new();
+        for (name, spec) in specs {
+            let [x, y, w, h] = spec.rect;
+            if x.checked_add(w).map_or(true, |x2| x2 > image.width())
+                || y.checked_add(h).map_or(true, |y2| y2 > image.height())
+            {
+                return Err(SpriteSheetError::OutOfBounds { name: name.clone() });
+            }
+            let item = atlas
+                .insert(&crop(image, x, y, w, h))
+                .ok_or_else(|| SpriteSheetError::TooLarge { name: name.clone() })?;
+            sprites.insert(
+                name.clone(),
+                Sprite {
+                    item,
+                    offset: vec2(-spec.anchor[0], -spec.anchor[1]),
+                },
+            );
+        }
+        Ok(SpriteSheet { sprites })
+    }
+
+    /// Return a sprite by name.
+    pub fn get(&self, name: &str) -> Option<&Sprite> { self.sprites.get(name) }
+
+    /// Iterate the sprites in alphabetical order of their names.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Sprite)> {
+        self.sprites.iter().map(|(k, v)| (k.as_str(), v))
+    }
+
+    pub fn len(&self) -> usize { self.sprites.len() }
+
+    pub fn is_empty(&self) -> bool { self.sprites.is_empty() }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{tile_sheet, SpriteSheet};
+    use atlas::DynamicAtlas;
+    use euclid::size2;
+    use image::{Rgba, RgbaImage};
+
+    #[test]
+    fn test_sprite_sheet() {
+        // Pixels encode their own coordinates.
+        let sheet = RgbaImage::from_fn(20, 11, |x, y| Rgba {
+            data: [x as u8, y as u8, 0, 255],
+        });
+
+        let tiles = tile_sheet(&sheet, size2(4, 4), 2, 1);
+        // Partial tiles at the edges are dropped.
+        assert_eq!(tiles.len(), 3 * 2);
+        assert_eq!(tiles[0].get_pixel(0, 0).data, [2, 2, 0, 255]);
+        assert_eq!(tiles[4].get_pixel(3, 3).data, [10, 10, 0, 255]);
+        assert!(tile_sheet(&sheet, size2(30, 4), 0, 0).is_empty());
+        assert!(tile_sheet(&sheet, size2(0, 4), 0, 0).is_empty());
+
+        let mut atlas = DynamicAtlas::new(size2(16, 16));
+        let sprites = SpriteSheet::from_json(
+            r#"{ "a": { "rect": [3, 4, 2, 2], "anchor": [1, 2] } }"#,
+            &sheet,
+            &mut atlas,
+        )
+        .unwrap();
+        assert_eq!(sprites.len(), 1);
+        let (name, a) = sprites.iter().next().unwrap();
+        assert_eq!(name, "a");
+        let p = a.item.rect.origin;
+        let page = atlas.page(a.item.page);
+        assert_eq!(page.get_pixel(p.x as u32, p.y as u32).data, [3, 4, 0, 255]);
+
+        let huge = r#"{ "big": { "rect": [0, 0, 20, 11] } }"#;
+        assert!(SpriteSheet::from_json(huge, &sheet, &mut atlas).is_err());
+        let overflow = r#"{ "x": { "rect": [4294967295, 0, 2, 2] } }"#;
+        assert!(SpriteSheet::from_json(overflow, &sheet, &mut atlas).is_err());
+    }
+}
@@ -0,0 +1,166 @@
+use euclid::vec2;
+use fov::{Fov, PolarPoint};
+use num::Integer;
+use CellVector;
+
+/// Field of view for maps with square cells and 8-way movement.
+///
+/// Uses the same arc splitting algorithm and `FovValue` propagation as `HexFov`, with the circles
+/// of the polar coordinates being the square rings of cells at a fixed chessboard distance from
+/// the origin.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate euclid;
+/// # extern crate calx;
+/// # fn main() {
+/// use std::collections::HashSet;
+/// use euclid::vec2;
+/// use calx::{CellVector, FovValue, SquareFov};
+///
+/// #[derive(PartialEq, Clone)]
+/// struct Sight(i32);
+///
+/// impl FovValue for Sight {
+///     fn advance(&self, offset: CellVector) -> Option<Self> {
+///         let is_wall = offset == vec2(2, 0);
+///         if !is_wall && offset.x.abs().max(offset.y.abs()) < self.0 {
+///             Some(self.clone())
+///         } else {
+///             None
+///         }
+///     }
+/// }
+///
+/// let seen: HashSet<CellVector> = SquareFov::new(Sight(5)).map(|(p, _)| p).collect();
+/// assert!(seen.contains(&vec2(-4, 4)));
+/// assert!(!seen.contains(&vec2(5, 0)));
+/// // Shadow behind the wall.
+/// assert!(!seen.contains(&vec2(2, 0)));
+/// assert!(!seen.contains(&vec2(4, 0)));
+/// assert!(seen.contains(&vec2(4, 2)));
+/// # }
+/// ```
+pub type SquareFov<T> = Fov<SquarePolarPoint, T>;
+
+/// Points on a square ring expressed in polar coordinates.
+///
+/// The position goes from 0 to 8 along the ring, one unit for each half side of the square
+/// starting from the north and moving clockwise.
+#[derive(Copy, Clone, PartialEq)]
+pub struct SquarePolarPoint {
+    pos: f32,
+    radius: u32,
+}
+
+/// The eight directions in clockwise order starting from north.
+static DIRS8: [(i32, i32); 8] = [
+    (0, -1),
+    (1, -1),
+    (1, 0),
+    (1, 1),
+    (0, 1),
+    (-1, 1),
+    (-1, 0),
+    (-1, -1),
+];
+
+impl SquarePolarPoint {
+    /// Index of the discrete cell along the ring that corresponds to this point.
+    fn winding_index(&self) -> i32 { (self.pos + 0.5).floor() as i32 }
+
+    fn end_index(&self) -> i32 { (self.pos + 0.5).ceil() as i32 }
+}
+
+impl PolarPoint for SquarePolarPoint {
+    fn unit_circle_endpoints() -> (Self, Self) {
+        (
+            SquarePolarPoint {
+                pos: 0.0,
+                radius: 1,
+            },
+            SquarePolarPoint {
+                pos: 8.0,
+                radius: 1,
+            },
+        )
+    }
+
+    fn is_below(&self, other: &SquarePolarPoint) -> bool {
+        self.winding_index() < other.end_index()
+    }
+
+    fn to_v2(&self) -> CellVector {
+        if self.radius == 0 {
+            return vec2(0, 0);
+        }
+        let radius = self.radius as i32;
+        let index = self.winding_index();
+        let sector = index.mod_floor(&(radius * 8)) / radius;
+        let offset = index.mod_floor(&radius);
+
+        // Half sides starting from the middle of a side run along the side, half sides starting
+        // from a corner run towards the middle of the next side.
+        let rod = DIRS8[sector as usize];
+        let tangent = DIRS8[(sector as usize + if sector % 2 == 0 { 2 } else { 3 }) % 8];
+
+        vec2(
+            rod.0 * radius + tangent.0 * offset,
+            rod.1 * radius + tangent.1 * offset,
+        )
+    }
+
+    fn expand(&self) -> Self {
+        SquarePolarPoint {
+            pos: self.pos * (self.radius + 1) as f32 / self.radius as f32,
+            radius: self.radius + 1,
+        }
+    }
+
+    fn advance(&mut self) { self.pos = (self.pos + 0.5).floor() + 0.5; }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{SquareFov, SquarePolarPoint};
+    use fov::{FovValue, PolarPoint};
+    use std::collections::HashSet;
+    use CellVector;
+
+    #[derive(PartialEq, Clone)]
+    struct Range(i32);
+
+    impl FovValue for Range {
+        fn advance(&self, offset: CellVector) -> Option<Self> {
+            if offset.x.abs().max(offset.y.abs()) <= self.0 {
+                Some(self.clone())
+            } else {
+                None
+            }
+        }
+    }
+
+    #[test]
+    fn test_square_ring() {
+        // Walking around a ring visits every cell at that chessboard distance.
+        let (mut p, end) = SquarePolarPoint::unit_circle_endpoints();
+        let end = end.expand().expand();
+        p = p.expand().expand();
+
+        let mut ring = Vec::new();
+        while p.is_below(&end) {
+            ring.push(p.to_v2());
+            p.advance();
+        }
+        let unique: HashSet<CellVector> = ring.iter().cloned().collect();
+        assert_eq!(unique.len(), 24);
+        assert!(ring.iter().all(|v| v.x.abs().max(v.y.abs()) == 3));
+    }
+
+    #[test]
+    fn test_open_square_fov() {
+        let seen: HashSet<CellVector> = SquareFov::new(Range(4)).map(|(p, _)| p).collect();
+        assert_eq!(seen.len(), 81);
+    }
+}
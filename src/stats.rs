@@ -0,0 +1,332 @@
+//! Summary statistics
+
+use euclid::Rect;
+use gui::{bar_graph, GuiDraw, GuiStyle};
+use std::iter::FromIterator;
+
+/// Mean, variance and range of a sequence of values, updated one value at a time.
+///
+/// # Examples
+///
+/// ```
+/// use calx::RunningStats;
+///
+/// let stats: RunningStats = vec![2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0].into_iter().collect();
+/// assert_eq!(stats.count(), 8);
+/// assert_eq!(stats.mean(), 5.0);
+/// assert!((stats.variance() - 32.0 / 7.0).abs() < 1e-9);
+/// assert_eq!((stats.min(), stats.max()), (2.0, 9.0));
+/// ```
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct RunningStats {
+    n: usize,
+    mean: f64,
+    m2: f64,
+    min: f64,
+    max: f64,
+}
+
+impl RunningStats {
+    pub fn new() -> RunningStats {
+        RunningStats {
+            n: 0,
+            mean: 0.0,
+            m2: 0.0,
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+        }
+    }
+
+    /// Add a value to the statistics.
+    pub fn push(&mut self, x: f64) {
+        // Welford's algorithm, numerically stable for long sequences.
+        self.n += 1;
+        let delta = x - self.mean;
+        self.mean += delta / self.n as f64;
+        self.m2 += delta * (x - self.mean);
+        self.min = self.min.min(x);
+        self.max = self.max.max(x);
+    }
+
+    pub fn count(&self) -> usize { self.n }
+
+    /// Return the mean of the values, or zero if there are no values.
+    pub fn mean(&self) -> f64 { self.mean }
+
+    /// Return the sample variance of the values, or zero if there are less than two values.
+    pub fn variance(&self) -> f64 {
+        if self.n < 2 {
+            0.0
+        } else {
+            self.m2 / (self.n - 1) as f64
+        }
+    }
+
+    pub fn std_dev(&self) -> f64 { self.variance().sqrt() }
+
+    /// Return the standard error of the mean.
+    pub fn std_error(&self) -> f64 {
+        if self.n == 0 {
+            0.0
+        } else {
+            self.std_dev() / (self.n as f64).sqrt()
+        }
+    }
+
+    /// Return the smallest value, infinity if there are no values.
+    pub fn min(&self) -> f64 { self.min }
+
+    /// Return the largest value, negative infinity if there are no values.
+    pub fn max(&self) -> f64 { self.max }
+
+    /// Combine with statistics collected from a different set of values.
+    pub fn merge(&mut self, other: &RunningStats) {
+        if other.n == 0 {
+            return;
+        }
+        let n = self.n + other.n;
+        let delta = other.mean - self.mean;
+        self.mean += delta * other.n as f64 / n as f64;
+        self.m2 += other.m2 + delta * delta * self.n as f64 * other.n as f64 / n as f64;
+        self.n = n;
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+    }
+}
+
+impl Default for RunningStats {
+    fn default() -> Self { RunningStats::new() }
+}
+
+impl Extend<f64> for RunningStats {
+    fn extend<I: IntoIterator<Item = f64>>(&mut self, iter: I) {
+        for x in iter {
+            self.push(x);
+        }
+    }
+}
+
+impl FromIterator<f64> for RunningStats {
+    fn from_iter<I: IntoIterator<Item = f64>>(iter: I) -> Self {
+        let mut ret = RunningStats::new();
+        ret.extend(iter);
+        ret
+    }
+}
+
+/// Return the value below which the fraction `p` of the values fall.
+///
+/// Interpolates linearly between the closest values. Returns `None` for an empty slice. The
+/// values are sorted in place.
+///
+/// # Examples
+///
+/// ```
+/// use calx::percentile;
+///
+/// let mut frame_times = vec![16.0, 17.0, 15.0, 33.0, 16.0];
+/// assert_eq!(percentile(&mut frame_times, 0.5), Some(16.0));
+/// assert_eq!(percentile(&mut frame_times, 1.0), Some(33.0));
+/// assert_eq!(percentile(&mut frame_times, 0.875), Some(25.0));
+/// ```
+pub fn percentile(values: &mut [f64], p: f64) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+    values.sort_by(|a, b| a.partial_cmp(b).expect("Cannot sort NaN"));
+
+    let pos = p.clamp(0.0, 1.0) * (values.len() - 1) as f64;
+    let i = pos.floor() as usize;
+    if i + 1 >= values.len() {
+        return Some(values[values.len() - 1]);
+    }
+    Some(values[i] + (values[i + 1] - values[i]) * (pos - i as f64))
+}
+
+/// Value counts in fixed-width buckets over a range.
+///
+/// Values outside the range are counted separately as underflow and overflow.
+///
+/// # Examples
+///
+/// ```
+/// use calx::Histogram;
+///
+/// let mut damage = Histogram::new(0.0, 10.0, 5);
+/// for &x in &[1.0, 3.0, 3.5, 4.0, 5.0, 5.5, 9.0, 12.0] {
+///     damage.push(x);
+/// }
+/// assert_eq!(damage.buckets(), &[1, 2, 3, 0, 1]);
+/// assert_eq!(damage.overflow(), 1);
+/// assert_eq!(damage.sparkline(), "▃▆█▁▃");
+/// assert_eq!(damage.percentile(0.25), 3.0);
+/// ```
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Histogram {
+    min: f64,
+    max: f64,
+    buckets: Vec<usize>,
+    underflow: usize,
+    overflow: usize,
+}
+
+impl Histogram {
+    /// Create an empty histogram over [min, max) with the given number of buckets.
+    pub fn new(min: f64, max: f64, n_buckets: usize) -> Histogram {
+        assert!(max > min, "Empty histogram range");
+        assert!(n_buckets > 0, "Histogram needs buckets");
+        Histogram {
+            min,
+            max,
+            buckets: vec![0; n_buckets],
+            underflow: 0,
+            overflow: 0,
+        }
+    }
+
+    /// Add a value to the histogram.
+    pub fn push(&mut self, x: f64) {
+        if x < self.min {
+            self.underflow += 1;
+        } else if x >= self.max {
+            self.overflow += 1;
+        } else {
+            let i = ((x - self.min) / self.bucket_width()) as usize;
+            // Guard against rounding up to the end of the range.
+            let last = self.buckets.len() - 1;
+            self.buckets[i.min(last)] += 1;
+        }
+    }
+
+    /// Return the counts of the buckets from low to high.
+    pub fn buckets(&self) -> &[usize] { &self.buckets }
+
+    /// Return the number of values below the range.
+    pub fn underflow(&self) -> usize { self.underflow }
+
+    /// Return the number of values above the range.
+    pub fn overflow(&self) -> usize { self.overflow }
+
+    /// Return the number of all added values.
+    pub fn count(&self) -> usize {
+        self.underflow + self.overflow + self.buckets.iter().sum::<usize>()
+    }
+
+    pub fn bucket_width(&self) -> f64 { (self.max - self.min) / self.buckets.len() as f64 }
+
+    /// Return the value range of a bucket.
+    pub fn bucket_range(&self, i: usize) -> (f64, f64) {
+        let w = self.bucket_width();
+        (self.min + i as f64 * w, self.min + (i + 1) as f64 * w)
+    }
+
+    /// Estimate the value below which the fraction `p` of the values fall.
+    ///
+    /// Assumes the values are spread evenly within each bucket. Values outside the range are
+    /// counted as being at the range ends.
+    pub fn percentile(&self, p: f64) -> f64 {
+        let mut remaining = p.clamp(0.0, 1.0) * self.count() as f64;
+        if remaining <= self.underflow as f64 {
+            return self.min;
+        }
+        remaining -= self.underflow as f64;
+
+        for (i, &n) in self.buckets.iter().enumerate() {
+            if remaining <= n as f64 && n > 0 {
+                let (a, b) = self.bucket_range(i);
+                return a + (b - a) * remaining / n as f64;
+            }
+            remaining -= n as f64;
+        }
+        self.max
+    }
+
+    /// Render the bucket counts as a line of unicode block characters.
+    ///
+    /// The tallest bucket is drawn with a full block and empty buckets with the lowest block.
+    pub fn sparkline(&self) -> String {
+        const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+        let max = self.buckets.iter().cloned().max().unwrap_or(0).max(1);
+        self.buckets
+            .iter()
+            .map(|&n| BARS[(n * (BARS.len() - 1) + max / 2) / max])
+            .collect()
+    }
+
+    /// Draw the bucket counts as a bar graph into an area.
+    ///
+    /// The bars are scaled so that the tallest bucket fills the height of the area.
+    pub fn draw(&self, area: Rect<f32>, style: &GuiStyle) -> Vec<GuiDraw> {
+        let max = self.buckets.iter().cloned().max().unwrap_or(0).max(1) as f32;
+        let heights: Vec<f32> = self.buckets.iter().map(|&n| n as f32 / max).collect();
+        let mut ret = vec![GuiDraw::Fill {
+            area,
+            color: style.background,
+        }];
+        ret.extend(bar_graph(area, &heights, style.highlight));
+        ret
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Histogram, RunningStats};
+    use euclid::rect;
+    use gui::{GuiDraw, GuiStyle};
+    use rand::Rng;
+    use rng::seeded_rng;
+
+    #[test]
+    fn test_merge_and_histogram() {
+        let mut rng = seeded_rng(&"stats");
+        let values: Vec<f64> = (0..1000).map(|_| rng.gen_range(-5.0, 15.0)).collect();
+
+        let all: RunningStats = values.iter().cloned().collect();
+        let mut a: RunningStats = values[..300].iter().cloned().collect();
+        let b: RunningStats = values[300..].iter().cloned().collect();
+        a.merge(&b);
+        assert_eq!(a.count(), all.count());
+        assert!((a.mean() - all.mean()).abs() < 1e-9);
+        assert!((a.variance() - all.variance()).abs() < 1e-9);
+        assert_eq!((a.min(), a.max()), (all.min(), all.max()));
+
+        // Uniform distribution, mean 5 and variance 20^2 / 12.
+        assert!((all.mean() - 5.0).abs() < 4.0 * all.std_error());
+        assert!((all.variance() - 400.0 / 12.0).abs() < 3.0);
+
+        let mut hist = Histogram::new(0.0, 10.0, 10);
+        for &x in &values {
+            hist.push(x);
+        }
+        assert_eq!(hist.count(), 1000);
+        assert!((hist.percentile(0.5) - 5.0).abs() < 1.0);
+        assert_eq!(hist.percentile(0.0), 0.0);
+        assert_eq!(hist.percentile(1.0), 10.0);
+        assert_eq!(hist.sparkline().chars().count(), 10);
+    }
+
+    #[test]
+    fn test_draw_histogram() {
+        let mut hist = Histogram::new(0.0, 4.0, 4);
+        for &x in &[0.5, 2.5, 2.5, 2.7, 3.1, 3.9] {
+            hist.push(x);
+        }
+        let style = GuiStyle::default();
+        let bars: Vec<_> = hist
+            .draw(rect(0.0, 0.0, 40.0, 30.0), &style)
+            .into_iter()
+            .filter_map(|d| match d {
+                GuiDraw::Fill { area, color } if color == style.highlight => Some(area),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            bars,
+            vec![
+                rect(0.0, 20.0, 10.0, 10.0),
+                rect(20.0, 0.0, 10.0, 30.0),
+                rect(30.0, 10.0, 10.0, 20.0),
+            ]
+        );
+    }
+}
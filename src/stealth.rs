@@ -0,0 +1,234 @@
+//! Noticing sneaking actors by sight and sound
+
+use alg_misc::Deciban;
+use hex::HexGeom;
+use rand::Rng;
+use rng::RngExt;
+use sound::SoundField;
+use std::collections::{HashMap, HashSet};
+use {CellVector, Rgba};
+
+/// Perception skills of an observer, added to the odds of noticing things.
+#[derive(Copy, Clone, PartialEq, Default, Debug, Serialize, Deserialize)]
+pub struct Perception {
+    pub sight: Deciban,
+    pub hearing: Deciban,
+}
+
+/// Observer trying to notice a target.
+#[derive(Copy, Clone, Debug)]
+pub struct Observer<'a> {
+    pub pos: CellVector,
+    pub perception: Perception,
+    /// Cells the observer can currently see.
+    pub fov: &'a HashSet<CellVector>,
+}
+
+/// Rules for how likely observers are to notice targets.
+///
+/// A target in the observer's field of view can be seen, and seeing it gets harder with
+/// distance and darkness. A target making noise can be heard if its sound reaches the observer,
+/// and louder sounds are easier to hear. The target is detected if it is either seen or heard.
+/// The light levels are the ones from `illuminate` and the noise is a `SoundField` with the
+/// target's sound.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate euclid;
+/// # extern crate calx;
+/// # fn main() {
+/// use euclid::vec2;
+/// use calx::{
+///     hex_disc, illuminate, seeded_rng, Light, Observer, Perception, SoundField, StealthModel,
+/// };
+///
+/// let model = StealthModel::default();
+/// let guard = Observer {
+///     pos: vec2(0, 0),
+///     perception: Perception::default(),
+///     fov: &hex_disc(vec2(0, 0), 6).collect(),
+/// };
+///
+/// let torch = Light::new(vec2(2, 0), [2.0, 1.6, 1.0], 3);
+/// let light = illuminate(&[torch], |_| true);
+/// let silence = SoundField::new(vec![], |_| Some(1));
+///
+/// // Thief in the torchlight is easy to spot.
+/// let lit = model.detection_odds(&guard, vec2(3, 0), &light, &silence).unwrap();
+/// // Thief in the shadows is much harder to spot.
+/// let dark = model.detection_odds(&guard, vec2(-3, 0), &light, &silence).unwrap();
+/// assert!(lit.to_p() > 0.9);
+/// assert!(dark.to_p() < 0.5);
+///
+/// // Nothing to notice out of sight and earshot.
+/// assert!(model.detection_odds(&guard, vec2(10, 0), &light, &silence).is_none());
+///
+/// // Until the thief knocks something over.
+/// let crash = SoundField::new(vec![(vec2(10, 0), 20)], |_| Some(1));
+/// let heard = model.detection_odds(&guard, vec2(10, 0), &light, &crash).unwrap();
+/// assert!(heard.to_p() > 0.5);
+///
+/// let mut rng = seeded_rng(&1);
+/// if model.roll(&mut rng, &guard, vec2(3, 0), &light, &silence) {
+///     println!("Who goes there?");
+/// }
+/// # }
+/// ```
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct StealthModel {
+    /// Odds of seeing a fully lit target in the adjacent cell.
+    pub base_sight: Deciban,
+    /// Odds lost for every cell of distance to the target beyond the first.
+    pub distance_penalty: Deciban,
+    /// Odds lost for a target in complete darkness, scaled down as the light gets brighter.
+    pub darkness_penalty: Deciban,
+    /// Light level of the cells that no light reaches.
+    pub ambient_light: f32,
+    /// Odds of hearing the faintest sound.
+    pub base_hearing: Deciban,
+    /// Odds gained for every step of sound intensity above the faintest.
+    pub loudness_bonus: Deciban,
+}
+
+impl Default for StealthModel {
+    fn default() -> StealthModel {
+        StealthModel {
+            base_sight: Deciban(15.0),
+            distance_penalty: Deciban(1.0),
+            darkness_penalty: Deciban(20.0),
+            ambient_light: 0.0,
+            base_hearing: Deciban(-10.0),
+            loudness_bonus: Deciban(2.0),
+        }
+    }
+}
+
+impl StealthModel {
+    /// Return the odds of seeing a target in view at a distance and with a light level.
+    ///
+    /// Light levels are measured as luma, with 1.0 and above counting as fully lit.
+    pub fn sight_odds(&self, perception: &Perception, distance: i32, light: f32) -> Deciban {
+        let darkness = 1.0 - light.clamp(0.0, 1.0);
+        self.base_sight + perception.sight
+            - Deciban(self.distance_penalty.0 * (distance - 1).max(0) as f32)
+            - Deciban(self.darkness_penalty.0 * darkness)
+    }
+
+    /// Return the odds of hearing a sound with the given intensity, `None` if it's inaudible.
+    pub fn hearing_odds(&self, perception: &Perception, level: i32) -> Option<Deciban> {
+        if level <= 0 {
+            return None;
+        }
+        Some(
+            self.base_hearing
+                + perception.hearing
+                + Deciban(self.loudness_bonus.0 * (level - 1) as f32),
+        )
+    }
+
+    /// Return the odds of the observer noticing the target by either sight or sound.
+    ///
+    /// Returns `None` if the target is neither in view nor heard.
+    pub fn detection_odds(
+        &self,
+        observer: &Observer,
+        target: CellVector,
+        light: &HashMap<CellVector, Rgba>,
+        noise: &SoundField,
+    ) -> Option<Deciban> {
+        let sight = if observer.fov.contains(&target) {
+            let luma = light.get(&target).map_or(self.ambient_light, |c| c.luma());
+            let dist = (target - observer.pos).hex_dist();
+            Some(self.sight_odds(&observer.perception, dist, luma))
+        } else {
+            None
+        };
+        let hearing = self.hearing_odds(&observer.perception, noise.level(observer.pos));
+
+        match (sight, hearing) {
+            (Some(a), Some(b)) => {
+                // Either sense can notice the target, so the target goes unnoticed only if both
+                // miss it.
+                let p_miss = (1.0 - a.to_p()) * (1.0 - b.to_p());
+                Some(Deciban::new((1.0 - p_miss).min(MAX_P)))
+            }
+            (a, b) => a.or(b),
+        }
+    }
+
+    /// Roll whether the observer notices the target.
+    pub fn roll<R: Rng + ?Sized>(
+        &self,
+        rng: &mut R,
+        observer: &Observer,
+        target: CellVector,
+        light: &HashMap<CellVector, Rgba>,
+        noise: &SoundField,
+    ) -> bool {
+        self.detection_odds(observer, target, light, noise)
+            .is_some_and(|db| rng.with_log_odds(db))
+    }
+}
+
+/// Highest combined probability, `Deciban::new` needs a probability below 1.
+const MAX_P: f32 = 0.999_999;
+
+#[cfg(test)]
+mod test {
+    use super::{Observer, Perception, StealthModel};
+    use alg_misc::Deciban;
+    use euclid::vec2;
+    use rng::seeded_rng;
+    use sound::SoundField;
+    use std::collections::{HashMap, HashSet};
+    use Rgba;
+
+    #[test]
+    fn test_stealth_model() {
+        let model = StealthModel::default();
+        let keen = Perception {
+            sight: Deciban(5.0),
+            hearing: Deciban(5.0),
+        };
+        let p = Perception::default();
+
+        assert_eq!(model.sight_odds(&p, 1, 1.0), Deciban(15.0));
+        assert_eq!(model.sight_odds(&p, 0, 2.0), Deciban(15.0));
+        assert_eq!(model.sight_odds(&p, 6, 0.5), Deciban(0.0));
+        assert_eq!(model.sight_odds(&keen, 6, 0.5), Deciban(5.0));
+        assert_eq!(model.hearing_odds(&p, 0), None);
+        assert_eq!(model.hearing_odds(&p, 1), Some(Deciban(-10.0)));
+        assert_eq!(model.hearing_odds(&keen, 6), Some(Deciban(5.0)));
+
+        let fov: HashSet<_> = vec![vec2(0, 0), vec2(1, 0)].into_iter().collect();
+        let observer = Observer {
+            pos: vec2(0, 0),
+            perception: p,
+            fov: &fov,
+        };
+        let mut light = HashMap::new();
+        light.insert(vec2(1, 0), Rgba::new(1.0, 1.0, 1.0, 1.0));
+        let quiet = SoundField::new(vec![], |_| Some(1));
+        let noise = SoundField::new(vec![(vec2(0, 0), 6)], |_| Some(1));
+
+        let odds = |target, noise| model.detection_odds(&observer, target, &light, noise);
+        assert_eq!(odds(vec2(1, 0), &quiet), Some(Deciban(15.0)));
+        assert_eq!(odds(vec2(2, 0), &quiet), None);
+        assert_eq!(odds(vec2(2, 0), &noise), Some(Deciban(0.0)));
+        let mut dim = HashMap::new();
+        dim.insert(vec2(1, 0), Rgba::new(0.25, 0.25, 0.25, 1.0));
+        // Two even chances combine into 3 in 4.
+        let both = model
+            .detection_odds(&observer, vec2(1, 0), &dim, &noise)
+            .unwrap();
+        assert!((both.to_p() - 0.75).abs() < 1e-3);
+
+        let mut rng = seeded_rng(&1);
+        assert!(!model.roll(&mut rng, &observer, vec2(2, 0), &light, &quiet));
+        let seen = (0..1000)
+            .filter(|_| model.roll(&mut rng, &observer, vec2(1, 0), &dim, &noise))
+            .count();
+        assert!(seen > 700 && seen < 800);
+    }
+}
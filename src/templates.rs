@@ -0,0 +1,242 @@
+//! Random text generation from grammar rules
+
+use alg_misc::WeightedChoice;
+use rand::Rng;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+/// Maximum nesting of rule references before expansion gives up.
+const MAX_DEPTH: usize = 32;
+
+/// Error from expanding a text template.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum TemplateError {
+    /// Template refers to a rule that isn't in the grammar.
+    UnknownRule(String),
+    /// Template uses an unknown filter.
+    UnknownFilter(String),
+    /// A `{` has no matching `}`.
+    Unclosed,
+    /// Rules refer to each other too deeply, probably in an infinite loop.
+    TooDeep,
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TemplateError::UnknownRule(ref name) => write!(f, "Unknown rule '{}'", name),
+            TemplateError::UnknownFilter(ref name) => write!(f, "Unknown filter '{}'", name),
+            TemplateError::Unclosed => write!(f, "Unclosed rule reference"),
+            TemplateError::TooDeep => write!(f, "Rule references nested too deep"),
+        }
+    }
+}
+
+impl Error for TemplateError {}
+
+/// Weighted grammar rules for generating varied game messages.
+///
+/// Templates refer to rules in curly braces. A rule reference is replaced with one of the rule's
+/// alternatives, picked randomly by weight, and the alternatives can refer to further rules.
+/// Filters can be applied to the result with `|`:
+///
+/// * `cap` capitalizes the first letter
+/// * `upper` turns all letters to upper case
+/// * `plural` adds an English plural ending, also works for turning verbs into third person
+/// * `a` adds the indefinite article "a" or "an"
+///
+/// Curly braces are escaped by doubling them.
+///
+/// # Examples
+///
+/// ```
+/// use calx::{seeded_rng, Grammar};
+///
+/// let grammar = Grammar::new()
+///     .rule("monster", &["orc", "imp", "{adj} newt"])
+///     .rule("adj", &["angry", "ugly"])
+///     .weighted_rule("hit", &[(3.0, "hit"), (1.0, "bash")]);
+///
+/// let mut rng = seeded_rng(&1);
+/// let msg = grammar.expand(&mut rng, "{monster|a|cap} {hit|plural} you!").unwrap();
+/// assert!(msg.starts_with("An ") || msg.starts_with("A "));
+/// assert!(msg.contains(" hits you!") || msg.contains(" bashes you!"));
+///
+/// assert_eq!(grammar.expand(&mut rng, "{{literal}}").unwrap(), "{literal}");
+/// assert!(grammar.expand(&mut rng, "{dragon}").is_err());
+/// ```
+#[derive(Clone, Default, Debug, Serialize, Deserialize)]
+pub struct Grammar {
+    rules: HashMap<String, Vec<(f32, String)>>,
+}
+
+impl Grammar {
+    pub fn new() -> Grammar { Default::default() }
+
+    /// Add a rule with equally likely alternatives.
+    ///
+    /// Replaces any existing rule with the same name.
+    pub fn rule(self, name: &str, alternatives: &[&str]) -> Grammar {
+        let weighted: Vec<(f32, &str)> = alternatives.iter().map(|&a| (1.0, a)).collect();
+        self.weighted_rule(name, &weighted)
+    }
+
+    /// Add a rule with alternatives that have relative weights.
+    ///
+    /// Replaces any existing rule with the same name.
+    pub fn weighted_rule(mut self, name: &str, alternatives: &[(f32, &str)]) -> Grammar {
+        self.set(name, alternatives);
+        self
+    }
+
+    /// Set a rule in place, useful for binding game values like the player's name.
+    pub fn set(&mut self, name: &str, alternatives: &[(f32, &str)]) {
+        self.rules.insert(
+            name.to_string(),
+            alternatives
+                .iter()
+                .map(|&(w, a)| (w, a.to_string()))
+                .collect(),
+        );
+    }
+
+    /// Generate text from a template.
+    pub fn expand<R: Rng + ?Sized>(
+        &self,
+        rng: &mut R,
+        template: &str,
+    ) -> Result<String, TemplateError> {
+        self.expand_at(rng, template, 0)
+    }
+
+    fn expand_at<R: Rng + ?Sized>(
+        &self,
+        rng: &mut R,
+        template: &str,
+        depth: usize,
+    ) -> Result<String, TemplateError> {
+        if depth > MAX_DEPTH {
+            return Err(TemplateError::TooDeep);
+        }
+
+        let mut ret = String::new();
+        let mut chars = template.chars().peekable();
+        while let Some(c) = chars.next() {
+            if (c == '{' || c == '}') && chars.peek() == Some(&c) {
+                chars.next();
+                ret.push(c);
+                continue;
+            }
+            if c != '{' {
+                ret.push(c);
+                continue;
+            }
+
+            let mut reference = String::new();
+            loop {
+                match chars.next() {
+                    Some('}') => break,
+                    Some(c) => reference.push(c),
+                    None => return Err(TemplateError::Unclosed),
+                }
+            }
+
+            let mut parts = reference.split('|').map(|s| s.trim());
+            let name = parts.next().unwrap_or("");
+            let alternatives = self
+                .rules
+                .get(name)
+                .ok_or_else(|| TemplateError::UnknownRule(name.to_string()))?;
+            let mut text = match alternatives.iter().weighted_choice(rng, |&&(w, _)| w) {
+                Some((_, alt)) => self.expand_at(rng, alt, depth + 1)?,
+                None => String::new(),
+            };
+            for filter in parts {
+                text = apply_filter(filter, &text)?;
+            }
+            ret.push_str(&text);
+        }
+        Ok(ret)
+    }
+}
+
+fn apply_filter(filter: &str, text: &str) -> Result<String, TemplateError> {
+    match filter {
+        "cap" => {
+            let mut chars = text.chars();
+            Ok(match chars.next() {
+                Some(c) => c.to_uppercase().chain(chars).collect(),
+                None => String::new(),
+            })
+        }
+        "upper" => Ok(text.to_uppercase()),
+        "plural" => Ok(pluralize(text)),
+        "a" => {
+            let is_vowel = text
+                .chars()
+                .next()
+                .is_some_and(|c| "aeiouAEIOU".contains(c));
+            Ok(format!("{} {}", if is_vowel { "an" } else { "a" }, text))
+        }
+        _ => Err(TemplateError::UnknownFilter(filter.to_string())),
+    }
+}
+
+/// Add an English plural ending to the last word of a phrase.
+fn pluralize(text: &str) -> String {
+    let lower = text.to_lowercase();
+    if ["s", "x", "z", "ch", "sh"]
+        .iter()
+        .any(|e| lower.ends_with(e))
+    {
+        format!("{}es", text)
+    } else if lower.ends_with('y')
+        && !lower[..lower.len() - 1].ends_with(|c: char| "aeiou".contains(c))
+    {
+        format!("{}ies", &text[..text.len() - 1])
+    } else if text.is_empty() {
+        String::new()
+    } else {
+        format!("{}s", text)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{pluralize, Grammar, TemplateError};
+    use rng::seeded_rng;
+
+    #[test]
+    fn test_expand() {
+        assert_eq!(pluralize("giant newt"), "giant newts");
+        assert_eq!(pluralize("bash"), "bashes");
+        assert_eq!(pluralize("fly"), "flies");
+        assert_eq!(pluralize("key"), "keys");
+
+        let mut rng = seeded_rng(&1);
+        let grammar = Grammar::new()
+            .rule("name", &["eel"])
+            .rule("loop", &["{loop}"])
+            .weighted_rule("never", &[(0.0, "no"), (1.0, "yes")]);
+        assert_eq!(
+            grammar.expand(&mut rng, "{name|a|upper} {name | plural}"),
+            Ok("AN EEL eels".to_string())
+        );
+        for _ in 0..100 {
+            assert_eq!(grammar.expand(&mut rng, "{never}"), Ok("yes".to_string()));
+        }
+        assert_eq!(
+            grammar.expand(&mut rng, "{loop}"),
+            Err(TemplateError::TooDeep)
+        );
+        assert_eq!(
+            grammar.expand(&mut rng, "{name|bold}"),
+            Err(TemplateError::UnknownFilter("bold".to_string()))
+        );
+        assert_eq!(
+            grammar.expand(&mut rng, "{name"),
+            Err(TemplateError::Unclosed)
+        );
+    }
+}
@@ -0,0 +1,192 @@
+//! Synthetic maps for tests and benchmarks
+
+use euclid::vec2;
+use hex::{hex_disc, HexGeom};
+use rand::{Rng, XorShiftRng};
+use rng::seeded_rng;
+use std::collections::HashSet;
+use std::hash::Hash;
+use CellVector;
+
+/// A hex disc shaped map of open and blocked cells centered at the origin.
+///
+/// Cells outside the disc count as blocked.
+#[derive(Clone, Debug)]
+pub struct TestMap {
+    pub radius: i32,
+    pub walls: HashSet<CellVector>,
+}
+
+impl TestMap {
+    /// Create a map with no walls.
+    pub fn open(radius: i32) -> TestMap {
+        TestMap {
+            radius,
+            walls: HashSet::new(),
+        }
+    }
+
+    /// Create a map with randomly placed walls.
+    ///
+    /// Each cell except the origin is a wall with probability `density`. The same seed always
+    /// produces the same map.
+    pub fn scattered(seed: &impl Hash, radius: i32, density: f32) -> TestMap {
+        TestMap::random(&mut seeded_rng(seed), radius, density)
+    }
+
+    /// Create a map with randomly placed walls using a random number generator.
+    pub fn random<R: Rng + ?Sized>(rng: &mut R, radius: i32, density: f32) -> TestMap {
+        let walls = hex_disc(vec2(0, 0), radius)
+            .filter(|&p: &CellVector| p != vec2(0, 0) && rng.gen_range(0.0, 1.0) < density)
+            .collect();
+        TestMap { radius, walls }
+    }
+
+    /// Create a map with single-cell pillars placed on a regular lattice.
+    pub fn pillars(radius: i32, spacing: i32) -> TestMap {
+        debug_assert!(spacing > 1);
+        let walls = hex_disc(vec2(0, 0), radius)
+            .filter(|&p: &CellVector| p != vec2(0, 0) && p.x % spacing == 0 && p.y % spacing == 0)
+            .collect();
+        TestMap { radius, walls }
+    }
+
+    /// Return whether a cell is blocked.
+    pub fn is_wall(&self, pos: CellVector) -> bool {
+        pos.hex_dist() > self.radius || self.walls.contains(&pos)
+    }
+
+    /// Iterate the open cells of the map.
+    pub fn floor<'a>(&'a self) -> impl Iterator<Item = CellVector> + 'a {
+        hex_disc(vec2(0, 0), self.radius).filter(move |&p| !self.is_wall(p))
+    }
+}
+
+/// Generate a random cell vector within the given hex distance from the origin.
+pub fn random_cell<R: Rng + ?Sized>(rng: &mut R, radius: i32) -> CellVector {
+    loop {
+        let v = vec2(
+            rng.gen_range(-radius, radius + 1),
+            rng.gen_range(-radius, radius + 1),
+        );
+        if v.hex_dist() <= radius {
+            return v;
+        }
+    }
+}
+
+/// Check a property against a number of randomly generated cases.
+///
+/// Each case gets its own random number generator derived from the seed and the case index. If a
+/// case fails, panics with the error and the case index so the failure can be reproduced with
+/// `seeded_rng(&(seed, case))`.
+///
+/// # Examples
+///
+/// ```
+/// use calx::test_maps::{check_property, random_cell};
+/// use calx::HexGeom;
+///
+/// check_property(&"hex_dist", 100, |rng| {
+///     let (a, b) = (random_cell(rng, 10), random_cell(rng, 10));
+///     if (a - b).hex_dist() == (b - a).hex_dist() {
+///         Ok(())
+///     } else {
+///         Err(format!("Asymmetric distance between {:?} and {:?}", a, b))
+///     }
+/// });
+/// ```
+pub fn check_property<S, F>(seed: &S, n_cases: usize, mut property: F)
+where
+    S: Hash,
+    F: FnMut(&mut XorShiftRng) -> Result<(), String>,
+{
+    for case in 0..n_cases {
+        let mut rng = seeded_rng(&(seed, case));
+        if let Err(e) = property(&mut rng) {
+            panic!("Property failed on case {}: {}", case, e);
+        }
+    }
+}
+
+/// Check that a path is a sequence of adjacent hex cells that are all passable.
+pub fn check_path<F>(path: &[CellVector], is_passable: F) -> Result<(), String>
+where
+    F: Fn(CellVector) -> bool,
+{
+    for (i, &p) in path.iter().enumerate() {
+        if !is_passable(p) {
+            return Err(format!("Path step {} at {:?} is blocked", i, p));
+        }
+        if i > 0 && (p - path[i - 1]).hex_dist() != 1 {
+            return Err(format!(
+                "Path step {} from {:?} to {:?} is not between adjacent cells",
+                i,
+                path[i - 1],
+                p
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Check that a field of view function is symmetric over a set of origin points.
+///
+/// The `fov` function returns the set of absolute positions visible from an origin. The check
+/// fails if some origin sees another origin that doesn't see it back.
+pub fn check_fov_symmetry<F>(origins: &[CellVector], fov: F) -> Result<(), String>
+where
+    F: Fn(CellVector) -> HashSet<CellVector>,
+{
+    let views: Vec<HashSet<CellVector>> = origins.iter().map(|&p| fov(p)).collect();
+    for (i, &a) in origins.iter().enumerate() {
+        for (j, &b) in origins.iter().enumerate() {
+            if views[i].contains(&b) && !views[j].contains(&a) {
+                return Err(format!("{:?} sees {:?} but not the other way around", a, b));
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::TestMap;
+    use euclid::vec2;
+
+    #[test]
+    fn test_scattered_map() {
+        let map = TestMap::scattered(&123, 20, 0.3);
+        assert_eq!(map.walls, TestMap::scattered(&123, 20, 0.3).walls);
+        assert!(!map.is_wall(vec2(0, 0)));
+        assert!(map.is_wall(vec2(21, 0)));
+
+        // 1261 cells in the disc.
+        let n_walls = map.walls.len();
+        assert!(n_walls > 300 && n_walls < 450);
+        assert_eq!(1261 - n_walls, map.floor().count());
+    }
+
+    #[test]
+    fn test_invariant_checks() {
+        use super::{check_fov_symmetry, check_path};
+        use std::collections::HashSet;
+
+        let map = TestMap::pillars(5, 2);
+        assert!(check_path(&[vec2(0, 0), vec2(1, 0), vec2(1, 1)], |p| !map.is_wall(p)).is_ok());
+        assert!(check_path(&[vec2(0, 0), vec2(1, -1)], |p| !map.is_wall(p)).is_err());
+        assert!(check_path(&[vec2(1, 0), vec2(2, 0)], |p| !map.is_wall(p)).is_err());
+
+        let points = [vec2(0, 0), vec2(3, 1), vec2(-2, 2)];
+        let all: HashSet<_> = points.iter().cloned().collect();
+        assert!(check_fov_symmetry(&points, |_| all.clone()).is_ok());
+        assert!(check_fov_symmetry(&points, |p| {
+            if p == vec2(0, 0) {
+                all.clone()
+            } else {
+                HashSet::new()
+            }
+        })
+        .is_err());
+    }
+}
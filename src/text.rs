@@ -179,3 +179,199 @@ where
     }
     Ok(ret)
 }
+
+/// Piece of laid out text with a single style.
+#[derive(Clone, PartialEq, Debug)]
+pub struct TextFragment {
+    /// Horizontal position from the start of the line.
+    pub x: f32,
+    pub width: f32,
+    pub text: String,
+    /// Markup tags in effect for the fragment as name and value pairs, outermost first.
+    pub tags: Vec<(String, String)>,
+}
+
+impl TextFragment {
+    /// Return the value of the innermost tag with the given name.
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .rev()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| &v[..])
+    }
+}
+
+/// Lay out text with inline markup into lines that fit a given width.
+///
+/// Explicit newlines start new lines and lines are otherwise broken at whitespace. Words longer
+/// than a whole line are broken between characters. Markup tags are in square brackets,
+/// `[name=value]` starts a tagged span and `[/name]` ends it. A tag without a value like `[b]`
+/// gets an empty value. Brackets are escaped by doubling them like in `templatize`.
+///
+/// Returns the lines as lists of fragments where each fragment has uniform markup. Use a
+/// `char_width` that returns 1 for everything to lay out text in fixed width columns.
+///
+/// # Examples
+///
+/// ```
+/// use calx::layout_text;
+///
+/// let lines = layout_text("You hit the [color=red]big angry orc[/color]!", |_| 1.0, 12.0);
+/// assert_eq!(lines.len(), 3);
+/// assert_eq!(lines[0][0].text, "You hit the");
+/// assert_eq!(lines[1][0].text, "big angry");
+/// assert_eq!(lines[1][0].tag("color"), Some("red"));
+///
+/// let last = &lines[2];
+/// assert_eq!((&last[0].text[..], last[0].tag("color")), ("orc", Some("red")));
+/// assert_eq!((&last[1].text[..], last[1].tag("color")), ("!", None));
+/// assert_eq!(last[1].x, 3.0);
+/// ```
+pub fn layout_text<F>(text: &str, char_width: F, max_width: f32) -> Vec<Vec<TextFragment>>
+where
+    F: Fn(char) -> f32,
+{
+    let (chars, styles) = parse_markup(text);
+
+    let mut lines: Vec<Vec<(char, usize)>> = Vec::new();
+    for paragraph in chars.split(|&(c, _)| c == '\n') {
+        let mut line: Vec<(char, usize)> = Vec::new();
+        let mut width = 0.0;
+        let mut is_first_line = true;
+        let mut i = 0;
+
+        while i < paragraph.len() {
+            let is_space = paragraph[i].0.is_whitespace();
+            let len = paragraph[i..]
+                .iter()
+                .take_while(|&&(c, _)| c.is_whitespace() == is_space)
+                .count();
+            let token = &paragraph[i..i + len];
+            let token_width: f32 = token.iter().map(|&(c, _)| char_width(c)).sum();
+            let has_content = line.iter().any(|&(c, _)| !c.is_whitespace());
+
+            if is_space {
+                // Drop whitespace at the start of wrapped lines.
+                if !line.is_empty() || is_first_line {
+                    line.extend_from_slice(token);
+                    width += token_width;
+                }
+            } else if width + token_width <= max_width {
+                line.extend_from_slice(token);
+                width += token_width;
+            } else if has_content {
+                // Wrap and retry the word on the new line.
+                trim_end(&mut line);
+                lines.push(line);
+                line = Vec::new();
+                width = 0.0;
+                is_first_line = false;
+                continue;
+            } else if token_width <= max_width {
+                // Drop indentation that leaves no room for the word.
+                line.clear();
+                line.extend_from_slice(token);
+                width = token_width;
+            } else {
+                // Word doesn't fit on a line of its own, break it up.
+                line.clear();
+                width = 0.0;
+                for &(c, style) in token {
+                    let w = char_width(c);
+                    if width + w > max_width && !line.is_empty() {
+                        lines.push(line);
+                        line = Vec::new();
+                        width = 0.0;
+                        is_first_line = false;
+                    }
+                    line.push((c, style));
+                    width += w;
+                }
+            }
+            i += len;
+        }
+
+        trim_end(&mut line);
+        lines.push(line);
+    }
+
+    lines
+        .into_iter()
+        .map(|line| {
+            let mut ret: Vec<TextFragment> = Vec::new();
+            let mut x = 0.0;
+            let mut prev_style = None;
+            for (c, style) in line {
+                let w = char_width(c);
+                if prev_style != Some(style) {
+                    ret.push(TextFragment {
+                        x,
+                        width: 0.0,
+                        text: String::new(),
+                        tags: styles[style].clone(),
+                    });
+                    prev_style = Some(style);
+                }
+                let fragment = ret.last_mut().unwrap();
+                fragment.text.push(c);
+                fragment.width += w;
+                x += w;
+            }
+            ret
+        })
+        .collect()
+}
+
+fn trim_end(line: &mut Vec<(char, usize)>) {
+    while line.last().is_some_and(|&(c, _)| c.is_whitespace()) {
+        line.pop();
+    }
+}
+
+type Tags = Vec<(String, String)>;
+
+/// Turn markup text into characters with indices to a list of tag sets.
+fn parse_markup(text: &str) -> (Vec<(char, usize)>, Vec<Tags>) {
+    let mut styles = vec![Vec::new()];
+    let mut tags: Tags = Vec::new();
+    let mut style = 0;
+    let mut ret = Vec::new();
+
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if (c == '[' || c == ']') && chars.peek() == Some(&c) {
+            // Escaped bracket.
+            chars.next();
+            ret.push((c, style));
+            continue;
+        }
+
+        if c != '[' {
+            ret.push((c, style));
+            continue;
+        }
+
+        let tag: String = chars.by_ref().take_while(|&c| c != ']').collect();
+        if let Some(name) = tag.strip_prefix('/') {
+            if let Some(i) = tags.iter().rposition(|(n, _)| n == name) {
+                tags.remove(i);
+            }
+        } else {
+            let mut parts = tag.splitn(2, '=');
+            let name = parts.next().unwrap_or("").to_string();
+            let value = parts.next().unwrap_or("").to_string();
+            tags.push((name, value));
+        }
+
+        style = match styles.iter().position(|s| *s == tags) {
+            Some(i) => i,
+            None => {
+                styles.push(tags.clone());
+                styles.len() - 1
+            }
+        };
+    }
+
+    (ret, styles)
+}
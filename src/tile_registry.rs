@@ -0,0 +1,261 @@
+//! Mapping game symbols to tile graphics
+
+use atlas::AtlasItem;
+use serde_json;
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::fmt;
+use std::ops::Index;
+use std::str::FromStr;
+use Rgba;
+
+/// Interned handle to tile graphics in a `TileRegistry`.
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Serialize, Deserialize)]
+pub struct TileId(u32);
+
+/// Tile description in a tile graphics data file.
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct TileSpec {
+    /// Names of the atlas images of the tile.
+    ///
+    /// Tiles whose look depends on the neighboring cells, like walls that join each other, have
+    /// one image for each kernel variant.
+    pub images: Vec<String>,
+    /// Color the images are multiplied with, in any format that `Rgba` parses.
+    #[serde(default)]
+    pub tint: Option<String>,
+}
+
+/// Tile graphics resolved against the texture atlas.
+#[derive(Clone, PartialEq, Debug)]
+pub struct TileGraphics {
+    /// Atlas items of the kernel variants, never empty.
+    pub variants: Vec<AtlasItem>,
+    pub tint: Rgba,
+}
+
+impl TileGraphics {
+    /// Return the atlas item of a kernel variant.
+    ///
+    /// Variant indices past the last variant wrap around, so a tile without variants can be
+    /// drawn with any index.
+    pub fn variant(&self, idx: usize) -> AtlasItem { self.variants[idx % self.variants.len()] }
+}
+
+/// Error from building a `TileRegistry`.
+#[derive(Debug)]
+pub enum TileRegistryError {
+    /// The data file could not be parsed.
+    Parse(serde_json::Error),
+    /// A tile has an empty image list.
+    NoImages { symbol: String },
+    /// A tile refers to an image that isn't in the atlas.
+    MissingImage { symbol: String, image: String },
+    /// A tile's tint is not a valid color.
+    BadTint { symbol: String, tint: String },
+}
+
+impl fmt::Display for TileRegistryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TileRegistryError::Parse(ref e) => write!(f, "Malformed tile data: {}", e),
+            TileRegistryError::NoImages { ref symbol } => {
+                write!(f, "Tile '{}' has no images", symbol)
+            }
+            TileRegistryError::MissingImage {
+                ref symbol,
+                ref image,
+            } => write!(f, "Tile '{}' uses unknown image '{}'", symbol, image),
+            TileRegistryError::BadTint {
+                ref symbol,
+                ref tint,
+            } => write!(f, "Tile '{}' has invalid tint '{}'", symbol, tint),
+        }
+    }
+}
+
+impl Error for TileRegistryError {}
+
+impl From<serde_json::Error> for TileRegistryError {
+    fn from(e: serde_json::Error) -> TileRegistryError { TileRegistryError::Parse(e) }
+}
+
+/// Registry that maps terrain and entity symbols to their tile graphics.
+///
+/// The registry is loaded from a data file and every image name in it is checked against the
+/// atlas at load time, so the renderer can look up the graphics of a game symbol instead of
+/// using hard-coded atlas indices. Symbols are interned into `TileId` values for cheap lookups
+/// in the drawing loop.
+///
+/// The data file is a JSON object from symbols to `TileSpec` values. Symbols get their ids in
+/// alphabetical order, so the ids are the same every time the same file is loaded.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate euclid;
+/// # extern crate calx;
+/// # fn main() {
+/// use std::collections::HashMap;
+/// use euclid::rect;
+/// use calx::{AtlasItem, TileRegistry};
+///
+/// let mut atlas = HashMap::new();
+/// for (i, name) in ["floor", "wall_0", "wall_1", "orc"].iter().enumerate() {
+///     let item = AtlasItem { page: 0, rect: rect(i as i32 * 8, 0, 8, 8) };
+///     atlas.insert(name.to_string(), item);
+/// }
+///
+/// let data = r##"{
+///     "floor": { "images": ["floor"], "tint": "#808080" },
+///     "wall": { "images": ["wall_0", "wall_1"] },
+///     "orc": { "images": ["orc"], "tint": "olivedrab" }
+/// }"##;
+/// let tiles = TileRegistry::from_json(data, |name| atlas.get(name).cloned()).unwrap();
+///
+/// let wall = tiles.id("wall").unwrap();
+/// assert_eq!(tiles[wall].variant(1), atlas["wall_1"]);
+/// assert_eq!(tiles.get("orc").unwrap().variant(0), atlas["orc"]);
+/// assert!(tiles.get("dragon").is_none());
+///
+/// // Images missing from the atlas are caught when loading.
+/// let bad = r#"{ "dragon": { "images": ["dragon"] } }"#;
+/// assert!(TileRegistry::from_json(bad, |name| atlas.get(name).cloned()).is_err());
+/// # }
+/// ```
+#[derive(Clone, Default, Debug)]
+pub struct TileRegistry {
+    ids: HashMap<String, TileId>,
+    tiles: Vec<TileGraphics>,
+}
+
+impl TileRegistry {
+    /// Load the registry from a JSON data file.
+    ///
+    /// The `atlas` function looks up atlas items by image name.
+    pub fn from_json<F>(json: &str, atlas: F) -> Result<TileRegistry, TileRegistryError>
+    where
+        F: Fn(&str) -> Option<AtlasItem>,
+    {
+        let specs: BTreeMap<String, TileSpec> = serde_json::from_str(json)?;
+        TileRegistry::from_specs(specs, atlas)
+    }
+
+    /// Build the registry from tile descriptions.
+    ///
+    /// The `atlas` function looks up atlas items by image name.
+    pub fn from_specs<F>(
+        specs: BTreeMap<String, TileSpec>,
+        atlas: F,
+    ) -> Result<TileRegistry, TileRegistryError>
+    where
+        F: Fn(&str) -> Option<AtlasItem>,
+    {
+        let mut ret = TileRegistry::default();
+        for (symbol, spec) in specs {
+            if spec.images.is_empty() {
+                return Err(TileRegistryError::NoImages { symbol });
+            }
+
+            let mut variants = Vec::new();
+            for image in spec.images {
+                match atlas(&image) {
+                    Some(item) => variants.push(item),
+                    None => return Err(TileRegistryError::MissingImage { symbol, image }),
+                }
+            }
+
+            let tint = match spec.tint {
+                None => Rgba::new(1.0, 1.0, 1.0, 1.0),
+                Some(tint) => match Rgba::from_str(&tint) {
+                    Ok(c) => c,
+                    Err(_) => return Err(TileRegistryError::BadTint { symbol, tint }),
+                },
+            };
+
+            ret.ids.insert(symbol, TileId(ret.tiles.len() as u32));
+            ret.tiles.push(TileGraphics { variants, tint });
+        }
+        Ok(ret)
+    }
+
+    /// Return the interned id of a symbol.
+    pub fn id(&self, symbol: &str) -> Option<TileId> { self.ids.get(symbol).cloned() }
+
+    /// Return the graphics of a symbol.
+    pub fn get(&self, symbol: &str) -> Option<&TileGraphics> {
+        self.id(symbol).map(|id| &self[id])
+    }
+
+    pub fn len(&self) -> usize { self.tiles.len() }
+
+    pub fn is_empty(&self) -> bool { self.tiles.is_empty() }
+}
+
+impl Index<TileId> for TileRegistry {
+    type Output = TileGraphics;
+
+    fn index(&self, id: TileId) -> &TileGraphics { &self.tiles[id.0 as usize] }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{TileId, TileRegistry, TileRegistryError};
+    use atlas::AtlasItem;
+    use euclid::rect;
+    use Rgba;
+
+    fn atlas(name: &str) -> Option<AtlasItem> {
+        let page = match name {
+            "a" => 0,
+            "b" => 1,
+            _ => return None,
+        };
+        Some(AtlasItem {
+            page,
+            rect: rect(0, 0, 8, 8),
+        })
+    }
+
+    #[test]
+    fn test_tile_registry() {
+        let tiles = TileRegistry::from_json(
+            r#"{
+                "z": { "images": ["a"] },
+                "y": { "images": ["b", "a"], "tint": "red" }
+            }"#,
+            atlas,
+        )
+        .unwrap();
+        assert_eq!(tiles.len(), 2);
+        // Ids are assigned in alphabetical order.
+        assert_eq!(tiles.id("y"), Some(TileId(0)));
+        assert_eq!(tiles.id("z"), Some(TileId(1)));
+
+        let y = &tiles[TileId(0)];
+        assert_eq!(y.tint, Rgba::new(1.0, 0.0, 0.0, 1.0));
+        assert_eq!(y.variant(0).page, 1);
+        assert_eq!(y.variant(3).page, 0);
+        assert_eq!(tiles.get("z").unwrap().tint, Rgba::new(1.0, 1.0, 1.0, 1.0));
+
+        let err = |json| TileRegistry::from_json(json, atlas).unwrap_err();
+        match err(r#"{ "x": { "images": ["a", "c"] } }"#) {
+            TileRegistryError::MissingImage { symbol, image } => {
+                assert_eq!((&symbol[..], &image[..]), ("x", "c"))
+            }
+            e => panic!("Unexpected error {}", e),
+        }
+        match err(r#"{ "x": { "images": [] } }"#) {
+            TileRegistryError::NoImages { .. } => {}
+            e => panic!("Unexpected error {}", e),
+        }
+        match err(r#"{ "x": { "images": ["a"], "tint": "plaid" } }"#) {
+            TileRegistryError::BadTint { .. } => {}
+            e => panic!("Unexpected error {}", e),
+        }
+        match err(r#"{ "x": "a" }"#) {
+            TileRegistryError::Parse(_) => {}
+            e => panic!("Unexpected error {}", e),
+        }
+    }
+}
@@ -0,0 +1,312 @@
+//! Cell index textures for drawing map layers with a shader
+
+use atlas::AtlasItem;
+use euclid::{rect, Rect, Size2D, TypedRect};
+use grid::Grid;
+use image::{Rgba as ImageRgba, RgbaImage};
+use std::collections::BTreeSet;
+use std::error::Error;
+use std::fmt;
+use {CellSpace, CellVector};
+
+/// Width and height of the regions the index texture is uploaded in, in cells.
+const CHUNK_SIZE: i32 = 16;
+
+/// Fragment shader that draws a map layer from a `TileIndexMap` texture.
+///
+/// The shader expects a full-screen quad that passes the fragment's position in screen pixels,
+/// with y growing downwards, as `screen_pos`. `index_map` is the index texture with nearest
+/// neighbor filtering and `atlas` is an array texture with the atlas pages as layers.
+/// `map_origin` is the screen position of the top left corner of the map area and `cell_size`
+/// is the size of a cell on screen, both in pixels. `tile_size` is the size of a tile on the
+/// atlas in pixels.
+pub const TILEMAP_FRAGMENT_SHADER: &str = r#"#version 330
+uniform sampler2D index_map;
+uniform sampler2DArray atlas;
+uniform vec2 map_origin;
+uniform vec2 cell_size;
+uniform vec2 tile_size;
+in vec2 screen_pos;
+out vec4 color;
+
+void main() {
+    vec2 cell_pos = (screen_pos - map_origin) / cell_size;
+    ivec2 cell = ivec2(floor(cell_pos));
+    if (any(lessThan(cell, ivec2(0))) || any(greaterThanEqual(cell, textureSize(index_map, 0)))) {
+        discard;
+    }
+    vec4 idx = texelFetch(index_map, cell, 0) * 255.0;
+    if (idx.a < 0.5) {
+        discard;
+    }
+    vec2 texel = (idx.xy + fract(cell_pos)) * tile_size;
+    color = texture(atlas, vec3(texel / vec2(textureSize(atlas, 0).xy), idx.z));
+}
+"#;
+
+/// Error from setting a tile that can't be stored in a `TileIndexMap`.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum TileIndexError {
+    /// The atlas item isn't aligned to the tile size.
+    Unaligned(AtlasItem),
+    /// The tile column, row or atlas page doesn't fit in a texel channel.
+    OutOfRange(AtlasItem),
+}
+
+impl fmt::Display for TileIndexError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TileIndexError::Unaligned(ref t) => {
+                write!(f, "Atlas item {:?} is not aligned to tile size", t)
+            }
+            TileIndexError::OutOfRange(ref t) => {
+                write!(f, "Atlas item {:?} can't be encoded in index map", t)
+            }
+        }
+    }
+}
+
+impl Error for TileIndexError {}
+
+/// Per-cell texture of atlas tile positions for drawing a map layer in a single shader pass.
+///
+/// Each texel of the index texture holds the tile of one map cell, with the tile's column and
+/// row on the atlas page in tile units in the red and green channels, the atlas page in the
+/// blue channel and the alpha channel set for cells that have a tile. The shader in
+/// `TILEMAP_FRAGMENT_SHADER` then draws the whole layer without any per-cell vertex data.
+///
+/// Fill the texture from a map with `sync` once, and after that pass the cells that changed
+/// during a frame to `sync_cells`. Only cells whose tile actually changed mark their region of
+/// the texture as dirty, and the backend only uploads the regions from `take_dirty`.
+///
+/// The tiles must be aligned to the tile size on the atlas pages, with less than 256 tiles
+/// along each axis, and there can be at most 256 atlas pages. Setting a tile that breaks these
+/// rules is an error. Items packed into a `DynamicAtlas` from images of mixed sizes are
+/// generally not aligned, so the tiles should have an atlas of their own.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate euclid;
+/// # extern crate calx;
+/// # fn main() {
+/// use euclid::{rect, size2, vec2};
+/// use calx::{AtlasItem, Grid, TileIndexMap};
+///
+/// let floor = AtlasItem { page: 0, rect: rect(0, 0, 8, 8) };
+/// let wall = AtlasItem { page: 0, rect: rect(8, 0, 8, 8) };
+///
+/// let mut map = Grid::new(rect(0, 0, 40, 20), '.');
+/// let mut index = TileIndexMap::new(map.bounds(), size2(8, 8));
+/// let tile = |c: &char| match *c {
+///     '#' => Some(wall),
+///     _ => Some(floor),
+/// };
+///
+/// index.sync(&map, tile).unwrap();
+/// // Everything needs to be uploaded at first.
+/// assert_eq!(index.take_dirty().len(), 6);
+///
+/// map[vec2(20, 5)] = '#';
+/// index.sync_cells(&map, vec![vec2(20, 5)], tile).unwrap();
+/// // Only the region with the changed cell needs to be uploaded.
+/// assert_eq!(index.take_dirty(), vec![rect(16, 0, 16, 16)]);
+/// assert_eq!(index.image().get_pixel(20, 5).data, [1, 0, 0, 255]);
+/// # }
+/// ```
+pub struct TileIndexMap {
+    bounds: TypedRect<i32, CellSpace>,
+    tile_size: Size2D<i32>,
+    image: RgbaImage,
+    /// Chunks with changes that haven't been taken yet, as (row, column) pairs.
+    dirty: BTreeSet<(i32, i32)>,
+}
+
+impl TileIndexMap {
+    /// Create an empty index map covering an area of cells.
+    pub fn new(bounds: TypedRect<i32, CellSpace>, tile_size: Size2D<i32>) -> TileIndexMap {
+        assert!(
+            tile_size.width > 0 && tile_size.height > 0,
+            "Tile size must be positive"
+        );
+        let mut ret = TileIndexMap {
+            bounds,
+            tile_size,
+            image: RgbaImage::new(bounds.size.width as u32, bounds.size.height as u32),
+            dirty: BTreeSet::new(),
+        };
+        ret.mark_all_dirty();
+        ret
+    }
+
+    /// Return the area of cells the map covers.
+    pub fn bounds(&self) -> TypedRect<i32, CellSpace> { self.bounds }
+
+    /// Return the index texture.
+    pub fn image(&self) -> &RgbaImage { &self.image }
+
+    /// Set the tile of a cell.
+    ///
+    /// Returns false if the cell is outside the bounds of the map, or an error if the tile
+    /// can't be encoded.
+    pub fn set(
+        &mut self,
+        pos: CellVector,
+        tile: Option<AtlasItem>,
+    ) -> Result<bool, TileIndexError> {
+        let p = pos - self.bounds.origin.to_vector();
+        if p.x < 0 || p.y < 0 || p.x >= self.bounds.size.width || p.y >= self.bounds.size.height {
+            return Ok(false);
+        }
+        let data = match tile {
+            Some(t) => self.encode(&t)?,
+            None => [0, 0, 0, 0],
+        };
+        let texel = ImageRgba { data };
+        if *self.image.get_pixel(p.x as u32, p.y as u32) != texel {
+            self.image.put_pixel(p.x as u32, p.y as u32, texel);
+            self.dirty.insert((p.y / CHUNK_SIZE, p.x / CHUNK_SIZE));
+        }
+        Ok(true)
+    }
+
+    /// Update the tiles of all cells in the map area from a grid.
+    ///
+    /// This goes through every cell, use `sync_cells` to update only the cells that changed.
+    /// Cells outside the grid get no tile.
+    pub fn sync<T, F>(&mut self, grid: &Grid<T>, f: F) -> Result<(), TileIndexError>
+    where
+        F: Fn(&T) -> Option<AtlasItem>,
+    {
+        let origin = self.bounds.origin.to_vector();
+        let (w, h) = (self.bounds.size.width, self.bounds.size.height);
+        let cells = (0..h).flat_map(move |y| (0..w).map(move |x| origin + CellVector::new(x, y)));
+        self.sync_cells(grid, cells, f)
+    }
+
+    /// Update the tiles of the given cells from a grid.
+    ///
+    /// Cells outside the map area are skipped and cells outside the grid get no tile.
+    pub fn sync_cells<T, I, F>(
+        &mut self,
+        grid: &Grid<T>,
+        cells: I,
+        f: F,
+    ) -> Result<(), TileIndexError>
+    where
+        I: IntoIterator<Item = CellVector>,
+        F: Fn(&T) -> Option<AtlasItem>,
+    {
+        for pos in cells {
+            let tile = grid.get(pos).and_then(&f);
+            self.set(pos, tile)?;
+        }
+        Ok(())
+    }
+
+    /// Mark the whole texture to be uploaded again, for example after the graphics context
+    /// was lost.
+    pub fn mark_all_dirty(&mut self) {
+        let (w, h) = (self.bounds.size.width, self.bounds.size.height);
+        for y in 0..(h + CHUNK_SIZE - 1) / CHUNK_SIZE {
+            for x in 0..(w + CHUNK_SIZE - 1) / CHUNK_SIZE {
+                self.dirty.insert((y, x));
+            }
+        }
+    }
+
+    /// Return the texel areas of the index texture that changed since the last call.
+    pub fn take_dirty(&mut self) -> Vec<Rect<i32>> {
+        let (w, h) = (self.bounds.size.width, self.bounds.size.height);
+        let ret = self
+            .dirty
+            .iter()
+            .map(|&(y, x)| {
+                let (x, y) = (x * CHUNK_SIZE, y * CHUNK_SIZE);
+                rect(x, y, CHUNK_SIZE.min(w - x), CHUNK_SIZE.min(h - y))
+            })
+            .collect();
+        self.dirty.clear();
+        ret
+    }
+
+    fn encode(&self, tile: &AtlasItem) -> Result<[u8; 4], TileIndexError> {
+        let (x, y) = (tile.rect.origin.x, tile.rect.origin.y);
+        let (w, h) = (self.tile_size.width, self.tile_size.height);
+        if x % w != 0 || y % h != 0 {
+            return Err(TileIndexError::Unaligned(*tile));
+        }
+        if x < 0 || y < 0 || x / w >= 256 || y / h >= 256 || tile.page >= 256 {
+            return Err(TileIndexError::OutOfRange(*tile));
+        }
+        Ok([(x / w) as u8, (y / h) as u8, tile.page as u8, 255])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{TileIndexError, TileIndexMap};
+    use atlas::AtlasItem;
+    use euclid::{rect, size2, vec2};
+    use grid::Grid;
+
+    #[test]
+    fn test_tile_index_map() {
+        let mut index = TileIndexMap::new(rect(-10, -10, 20, 10), size2(16, 16));
+        assert_eq!(
+            index.take_dirty(),
+            vec![rect(0, 0, 16, 10), rect(16, 0, 4, 10)]
+        );
+        assert!(index.take_dirty().is_empty());
+
+        let item = AtlasItem {
+            page: 2,
+            rect: rect(48, 32, 16, 16),
+        };
+        assert_eq!(index.set(vec2(-10, -10), Some(item)), Ok(true));
+        assert_eq!(index.set(vec2(10, -10), Some(item)), Ok(false));
+        assert_eq!(index.image().get_pixel(0, 0).data, [3, 2, 2, 255]);
+        assert_eq!(index.take_dirty(), vec![rect(0, 0, 16, 10)]);
+
+        // Setting the same tile again doesn't dirty anything.
+        assert_eq!(index.set(vec2(-10, -10), Some(item)), Ok(true));
+        assert!(index.take_dirty().is_empty());
+
+        // Unaligned and unencodable items are errors and leave the cell alone.
+        let unaligned = AtlasItem {
+            page: 0,
+            rect: rect(5, 0, 16, 16),
+        };
+        assert_eq!(
+            index.set(vec2(-10, -10), Some(unaligned)),
+            Err(TileIndexError::Unaligned(unaligned))
+        );
+        let far = AtlasItem {
+            page: 256,
+            rect: rect(0, 0, 16, 16),
+        };
+        assert_eq!(
+            index.set(vec2(-10, -10), Some(far)),
+            Err(TileIndexError::OutOfRange(far))
+        );
+        assert_eq!(index.image().get_pixel(0, 0).data, [3, 2, 2, 255]);
+        assert!(index.take_dirty().is_empty());
+
+        // Cells outside the grid are cleared.
+        let grid = Grid::new(rect(-10, -10, 5, 5), ());
+        index.sync(&grid, |_| None).unwrap();
+        assert_eq!(index.image().get_pixel(0, 0).data, [0, 0, 0, 0]);
+        assert_eq!(index.take_dirty(), vec![rect(0, 0, 16, 10)]);
+        index.sync(&grid, |_| Some(item)).unwrap();
+        assert_eq!(index.image().get_pixel(4, 4).data, [3, 2, 2, 255]);
+        assert_eq!(index.image().get_pixel(5, 5).data, [0, 0, 0, 0]);
+        index.take_dirty();
+
+        // Only the listed cells are looked at.
+        let cells = vec![vec2(-9, -9), vec2(6, -6), vec2(50, 50)];
+        index.sync_cells(&grid, cells, |_| None).unwrap();
+        assert_eq!(index.image().get_pixel(0, 0).data, [3, 2, 2, 255]);
+        assert_eq!(index.image().get_pixel(1, 1).data, [0, 0, 0, 0]);
+        assert_eq!(index.take_dirty(), vec![rect(0, 0, 16, 10)]);
+    }
+}
@@ -1,5 +1,10 @@
 //! Time-related utilities
 
+use euclid::{point2, Rect};
+use gui::{bar_graph, GuiDraw, GuiStyle};
+use stats::Histogram;
+use std::collections::VecDeque;
+use std::fmt;
 use time;
 
 /// Animation cycle based on system clock.
@@ -115,3 +120,431 @@ impl TimestepLoop {
     /// Return the average render frame duration in seconds.
     pub fn average_frame_s(&self) -> f64 { self.average_frame_s }
 }
+
+/// Fixed-timestep game loop driver.
+///
+/// Game logic is updated at a fixed tick rate regardless of the rendering frame rate. Real time
+/// is accumulated between frames and as many update ticks are run as fit in the accumulated time.
+/// The leftover fraction of a tick is passed to the render callback as an interpolation alpha in
+/// [0, 1), so the renderer can blend between the previous and the current logic state.
+///
+/// Frame times are capped at the maximum frame time, so that a slow frame can't make the loop
+/// fall further and further behind trying to catch up with the updates.
+///
+/// ```
+/// use calx::GameLoop;
+///
+/// let mut game_loop = GameLoop::new(0.1).max_frame_s(0.5);
+/// let mut ticks = Vec::new();
+/// let mut alpha = 0.0;
+///
+/// game_loop.advance(0.25, |tick| ticks.push(tick), |a| alpha = a);
+/// assert_eq!(ticks, vec![0, 1]);
+/// assert!((alpha - 0.5).abs() < 1e-6);
+///
+/// // Long stall, only catch up to the max frame time.
+/// game_loop.advance(10.0, |tick| ticks.push(tick), |a| alpha = a);
+/// assert_eq!(game_loop.current_tick(), 7);
+/// ```
+///
+/// In the actual game loop, call `frame` once per iteration of the event pump and let it measure
+/// the elapsed time.
+///
+/// ```no_run
+/// use calx::GameLoop;
+///
+/// let mut game_loop = GameLoop::new(1.0 / 30.0);
+/// loop {
+///     // Process window events here.
+///     game_loop.frame(
+///         |_tick| { /* Update game logic */ },
+///         |_alpha| { /* Draw graphics */ },
+///     );
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct GameLoop {
+    timestep_s: f64,
+    max_frame_s: f64,
+    last_time: Option<f64>,
+    accum: f64,
+    current_tick: u64,
+}
+
+impl GameLoop {
+    /// Create a loop with the given logic update interval.
+    ///
+    /// The maximum frame time defaults to 0.25 seconds.
+    pub fn new(timestep_s: f64) -> GameLoop {
+        assert!(timestep_s > 0.0, "Timestep must be positive");
+        GameLoop {
+            timestep_s,
+            max_frame_s: 0.25,
+            last_time: None,
+            accum: 0.0,
+            current_tick: 0,
+        }
+    }
+
+    /// Set the longest frame duration that is caught up with update ticks.
+    ///
+    /// If a frame takes longer than this, the game slows down instead of running a burst of
+    /// updates.
+    pub fn max_frame_s(mut self, max_frame_s: f64) -> GameLoop {
+        debug_assert!(max_frame_s >= self.timestep_s);
+        self.max_frame_s = max_frame_s;
+        self
+    }
+
+    /// Run the updates and the render for a frame using the system clock.
+    ///
+    /// The first call only renders, the time is measured from there on. Returns the number of
+    /// update ticks that were run.
+    pub fn frame<U, R>(&mut self, update: U, render: R) -> usize
+    where
+        U: FnMut(u64),
+        R: FnOnce(f64),
+    {
+        let now = time::precise_time_s();
+        let delta = self.last_time.map_or(0.0, |t| now - t);
+        self.last_time = Some(now);
+        self.advance(delta, update, render)
+    }
+
+    /// Run the updates and the render for a frame that took the given time.
+    ///
+    /// The update callback gets the index of the tick being run and the render callback gets the
+    /// interpolation alpha. Returns the number of update ticks that were run.
+    pub fn advance<U, R>(&mut self, delta_s: f64, mut update: U, render: R) -> usize
+    where
+        U: FnMut(u64),
+        R: FnOnce(f64),
+    {
+        self.accum += delta_s.clamp(0.0, self.max_frame_s);
+        let mut updates = 0;
+        while self.accum >= self.timestep_s {
+            update(self.current_tick);
+            self.current_tick += 1;
+            self.accum -= self.timestep_s;
+            updates += 1;
+        }
+        render(self.alpha());
+        updates
+    }
+
+    /// Return the fraction of the next update tick that has accumulated.
+    pub fn alpha(&self) -> f64 { self.accum / self.timestep_s }
+
+    /// Return length of the update tick in seconds
+    pub fn timestep_s(&self) -> f64 { self.timestep_s }
+
+    /// Return the number of update ticks run since `GameLoop` creation.
+    pub fn current_tick(&self) -> u64 { self.current_tick }
+}
+
+/// Rolling frame time statistics for spotting hitches.
+///
+/// Keeps the durations of the most recent frames for the rolling FPS and the frame time graph,
+/// and a histogram and spike count over all frames since the last reset. A frame is counted as a
+/// spike if it takes more than twice as long as the rolling average.
+///
+/// `draw` makes a small FPS overlay with a bar graph of the recent frame times as GUI drawing
+/// commands. `bars` gives the scaled frame times for drawing the graph in some other way.
+///
+/// ```
+/// # extern crate euclid;
+/// # extern crate calx;
+/// # fn main() {
+/// use euclid::rect;
+/// use calx::{FrameCounter, GuiDraw, GuiStyle};
+///
+/// let mut counter = FrameCounter::new(60);
+/// for _ in 0..59 {
+///     counter.push(1.0 / 60.0);
+/// }
+/// counter.push(0.1);
+///
+/// assert_eq!(counter.spike_count(), 1);
+/// assert_eq!(counter.worst_frame_s(), 0.1);
+/// assert!(counter.fps() < 60.0 && counter.fps() > 40.0);
+/// assert_eq!(counter.bars(0.05).last(), Some(&1.0));
+/// println!("{}", counter);
+///
+/// // Background, text line and one bar per frame.
+/// let overlay = counter.draw(rect(0.0, 0.0, 120.0, 40.0), 0.05, &GuiStyle::default());
+/// assert_eq!(overlay.len(), 62);
+/// assert_eq!(
+///     overlay.last(),
+///     Some(&GuiDraw::Fill {
+///         area: rect(118.0, 12.0, 2.0, 28.0),
+///         color: GuiStyle::default().highlight
+///     })
+/// );
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct FrameCounter {
+    window_size: usize,
+    frames: VecDeque<f64>,
+    window_total_s: f64,
+    histogram: Histogram,
+    spike_count: usize,
+    last_time: Option<f64>,
+}
+
+impl FrameCounter {
+    /// Create a counter that averages over the given number of most recent frames.
+    ///
+    /// The histogram covers frame times from 0 to 100 ms in 1 ms buckets.
+    pub fn new(window_size: usize) -> FrameCounter {
+        assert!(window_size > 0, "Empty frame window");
+        FrameCounter {
+            window_size,
+            frames: VecDeque::with_capacity(window_size),
+            window_total_s: 0.0,
+            histogram: Histogram::new(0.0, 0.1, 100),
+            spike_count: 0,
+            last_time: None,
+        }
+    }
+
+    /// Record a frame using the system clock, call once per frame.
+    pub fn tick(&mut self) {
+        let now = time::precise_time_s();
+        if let Some(last) = self.last_time {
+            self.push(now - last);
+        }
+        self.last_time = Some(now);
+    }
+
+    /// Record a frame that took the given time.
+    pub fn push(&mut self, frame_s: f64) {
+        if !self.frames.is_empty() && frame_s > 2.0 * self.average_frame_s() {
+            self.spike_count += 1;
+        }
+        if self.frames.len() == self.window_size {
+            self.window_total_s -= self.frames.pop_front().unwrap_or(0.0);
+        }
+        self.frames.push_back(frame_s);
+        self.window_total_s += frame_s;
+        self.histogram.push(frame_s);
+    }
+
+    /// Clear the histogram and the spike count.
+    ///
+    /// The rolling window is kept so that FPS stays available.
+    pub fn reset(&mut self) {
+        self.histogram = Histogram::new(0.0, 0.1, 100);
+        self.spike_count = 0;
+    }
+
+    /// Return the frames per second over the rolling window.
+    pub fn fps(&self) -> f64 {
+        if self.window_total_s > 0.0 {
+            self.frames.len() as f64 / self.window_total_s
+        } else {
+            0.0
+        }
+    }
+
+    /// Return the average frame duration over the rolling window.
+    pub fn average_frame_s(&self) -> f64 {
+        if self.frames.is_empty() {
+            0.0
+        } else {
+            self.window_total_s / self.frames.len() as f64
+        }
+    }
+
+    /// Return the longest frame duration in the rolling window.
+    pub fn worst_frame_s(&self) -> f64 { self.frames.iter().cloned().fold(0.0, f64::max) }
+
+    /// Return the number of spike frames since the last reset.
+    pub fn spike_count(&self) -> usize { self.spike_count }
+
+    /// Return the histogram of frame durations since the last reset.
+    pub fn histogram(&self) -> &Histogram { &self.histogram }
+
+    /// Return the frame durations in the rolling window from oldest to newest.
+    pub fn frame_times(&self) -> impl Iterator<Item = f64> + '_ { self.frames.iter().cloned() }
+
+    /// Return the frame durations in the rolling window as bar heights.
+    ///
+    /// The heights are fractions of `full_scale_s` clamped to [0, 1].
+    pub fn bars(&self, full_scale_s: f64) -> Vec<f32> {
+        self.frames
+            .iter()
+            .map(|&t| (t / full_scale_s).clamp(0.0, 1.0) as f32)
+            .collect()
+    }
+
+    /// Draw an FPS overlay into an area.
+    ///
+    /// The top row shows the FPS and the worst frame time, the rest of the area has a bar for
+    /// each frame in the rolling window, newest on the right. Frame times of `full_scale_s` or
+    /// more reach the top of the graph.
+    pub fn draw(&self, area: Rect<f32>, full_scale_s: f64, style: &GuiStyle) -> Vec<GuiDraw> {
+        let mut ret = vec![
+            GuiDraw::Fill {
+                area,
+                color: style.background,
+            },
+            GuiDraw::Text {
+                pos: point2(area.origin.x + style.char_width / 2.0, area.origin.y),
+                text: format!(
+                    "{:.0} FPS, worst {:.1} ms",
+                    self.fps(),
+                    self.worst_frame_s() * 1e3
+                ),
+                color: style.text,
+            },
+        ];
+
+        // Keep the bar width fixed while the window is filling up.
+        let mut heights = vec![0.0; self.window_size - self.frames.len()];
+        heights.extend(self.bars(full_scale_s));
+        let mut graph = area;
+        graph.origin.y += style.row_height;
+        graph.size.height = (graph.size.height - style.row_height).max(0.0);
+        ret.extend(bar_graph(graph, &heights, style.highlight));
+        ret
+    }
+}
+
+impl fmt::Display for FrameCounter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:.1} FPS, {:.1} ms avg, {:.1} ms worst, {} spikes",
+            self.fps(),
+            self.average_frame_s() * 1e3,
+            self.worst_frame_s() * 1e3,
+            self.spike_count
+        )
+    }
+}
+
+/// Scalable and pausable animation time source.
+///
+/// The clock only advances when it is explicitly given elapsed real time, scaled with the time
+/// scale. Animations that read their time from the clock instead of the system clock freeze when
+/// the clock is paused and slow down or speed up with the time scale.
+///
+/// ```
+/// use calx::AnimClock;
+///
+/// let mut clock = AnimClock::new();
+/// clock.advance(1.0);
+/// clock.set_time_scale(0.5);
+/// clock.advance(1.0);
+/// assert_eq!(clock.now_s(), 1.5);
+///
+/// clock.set_paused(true);
+/// clock.advance(1.0);
+/// assert_eq!(clock.now_s(), 1.5);
+/// assert_eq!(clock.cycle_anim(0.5, 4), 3);
+/// ```
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AnimClock {
+    now_s: f64,
+    time_scale: f64,
+    paused: bool,
+}
+
+impl AnimClock {
+    pub fn new() -> AnimClock {
+        AnimClock {
+            now_s: 0.0,
+            time_scale: 1.0,
+            paused: false,
+        }
+    }
+
+    /// Advance the clock by the given amount of real time.
+    pub fn advance(&mut self, real_delta_s: f64) {
+        if !self.paused {
+            self.now_s += real_delta_s * self.time_scale;
+        }
+    }
+
+    /// Return the current clock time in seconds.
+    pub fn now_s(&self) -> f64 { self.now_s }
+
+    pub fn is_paused(&self) -> bool { self.paused }
+
+    pub fn set_paused(&mut self, paused: bool) { self.paused = paused; }
+
+    pub fn time_scale(&self) -> f64 { self.time_scale }
+
+    /// Set the speed of the clock relative to real time.
+    ///
+    /// Values below 1 give slow motion and values above 1 fast forward.
+    pub fn set_time_scale(&mut self, time_scale: f64) {
+        debug_assert!(time_scale >= 0.0);
+        self.time_scale = time_scale;
+    }
+
+    /// Animation cycle based on clock time.
+    pub fn cycle_anim(&self, period_s: f64, num_frames: usize) -> usize {
+        debug_assert!(period_s > 0.0);
+        debug_assert!(num_frames > 0);
+        (self.now_s / period_s) as usize % num_frames
+    }
+
+    /// Time-plot that spikes at given intervals for the given time.
+    pub fn spike(&self, down_s: f64, up_s: f64) -> bool { self.now_s % (down_s + up_s) > down_s }
+
+    /// Single-shot animation started at the given clock time.
+    pub fn single_anim(&self, start_s: f64, period_s: f64, num_frames: usize) -> usize {
+        debug_assert!(period_s > 0.0);
+        debug_assert!(num_frames > 0);
+        let idx = ((self.now_s - start_s) / period_s).max(0.0) as usize;
+        idx.min(num_frames - 1)
+    }
+}
+
+impl Default for AnimClock {
+    fn default() -> Self { AnimClock::new() }
+}
+
+/// Separate animation clocks for the game world and the user interface.
+///
+/// Pausing or slowing down the world clock leaves menus and other interface animations running
+/// at normal speed.
+#[derive(Clone, Debug)]
+pub struct GameClocks {
+    pub world: AnimClock,
+    pub ui: AnimClock,
+    last_update_s: Option<f64>,
+}
+
+impl GameClocks {
+    pub fn new() -> GameClocks {
+        GameClocks {
+            world: AnimClock::new(),
+            ui: AnimClock::new(),
+            last_update_s: None,
+        }
+    }
+
+    /// Advance both clocks by the system time elapsed since the previous update.
+    ///
+    /// Call this once per frame.
+    pub fn update(&mut self) {
+        let now = time::precise_time_s();
+        if let Some(last) = self.last_update_s {
+            self.advance(now - last);
+        }
+        self.last_update_s = Some(now);
+    }
+
+    /// Advance both clocks by the given amount of real time.
+    pub fn advance(&mut self, real_delta_s: f64) {
+        self.world.advance(real_delta_s);
+        self.ui.advance(real_delta_s);
+    }
+}
+
+impl Default for GameClocks {
+    fn default() -> Self { GameClocks::new() }
+}
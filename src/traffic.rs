@@ -0,0 +1,131 @@
+//! Decaying record of recent movement for spreading out crowds
+
+use std::collections::HashMap;
+use CellVector;
+
+/// Record of how much actors have recently moved through each cell.
+///
+/// Add the traffic field to the path costs of a weighted path search and groups of actors will
+/// spread across parallel routes instead of lining up behind each other in the shortest one.
+/// Call `decay` once every game turn so that old traffic fades away.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate euclid;
+/// # extern crate calx;
+/// # fn main() {
+/// use euclid::vec2;
+/// use calx::{astar_path_weighted, hex_neighbors, CellVector, HexGeom, TrafficMap};
+///
+/// // Two side by side corridors from (0, 0) to (4, 0), the straight one is shorter.
+/// let floor = |p: CellVector| p.x >= 0 && p.x <= 4 && (p.y == 0 || p.y == -1);
+/// let mut traffic = TrafficMap::new(0.9);
+///
+/// let mut path_for_next_actor = |traffic: &TrafficMap| {
+///     let neighbors = |&p: &CellVector| {
+///         hex_neighbors(p)
+///             .filter(move |&q| floor(q))
+///             .map(move |q| (q, 1.0 + traffic.cost(q, 2.0)))
+///     };
+///     let heuristic = |&p: &CellVector| (vec2(4, 0) - p).hex_dist() as f32;
+///     astar_path_weighted(vec2(0, 0), &vec2(4, 0), neighbors, heuristic).unwrap().0
+/// };
+///
+/// let first = path_for_next_actor(&traffic);
+/// assert!(first.iter().all(|p| p.y == 0));
+/// traffic.record_path(&first);
+///
+/// // The next actor takes the other corridor to avoid the crowd.
+/// let second = path_for_next_actor(&traffic);
+/// assert!(second.iter().any(|p| p.y == -1));
+///
+/// // Traffic fades over time.
+/// let before = traffic.get(vec2(2, 0));
+/// traffic.decay();
+/// assert!(traffic.get(vec2(2, 0)) < before);
+/// # }
+/// ```
+#[derive(Clone, PartialEq, Debug)]
+pub struct TrafficMap {
+    cells: HashMap<CellVector, f32>,
+    decay_rate: f32,
+}
+
+/// Traffic values below this are dropped to keep the map from growing without bound.
+const MIN_TRAFFIC: f32 = 0.01;
+
+impl TrafficMap {
+    /// Create an empty traffic map.
+    ///
+    /// The traffic of each cell is multiplied by `decay_rate` every turn, so it should be
+    /// between 0 and 1, with higher values remembering traffic for longer.
+    pub fn new(decay_rate: f32) -> TrafficMap {
+        assert!((0.0..1.0).contains(&decay_rate), "Decay rate must be in [0, 1)");
+        TrafficMap {
+            cells: HashMap::new(),
+            decay_rate,
+        }
+    }
+
+    /// Record an actor entering a cell.
+    pub fn record(&mut self, pos: CellVector) { *self.cells.entry(pos).or_insert(0.0) += 1.0; }
+
+    /// Record an actor's planned or traveled path, excluding the start cell.
+    pub fn record_path(&mut self, path: &[CellVector]) {
+        for &pos in path.iter().skip(1) {
+            self.record(pos);
+        }
+    }
+
+    /// Return the current traffic level of a cell.
+    pub fn get(&self, pos: CellVector) -> f32 { self.cells.get(&pos).cloned().unwrap_or(0.0) }
+
+    /// Return the extra path cost for moving into a cell.
+    ///
+    /// The weight is the extra cost of a cell with one unit of traffic. Lower weights make
+    /// actors prefer short paths more, higher weights make them spread out more readily.
+    pub fn cost(&self, pos: CellVector, weight: f32) -> f32 { self.get(pos) * weight }
+
+    /// Fade out the traffic by one turn.
+    pub fn decay(&mut self) {
+        let rate = self.decay_rate;
+        self.cells.retain(|_, t| {
+            *t *= rate;
+            *t >= MIN_TRAFFIC
+        });
+    }
+
+    /// Forget all traffic.
+    pub fn clear(&mut self) { self.cells.clear(); }
+}
+
+#[cfg(test)]
+mod test {
+    use super::TrafficMap;
+    use euclid::vec2;
+
+    #[test]
+    fn test_traffic() {
+        let mut traffic = TrafficMap::new(0.5);
+        traffic.record(vec2(1, 0));
+        traffic.record_path(&[vec2(0, 0), vec2(1, 0), vec2(2, 0)]);
+        assert_eq!(traffic.get(vec2(0, 0)), 0.0);
+        assert_eq!(traffic.get(vec2(1, 0)), 2.0);
+        assert_eq!(traffic.cost(vec2(2, 0), 3.0), 3.0);
+
+        traffic.decay();
+        assert_eq!(traffic.get(vec2(1, 0)), 1.0);
+        assert_eq!(traffic.get(vec2(2, 0)), 0.5);
+
+        // Faded traffic gets dropped.
+        for _ in 0..10 {
+            traffic.decay();
+        }
+        assert!(traffic.cells.is_empty());
+
+        traffic.record(vec2(5, 5));
+        traffic.clear();
+        assert_eq!(traffic.get(vec2(5, 5)), 0.0);
+    }
+}
@@ -0,0 +1,225 @@
+//! Debug panel for tuning config values at runtime
+
+use config::{Config, ConfigError};
+use euclid::{rect, Rect};
+use gui::Gui;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{self, Value};
+
+#[derive(Clone, Debug)]
+enum FieldKind {
+    Slider { min: f32, max: f32 },
+    Toggle,
+}
+
+#[derive(Clone, Debug)]
+struct TuningField {
+    /// Dot-separated path to the field in the settings, like `"audio.volume"`.
+    path: String,
+    kind: FieldKind,
+}
+
+/// Debug panel that shows fields of a `Config` as GUI widgets.
+///
+/// Fields are registered by their dot-separated path in the serialized settings, numbers get a
+/// slider and booleans get a toggle box. When the user changes a widget, the new settings are
+/// written back to the config file, so tuning values doesn't need a recompile and the tuned
+/// values stay around for the next run.
+///
+/// Integer fields are rounded to the nearest integer. Fields that don't exist or have the wrong
+/// type are shown as a label only.
+///
+/// # Examples
+///
+/// ```no_run
+/// # #[macro_use] extern crate serde_derive;
+/// # extern crate euclid;
+/// # extern crate calx;
+/// # fn main() {
+/// use euclid::rect;
+/// use calx::{Config, Gui, TuningPanel};
+///
+/// #[derive(Default, Serialize, Deserialize)]
+/// #[serde(default)]
+/// struct Tuning {
+///     monster_speed: f32,
+///     spawn_rate: u32,
+///     show_paths: bool,
+/// }
+///
+/// let mut config: Config<Tuning> = Config::load("mygame", "tuning.json").unwrap();
+/// let panel = TuningPanel::new()
+///     .slider("monster_speed", 0.0, 4.0)
+///     .slider("spawn_rate", 0.0, 100.0)
+///     .toggle("show_paths");
+///
+/// let mut gui = Gui::default();
+/// // Every frame while the panel is open.
+/// panel.draw(&mut gui, rect(0.0, 0.0, 200.0, 36.0), &mut config).unwrap();
+/// let _draw = gui.end_frame();
+/// # }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct TuningPanel {
+    fields: Vec<TuningField>,
+}
+
+impl TuningPanel {
+    pub fn new() -> TuningPanel { Default::default() }
+
+    /// Add a slider for a numeric field with a value range.
+    pub fn slider(mut self, path: &str, min: f32, max: f32) -> TuningPanel {
+        self.fields.push(TuningField {
+            path: path.to_string(),
+            kind: FieldKind::Slider { min, max },
+        });
+        self
+    }
+
+    /// Add a toggle box for a boolean field.
+    pub fn toggle(mut self, path: &str) -> TuningPanel {
+        self.fields.push(TuningField {
+            path: path.to_string(),
+            kind: FieldKind::Toggle,
+        });
+        self
+    }
+
+    /// Draw the fields into an area, one row per field, and save any changes to the config.
+    ///
+    /// The left half of each row has the field name and the right half the widget. Returns
+    /// whether the settings changed.
+    pub fn draw<T>(
+        &self,
+        gui: &mut Gui,
+        area: Rect<f32>,
+        config: &mut Config<T>,
+    ) -> Result<bool, ConfigError>
+    where
+        T: Serialize + DeserializeOwned + Default,
+    {
+        let mut settings = serde_json::to_value(&**config)?;
+        let row_h = gui.style.row_height;
+        let label_w = area.size.width / 2.0;
+        let mut changed = false;
+
+        for (i, field) in self.fields.iter().enumerate() {
+            let y = area.origin.y + i as f32 * row_h;
+            if y + row_h > area.max_y() {
+                break;
+            }
+            gui.label(rect(area.origin.x, y, label_w, row_h), &field.path);
+            let widget = rect(area.origin.x + label_w, y, area.size.width - label_w, row_h);
+            let pointer = format!("/{}", field.path.replace('.', "/"));
+            let value = match settings.pointer_mut(&pointer) {
+                Some(value) => value,
+                None => continue,
+            };
+
+            changed |= gui.with_id(i, |gui| match (&field.kind, value) {
+                (&FieldKind::Slider { min, max }, value) if value.is_number() => {
+                    let mut x = value.as_f64().unwrap_or(0.0) as f32;
+                    if !gui.slider(widget, &mut x, min, max) {
+                        return false;
+                    }
+                    let new = if value.is_f64() {
+                        Value::from(f64::from(x))
+                    } else if value.is_u64() {
+                        Value::from(x.round().max(0.0) as u64)
+                    } else {
+                        Value::from(x.round() as i64)
+                    };
+                    let is_changed = *value != new;
+                    *value = new;
+                    is_changed
+                }
+                (&FieldKind::Toggle, &mut Value::Bool(ref mut b)) => gui.toggle(widget, "", b),
+                _ => false,
+            });
+        }
+
+        if changed {
+            config.set(serde_json::from_value(settings)?)?;
+        }
+        Ok(changed)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::TuningPanel;
+    use config::Config;
+    use euclid::{point2, rect};
+    use gui::{Gui, GuiEvent};
+    use std::fs;
+    use tempdir::TempDir;
+
+    #[derive(Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
+    #[serde(default)]
+    struct Tuning {
+        speed: f32,
+        monsters: Monsters,
+    }
+
+    #[derive(Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
+    #[serde(default)]
+    struct Monsters {
+        count: u32,
+        is_aggressive: bool,
+    }
+
+    #[test]
+    fn test_tuning_panel() {
+        let dir = TempDir::new("calx-tuning").unwrap();
+        let path = dir.path().join("tuning.json");
+        let mut config: Config<Tuning> = Config::load_from(&path).unwrap();
+        let panel = TuningPanel::new()
+            .slider("speed", 0.0, 2.0)
+            .slider("monsters.count", 0.0, 10.0)
+            .toggle("monsters.is_aggressive")
+            .toggle("missing");
+
+        let mut gui = Gui::default();
+        let row_h = gui.style.row_height;
+        let area = rect(0.0, 0.0, 200.0, row_h * 4.0);
+        let frame = |gui: &mut Gui, events: Vec<GuiEvent>, config: &mut Config<Tuning>| {
+            for e in events {
+                gui.event(e);
+            }
+            let changed = panel.draw(gui, area, config).unwrap();
+            gui.end_frame();
+            changed
+        };
+
+        // Nothing changes without input.
+        assert!(!frame(&mut gui, vec![], &mut config));
+
+        // Drag the speed slider to the middle.
+        let events = vec![GuiEvent::MouseMove(point2(150.0, 1.0)), GuiEvent::MouseDown];
+        assert!(frame(&mut gui, events, &mut config));
+        assert!(!frame(&mut gui, vec![GuiEvent::MouseUp], &mut config));
+        assert_eq!(config.speed, 1.0);
+
+        // Integer values get rounded.
+        let events = vec![
+            GuiEvent::MouseMove(point2(131.0, row_h + 1.0)),
+            GuiEvent::MouseDown,
+        ];
+        frame(&mut gui, events, &mut config);
+        frame(&mut gui, vec![GuiEvent::MouseUp], &mut config);
+        assert_eq!(config.monsters.count, 3);
+
+        let events = vec![
+            GuiEvent::MouseMove(point2(105.0, row_h * 2.0 + 1.0)),
+            GuiEvent::MouseDown,
+        ];
+        frame(&mut gui, events, &mut config);
+        assert!(frame(&mut gui, vec![GuiEvent::MouseUp], &mut config));
+        assert!(config.monsters.is_aggressive);
+
+        // Changes were written to the file.
+        let saved: Tuning = ::serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(saved, *config);
+    }
+}
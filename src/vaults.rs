@@ -0,0 +1,453 @@
+//! Placing prefab vaults into generated maps
+
+use euclid::vec2;
+use grid::Grid;
+use hex::{hex_neighbors, taxicab_neighbors};
+use mapgen::StructuringElement;
+use rand::Rng;
+use space::CellShape;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use CellVector;
+
+/// Prefab map piece to be placed in a generated map.
+#[derive(Clone, Debug)]
+pub struct Vault<T> {
+    pub name: String,
+    pub cells: HashMap<CellVector, T>,
+    /// Cells that must connect to the map's existing floor.
+    pub doors: Vec<CellVector>,
+}
+
+impl<T> Vault<T> {
+    pub fn new(name: &str, cells: HashMap<CellVector, T>) -> Vault<T> {
+        Vault {
+            name: name.to_string(),
+            cells,
+            doors: Vec::new(),
+        }
+    }
+
+    /// Add a door cell to the vault.
+    pub fn door(mut self, pos: CellVector) -> Vault<T> {
+        debug_assert!(self.cells.contains_key(&pos), "Door outside vault");
+        self.doors.push(pos);
+        self
+    }
+}
+
+/// Rotation and reflection of a vault.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Serialize, Deserialize)]
+pub struct Orientation {
+    /// Number of clockwise rotation steps, a step is 90 degrees on square maps and 60 degrees on
+    /// hex maps.
+    pub rotation: u8,
+    /// Whether the vault is reflected before rotating.
+    pub mirrored: bool,
+}
+
+impl Orientation {
+    /// Return the unchanged orientation.
+    pub fn identity() -> Orientation {
+        Orientation {
+            rotation: 0,
+            mirrored: false,
+        }
+    }
+
+    /// Transform a vault cell position.
+    pub fn apply(self, shape: CellShape, v: CellVector) -> CellVector {
+        let mut v = v;
+        if self.mirrored {
+            v = match shape {
+                CellShape::Square => vec2(-v.x, v.y),
+                CellShape::Hex => vec2(v.y, v.x),
+            };
+        }
+        for _ in 0..self.rotation {
+            v = match shape {
+                CellShape::Square => vec2(-v.y, v.x),
+                CellShape::Hex => vec2(v.x - v.y, v.x),
+            };
+        }
+        v
+    }
+}
+
+/// Vault successfully placed in the map.
+#[derive(Clone, Debug)]
+pub struct Placement<T> {
+    /// Index of the vault in the list given to the placer.
+    pub index: usize,
+    pub name: String,
+    /// Map position of the vault origin.
+    pub offset: CellVector,
+    pub orientation: Orientation,
+    /// Vault cells at their map positions.
+    pub cells: HashMap<CellVector, T>,
+}
+
+/// Vault that could not be placed and the reasons why its candidate positions were rejected.
+///
+/// Each candidate position and orientation is counted under the first constraint it fails.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct PlacementFailure {
+    /// Index of the vault in the list given to the placer.
+    pub index: usize,
+    pub name: String,
+    /// Number of candidates tried.
+    pub candidates: usize,
+    /// Candidates with cells outside the map.
+    pub out_of_bounds: usize,
+    /// Candidates overlapping map floor or an earlier vault.
+    pub overlap: usize,
+    /// Candidates too close to an earlier vault.
+    pub too_close: usize,
+    /// Candidates where no door touches the map floor.
+    pub unconnected: usize,
+}
+
+impl fmt::Display for PlacementFailure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Could not place vault '{}', {} candidates: {} out of bounds, {} overlapping, \
+             {} too close to other vaults, {} unconnected",
+            self.name,
+            self.candidates,
+            self.out_of_bounds,
+            self.overlap,
+            self.too_close,
+            self.unconnected
+        )
+    }
+}
+
+/// Result of placing a set of vaults.
+#[derive(Clone, Debug)]
+pub struct PlacementReport<T> {
+    pub placed: Vec<Placement<T>>,
+    pub failed: Vec<PlacementFailure>,
+}
+
+impl<T> PlacementReport<T> {
+    /// Return whether every vault was placed.
+    pub fn is_complete(&self) -> bool { self.failed.is_empty() }
+}
+
+/// Constraint solver for placing vaults into a generated map.
+///
+/// The map is a floor mask like the ones from `mapgen`, `true` for floor and `false` for wall.
+/// Vaults are placed in solid wall areas without overlapping the floor or each other, with at
+/// least one door cell next to the map floor so the vault joins the existing corridor network.
+/// Vaults without door cells don't need to be connected.
+///
+/// Every position and allowed orientation is tried in random order, so a vault is only reported
+/// as failed if there is no valid place left for it. Vaults are placed in the order they are
+/// given, put the hardest to fit ones first.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate euclid;
+/// # extern crate calx;
+/// # fn main() {
+/// use euclid::{rect, vec2};
+/// use calx::{seeded_rng, Grid, Vault, VaultPlacer};
+///
+/// // Corridor through solid rock.
+/// let map = Grid::from_fn(rect(0, 0, 20, 12), |p| p.y == 6 && p.x > 0 && p.x < 19);
+/// let shrine = Vault::new(
+///     "shrine",
+///     vec![(vec2(0, 0), '+'), (vec2(1, 0), '.'), (vec2(2, 0), '_')].into_iter().collect(),
+/// ).door(vec2(0, 0));
+///
+/// let report = VaultPlacer::hex()
+///     .min_spacing(1)
+///     .place(&mut seeded_rng(&1), &map, &[shrine.clone(), shrine.clone(), shrine]);
+/// assert!(report.is_complete());
+/// for placed in &report.placed {
+///     assert!(placed.cells.keys().all(|&p| !map[p]));
+/// }
+///
+/// // There's no room for a vault in an open field.
+/// let field = Grid::new(rect(0, 0, 10, 10), true);
+/// let tiny = Vault::new("tiny", vec![(vec2(0, 0), '#')].into_iter().collect());
+/// let report = VaultPlacer::square().place(&mut seeded_rng(&1), &field, &[tiny]);
+/// let failure = &report.failed[0];
+/// assert_eq!(failure.overlap, failure.candidates);
+/// # }
+/// ```
+#[derive(Copy, Clone, Debug)]
+pub struct VaultPlacer {
+    shape: CellShape,
+    rotate: bool,
+    mirror: bool,
+    min_spacing: i32,
+}
+
+impl VaultPlacer {
+    /// Create a placer for hex maps with rotations and reflections enabled.
+    pub fn hex() -> VaultPlacer {
+        VaultPlacer {
+            shape: CellShape::Hex,
+            rotate: true,
+            mirror: true,
+            min_spacing: 0,
+        }
+    }
+
+    /// Create a placer for square maps with rotations and reflections enabled.
+    pub fn square() -> VaultPlacer {
+        VaultPlacer {
+            shape: CellShape::Square,
+            ..VaultPlacer::hex()
+        }
+    }
+
+    /// Set whether vaults may be rotated.
+    pub fn rotate(mut self, rotate: bool) -> VaultPlacer {
+        self.rotate = rotate;
+        self
+    }
+
+    /// Set whether vaults may be reflected.
+    pub fn mirror(mut self, mirror: bool) -> VaultPlacer {
+        self.mirror = mirror;
+        self
+    }
+
+    /// Set the number of cells that must be left between two vaults.
+    pub fn min_spacing(mut self, cells: i32) -> VaultPlacer {
+        self.min_spacing = cells.max(0);
+        self
+    }
+
+    /// Return the orientations vaults can be placed in.
+    pub fn orientations(&self) -> Vec<Orientation> {
+        let rotations = match (self.rotate, self.shape) {
+            (false, _) => 1,
+            (true, CellShape::Square) => 4,
+            (true, CellShape::Hex) => 6,
+        };
+        let mirrors: &[bool] = if self.mirror {
+            &[false, true]
+        } else {
+            &[false]
+        };
+        let mut ret = Vec::new();
+        for &mirrored in mirrors {
+            for rotation in 0..rotations {
+                ret.push(Orientation { rotation, mirrored });
+            }
+        }
+        ret
+    }
+
+    /// Place vaults into a map.
+    pub fn place<R, T>(
+        &self,
+        rng: &mut R,
+        map: &Grid<bool>,
+        vaults: &[Vault<T>],
+    ) -> PlacementReport<T>
+    where
+        R: Rng + ?Sized,
+        T: Clone,
+    {
+        let spacing = match self.shape {
+            CellShape::Square => StructuringElement::Square(self.min_spacing),
+            CellShape::Hex => StructuringElement::Hex(self.min_spacing),
+        }
+        .offsets();
+
+        let orientations = self.orientations();
+        let mut occupied: HashSet<CellVector> = HashSet::new();
+        let mut reserved: HashSet<CellVector> = HashSet::new();
+        let mut report = PlacementReport {
+            placed: Vec::new(),
+            failed: Vec::new(),
+        };
+
+        for (index, vault) in vaults.iter().enumerate() {
+            let mut candidates: Vec<(CellVector, Orientation)> = Vec::new();
+            for (pos, _) in map.iter() {
+                for &o in &orientations {
+                    candidates.push((pos, o));
+                }
+            }
+            rng.shuffle(&mut candidates);
+
+            let mut failure = PlacementFailure {
+                index,
+                name: vault.name.clone(),
+                candidates: candidates.len(),
+                out_of_bounds: 0,
+                overlap: 0,
+                too_close: 0,
+                unconnected: 0,
+            };
+
+            let found = candidates.into_iter().find(|&(offset, orientation)| {
+                let place = |v: CellVector| offset + orientation.apply(self.shape, v);
+                let cells: Vec<CellVector> = vault.cells.keys().map(|&v| place(v)).collect();
+                if !cells.iter().all(|&p| map.contains(p)) {
+                    failure.out_of_bounds += 1;
+                    return false;
+                }
+                if cells.iter().any(|&p| map[p] || occupied.contains(&p)) {
+                    failure.overlap += 1;
+                    return false;
+                }
+                if cells.iter().any(|p| reserved.contains(p)) {
+                    failure.too_close += 1;
+                    return false;
+                }
+                if !vault.doors.is_empty()
+                    && !vault
+                        .doors
+                        .iter()
+                        .any(|&d| self.touches_floor(map, &occupied, place(d)))
+                {
+                    failure.unconnected += 1;
+                    return false;
+                }
+                true
+            });
+
+            match found {
+                Some((offset, orientation)) => {
+                    let cells: HashMap<CellVector, T> = vault
+                        .cells
+                        .iter()
+                        .map(|(&v, t)| (offset + orientation.apply(self.shape, v), t.clone()))
+                        .collect();
+                    for &p in cells.keys() {
+                        occupied.insert(p);
+                        reserved.extend(spacing.iter().map(|&d| p + d));
+                    }
+                    report.placed.push(Placement {
+                        index,
+                        name: vault.name.clone(),
+                        offset,
+                        orientation,
+                        cells,
+                    });
+                }
+                None => report.failed.push(failure),
+            }
+        }
+        report
+    }
+
+    fn touches_floor(
+        &self,
+        map: &Grid<bool>,
+        occupied: &HashSet<CellVector>,
+        pos: CellVector,
+    ) -> bool {
+        let is_floor = |p: &CellVector| map.get(*p) == Some(&true) && !occupied.contains(p);
+        match self.shape {
+            CellShape::Square => taxicab_neighbors(pos).any(|p| is_floor(&p)),
+            CellShape::Hex => hex_neighbors(pos).any(|p| is_floor(&p)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Orientation, Vault, VaultPlacer};
+    use euclid::{rect, vec2};
+    use grid::Grid;
+    use hex::HexGeom;
+    use rng::seeded_rng;
+    use space::CellShape;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_orientations() {
+        // All hex orientations of a direction vector are distinct directions.
+        let placer = VaultPlacer::hex();
+        let dirs: HashSet<_> = placer
+            .orientations()
+            .iter()
+            .map(|o| o.apply(CellShape::Hex, vec2(1, 0)))
+            .inspect(|v| assert_eq!(v.hex_dist(), 1))
+            .map(|v| (v.x, v.y))
+            .collect();
+        assert_eq!(dirs.len(), 6);
+        assert_eq!(placer.orientations().len(), 12);
+        assert_eq!(VaultPlacer::square().orientations().len(), 8);
+        assert_eq!(
+            VaultPlacer::square()
+                .mirror(false)
+                .rotate(false)
+                .orientations()
+                .len(),
+            1
+        );
+
+        let asymmetric = [vec2(0, 0), vec2(1, 0), vec2(2, 0), vec2(0, 1)];
+        for &shape in &[CellShape::Hex, CellShape::Square] {
+            let placer = match shape {
+                CellShape::Hex => VaultPlacer::hex(),
+                CellShape::Square => VaultPlacer::square(),
+            };
+            let shapes: HashSet<Vec<_>> = placer
+                .orientations()
+                .iter()
+                .map(|o| {
+                    let mut v: Vec<_> = asymmetric
+                        .iter()
+                        .map(|&p| {
+                            let p = o.apply(shape, p);
+                            (p.x, p.y)
+                        })
+                        .collect();
+                    v.sort();
+                    v
+                })
+                .collect();
+            assert_eq!(shapes.len(), placer.orientations().len());
+        }
+        assert_eq!(
+            Orientation::identity().apply(CellShape::Hex, vec2(3, 1)),
+            vec2(3, 1)
+        );
+    }
+
+    #[test]
+    fn test_place() {
+        let map = Grid::from_fn(rect(0, 0, 16, 30), |p| p.x == 8);
+        let block: Vault<char> = Vault::new(
+            "block",
+            (0..3)
+                .flat_map(|y| (0..3).map(move |x| (vec2(x, y), '#')))
+                .collect(),
+        )
+        .door(vec2(1, 0));
+
+        let placer = VaultPlacer::square().min_spacing(2);
+        let report = placer.place(&mut seeded_rng(&2), &map, &vec![block.clone(); 4]);
+        assert!(report.is_complete());
+        for (i, a) in report.placed.iter().enumerate() {
+            assert!(a.cells.keys().all(|&p| !map[p]));
+            for b in &report.placed[i + 1..] {
+                for p in a.cells.keys() {
+                    for q in b.cells.keys() {
+                        assert!((p.x - q.x).abs().max((p.y - q.y).abs()) > 2);
+                    }
+                }
+            }
+        }
+
+        // At most two vaults fit along each side of a short corridor.
+        let map = Grid::from_fn(rect(0, 0, 16, 12), |p| p.x == 8);
+        let report = placer.place(&mut seeded_rng(&2), &map, &vec![block; 6]);
+        assert!(report.placed.len() <= 4);
+        assert_eq!(report.placed.len() + report.failed.len(), 6);
+        assert_eq!(report.failed[0].index, report.placed.len());
+        assert!(report.failed[0].too_close > 0);
+        assert!(report.failed[0].to_string().contains("'block'"));
+    }
+}
@@ -0,0 +1,143 @@
+use euclid::{point2, size2, Point2D, Rect, Size2D};
+
+/// Policy for fitting a fixed resolution screen buffer into a window.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub enum ScaleMode {
+    /// Scale by the largest whole number that fits and letterbox the rest.
+    ///
+    /// Keeps every virtual pixel the same size. If the window is smaller than the virtual
+    /// resolution, the buffer is shrunk to fit like with `Aspect`.
+    PixelPerfect,
+    /// Scale as large as possible while keeping the aspect ratio and letterbox the rest.
+    Aspect,
+    /// Stretch to fill the whole window.
+    Stretch,
+}
+
+/// Placement of a virtual resolution screen buffer in a window.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate euclid;
+/// # extern crate calx;
+/// # fn main() {
+/// use euclid::{point2, rect, size2};
+/// use calx::{ScaleMode, Viewport};
+///
+/// let view = Viewport::new(ScaleMode::PixelPerfect, size2(320, 180), size2(1000, 600));
+/// assert_eq!(view.scale(), (3.0, 3.0));
+/// assert_eq!(view.rect(), rect(20.0, 30.0, 960.0, 540.0));
+///
+/// assert_eq!(view.window_to_virtual(point2(20.0, 30.0)), Some(point2(0.0, 0.0)));
+/// assert_eq!(view.window_to_virtual(point2(500.0, 300.0)), Some(point2(160.0, 90.0)));
+/// // Mouse on the letterbox border.
+/// assert_eq!(view.window_to_virtual(point2(10.0, 300.0)), None);
+/// # }
+/// ```
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Viewport {
+    virtual_size: Size2D<u32>,
+    rect: Rect<f32>,
+}
+
+impl Viewport {
+    /// Fit a virtual resolution into a window with the given scaling mode.
+    pub fn new(mode: ScaleMode, virtual_size: Size2D<u32>, window_size: Size2D<u32>) -> Viewport {
+        let (vw, vh) = (virtual_size.width as f32, virtual_size.height as f32);
+        let (ww, wh) = (window_size.width as f32, window_size.height as f32);
+        let aspect_scale = (ww / vw).min(wh / vh);
+
+        let size = match mode {
+            ScaleMode::Stretch => size2(ww, wh),
+            ScaleMode::Aspect => size2(vw * aspect_scale, vh * aspect_scale),
+            ScaleMode::PixelPerfect => {
+                let scale = if aspect_scale >= 1.0 {
+                    aspect_scale.floor()
+                } else {
+                    aspect_scale
+                };
+                size2(vw * scale, vh * scale)
+            }
+        };
+        // Center on whole pixels so that pixel perfect scaling stays sharp.
+        let origin = point2(
+            ((ww - size.width) / 2.0).floor(),
+            ((wh - size.height) / 2.0).floor(),
+        );
+
+        Viewport {
+            virtual_size,
+            rect: Rect::new(origin, size),
+        }
+    }
+
+    /// Return the window area the virtual screen is drawn to.
+    ///
+    /// The window outside the area is letterbox border.
+    pub fn rect(&self) -> Rect<f32> { self.rect }
+
+    /// Return the horizontal and vertical window pixels per virtual pixel.
+    pub fn scale(&self) -> (f32, f32) {
+        (
+            self.rect.size.width / self.virtual_size.width as f32,
+            self.rect.size.height / self.virtual_size.height as f32,
+        )
+    }
+
+    /// Convert window coordinates, such as the mouse position, to virtual screen coordinates.
+    ///
+    /// Returns `None` for points outside the virtual screen.
+    pub fn window_to_virtual(&self, pos: Point2D<f32>) -> Option<Point2D<f32>> {
+        if !self.rect.contains(&pos) {
+            return None;
+        }
+        let (sx, sy) = self.scale();
+        Some(point2(
+            (pos.x - self.rect.origin.x) / sx,
+            (pos.y - self.rect.origin.y) / sy,
+        ))
+    }
+
+    /// Convert virtual screen coordinates to window coordinates.
+    pub fn virtual_to_window(&self, pos: Point2D<f32>) -> Point2D<f32> {
+        let (sx, sy) = self.scale();
+        point2(
+            self.rect.origin.x + pos.x * sx,
+            self.rect.origin.y + pos.y * sy,
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ScaleMode, Viewport};
+    use euclid::{point2, rect, size2};
+
+    #[test]
+    fn test_viewport() {
+        let virt = size2(320, 200);
+
+        let stretch = Viewport::new(ScaleMode::Stretch, virt, size2(640, 300));
+        assert_eq!(stretch.rect(), rect(0.0, 0.0, 640.0, 300.0));
+        assert_eq!(stretch.scale(), (2.0, 1.5));
+
+        let aspect = Viewport::new(ScaleMode::Aspect, virt, size2(800, 400));
+        assert_eq!(aspect.rect(), rect(80.0, 0.0, 640.0, 400.0));
+        let aspect = Viewport::new(ScaleMode::Aspect, virt, size2(800, 800));
+        assert_eq!(aspect.scale(), (2.5, 2.5));
+
+        let pixel = Viewport::new(ScaleMode::PixelPerfect, virt, size2(800, 800));
+        assert_eq!(pixel.scale(), (2.0, 2.0));
+        // Too small window shrinks.
+        let small = Viewport::new(ScaleMode::PixelPerfect, virt, size2(160, 160));
+        assert_eq!(small.scale(), (0.5, 0.5));
+
+        for view in &[stretch, aspect, pixel, small] {
+            let p = point2(123.0, 45.0);
+            let back = view.window_to_virtual(view.virtual_to_window(p)).unwrap();
+            assert!((back - p).length() < 1e-3);
+        }
+        assert_eq!(pixel.window_to_virtual(point2(799.0, 10.0)), None);
+    }
+}
@@ -0,0 +1,225 @@
+//! Heads-up display of watched debug values
+
+use euclid::{point2, rect, Rect};
+use gui::{bar_graph, GuiDraw, GuiStyle};
+use std::collections::VecDeque;
+
+enum Source {
+    Text(Box<dyn FnMut() -> String>),
+    Number(Box<dyn FnMut() -> f32>),
+}
+
+struct Watch {
+    name: String,
+    source: Source,
+    /// Value shown on the HUD, updated by `WatchHud::update`.
+    value: String,
+    /// Recent numeric values from oldest to newest.
+    history: VecDeque<f32>,
+    /// Number of samples shown in the graph, zero for no graph.
+    graph_len: usize,
+}
+
+/// HUD corner that shows named debug values and graphs of their recent history.
+///
+/// Watches are registered with a name and a function that reads the value from the game, and
+/// `update` reads all of them once per frame. Numeric watches can have a graph of their recent
+/// values, which makes it easy to see how AI weights or generator parameters behave over time
+/// while tuning them.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate euclid;
+/// # extern crate calx;
+/// # fn main() {
+/// use std::cell::Cell;
+/// use std::rc::Rc;
+/// use euclid::rect;
+/// use calx::{GuiStyle, WatchHud};
+///
+/// let aggression = Rc::new(Cell::new(0.5f32));
+/// let mut hud = WatchHud::new();
+/// {
+///     let aggression = aggression.clone();
+///     hud.graph("aggression", 100, move || aggression.get());
+/// }
+/// hud.text("state", || "hunting".to_string());
+///
+/// // Every frame.
+/// hud.update();
+/// let _draw = hud.draw(rect(0.0, 0.0, 200.0, 24.0), &GuiStyle::default());
+///
+/// assert_eq!(hud.lines(), vec!["aggression: 0.500", "state: hunting"]);
+/// # }
+/// ```
+#[derive(Default)]
+pub struct WatchHud {
+    watches: Vec<Watch>,
+}
+
+impl WatchHud {
+    pub fn new() -> WatchHud { Default::default() }
+
+    /// Watch a text value.
+    pub fn text<F>(&mut self, name: &str, f: F)
+    where
+        F: FnMut() -> String + 'static,
+    {
+        self.add(name, Source::Text(Box::new(f)), 0);
+    }
+
+    /// Watch a numeric value.
+    pub fn number<F>(&mut self, name: &str, f: F)
+    where
+        F: FnMut() -> f32 + 'static,
+    {
+        self.add(name, Source::Number(Box::new(f)), 0);
+    }
+
+    /// Watch a numeric value and graph its last `len` values.
+    pub fn graph<F>(&mut self, name: &str, len: usize, f: F)
+    where
+        F: FnMut() -> f32 + 'static,
+    {
+        self.add(name, Source::Number(Box::new(f)), len);
+    }
+
+    fn add(&mut self, name: &str, source: Source, graph_len: usize) {
+        // Registering a name again replaces the old watch.
+        self.watches.retain(|w| w.name != name);
+        self.watches.push(Watch {
+            name: name.to_string(),
+            source,
+            value: String::new(),
+            history: VecDeque::with_capacity(graph_len),
+            graph_len,
+        });
+    }
+
+    /// Remove a watch.
+    pub fn remove(&mut self, name: &str) { self.watches.retain(|w| w.name != name); }
+
+    /// Read the current values of all the watches, call once per frame.
+    pub fn update(&mut self) {
+        for w in &mut self.watches {
+            w.value = match w.source {
+                Source::Text(ref mut f) => f(),
+                Source::Number(ref mut f) => {
+                    let x = f();
+                    if w.graph_len > 0 {
+                        if w.history.len() == w.graph_len {
+                            w.history.pop_front();
+                        }
+                        w.history.push_back(x);
+                    }
+                    format!("{:.3}", x)
+                }
+            };
+        }
+    }
+
+    /// Return the watches as text lines in registration order.
+    pub fn lines(&self) -> Vec<String> {
+        self.watches
+            .iter()
+            .map(|w| format!("{}: {}", w.name, w.value))
+            .collect()
+    }
+
+    /// Draw the watches into an area, one row per watch.
+    ///
+    /// Graphs are drawn on the right half of their row, scaled to the range of the values in
+    /// the graph. Watches that don't fit in the area are left out.
+    pub fn draw(&self, area: Rect<f32>, style: &GuiStyle) -> Vec<GuiDraw> {
+        let row_h = style.row_height;
+        let rows = ((area.size.height / row_h).floor() as usize).min(self.watches.len());
+        let mut background = area;
+        background.size.height = rows as f32 * row_h;
+        let mut ret = vec![GuiDraw::Fill {
+            area: background,
+            color: style.background,
+        }];
+
+        let half_w = area.size.width / 2.0;
+        for (i, (w, line)) in self.watches.iter().zip(self.lines()).enumerate().take(rows) {
+            let y = area.origin.y + i as f32 * row_h;
+            ret.push(GuiDraw::Text {
+                pos: point2(area.origin.x + style.char_width / 2.0, y),
+                text: line,
+                color: style.text,
+            });
+
+            if w.graph_len > 0 && !w.history.is_empty() {
+                let min = w.history.iter().cloned().fold(f32::INFINITY, f32::min);
+                let max = w.history.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+                // Keep flat lines visible at half height.
+                let heights: Vec<f32> = if max > min {
+                    w.history.iter().map(|&x| (x - min) / (max - min)).collect()
+                } else {
+                    vec![0.5; w.history.len()]
+                };
+                let mut bars = vec![0.0; w.graph_len - heights.len()];
+                bars.extend(heights);
+                let graph = rect(area.origin.x + half_w, y + 1.0, half_w, row_h - 2.0);
+                ret.extend(bar_graph(graph, &bars, style.highlight));
+            }
+        }
+        ret
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::WatchHud;
+    use euclid::rect;
+    use gui::{GuiDraw, GuiStyle};
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    #[test]
+    fn test_watch_hud() {
+        let x = Rc::new(Cell::new(1.0f32));
+        let mut hud = WatchHud::new();
+        {
+            let x = x.clone();
+            hud.graph("x", 4, move || x.get());
+        }
+        hud.number("zero", || 0.0);
+        hud.text("mode", || "idle".to_string());
+        hud.text("mode", || "busy".to_string());
+
+        for &v in &[1.0, 3.0, 2.0, 5.0, 4.0] {
+            x.set(v);
+            hud.update();
+        }
+        assert_eq!(hud.lines(), vec!["x: 4.000", "zero: 0.000", "mode: busy"]);
+
+        let style = GuiStyle::default();
+        let row_h = style.row_height;
+        let draw = hud.draw(rect(0.0, 0.0, 80.0, row_h * 2.5), &style);
+        let texts: Vec<&str> = draw
+            .iter()
+            .filter_map(|d| match d {
+                GuiDraw::Text { text, .. } => Some(&text[..]),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(texts, vec!["x: 4.000", "zero: 0.000"]);
+
+        // Graph of the last four values scaled from 2 to 5, the lowest value has no bar.
+        let bars: Vec<(f32, f32)> = draw
+            .iter()
+            .filter_map(|d| match d {
+                GuiDraw::Fill { area, color } if *color == style.highlight => {
+                    Some((area.origin.x, (area.size.height * 3.0).round()))
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(bars, vec![(40.0, 10.0), (60.0, 30.0), (70.0, 20.0)]);
+
+        hud.remove("x");
+        assert_eq!(hud.lines(), vec!["zero: 0.000", "mode: busy"]);
+    }
+}
@@ -112,3 +112,72 @@ fn test_retry_gen() {
 
     assert!(retry_gen(1000, &mut rand::thread_rng(), failing_gen).is_ok());
 }
+
+#[test]
+fn test_serde_value_types() {
+    use calx::{CellVector, Deciban, Dir6, Rgba, SRgba};
+    use serde_json::{from_str, to_string};
+
+    let v: CellVector = CellVector::new(-3, 12);
+    assert_eq!(v, from_str(&to_string(&v).unwrap()).unwrap());
+
+    let c = Rgba::new(0.25, 0.5, 0.75, 1.0);
+    assert_eq!(c, from_str(&to_string(&c).unwrap()).unwrap());
+
+    let c = SRgba::new(0x12, 0x34, 0x56, 0x78);
+    assert_eq!(c, from_str(&to_string(&c).unwrap()).unwrap());
+
+    let d = Dir6::Southwest;
+    assert_eq!(d, from_str(&to_string(&d).unwrap()).unwrap());
+
+    let db = Deciban(-4.5);
+    assert_eq!(db, from_str(&to_string(&db).unwrap()).unwrap());
+}
+
+#[test]
+fn test_space_filling_curves() {
+    use calx::{from_hilbert, from_morton, to_hilbert, to_morton};
+
+    assert_eq!(0b0111, to_morton(0b11, 0b01));
+    for y in 0..64 {
+        for x in 0..64 {
+            assert_eq!((x, y), from_morton(to_morton(x, y)));
+        }
+    }
+    assert_eq!((0xffff, 0xffff), from_morton(to_morton(0xffff, 0xffff)));
+
+    for &order in &[0, 1, 3, 6] {
+        let n = 1 << order;
+        let mut prev = None;
+        for i in 0..(n * n) {
+            let (x, y) = from_hilbert(order, i);
+            assert!(x < n && y < n);
+            assert_eq!(i, to_hilbert(order, x, y));
+            // Successive points along the curve are adjacent.
+            if let Some((px, py)) = prev {
+                let d = (x as i32 - px as i32).abs() + (y as i32 - py as i32).abs();
+                assert_eq!(1, d);
+            }
+            prev = Some((x, y));
+        }
+    }
+    assert_eq!(0xffff_ffff, to_hilbert(16, 0xffff, 0));
+    assert_eq!((0xffff, 0), from_hilbert(16, 0xffff_ffff));
+}
+
+#[test]
+fn test_fov_golden() {
+    use calx::test_maps::TestMap;
+    use calx::{assert_golden, snapshot, CellVector, HexFov, Sight};
+    let map = TestMap::pillars(6, 3);
+    let is_open = |p| !map.is_wall(p);
+    let seen = HexFov::new(Sight::new(CellVector::zero(), map.radius, &is_open))
+        .filter(|&(p, _)| !map.is_wall(p))
+        .flat_map(|(p, _)| {
+            // Show the walls the visible cells are next to.
+            calx::hex_neighbors(p).chain(Some(p)).collect::<Vec<CellVector>>()
+        })
+        .map(|p| (p, if map.is_wall(p) { '#' } else { '.' }));
+
+    assert_golden("tests/golden/fov_pillars.txt", &snapshot(seen));
+}